@@ -0,0 +1,174 @@
+use std::sync::OnceLock;
+
+use crate::config::{ApprovalLevel, AutoExecMode, Config};
+
+static LEVEL: OnceLock<ApprovalLevel> = OnceLock::new();
+
+/// Programs and subcommands that reach the network.
+const NETWORK_COMMANDS: &[&str] = &[
+    "curl",
+    "wget",
+    "ssh",
+    "scp",
+    "sftp",
+    "rsync",
+    "ftp",
+    "nc",
+    "ncat",
+    "telnet",
+    "invoke-webrequest",
+    "invoke-restmethod",
+    "iwr",
+    "irm",
+    "git clone",
+    "git fetch",
+    "git pull",
+    "git push",
+    "git ls-remote",
+    "npm install",
+    "npm i",
+    "npm publish",
+    "pnpm install",
+    "pnpm add",
+    "yarn add",
+    "yarn install",
+    "pip install",
+    "pip3 install",
+    "cargo install",
+    "cargo publish",
+    "go get",
+    "go install",
+    "gh",
+    "docker pull",
+    "docker push",
+];
+
+/// `find` primaries that delete files, run commands or write output files.
+const FIND_ACTIONS: &[&str] = &[
+    "-delete", "-exec", "-execdir", "-ok", "-okdir", "-fprint", "-fprint0", "-fprintf", "-fls",
+];
+
+/// `git branch` flags that only list branches; anything else may create,
+/// rename or delete one.
+const GIT_BRANCH_LIST_FLAGS: &[&str] = &["-a", "-r", "-v", "-vv", "--list", "--show-current"];
+
+/// How commands that reach the network are treated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkAccess {
+    Allow,
+    /// Always confirm, even trusted commands.
+    Ask,
+    Block,
+}
+
+/// What the agent may do without asking, resolved from `approval`.
+pub struct ApprovalPolicy {
+    pub exec_mode: AutoExecMode,
+    pub confirm_exec: bool,
+    pub file_writes: bool,
+    pub network: NetworkAccess,
+}
+
+/// Records the level for this process: `--approval` if given, else the
+/// `approval` config key (the caller folds the flag into `cfg`).
+pub fn init_approval(cfg: &Config) {
+    let _ = LEVEL.set(cfg.approval);
+}
+
+pub fn approval_level() -> ApprovalLevel {
+    LEVEL.get().copied().unwrap_or(ApprovalLevel::Custom)
+}
+
+/// The preset's policy, or the individual config fields under `custom`.
+pub fn approval_policy(cfg: &Config) -> ApprovalPolicy {
+    match approval_level() {
+        ApprovalLevel::Custom => ApprovalPolicy {
+            exec_mode: cfg.auto_exec_mode,
+            confirm_exec: cfg.auto_confirm_exec,
+            file_writes: true,
+            network: NetworkAccess::Allow,
+        },
+        ApprovalLevel::ReadOnly => ApprovalPolicy {
+            exec_mode: AutoExecMode::Safe,
+            confirm_exec: false,
+            file_writes: false,
+            network: NetworkAccess::Block,
+        },
+        ApprovalLevel::AutoEdit => ApprovalPolicy {
+            exec_mode: AutoExecMode::All,
+            confirm_exec: true,
+            file_writes: true,
+            network: NetworkAccess::Ask,
+        },
+        ApprovalLevel::FullAuto => ApprovalPolicy {
+            exec_mode: AutoExecMode::All,
+            confirm_exec: false,
+            file_writes: true,
+            network: NetworkAccess::Allow,
+        },
+    }
+}
+
+/// True when some part of `cmd` runs a known network client or a
+/// fetch/push/install subcommand.
+pub fn is_network_command(cmd: &str) -> bool {
+    cmd.split(['&', '|', ';', '\n']).any(|seg| {
+        let words: Vec<String> = seg
+            .split_whitespace()
+            .map(|w| w.to_ascii_lowercase())
+            .collect();
+        let Some(first) = words.first() else {
+            return false;
+        };
+        let program = first.rsplit(['/', '\\']).next().unwrap_or(first);
+        let program = program.strip_suffix(".exe").unwrap_or(program);
+        NETWORK_COMMANDS.iter().any(|entry| {
+            let parts: Vec<&str> = entry.split(' ').collect();
+            parts[0] == program
+                && words.len() >= parts.len()
+                && parts[1..].iter().zip(&words[1..]).all(|(p, w)| p == w)
+        })
+    })
+}
+
+/// True when every part of `cmd` is a built-in read-only command (`ls`,
+/// `cat`, `rg`, `git status`, ...) and nothing writes: no output redirection,
+/// no command substitution and no `find -delete`/`-exec`.
+pub fn is_read_only_command(cmd: &str) -> bool {
+    let cmd = cmd.replace("2>&1", "");
+    if cmd.contains(['>', '`']) || cmd.contains("$(") {
+        return false;
+    }
+    let mut segments = cmd
+        .split(['&', '|', ';', '\n'])
+        .map(str::trim)
+        .filter(|seg| !seg.is_empty())
+        .peekable();
+    segments.peek().is_some() && segments.all(is_read_only_segment)
+}
+
+fn is_read_only_segment(seg: &str) -> bool {
+    let words: Vec<String> = seg
+        .split_whitespace()
+        .map(|w| w.to_ascii_lowercase())
+        .collect();
+    let Some(first) = words.first() else {
+        return false;
+    };
+    match first.as_str() {
+        "ls" | "dir" | "pwd" | "cat" | "type" | "rg" | "grep" | "findstr" | "tree"
+        | "get-childitem" | "get-content" | "get-location" => true,
+        "find" => !words.iter().any(|w| FIND_ACTIONS.contains(&w.as_str())),
+        "git" => match words.get(1).map(String::as_str) {
+            Some("status") => true,
+            Some("diff" | "log" | "show") => !words[2..]
+                .iter()
+                .any(|w| w == "-o" || w.starts_with("--output")),
+            Some("branch") => words[2..]
+                .iter()
+                .all(|w| GIT_BRANCH_LIST_FLAGS.contains(&w.as_str())),
+            _ => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// `<path>.lock`, the advisory lock file guarding `path`.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+/// Runs `f` while holding an exclusive advisory lock on `path`, so other
+/// dongshan processes (e.g. `dongshan web` next to a chat) wait instead of
+/// interleaving their read-modify-write cycles. The lock is released when
+/// the lock file is closed, also if `f` fails or the process dies.
+pub fn with_file_lock<T>(path: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let lock = lock_path(path);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock)
+        .with_context(|| format!("Failed to open lock file {}", lock.display()))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", lock.display()))?;
+    let result = f();
+    drop(file);
+    result
+}
+
+/// Replaces `path` with `contents` through a temp file in the same directory
+/// and a rename, so readers see either the old or the new file, never a
+/// partial one. Callers that race on the same file take [`with_file_lock`].
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    let written = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(err).with_context(|| format!("Failed to write {}", path.display()));
+    }
+    Ok(())
+}
+
+/// [`write_atomic`] under the file's lock.
+pub fn write_locked(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    with_file_lock(path, || write_atomic(path, contents))
+}
@@ -0,0 +1,86 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::commands::generate_commit_message;
+use crate::config::{Config, config_for_model, utility_model};
+use crate::logging::log_verbose;
+
+/// Subject prefix that marks commits made by `auto_commit`.
+const AUTO_COMMIT_PREFIX: &str = "[dongshan]";
+
+fn git(args: &[&str], paths: &[String]) -> Result<std::process::Output> {
+    Command::new("git")
+        .args(args)
+        .arg("--")
+        .args(paths)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))
+}
+
+/// Commits `paths` (the files the agent changed in this step) with a generated
+/// message and returns its subject. Other staged or modified files are left alone.
+/// Git hooks run unless `auto_commit_no_verify` is set.
+pub async fn commit_agent_changes(cfg: &Config, paths: &[String]) -> Result<String> {
+    if paths.is_empty() {
+        bail!("no changed files to commit");
+    }
+    let add = git(&["add", "-A"], paths)?;
+    if !add.status.success() {
+        bail!(
+            "git add failed: {}",
+            String::from_utf8_lossy(&add.stderr).trim()
+        );
+    }
+    let diff = git(
+        &["diff", "--cached", "--no-color", "--stat", "--patch"],
+        paths,
+    )?;
+    let diff = String::from_utf8_lossy(&diff.stdout).to_string();
+    let message = match commit_message(cfg, &diff).await {
+        Ok(message) => message,
+        Err(err) => {
+            log_verbose(
+                "auto-commit",
+                &format!("message generation failed: {err:#}"),
+            );
+            fallback_message(paths)
+        }
+    };
+    let message = format!("{} {}", AUTO_COMMIT_PREFIX, message);
+    let mut args = vec!["commit", "-q"];
+    if cfg.auto_commit_no_verify {
+        args.push("--no-verify");
+    }
+    args.extend(["-m", &message]);
+    let commit = git(&args, paths)?;
+    if !commit.status.success() {
+        bail!(
+            "git commit failed: {}",
+            String::from_utf8_lossy(&commit.stderr).trim()
+        );
+    }
+    Ok(message.lines().next().unwrap_or_default().to_string())
+}
+
+/// Message from `utility_model` when configured, else the active model.
+async fn commit_message(cfg: &Config, diff: &str) -> Result<String> {
+    let message = match utility_model(cfg, None) {
+        Some(model) => generate_commit_message(&config_for_model(cfg, model), diff, false).await?,
+        None => generate_commit_message(cfg, diff, false).await?,
+    };
+    if message.trim().is_empty() {
+        bail!("empty commit message");
+    }
+    Ok(message)
+}
+
+fn fallback_message(paths: &[String]) -> String {
+    let shown: Vec<&str> = paths.iter().take(3).map(String::as_str).collect();
+    let more = paths.len().saturating_sub(shown.len());
+    if more > 0 {
+        format!("Update {} (+{} more)", shown.join(", "), more)
+    } else {
+        format!("Update {}", shown.join(", "))
+    }
+}
@@ -0,0 +1,310 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::config::config_dir;
+use crate::diagnostics::now_unix_ts;
+use crate::transcript::record_file_write;
+use crate::workspace_index::fnv1a;
+
+/// One saved copy of a file, stored as
+/// `~/.dongshan/backups/<workspace>/<timestamp>/<relative path>`.
+#[derive(Debug, Clone)]
+pub struct BackupEntry {
+    /// Unix timestamp of the backup directory.
+    pub ts: u64,
+    /// Path relative to the workspace (or `_abs/...` for outside files).
+    pub rel: PathBuf,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+pub fn backups_root() -> Result<PathBuf> {
+    Ok(config_dir()?.join("backups"))
+}
+
+/// Backup directory of the current workspace: `<dir name>-<path hash>`.
+pub fn workspace_backup_dir() -> Result<PathBuf> {
+    let root = workspace_root()?;
+    let name = root
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(sanitize_part)
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "root".to_string());
+    let hash = fnv1a(root.to_string_lossy().as_bytes());
+    Ok(backups_root()?.join(format!("{}-{:08x}", name, hash as u32)))
+}
+
+fn workspace_root() -> Result<PathBuf> {
+    let cwd = std::env::current_dir().context("Failed to resolve current directory")?;
+    Ok(cwd.canonicalize().unwrap_or(cwd))
+}
+
+/// Key under which `file` is stored inside a timestamp directory.
+fn relative_key(file: &Path) -> Result<PathBuf> {
+    let root = workspace_root()?;
+    let abs = if file.is_absolute() {
+        file.to_path_buf()
+    } else {
+        root.join(file)
+    };
+    let abs = abs.canonicalize().unwrap_or(abs);
+    if let Ok(rel) = abs.strip_prefix(&root) {
+        return Ok(rel.to_path_buf());
+    }
+    let mut key = PathBuf::from("_abs");
+    for part in abs.components() {
+        if let Component::Normal(p) = part {
+            key.push(sanitize_part(&p.to_string_lossy()));
+        }
+    }
+    Ok(key)
+}
+
+fn sanitize_part(part: &str) -> String {
+    part.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Saves `content` as the newest backup of `file` and returns the backup path.
+pub fn save_backup(file: &Path, content: impl AsRef<[u8]>) -> Result<PathBuf> {
+    let rel = relative_key(file)?;
+    let ws_dir = workspace_backup_dir()?;
+    // Several edits of the same file within one second get consecutive stamps.
+    let mut ts = now_unix_ts();
+    while ws_dir.join(ts.to_string()).join(&rel).exists() {
+        ts += 1;
+    }
+    let path = ws_dir.join(ts.to_string()).join(&rel);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Written next to a timestamp directory (`<ts>.checkpoint.json`) when the
+/// backups in it belong to one multi-file change that can be rolled back.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Command that made the change, e.g. `refactor`.
+    #[serde(default)]
+    pub kind: String,
+    pub label: String,
+    pub ts: u64,
+    /// Workspace-relative paths saved in the directory.
+    pub files: Vec<PathBuf>,
+}
+
+fn checkpoint_manifest(ws_dir: &Path, ts: u64) -> PathBuf {
+    ws_dir.join(format!("{}.checkpoint.json", ts))
+}
+
+/// Saves the current content of every file (all inside the workspace) under
+/// one timestamp, so the whole change can be reverted with [`rollback_checkpoint`].
+pub fn save_checkpoint(kind: &str, label: &str, files: &[(PathBuf, String)]) -> Result<Checkpoint> {
+    let root = workspace_root()?;
+    if let Some((file, _)) = files
+        .iter()
+        .find(|(f, _)| f.is_absolute() && !f.starts_with(&root))
+    {
+        bail!("{} is outside the workspace", file.display());
+    }
+    let ws_dir = workspace_backup_dir()?;
+    let mut ts = now_unix_ts();
+    while ws_dir.join(ts.to_string()).exists() || checkpoint_manifest(&ws_dir, ts).exists() {
+        ts += 1;
+    }
+    let mut saved = Vec::new();
+    for (file, content) in files {
+        let rel = relative_key(file)?;
+        let path = ws_dir.join(ts.to_string()).join(&rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        saved.push(rel);
+    }
+    let checkpoint = Checkpoint {
+        kind: kind.to_string(),
+        label: label.to_string(),
+        ts,
+        files: saved,
+    };
+    let manifest = checkpoint_manifest(&ws_dir, ts);
+    fs::write(&manifest, serde_json::to_string_pretty(&checkpoint)?)
+        .with_context(|| format!("Failed to write {}", manifest.display()))?;
+    Ok(checkpoint)
+}
+
+/// Newest checkpoint of `kind` in the current workspace that has not been
+/// rolled back.
+pub fn latest_checkpoint(kind: &str) -> Result<Option<Checkpoint>> {
+    let ws_dir = workspace_backup_dir()?;
+    let mut dirs = timestamp_dirs(&ws_dir)?;
+    dirs.sort_by_key(|(ts, _)| std::cmp::Reverse(*ts));
+    for (ts, _) in dirs {
+        let manifest = checkpoint_manifest(&ws_dir, ts);
+        if let Ok(text) = fs::read_to_string(&manifest) {
+            let checkpoint: Checkpoint = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid checkpoint {}", manifest.display()))?;
+            if checkpoint.kind == kind {
+                return Ok(Some(checkpoint));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Restores every file of the newest checkpoint of `kind`. Current contents
+/// are backed up first; the checkpoint is consumed, its backups stay listed.
+pub fn rollback_checkpoint(kind: &str) -> Result<Option<Checkpoint>> {
+    let Some(checkpoint) = latest_checkpoint(kind)? else {
+        return Ok(None);
+    };
+    let ws_dir = workspace_backup_dir()?;
+    let root = workspace_root()?;
+    for rel in &checkpoint.files {
+        let saved = ws_dir.join(checkpoint.ts.to_string()).join(rel);
+        let content =
+            fs::read(&saved).with_context(|| format!("Failed to read {}", saved.display()))?;
+        let file = root.join(rel);
+        if let Ok(current) = fs::read(&file)
+            && current != content
+        {
+            save_backup(&file, &current)?;
+        }
+        fs::write(&file, &content)
+            .with_context(|| format!("Failed to write {}", file.display()))?;
+        record_file_write("rollback", &file, Some(content.len()));
+    }
+    let manifest = checkpoint_manifest(&ws_dir, checkpoint.ts);
+    fs::remove_file(&manifest)
+        .with_context(|| format!("Failed to remove {}", manifest.display()))?;
+    Ok(Some(checkpoint))
+}
+
+/// Backups in the current workspace, newest first; only those of `file` when given.
+pub fn list_backups(file: Option<&Path>) -> Result<Vec<BackupEntry>> {
+    let ws_dir = workspace_backup_dir()?;
+    let only = file.map(relative_key).transpose()?;
+    let mut entries = Vec::new();
+    for (ts, dir) in timestamp_dirs(&ws_dir)? {
+        match &only {
+            Some(rel) => {
+                let path = dir.join(rel);
+                if path.is_file() {
+                    entries.push(entry(ts, rel.clone(), path));
+                }
+            }
+            None => collect_files(&dir, &dir, ts, &mut entries),
+        }
+    }
+    entries.sort_by(|a, b| b.ts.cmp(&a.ts).then_with(|| a.rel.cmp(&b.rel)));
+    Ok(entries)
+}
+
+fn entry(ts: u64, rel: PathBuf, path: PathBuf) -> BackupEntry {
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    BackupEntry {
+        ts,
+        rel,
+        path,
+        size,
+    }
+}
+
+fn collect_files(base: &Path, dir: &Path, ts: u64, out: &mut Vec<BackupEntry>) {
+    let Ok(read) = fs::read_dir(dir) else {
+        return;
+    };
+    for item in read.flatten() {
+        let path = item.path();
+        if path.is_dir() {
+            collect_files(base, &path, ts, out);
+        } else if let Ok(rel) = path.strip_prefix(base) {
+            out.push(entry(ts, rel.to_path_buf(), path.clone()));
+        }
+    }
+}
+
+fn timestamp_dirs(ws_dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    if !ws_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut dirs = Vec::new();
+    for item in
+        fs::read_dir(ws_dir).with_context(|| format!("Failed to read {}", ws_dir.display()))?
+    {
+        let path = item?.path();
+        let ts = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(ts) = ts
+            && path.is_dir()
+        {
+            dirs.push((ts, path));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Picks backup `version` of `file` (1 = newest).
+pub fn find_backup(file: &Path, version: usize) -> Result<BackupEntry> {
+    let entries = list_backups(Some(file))?;
+    if entries.is_empty() {
+        bail!("No backups of {} in this workspace", file.display());
+    }
+    if version == 0 || version > entries.len() {
+        bail!(
+            "Backup version {} does not exist; {} has {} (1 = newest)",
+            version,
+            file.display(),
+            entries.len()
+        );
+    }
+    Ok(entries[version - 1].clone())
+}
+
+/// Deletes backup directories older than `cutoff` in every workspace and
+/// returns how many files were removed.
+pub fn clean_backups(cutoff: u64) -> Result<usize> {
+    let root = backups_root()?;
+    if !root.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for ws in fs::read_dir(&root).with_context(|| format!("Failed to read {}", root.display()))? {
+        let ws_dir = ws?.path();
+        if !ws_dir.is_dir() {
+            continue;
+        }
+        for (ts, dir) in timestamp_dirs(&ws_dir)? {
+            if ts >= cutoff {
+                continue;
+            }
+            let mut files = Vec::new();
+            collect_files(&dir, &dir, ts, &mut files);
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+            removed += files.len();
+        }
+        if fs::read_dir(&ws_dir).is_ok_and(|mut d| d.next().is_none()) {
+            let _ = fs::remove_dir(&ws_dir);
+        }
+    }
+    Ok(removed)
+}
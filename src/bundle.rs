@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, config_path, load_config_or_default, save_config};
+use crate::diagnostics::now_unix_ts;
+use crate::prompt_store::{PromptDoc, get_prompt, list_prompts, save_prompt_with_source};
+
+const BUNDLE_FORMAT: u32 = 1;
+/// Entries larger than this are rejected on import.
+const MAX_ENTRY_BYTES: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    dongshan_version: String,
+    created_unix: u64,
+    secrets_included: bool,
+    prompts: Vec<String>,
+}
+
+pub struct ExportSummary {
+    pub prompts: usize,
+    pub models: usize,
+    pub secrets_included: bool,
+}
+
+pub struct ImportSummary {
+    pub config_replaced: bool,
+    pub config_backup: Option<String>,
+    pub prompts_added: Vec<String>,
+    pub prompts_replaced: Vec<String>,
+    pub prompts_skipped: Vec<String>,
+    pub secrets_included: bool,
+}
+
+/// Clears API keys, the webhook URL (which usually embeds a token) and
+/// `exec_env`, whose values are often credentials for the commands.
+fn strip_secrets(cfg: &mut Config) {
+    cfg.api_key = None;
+    for profile in cfg.model_profiles.values_mut() {
+        profile.api_key = None;
+    }
+    cfg.notify_webhook_url = None;
+    cfg.exec_env.clear();
+}
+
+/// Keeps the local secrets when the imported config was exported without them.
+fn keep_local_secrets(imported: &mut Config, local: &Config) {
+    if imported.api_key.is_none() {
+        imported.api_key = local.api_key.clone();
+    }
+    for (name, profile) in imported.model_profiles.iter_mut() {
+        if profile.api_key.is_none()
+            && let Some(local_profile) = local.model_profiles.get(name)
+        {
+            profile.api_key = local_profile.api_key.clone();
+        }
+    }
+    if imported.notify_webhook_url.is_none() {
+        imported.notify_webhook_url = local.notify_webhook_url.clone();
+    }
+    if imported.exec_env.is_empty() {
+        imported.exec_env = local.exec_env.clone();
+    }
+}
+
+fn append_file(
+    builder: &mut tar::Builder<GzEncoder<File>>,
+    path: &str,
+    data: &[u8],
+    mtime: u64,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, path, data)
+        .with_context(|| format!("Failed to add {} to bundle", path))
+}
+
+/// Writes config, model catalog and prompts to a `.tar.gz` at `out`. Secrets
+/// are left out unless `include_secrets` is set.
+pub fn export_bundle(out: &Path, include_secrets: bool) -> Result<ExportSummary> {
+    let mut cfg = load_config_or_default()?;
+    if !include_secrets {
+        strip_secrets(&mut cfg);
+    }
+    let prompts = list_prompts()?;
+    let now = now_unix_ts();
+    let manifest = Manifest {
+        format: BUNDLE_FORMAT,
+        dongshan_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_unix: now,
+        secrets_included: include_secrets,
+        prompts: prompts.iter().map(|p| p.name().to_string()).collect(),
+    };
+
+    if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let file = File::create(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    append_file(
+        &mut builder,
+        "manifest.json",
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+        now,
+    )?;
+    append_file(
+        &mut builder,
+        "config.toml",
+        toml::to_string_pretty(&cfg)?.as_bytes(),
+        now,
+    )?;
+    for (i, doc) in prompts.iter().enumerate() {
+        append_file(
+            &mut builder,
+            &format!("prompts/{:03}.json", i),
+            serde_json::to_string_pretty(doc)?.as_bytes(),
+            now,
+        )?;
+    }
+    builder
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .with_context(|| format!("Failed to write {}", out.display()))?;
+
+    Ok(ExportSummary {
+        prompts: prompts.len(),
+        models: cfg.model_catalog.len(),
+        secrets_included: include_secrets,
+    })
+}
+
+/// Reads the bundle entries into memory; nothing is extracted to disk, so
+/// entry paths in the archive cannot point outside `~/.dongshan`.
+fn read_entries(file: &Path) -> Result<BTreeMap<String, String>> {
+    let f = File::open(file).with_context(|| format!("Failed to open {}", file.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(f));
+    let mut out = BTreeMap::new();
+    let entries = archive
+        .entries()
+        .with_context(|| format!("{} is not a dongshan bundle (.tar.gz)", file.display()))?;
+    for entry in entries {
+        let entry = entry.context("Corrupt bundle entry")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().replace('\\', "/");
+        if entry.size() > MAX_ENTRY_BYTES {
+            bail!(
+                "Bundle entry {} is too large ({} bytes)",
+                path,
+                entry.size()
+            );
+        }
+        let mut text = String::new();
+        entry
+            .take(MAX_ENTRY_BYTES)
+            .read_to_string(&mut text)
+            .with_context(|| format!("Bundle entry {} is not UTF-8 text", path))?;
+        out.insert(path, text);
+    }
+    Ok(out)
+}
+
+/// Applies a bundle written by [`export_bundle`]. The current config is saved
+/// as `config.toml.bak` before it is replaced; prompts that already exist are
+/// kept unless `replace_prompts` is set.
+pub fn import_bundle(
+    file: &Path,
+    skip_config: bool,
+    replace_prompts: bool,
+) -> Result<ImportSummary> {
+    let entries = read_entries(file)?;
+    let manifest: Manifest = serde_json::from_str(
+        entries
+            .get("manifest.json")
+            .context("Bundle has no manifest.json")?,
+    )
+    .context("Invalid manifest.json in bundle")?;
+    if manifest.format > BUNDLE_FORMAT {
+        bail!(
+            "Bundle format {} is newer than this dongshan supports ({}); upgrade dongshan first",
+            manifest.format,
+            BUNDLE_FORMAT
+        );
+    }
+
+    let mut summary = ImportSummary {
+        config_replaced: false,
+        config_backup: None,
+        prompts_added: Vec::new(),
+        prompts_replaced: Vec::new(),
+        prompts_skipped: Vec::new(),
+        secrets_included: manifest.secrets_included,
+    };
+
+    if !skip_config && let Some(text) = entries.get("config.toml") {
+        let mut imported: Config = toml::from_str(text).context("Invalid config.toml in bundle")?;
+        let local = load_config_or_default()?;
+        keep_local_secrets(&mut imported, &local);
+        let path = config_path()?;
+        if path.exists() {
+            let backup = path.with_extension("toml.bak");
+            fs::copy(&path, &backup)
+                .with_context(|| format!("Failed to back up {}", path.display()))?;
+            summary.config_backup = Some(backup.display().to_string());
+        }
+        save_config(&imported)?;
+        summary.config_replaced = true;
+    }
+
+    for (path, text) in &entries {
+        if !path.starts_with("prompts/") || !path.ends_with(".json") {
+            continue;
+        }
+        let doc: PromptDoc = serde_json::from_str(text)
+            .with_context(|| format!("Invalid prompt {} in bundle", path))?;
+        let name = doc.name().trim().to_string();
+        if name.is_empty() {
+            continue;
+        }
+        match get_prompt(&name)? {
+            Some(existing) if existing == doc.content() => {}
+            Some(_) if !replace_prompts => summary.prompts_skipped.push(name),
+            Some(_) => {
+                save_prompt_with_source(&name, doc.content(), doc.source().cloned())?;
+                summary.prompts_replaced.push(name);
+            }
+            None => {
+                save_prompt_with_source(&name, doc.content(), doc.source().cloned())?;
+                summary.prompts_added.push(name);
+            }
+        }
+    }
+    Ok(summary)
+}
@@ -0,0 +1,156 @@
+/// Unified-style diff built from the change report's hunks.
+pub fn render_change_diff(original: &str, edited: &str) -> String {
+    let old_lines: Vec<&str> = original.lines().collect();
+    let new_lines: Vec<&str> = edited.lines().collect();
+    let mut out = String::new();
+    for chunk in build_change_report(original, edited).chunks {
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            chunk.old_start, chunk.old_len, chunk.new_start, chunk.new_len
+        ));
+        let old_start = chunk.old_start - 1;
+        let new_start = chunk.new_start - 1;
+        for line in &old_lines[old_start..(old_start + chunk.old_len).min(old_lines.len())] {
+            out.push_str(&format!("-{}\n", line));
+        }
+        for line in &new_lines[new_start..(new_start + chunk.new_len).min(new_lines.len())] {
+            out.push_str(&format!("+{}\n", line));
+        }
+    }
+    out
+}
+
+/// One changed region, 1-based, in the old and the new text.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeChunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ChangeReport {
+    pub chunks: Vec<ChangeChunk>,
+    pub inserted_lines: usize,
+    pub deleted_lines: usize,
+}
+
+/// Line-level hunks between two versions of a file; nearby hunks are merged.
+pub fn build_change_report(original: &str, edited: &str) -> ChangeReport {
+    let old_lines: Vec<&str> = original.lines().collect();
+    let new_lines: Vec<&str> = edited.lines().collect();
+    let mut i = 0usize;
+    let mut j = 0usize;
+    let mut chunks = Vec::new();
+
+    while i < old_lines.len() && j < new_lines.len() {
+        if old_lines[i] == new_lines[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let (di, dj) = find_next_anchor(&old_lines, &new_lines, i, j);
+        if di.is_none() || dj.is_none() {
+            chunks.push(ChangeChunk {
+                old_start: i + 1,
+                old_len: old_lines.len() - i,
+                new_start: j + 1,
+                new_len: new_lines.len() - j,
+            });
+            i = old_lines.len();
+            j = new_lines.len();
+            break;
+        }
+
+        let di = di.unwrap_or(0);
+        let dj = dj.unwrap_or(0);
+        if di > 0 || dj > 0 {
+            chunks.push(ChangeChunk {
+                old_start: i + 1,
+                old_len: di,
+                new_start: j + 1,
+                new_len: dj,
+            });
+        }
+        i += di;
+        j += dj;
+    }
+
+    if i < old_lines.len() || j < new_lines.len() {
+        chunks.push(ChangeChunk {
+            old_start: i + 1,
+            old_len: old_lines.len().saturating_sub(i),
+            new_start: j + 1,
+            new_len: new_lines.len().saturating_sub(j),
+        });
+    }
+
+    let mut merged = Vec::new();
+    for chunk in chunks {
+        if let Some(last) = merged.last_mut()
+            && are_adjacent(*last, chunk)
+        {
+            let last_old_end = last.old_start + last.old_len;
+            let last_new_end = last.new_start + last.new_len;
+            let chunk_old_end = chunk.old_start + chunk.old_len;
+            let chunk_new_end = chunk.new_start + chunk.new_len;
+            last.old_len = chunk_old_end
+                .saturating_sub(last.old_start)
+                .max(last_old_end - last.old_start);
+            last.new_len = chunk_new_end
+                .saturating_sub(last.new_start)
+                .max(last_new_end - last.new_start);
+            continue;
+        }
+        merged.push(chunk);
+    }
+
+    let inserted_lines = merged.iter().map(|c| c.new_len).sum::<usize>();
+    let deleted_lines = merged.iter().map(|c| c.old_len).sum::<usize>();
+
+    ChangeReport {
+        chunks: merged,
+        inserted_lines,
+        deleted_lines,
+    }
+}
+
+fn find_next_anchor(
+    old_lines: &[&str],
+    new_lines: &[&str],
+    i: usize,
+    j: usize,
+) -> (Option<usize>, Option<usize>) {
+    const LOOKAHEAD: usize = 80;
+    let mut best: Option<(usize, usize, usize)> = None;
+    let old_max = (old_lines.len() - i).min(LOOKAHEAD + 1);
+    let new_max = (new_lines.len() - j).min(LOOKAHEAD + 1);
+
+    for di in 0..old_max {
+        for dj in 0..new_max {
+            if old_lines[i + di] != new_lines[j + dj] {
+                continue;
+            }
+            let score = di + dj;
+            match best {
+                None => best = Some((score, di, dj)),
+                Some((best_score, _, _)) if score < best_score => best = Some((score, di, dj)),
+                _ => {}
+            }
+        }
+    }
+
+    if let Some((_, di, dj)) = best {
+        (Some(di), Some(dj))
+    } else {
+        (None, None)
+    }
+}
+
+fn are_adjacent(left: ChangeChunk, right: ChangeChunk) -> bool {
+    let left_old_end = left.old_start + left.old_len;
+    let left_new_end = left.new_start + left.new_len;
+    right.old_start <= left_old_end + 1 && right.new_start <= left_new_end + 1
+}
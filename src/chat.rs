@@ -5,37 +5,86 @@ use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::{Mutex, OnceLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result, bail};
 use encoding_rs::GBK;
 use serde::Serialize;
 use serde_json::{Value, json};
 
+use crate::approval::{
+    NetworkAccess, approval_level, approval_policy, is_network_command, is_read_only_command,
+};
+use crate::auto_commit::commit_agent_changes;
 use crate::chat_context::augment_user_input_with_workspace_context;
+use crate::checkpoint::{UndoAction, remember_command, remember_file, start_turn, undo_last_turn};
+use crate::ci::{ci_event, is_ci};
+use crate::commands::run_compare;
 use crate::config::{
-    AutoExecMode, Config, ModelApiProvider, ToolCallMode, active_effective_tool_mode,
-    build_system_prompt, config_dir, current_prompt_text, ensure_model_catalog, save_config,
-    set_active_model, set_model_tool_mode,
+    ApprovalLevel, AutoExecMode, Config, ModelApiProvider, ToolCallMode,
+    active_effective_tool_mode, add_model_with_active_profile, build_system_prompt,
+    config_for_model, current_prompt_text, ensure_model_catalog, history_budget_chars,
+    model_context_window, prompt_name, set_active_model, set_model_tool_mode, update_config,
+    utility_model,
 };
 use crate::diagnostics::{
     LastDiagnostic, TurnArtifact, now_unix_ts, read_last_diagnostic, write_last_diagnostic,
     write_turn_artifact,
 };
+use crate::exec_rules::{denying_rule, matches_exec_rules};
+use crate::file_chunks::{needs_chunking, summarize_file_chunks};
+use crate::file_index::indexed_files;
 use crate::fs_tools::{
-    grep_output, grep_recursive, list_files_output, list_files_recursive, read_text_file,
-    try_rg_files, try_rg_grep,
+    SearchOptions, display_path, grep_output, list_files_output, read_image_data_url,
+    read_text_file, search_workspace, workspace_files,
 };
+use crate::fuzzy_patch::{EditHit, apply_edit};
+use crate::i18n::{t, tf};
+use crate::ignore_rules::{IGNORE_FILE, is_dongshan_ignored};
+use crate::injection::guard_untrusted;
+use crate::line_editor::{LineEditor, compose_in_editor};
 use crate::llm::{
-    ChatMessage, NativeFunctionCall, build_openai_messages, call_llm_with_history,
-    call_llm_with_history_stream_tools, call_llm_with_messages_native_tools,
+    ChatMessage, NativeFunctionCall, build_openai_messages, call_llm, call_llm_with_history,
+    call_llm_with_history_stream_tools, call_llm_with_messages_native_tools, effective_seed,
 };
+use crate::logging::log_debug;
+use crate::memory::{forget, remember};
+use crate::notebook::{is_notebook, merge_notebook_edit, render_notebook};
+use crate::notify::{AgentRunSummary, notify_agent_finished, notify_desktop_if_slow};
 use crate::prompt_store::list_prompt_names;
+use crate::python_tool::run_python;
+use crate::redact::redact_secrets;
+use crate::repo_map::build_repo_map;
+use crate::run_report::{
+    RunOutcome, note_command, note_reply, note_verification, write_run_report,
+};
+use crate::session_meta::{
+    load_session_meta, record_session_seed, remove_session_meta, update_session_meta,
+    use_session_model, use_session_prompt,
+};
+use crate::shell::shell_command;
+use crate::storage::storage;
+use crate::test_failures::summarize_test_failures;
+use crate::tool_calls::{
+    ApplyPatchArgs, CreateFileArgs, DeleteArgs, EditFileArgs, GrepArgs, HttpGetArgs, ListFilesArgs,
+    MoveArgs, ParsedToolCall, PythonArgs, ReadFileArgs, ToolCall, ToolCallError, WebFetchArgs,
+};
+use crate::tool_output::fit_tool_output;
+use crate::transcript::{
+    record_agent_run, record_command, record_file_change, record_file_write, record_tool_call,
+    set_transcript_session,
+};
+use crate::trust::{ensure_workspace_trust, is_workspace_trusted, prompt_workspace_trust};
+use crate::usage::{
+    begin_usage_turn, estimate_tokens, is_budget_declined, reset_tracked_spend, tracked_turns,
+};
 use crate::util::{
-    WorkingStatus, ask, ask_or_eof, color_blue, color_cyan, color_dim, color_green, color_red,
-    color_rust, color_yellow, prefix_chars, print_startup_banner, render_markdown_terminal, tagged_prompt,
+    WorkingStatus, ask, color_blue, color_bold, color_cyan, color_dim, color_green, color_red,
+    color_rust, color_yellow, markdown_enabled, prefix_chars, print_paged, print_startup_banner, render_markdown_terminal, run_blocking, tagged_prompt,
     truncate_preview, truncate_with_suffix,
 };
+use crate::web_fetch::{fetch_url, http_get, is_fetch_allowed, is_http_allowed};
+use crate::write_jail::{find_write_outside_jail, in_extra_root, report_blocked};
 const MAX_AUTO_TOOL_STEPS: usize = 3;
 const MAX_COMMANDS_PER_RESPONSE: usize = 8;
 const MAX_FAILED_COMMANDS_PER_RESPONSE: usize = 2;
@@ -43,10 +92,22 @@ const MAX_INVALID_FORMAT_RETRIES: usize = 2;
 const MAX_WRITE_TASK_RETRIES: usize = 2;
 const MAX_WRITE_CLAIM_RETRIES: usize = 1;
 const MAX_DIFF_PREVIEW_FILES: usize = 20;
+/// Messages `/compact` keeps verbatim after the summary.
+const COMPACT_KEEP_MESSAGES: usize = 2;
+/// `/diff` output added to context is clipped to this many chars.
+const MAX_DIFF_CONTEXT_CHARS: usize = 20_000;
+/// `fs.read_file` returns at most this many lines per call; page with `offset`.
+const READ_MAX_LINES: usize = 2000;
+/// Default `max_results` of `fs.list_files` and `fs.grep`.
+const LIST_DEFAULT_RESULTS: usize = 500;
+const GREP_DEFAULT_RESULTS: usize = 200;
+/// Upper bound on `max_results` the model may request.
+const TOOL_MAX_RESULTS: usize = 2000;
 const MAX_FS_PREVIEW_TEXT_BYTES: usize = 1400;
 const MAX_FS_SNAPSHOT_HASH_BYTES: u64 = 1_000_000;
 const STRICT_TOOL_CALL_INSTRUCTION: &str = "You must execute using strict JSON tool_calls only. Allowed format example: {\"tool_calls\":[{\"tool\":\"fs_create_file\",\"args\":{\"path\":\"analysis.md\",\"content\":\"...\"}}]}. Do not output <think>, code_execution, or markdown code fences.";
 const WRITE_TASK_RETRY_MSG: &str = "The user asked you to create or modify files. Do not ask the user to save manually. You must execute tool_calls to write files in workspace, then report result. Use strict JSON tool_calls only.";
+const TOOL_ERROR_RETRY_MSG: &str = "None of your tool calls ran. Fix the errors reported above (check argument names and types) and call the tools again.";
 const WRITE_CLAIM_RETRY_MSG: &str = "You claimed file creation/update, but no file changes were detected. Do not claim success unless a real tool call has executed and changed files. Now execute required tool_calls to create/update the target file using strict JSON only.";
 
 #[derive(Default, Clone)]
@@ -56,11 +117,13 @@ struct DiffPreviewCache {
 }
 
 static DIFF_PREVIEW_CACHE: OnceLock<Mutex<DiffPreviewCache>> = OnceLock::new();
+/// Result of the latest auto verification, e.g. "cargo: ok".
+static LAST_VERIFICATION: Mutex<Option<String>> = Mutex::new(None);
+static AUTO_COMMITTED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
 static FS_BASELINE_SNAPSHOT: OnceLock<Mutex<Option<BTreeMap<String, FsEntry>>>> = OnceLock::new();
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct FsEntry {
-    is_dir: bool,
     size: u64,
     mtime_unix: i64,
     digest: String,
@@ -95,24 +158,75 @@ impl ChatExecutionMode {
 pub async fn run_chat(mut cfg: Config, session: &str) -> Result<()> {
     let mut active_session = resolve_session_name(session)?;
     let mut exec_mode = ChatExecutionMode::AgentAuto;
-    let mut render_markdown = true;
+    if !ensure_workspace_trust(&cfg)? {
+        exec_mode = ChatExecutionMode::ChatOnly;
+    }
+    let mut render_markdown = markdown_enabled();
+    load_session_meta(&active_session)?.apply(&mut cfg);
+    record_session_seed(&active_session, effective_seed(&cfg))?;
+    set_transcript_session(&active_session);
     print_startup_banner(&active_session, &cfg.model, exec_mode.as_str());
     let mut history = load_session_or_default(&active_session)?;
+    let mut line_editor = LineEditor::new();
     loop {
         println!("\n{}", color_dim("────────────────────────────────────────────────────────────────────────────────────────────────────────────────────────"));
-        let Some(input) = ask_or_eof(&format!("{} ", color_rust("● you>")))? else {
+        let Some(input) = line_editor.read_line(&format!("{} ", color_rust("● you>")))? else {
             break;
         };
+        start_turn();
+        let input = match input.trim().strip_prefix("/editor") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+                match compose_in_editor(rest.trim()) {
+                    Ok(Some(text)) => {
+                        println!(
+                            "{}",
+                            color_dim(&tf(
+                                "chat.editor_sent",
+                                &[&text.lines().count().to_string()]
+                            ))
+                        );
+                        text
+                    }
+                    Ok(None) => {
+                        println!("{}", t("chat.editor_empty"));
+                        continue;
+                    }
+                    Err(err) => {
+                        println!("{}", color_red(&format!("{err:#}")));
+                        continue;
+                    }
+                }
+            }
+            _ => input,
+        };
         if input.trim().eq_ignore_ascii_case("/exit") {
             break;
         }
         if input.trim().is_empty() {
             continue;
         }
+        let mut persona = None;
+        let input = match parse_as_command(&input) {
+            Some((name, message)) if name.is_empty() || message.is_empty() => {
+                println!("{}", t("chat.persona_usage"));
+                continue;
+            }
+            Some((name, message)) => {
+                if !list_prompt_names().unwrap_or_default().contains(&name) {
+                    println!("{}", tf("chat.persona_missing", &[&name]));
+                    continue;
+                }
+                println!("{}", color_dim(&tf("chat.persona", &[&name])));
+                persona = Some(name);
+                message
+            }
+            None => input,
+        };
+        begin_usage_turn(input.trim());
         let changed_before = current_changed_file_set()?;
 
-        if input.trim_start().starts_with('/') {
-            handle_chat_slash_command(
+        if persona.is_none() && input.trim_start().starts_with('/') {
+            let handled = handle_chat_slash_command(
                 input.trim(),
                 &mut cfg,
                 &mut history,
@@ -120,19 +234,25 @@ pub async fn run_chat(mut cfg: Config, session: &str) -> Result<()> {
                 &mut exec_mode,
                 &mut render_markdown,
             )
-            .await?;
+            .await;
+            skip_declined_turn(handled)?;
             save_session(&active_session, &history)?;
             print_changed_files_delta(&changed_before)?;
             continue;
         }
 
-        if handle_natural_language_tool_command(
-            input.trim(),
-            &mut cfg,
-            &mut history,
-            render_markdown,
-        )
-        .await?
+        if persona.is_none()
+            && skip_declined_turn(
+                handle_natural_language_tool_command(
+                    input.trim(),
+                    &mut cfg,
+                    &mut history,
+                    &active_session,
+                    render_markdown,
+                )
+                .await,
+            )?
+            .is_none_or(|handled| handled)
         {
             save_session(&active_session, &history)?;
             print_changed_files_delta(&changed_before)?;
@@ -145,16 +265,21 @@ pub async fn run_chat(mut cfg: Config, session: &str) -> Result<()> {
             Err(_) => should_use_agent_for_input(input.trim(), exec_mode),
         };
 
-        let ctx_working = WorkingStatus::start("collecting workspace context");
-        let augmented_input = augment_user_input_with_workspace_context(&input)?;
+        let turn_started = Instant::now();
+        let ctx_working = WorkingStatus::start(t("status.collecting_context"));
+        let augmented = augment_user_input_with_workspace_context(&cfg, &input).await;
         ctx_working.finish();
-        history.push(ChatMessage {
-            role: "user".to_string(),
-            content: augmented_input,
-        });
-
-        maybe_compact_history(&mut history, &cfg);
-        if use_agent {
+        let Some(augmented_input) = skip_declined_turn(augmented)? else {
+            continue;
+        };
+        let history_len = history.len();
+        history.push(ChatMessage::text("user", augmented_input));
+
+        maybe_compact_history(&mut history, &cfg).await;
+        cfg.review_turn = is_review_request(input.trim());
+        // `/as <prompt>` swaps the system prompt for this turn only.
+        cfg.turn_prompt = persona;
+        let turn = if use_agent {
             run_agent_turn(
                 &mut cfg,
                 &mut history,
@@ -162,21 +287,47 @@ pub async fn run_chat(mut cfg: Config, session: &str) -> Result<()> {
                 Some(&active_session),
                 render_markdown,
             )
-            .await?;
+            .await
         } else {
-            run_chat_turn(&mut cfg, &mut history, "chat-lite", render_markdown).await?;
+            run_chat_turn(&mut cfg, &mut history, "chat-lite", render_markdown).await
+        };
+        cfg.turn_prompt = None;
+        cfg.review_turn = false;
+        release_sent_images(&mut history);
+        if skip_declined_turn(turn)?.is_none() {
+            // Nothing was answered; drop the request so it is not replayed.
+            history.truncate(history_len);
         }
         save_session(&active_session, &history)?;
         print_changed_files_delta(&changed_before)?;
+        notify_desktop_if_slow(
+            &cfg,
+            turn_started.elapsed(),
+            t("notify.turn_done"),
+            input.trim(),
+        );
     }
 
     Ok(())
 }
 
+/// A declined cost budget ends the current turn (`None`) instead of the
+/// chat; other errors are passed on.
+fn skip_declined_turn<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Err(err) if is_budget_declined(&err) => {
+            println!("{}", color_yellow(&err.to_string()));
+            Ok(None)
+        }
+        other => other.map(Some),
+    }
+}
+
 async fn handle_natural_language_tool_command(
     input: &str,
     cfg: &mut Config,
     history: &mut Vec<ChatMessage>,
+    session: &str,
     render_markdown: bool,
 ) -> Result<bool> {
     let lower = input.to_lowercase();
@@ -206,9 +357,8 @@ async fn handle_natural_language_tool_command(
             println!("Prompt not found: {name}");
             return Ok(true);
         }
-        cfg.active_prompt = name.clone();
-        save_config(cfg)?;
-        let out = format!("Active prompt switched to '{}'.", name);
+        switch_session_prompt(cfg, session, &name)?;
+        let out = tf("chat.session_prompt", &[&name, &session]);
         println!("{out}");
         push_tool_result(history, input, "prompt.use", &out);
         return Ok(true);
@@ -243,9 +393,8 @@ async fn handle_natural_language_tool_command(
             println!("Model not found in catalog: {}", name);
             return Ok(true);
         }
-        set_active_model(cfg, &name);
-        save_config(cfg)?;
-        let out = format!("Active model switched to '{}'.", name);
+        switch_session_model(cfg, session, &name)?;
+        let out = tf("chat.session_model", &[&name, &session]);
         println!("{out}");
         push_tool_result(history, input, "model.use", &out);
         return Ok(true);
@@ -267,7 +416,12 @@ async fn handle_natural_language_tool_command(
                 submit_file_to_model(cfg, history, input, &path, render_markdown).await?;
             } else {
                 let content = read_text_file(Path::new(&path))?;
-                push_tool_result(history, input, "fs.read", &clip_output(&content, 8000));
+                push_tool_result(
+                    history,
+                    input,
+                    "fs.read",
+                    &fit_tool_output(cfg, "fs.read_file", &content),
+                );
                 println!(
                     "Read {} (content hidden). Ask a follow-up question to analyze it.",
                     path
@@ -279,9 +433,14 @@ async fn handle_natural_language_tool_command(
 
     if is_list_request(input, &lower) {
         let path = extract_path(input).unwrap_or_else(|| ".".to_string());
-        let out = list_files_output(Path::new(&path))?;
+        let out = run_blocking(move || list_files_output(Path::new(&path))).await?;
         print!("{out}");
-        push_tool_result(history, input, "fs.list", &clip_output(&out, 8000));
+        push_tool_result(
+            history,
+            input,
+            "fs.list",
+            &fit_tool_output(cfg, "fs.list_files", &out),
+        );
         return Ok(true);
     }
 
@@ -289,13 +448,18 @@ async fn handle_natural_language_tool_command(
         && let Some(pattern) = extract_search_pattern(input)
     {
         let path = extract_path(input).unwrap_or_else(|| ".".to_string());
-        let out = grep_output(Path::new(&path), &pattern)?;
+        let out = run_blocking(move || grep_output(Path::new(&path), &pattern)).await?;
         if out.trim().is_empty() {
             println!("No matches found.");
             push_tool_result(history, input, "fs.grep", "No matches found.");
         } else {
             print!("{out}");
-            push_tool_result(history, input, "fs.grep", &clip_output(&out, 8000));
+            push_tool_result(
+                history,
+                input,
+                "fs.grep",
+                &fit_tool_output(cfg, "fs.grep", &out),
+            );
         }
         return Ok(true);
     }
@@ -315,19 +479,36 @@ async fn submit_file_to_model(
         .extension()
         .and_then(|e| e.to_str())
         .unwrap_or("txt");
+    // Too large to send whole: summarize chunk by chunk, then answer from the summaries.
+    let file_section = if needs_chunking(cfg, &content) {
+        let chunked = summarize_file_chunks(cfg, path, user_request, &content).await?;
+        let note = chunked.note();
+        println!("{}", color_dim(&format!("{}: {}", path, note)));
+        format!(
+            "File: {} (too large to send whole; below are per-chunk summaries relevant to the request)\n\
+             Chunks: {}\n\n{}\n\n\
+             End the answer with a \"Chunks used:\" line naming the chunk numbers it relies on.",
+            path,
+            note,
+            chunked.render_summaries()
+        )
+    } else {
+        format!(
+            "File: {}\n```{}\n{}\n```",
+            path,
+            ext,
+            guard_untrusted(path, &redact_secrets(&content))
+        )
+    };
     let prompt = format!(
         "User asked to analyze this file and answer a concrete request.\n\
          Provide direct answer to user request first, then list supporting evidence from file.\n\
          Do not output shell commands unless user explicitly asks.\n\n\
-         Original user request:\n{}\n\n\
-         File: {}\n```{}\n{}\n```",
-        user_request, path, ext, content
+         Original user request:\n{}\n\n{}",
+        user_request, file_section
     );
-    history.push(ChatMessage {
-        role: "user".to_string(),
-        content: prompt,
-    });
-    maybe_compact_history(history, cfg);
+    history.push(ChatMessage::text("user", prompt));
+    maybe_compact_history(history, cfg).await;
     let system = build_system_prompt(cfg, "review");
     run_agent_turn_with_system(cfg, history, &system, None, render_markdown, false).await
 }
@@ -343,28 +524,65 @@ fn has_followup_analysis_intent(input: &str, lower: &str) -> bool {
         || input.contains("分析")
         || input.contains("觉得")
 }
+/// Switches the model for `session` only and records it in the session metadata;
+/// the global default in config.toml is left alone.
+fn switch_session_model(cfg: &mut Config, session: &str, model: &str) -> Result<()> {
+    use_session_model(cfg, model);
+    let chosen = cfg.global_model.is_some().then(|| model.to_string());
+    update_session_meta(session, |meta| meta.model = chosen)?;
+    // config.toml keeps its default model; only a new model joins the catalog.
+    update_config(|disk| add_model_with_active_profile(disk, model))
+}
+
+fn switch_session_prompt(cfg: &mut Config, session: &str, prompt: &str) -> Result<()> {
+    use_session_prompt(cfg, prompt);
+    let chosen = cfg.global_prompt.is_some().then(|| prompt.to_string());
+    update_session_meta(session, |meta| meta.prompt = chosen)
+}
+
+/// Drops image data from `history` once a turn has sent it, so later
+/// requests and the saved session do not carry it again.
+fn release_sent_images(history: &mut [ChatMessage]) {
+    for msg in history.iter_mut().filter(|m| !m.images.is_empty()) {
+        msg.images.clear();
+        msg.content
+            .push_str("\n(image sent in an earlier turn; use /image to attach it again)");
+    }
+}
+
 fn push_tool_result(history: &mut Vec<ChatMessage>, user_input: &str, tool: &str, output: &str) {
-    history.push(ChatMessage {
-        role: "user".to_string(),
-        content: user_input.to_string(),
-    });
-    history.push(ChatMessage {
-        role: "assistant".to_string(),
-        content: format!("tool[{tool}] output:\n{output}"),
-    });
+    history.push(ChatMessage::text("user", user_input));
+    history.push(ChatMessage::text(
+        "assistant",
+        format!(
+            "tool[{tool}] output:\n{}",
+            guard_untrusted(tool, &redact_secrets(output))
+        ),
+    ));
 }
 
 fn clip_output(text: &str, max_len: usize) -> String {
     truncate_with_suffix(text, max_len, "...\n[truncated]")
 }
 
-fn maybe_compact_history(history: &mut Vec<ChatMessage>, cfg: &Config) {
+/// Index of the message that started the latest turn: a user request (with its
+/// workspace context) or an attached image, never a tool-result feedback message.
+fn last_request_index(history: &[ChatMessage]) -> Option<usize> {
+    history.iter().rposition(|m| {
+        m.role == "user"
+            && (m.content.starts_with("Workspace CWD: ")
+                || m.content.starts_with("Attached image: "))
+    })
+}
+
+fn history_chars(history: &[ChatMessage]) -> usize {
+    history.iter().map(|m| m.content.chars().count()).sum()
+}
+
+async fn maybe_compact_history(history: &mut Vec<ChatMessage>, cfg: &Config) {
     let max_messages = cfg.history_max_messages.max(4);
-    let max_chars = cfg.history_max_chars.max(2000);
-    let total_chars = history
-        .iter()
-        .map(|m| m.content.chars().count())
-        .sum::<usize>();
+    let max_chars = history_budget_chars(cfg);
+    let total_chars = history_chars(history);
     if history.len() <= max_messages && total_chars <= max_chars {
         return;
     }
@@ -375,20 +593,41 @@ fn maybe_compact_history(history: &mut Vec<ChatMessage>, cfg: &Config) {
     let tail_keep = (max_messages / 2)
         .max(6)
         .min(history.len().saturating_sub(1));
+    compact_history(history, cfg, tail_keep, None).await;
+}
+
+/// Replaces all but the last `tail_keep` messages with a `[session-summary]`
+/// message and returns how many messages were summarized.
+async fn compact_history(
+    history: &mut Vec<ChatMessage>,
+    cfg: &Config,
+    tail_keep: usize,
+    instructions: Option<&str>,
+) -> usize {
     let split_at = history.len().saturating_sub(tail_keep);
     if split_at == 0 {
-        return;
+        return 0;
     }
 
     let older = &history[..split_at];
-    let summary = summarize_history(older);
+    let summary = match summarize_history_with_llm(cfg, older, instructions).await {
+        Ok(v) => v,
+        Err(err) => {
+            log_debug(
+                "compact",
+                &format!("LLM summary failed, using excerpt: {}", err),
+            );
+            summarize_history(older)
+        }
+    };
     let mut compacted = Vec::with_capacity(tail_keep + 1);
-    compacted.push(ChatMessage {
-        role: "assistant".to_string(),
-        content: format!("[session-summary]\n{}", summary),
-    });
+    compacted.push(ChatMessage::text(
+        "assistant",
+        format!("[session-summary]\n{}", summary),
+    ));
     compacted.extend_from_slice(&history[split_at..]);
     *history = compacted;
+    split_at
 }
 
 fn compact_native_messages(messages: &mut Vec<Value>, max_chars: usize) {
@@ -414,6 +653,55 @@ fn compact_native_messages(messages: &mut Vec<Value>, max_chars: usize) {
     }
 }
 
+/// Asks the model (or `summary_model`/`utility_model` when configured) for a structured summary
+/// of the compacted messages, so decisions, paths and TODOs survive compaction.
+async fn summarize_history_with_llm(
+    cfg: &Config,
+    messages: &[ChatMessage],
+    instructions: Option<&str>,
+) -> Result<String> {
+    let mut summary_cfg = cfg.clone();
+    if let Some(model) = utility_model(cfg, cfg.summary_model.as_deref()) {
+        set_active_model(&mut summary_cfg, model);
+    }
+    let mut transcript = String::new();
+    for m in messages {
+        let role = if m.role == "user" {
+            "user"
+        } else {
+            "assistant"
+        };
+        let body = truncate_with_suffix(m.content.trim(), 4000, " ...[clipped]");
+        transcript.push_str(&format!("[{}]\n{}\n\n", role, body));
+    }
+    let transcript = truncate_with_suffix(&transcript, 60_000, "\n...[transcript clipped]");
+    let system = "You compress coding-assistant conversations so work can continue without the original messages.\n\
+        Write a concise markdown summary with these sections:\n\
+        ## Goal\n## Decisions\n## Files (every path read, created or edited, with what changed)\n\
+        ## Commands and results\n## Open TODOs\n\
+        Keep exact file paths, identifiers, error messages and user preferences. Omit pleasantries. \
+        If an earlier [session-summary] is present, merge its facts instead of dropping them.";
+    let system = match instructions.map(str::trim).filter(|i| !i.is_empty()) {
+        Some(extra) => format!(
+            "{}\nAlso follow these instructions from the user:\n{}",
+            system, extra
+        ),
+        None => system.to_string(),
+    };
+    let working = WorkingStatus::start(t("status.compacting"));
+    let res = call_llm(&summary_cfg, &system, &transcript).await;
+    working.finish();
+    let summary = res?;
+    if summary.trim().is_empty() {
+        bail!("empty summary");
+    }
+    Ok(truncate_with_suffix(
+        summary.trim(),
+        8000,
+        "...\n[summary truncated]",
+    ))
+}
+
 fn summarize_history(messages: &[ChatMessage]) -> String {
     let mut lines = Vec::new();
     for m in messages.iter().rev().take(20).rev() {
@@ -502,6 +790,29 @@ fn parse_model_use(input: &str, lower: &str) -> Option<String> {
     None
 }
 
+/// Chat turns asking for a review also get `.dongshan/review-rules.md`.
+/// "review", "reviews", "reviewing", ... as a word of its own, so "preview"
+/// does not count, or 审查 / 评审.
+fn is_review_request(input: &str) -> bool {
+    input
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word.starts_with("review"))
+        || input.contains("审查")
+        || input.contains("评审")
+}
+
+/// Splits `/as <prompt> <message>` into the prompt name and the message.
+fn parse_as_command(input: &str) -> Option<(String, String)> {
+    let rest = input.trim_start().strip_prefix("/as")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let (name, message) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    Some((name.to_string(), message.trim().to_string()))
+}
+
 fn parse_prompt_use(input: &str, lower: &str) -> Option<String> {
     if let Some(idx) = lower.find("use prompt ") {
         let name = input[idx + "use prompt ".len()..].trim();
@@ -650,26 +961,42 @@ async fn handle_chat_slash_command(
                 "{}",
                 color_dim("─────────────────────────────────────────────")
             );
-            c("/help", "show this message");
-            c("/exit", "quit");
-            c("/status", "show model/tool/error status");
-            c("/render show|on|off", "toggle terminal markdown rendering");
-            c("/new [name]", "start a new session");
-            c("/clear", "clear current session history");
-            c("/session list", "list saved sessions");
-            c("/session use <name>", "switch session");
-            c("/session rm <name>", "delete session");
-            c("/mode show", "show current execution mode");
-            c("/mode chat|agent-auto|agent-force", "switch execution mode");
-            c("/read <file> [question]", "read a file into context");
-            c("/askfile <file> <question>", "ask about a file");
-            c("/list [path]", "list files");
-            c("/grep <pattern> [path]", "search files");
-            c("/prompt show", "show active prompt");
-            c("/prompt list", "list prompts");
-            c("/prompt use <name>", "switch prompt");
-            c("/model list", "list available models");
-            c("/model use <name>", "switch model");
+            c("/help", t("help.help"));
+            c("/exit", t("help.exit"));
+            c("/status", t("help.status"));
+            c("/render show|on|off", t("help.render"));
+            c("/new [name]", t("help.new"));
+            c("/clear", t("help.clear"));
+            c("/session list", t("help.session_list"));
+            c("/session use <name>", t("help.session_use"));
+            c("/session rm <name>", t("help.session_rm"));
+            c("/mode show", t("help.mode_show"));
+            c("/mode chat|agent-auto|agent-force", t("help.mode_set"));
+            c("/read <file> [question]", t("help.read"));
+            c("/askfile <file> <question>", t("help.askfile"));
+            c("/image <path> [question]", t("help.image"));
+            c("/fetch <url>", t("help.fetch"));
+            c("/diff [path]", t("help.diff"));
+            c("/undo", t("help.undo"));
+            c("/compact [instructions]", t("help.compact"));
+            c("/tokens", t("help.tokens"));
+            c("/cost", t("help.cost"));
+            c("/trust", t("help.trust"));
+            c("/remember [--global] <fact>", t("help.remember"));
+            c("/forget <id>", t("help.forget"));
+            c("/retry [--model <name>]", t("help.retry"));
+            c("/system show|set|append|reset", t("help.system"));
+            c("/list [path]", t("help.list"));
+            c("/grep <pattern> [path]", t("help.grep"));
+            c("/prompt show", t("help.prompt_show"));
+            c("/prompt list", t("help.prompt_list"));
+            c("/prompt use <name>", t("help.prompt_use"));
+            c("/as <prompt> <message>", t("help.as"));
+            c("/model list", t("help.model_list"));
+            c("/model use <name>", t("help.model_use"));
+            c("/compare <a,b,...> <prompt>", t("help.compare"));
+            c("/editor [text]", t("help.editor"));
+            c("<text> \\  |  ```", t("help.multiline"));
             println!(
                 "{}",
                 color_dim("─────────────────────────────────────────────")
@@ -683,13 +1010,17 @@ async fn handle_chat_slash_command(
                 fresh_session_name_for_workspace()?
             };
             history.clear();
+            reset_tracked_spend();
+            load_session_meta(&new_session)?.apply(cfg);
+            record_session_seed(&new_session, effective_seed(cfg))?;
+            set_transcript_session(&new_session);
             *active_session = new_session.clone();
             save_session(active_session, history)?;
-            println!("Started new session: {}", new_session);
+            println!("{}", tf("chat.new_session", &[&new_session]));
         }
         "/clear" => {
             history.clear();
-            println!("Session history cleared.");
+            println!("{}", t("chat.cleared"));
         }
         "/session" => {
             let Some(sub) = parts.next() else {
@@ -700,9 +1031,9 @@ async fn handle_chat_slash_command(
                 "list" => {
                     let sessions = list_saved_sessions()?;
                     if sessions.is_empty() {
-                        println!("No saved sessions.");
+                        println!("{}", t("chat.no_sessions"));
                     } else {
-                        println!("Saved sessions:");
+                        println!("{}", t("chat.saved_sessions"));
                         for name in sessions {
                             if name == *active_session {
                                 println!("* {name}");
@@ -720,12 +1051,12 @@ async fn handle_chat_slash_command(
                     let next_session = resolve_session_name(name)?;
                     let next_history = load_session_or_default(&next_session)?;
                     *history = next_history;
+                    load_session_meta(&next_session)?.apply(cfg);
+                    record_session_seed(&next_session, effective_seed(cfg))?;
+                    set_transcript_session(&next_session);
+                    reset_tracked_spend();
                     *active_session = next_session.clone();
-                    println!(
-                        "Switched session: {} ({} messages)",
-                        next_session,
-                        history.len()
-                    );
+                    println!("{}", tf("chat.switched", &[&next_session, &history.len()]));
                 }
                 "rm" => {
                     let Some(name) = parts.next() else {
@@ -734,13 +1065,14 @@ async fn handle_chat_slash_command(
                     };
                     let target = resolve_session_name(name)?;
                     if target == *active_session {
-                        println!("Cannot remove current active session: {}", target);
+                        println!("{}", tf("chat.cannot_remove_active", &[&target]));
                         return Ok(());
                     }
-                    if remove_session_file(&target)? {
-                        println!("Removed session: {}", target);
+                    remove_session_meta(&target)?;
+                    if storage().remove_session(&target)? {
+                        println!("{}", tf("chat.removed", &[&target]));
                     } else {
-                        println!("Session not found: {}", target);
+                        println!("{}", tf("chat.not_found", &[&target]));
                     }
                 }
                 _ => {
@@ -753,6 +1085,12 @@ async fn handle_chat_slash_command(
             if sub.eq_ignore_ascii_case("show") {
                 println!("mode: {}", color_yellow(exec_mode.as_str()));
             } else if let Some(next_mode) = ChatExecutionMode::parse(sub) {
+                if next_mode != ChatExecutionMode::ChatOnly
+                    && !is_workspace_trusted()
+                    && !prompt_workspace_trust(cfg)?
+                {
+                    return Ok(());
+                }
                 *exec_mode = next_mode;
                 println!("mode → {}", color_yellow(exec_mode.as_str()));
             } else {
@@ -785,7 +1123,12 @@ async fn handle_chat_slash_command(
             let question = parts.collect::<Vec<_>>().join(" ");
             if question.trim().is_empty() {
                 let content = read_text_file(Path::new(file))?;
-                push_tool_result(history, input, "fs.read", &clip_output(&content, 8000));
+                push_tool_result(
+                    history,
+                    input,
+                    "fs.read",
+                    &fit_tool_output(cfg, "fs.read_file", &content),
+                );
                 println!(
                     "Read {} (content hidden). Ask a follow-up question to analyze it.",
                     file
@@ -806,12 +1149,286 @@ async fn handle_chat_slash_command(
             }
             submit_file_to_model(cfg, history, &question, file, *render_markdown).await?;
         }
+        "/image" => {
+            let Some(file) = parts.next() else {
+                println!("{}", t("chat.image_usage"));
+                return Ok(());
+            };
+            let image = match read_image_data_url(Path::new(file)) {
+                Ok(image) => image,
+                Err(err) => {
+                    println!("{}", color_red(&format!("{err:#}")));
+                    return Ok(());
+                }
+            };
+            let question = parts.collect::<Vec<_>>().join(" ");
+            if question.trim().is_empty() {
+                history.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("Attached image: {}", file),
+                    images: vec![image],
+                });
+                println!("{}", tf("chat.image_attached", &[&file]));
+            } else {
+                history.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: format!("Attached image: {}\n\n{}", file, question),
+                    images: vec![image],
+                });
+                let turn = run_chat_turn(cfg, history, "chat-lite", *render_markdown).await;
+                release_sent_images(history);
+                turn?;
+            }
+        }
+        "/fetch" => {
+            let Some(url) = parts.next() else {
+                println!("{}", t("chat.fetch_usage"));
+                return Ok(());
+            };
+            let working = WorkingStatus::start(tf("status.fetching", &[&url]));
+            let page = fetch_url(url, None).await;
+            working.finish();
+            let page = match page {
+                Ok(page) => page,
+                Err(err) => {
+                    println!("{}", color_red(&format!("{err:#}")));
+                    return Ok(());
+                }
+            };
+            let context = page.to_context(cfg.fetch_max_chars);
+            push_tool_result(history, input, "web.fetch", &context);
+            println!(
+                "{}",
+                tf("chat.fetched", &[&page.url, &context.chars().count()])
+            );
+        }
+        "/diff" => {
+            let diff = match collect_git_diff(parts.next()) {
+                Ok(diff) => diff,
+                Err(err) => {
+                    println!("{}", color_red(&format!("{err:#}")));
+                    return Ok(());
+                }
+            };
+            if diff.trim().is_empty() {
+                println!("{}", t("chat.diff_empty"));
+                return Ok(());
+            }
+            let lines: Vec<String> = diff.lines().map(color_diff_line).collect();
+            print_paged(&lines)?;
+            let reply = ask(&tagged_prompt("diff", t("chat.diff_add_prompt")))?;
+            if matches!(reply.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+                let context = clip_output(&diff, MAX_DIFF_CONTEXT_CHARS);
+                push_tool_result(history, input, "git.diff", &context);
+                println!(
+                    "{}",
+                    tf("chat.diff_added", &[&context.chars().count().to_string()])
+                );
+            }
+        }
+        "/compact" => {
+            let instructions = input.trim()["/compact".len()..].trim();
+            // Keep the latest exchange verbatim so the conversation flows on.
+            if history.len() <= COMPACT_KEEP_MESSAGES {
+                println!("{}", t("chat.compact_nothing"));
+                return Ok(());
+            }
+            let before = history_chars(history);
+            let instructions = (!instructions.is_empty()).then_some(instructions);
+            let n = compact_history(history, cfg, COMPACT_KEEP_MESSAGES, instructions).await;
+            println!(
+                "{}",
+                tf(
+                    "chat.compacted",
+                    &[
+                        &n.to_string(),
+                        &before.to_string(),
+                        &history_chars(history).to_string()
+                    ]
+                )
+            );
+        }
+        "/tokens" => print_token_breakdown(cfg, history, *exec_mode),
+        "/cost" => print_session_cost(cfg),
+        "/trust" => {
+            if prompt_workspace_trust(cfg)? {
+                println!("{}", t("trust.granted"));
+            }
+        }
+        "/remember" => {
+            let rest = input
+                .trim()
+                .strip_prefix("/remember")
+                .unwrap_or_default()
+                .trim();
+            let (global, fact) = match rest.strip_prefix("--global") {
+                Some(fact) => (true, fact.trim()),
+                None => (false, rest),
+            };
+            if fact.is_empty() {
+                println!("{}", t("chat.remember_usage"));
+                return Ok(());
+            }
+            let id = remember(fact, global)?;
+            println!("{}", tf("chat.remembered", &[&id.to_string()]));
+        }
+        "/forget" => {
+            let Some(id) = parts
+                .next()
+                .and_then(|s| s.trim_start_matches('#').parse().ok())
+            else {
+                println!("{}", t("chat.forget_usage"));
+                return Ok(());
+            };
+            if forget(id)? {
+                println!("{}", tf("chat.forgot", &[&id.to_string()]));
+            } else {
+                println!("{}", tf("chat.forget_missing", &[&id.to_string()]));
+            }
+        }
+        "/retry" => {
+            let model = match (parts.next(), parts.next()) {
+                (None, _) => None,
+                (Some("--model"), Some(name)) => Some(name),
+                _ => {
+                    println!("{}", t("chat.retry_usage"));
+                    return Ok(());
+                }
+            };
+            if let Some(name) = model {
+                ensure_model_catalog(cfg);
+                if !cfg.model_catalog.iter().any(|m| m == name) {
+                    println!("{}", tf("chat.model_not_in_catalog", &[&name]));
+                    return Ok(());
+                }
+            }
+            let Some(idx) = last_request_index(history) else {
+                println!("{}", t("chat.retry_nothing"));
+                return Ok(());
+            };
+            let request = history[idx]
+                .content
+                .rsplit_once("User request: ")
+                .map(|(_, r)| r)
+                .unwrap_or(&history[idx].content)
+                .to_string();
+            let dropped = history.len() - idx - 1;
+            history.truncate(idx + 1);
+
+            // `--model` applies to this retry only; the active model is unchanged.
+            let mut retry_cfg = model.map(|m| config_for_model(cfg, m));
+            let turn_cfg = match retry_cfg.as_mut() {
+                Some(c) => c,
+                None => cfg,
+            };
+            println!(
+                "{}",
+                color_dim(&tf(
+                    "chat.retrying",
+                    &[&turn_cfg.model, &dropped.to_string()]
+                ))
+            );
+            let use_agent =
+                should_use_agent_for_turn(turn_cfg, &history[..idx], &request, *exec_mode)
+                    .await
+                    .unwrap_or_else(|_| should_use_agent_for_input(&request, *exec_mode));
+            if use_agent {
+                run_agent_turn(
+                    turn_cfg,
+                    history,
+                    "chat",
+                    Some(active_session),
+                    *render_markdown,
+                )
+                .await?;
+            } else {
+                run_chat_turn(turn_cfg, history, "chat-lite", *render_markdown).await?;
+            }
+        }
+        "/system" => {
+            let sub = parts.next().unwrap_or("show");
+            let text = input
+                .trim()
+                .strip_prefix("/system")
+                .map(str::trim)
+                .and_then(|rest| rest.strip_prefix(sub))
+                .map(str::trim)
+                .unwrap_or_default();
+            match sub {
+                "show" => {
+                    let agent = !matches!(*exec_mode, ChatExecutionMode::ChatOnly);
+                    let mode = if agent { "chat" } else { "chat-lite" };
+                    let source = match (&cfg.system_override, &cfg.system_append) {
+                        (None, None) => tf("chat.system_source_prompt", &[&cfg.active_prompt]),
+                        (Some(_), _) => t("chat.system_source_override").to_string(),
+                        (None, Some(_)) => tf("chat.system_source_append", &[&cfg.active_prompt]),
+                    };
+                    println!(
+                        "{}",
+                        color_dim(&tf("chat.system_header", &[&mode, &source]))
+                    );
+                    println!("{}", build_system_prompt(cfg, mode));
+                    return Ok(());
+                }
+                "set" | "append" if text.is_empty() => {
+                    println!("{}", tf("chat.system_text_usage", &[&sub]));
+                    return Ok(());
+                }
+                "set" => cfg.system_override = Some(text.to_string()),
+                "append" => {
+                    let joined = match cfg.system_append.take() {
+                        Some(prev) => format!("{}\n{}", prev, text),
+                        None => text.to_string(),
+                    };
+                    cfg.system_append = Some(joined);
+                }
+                "reset" => {
+                    cfg.system_override = None;
+                    cfg.system_append = None;
+                }
+                _ => {
+                    println!("{}", t("chat.system_usage"));
+                    return Ok(());
+                }
+            }
+            update_session_meta(active_session, |meta| {
+                meta.system_override = cfg.system_override.clone();
+                meta.system_append = cfg.system_append.clone();
+            })?;
+            let key = match sub {
+                "set" => "chat.system_set",
+                "append" => "chat.system_append",
+                _ => "chat.system_reset",
+            };
+            println!("{}", tf(key, &[active_session]));
+        }
+        "/undo" => match undo_last_turn()? {
+            None => println!("{}", t("chat.undo_nothing")),
+            Some(undone) => {
+                println!(
+                    "{}",
+                    tf("chat.undo_done", &[&undone.files.len().to_string()])
+                );
+                let cwd = std::env::current_dir().unwrap_or_default();
+                for (path, action) in undone.files {
+                    let shown = path.strip_prefix(&cwd).unwrap_or(&path).display();
+                    let line = match action {
+                        UndoAction::Restored => color_yellow(&format!("~ {}", shown)),
+                        UndoAction::Removed => color_red(&format!("- {}", shown)),
+                    };
+                    println!("{}", line);
+                }
+                if !undone.commands.is_empty() {
+                    println!("{}", color_yellow(t("chat.undo_commands")));
+                    for cmd in &undone.commands {
+                        println!("{}", color_yellow(&format!("$ {}", cmd)));
+                    }
+                }
+            }
+        },
         "/list" => {
             let path = parts.next().unwrap_or(".");
-            let path = Path::new(path);
-            if !try_rg_files(path)? {
-                list_files_recursive(path)?;
-            }
+            print!("{}", list_files_output(Path::new(path))?);
         }
         "/grep" => {
             let Some(pattern) = parts.next() else {
@@ -819,10 +1436,7 @@ async fn handle_chat_slash_command(
                 return Ok(());
             };
             let path = parts.next().unwrap_or(".");
-            let path = Path::new(path);
-            if !try_rg_grep(path, pattern)? {
-                grep_recursive(path, pattern)?;
-            }
+            print!("{}", grep_output(Path::new(path), pattern)?);
         }
         "/prompt" => {
             let Some(sub) = parts.next() else {
@@ -861,9 +1475,8 @@ async fn handle_chat_slash_command(
                         println!("Prompt not found: {name}");
                         return Ok(());
                     }
-                    cfg.active_prompt = name.to_string();
-                    save_config(cfg)?;
-                    println!("Active prompt switched to '{}'.", name);
+                    switch_session_prompt(cfg, active_session, name)?;
+                    println!("{}", tf("chat.session_prompt", &[&name, active_session]));
                 }
                 _ => {
                     println!("Usage: /prompt <show|list|use>");
@@ -893,13 +1506,25 @@ async fn handle_chat_slash_command(
                         println!("Model not in catalog: {}", name);
                         return Ok(());
                     }
-                    set_active_model(cfg, name);
-                    save_config(cfg)?;
-                    println!("Active model switched to '{}'.", name);
+                    switch_session_model(cfg, active_session, name)?;
+                    println!("{}", tf("chat.session_model", &[&name, active_session]));
                 }
                 _ => println!("Usage: /model <list|use>"),
             }
         }
+        "/compare" => {
+            let Some(models) = parts.next() else {
+                println!("{}", t("chat.compare_usage"));
+                return Ok(());
+            };
+            let prompt = parts.collect::<Vec<_>>().join(" ");
+            if prompt.trim().is_empty() {
+                println!("{}", t("chat.compare_usage"));
+                return Ok(());
+            }
+            let models = models.split(',').map(str::to_string).collect::<Vec<_>>();
+            run_compare(cfg, &models, &prompt).await?;
+        }
         _ => {
             println!("Unknown command: {}. Use /help.", cmd);
         }
@@ -917,13 +1542,6 @@ struct ExecResult {
     history_text: String,
 }
 
-#[derive(Debug, Clone)]
-struct ToolCall {
-    tool: String,
-    command: String,
-    args: Value,
-}
-
 #[derive(Debug, Clone, Serialize)]
 struct ToolResultRecord {
     tool: String,
@@ -942,6 +1560,15 @@ struct NativeToolExecution {
 fn maybe_execute_assistant_commands(cfg: &mut Config, answer: &str) -> Result<ExecResult> {
     let calls = extract_tool_calls(answer);
     if calls.is_empty() {
+        log_debug(
+            "tool-parse",
+            &format!(
+                "no tool calls; hints: code_execution={} legacy_shell={} tool_call={}",
+                contains_code_execution_hint(answer),
+                contains_legacy_shell_block(answer),
+                contains_tool_call_hint(answer)
+            ),
+        );
         if contains_code_execution_hint(answer) {
             let msg = "Detected incompatible tool protocol: `code_execution`.\nThis model/gateway is not following dongshan JSON tool_calls schema, so execution is skipped.\nPlease switch to a model/provider that supports OpenAI function-calling or strict JSON tool_calls.\n";
             let records = vec![ToolResultRecord {
@@ -1044,13 +1671,7 @@ fn maybe_execute_assistant_commands(cfg: &mut Config, answer: &str) -> Result<Ex
         }
         seen_calls += 1;
 
-        let tool = call.tool.trim().to_ascii_lowercase();
-        if tool.is_empty() {
-            skipped_count += 1;
-            continue;
-        }
-
-        let exec = execute_tool_call_with_progress(cfg, &call);
+        let exec = execute_parsed_tool_call(cfg, &call);
         let before_set = current_changed_file_set().unwrap_or_default();
 
         match exec {
@@ -1135,8 +1756,8 @@ fn native_tool_schemas() -> Vec<Value> {
             "type":"function",
             "function":{
                 "name":"fs_read_file",
-                "description":"Read a UTF-8 text file from workspace",
-                "parameters":{"type":"object","properties":{"path":{"type":"string"}},"required":["path"]}
+                "description":"Read a UTF-8 text file from workspace; runs in-process without confirmation. Long files are paged: pass offset (1-based line) and limit",
+                "parameters":{"type":"object","properties":{"path":{"type":"string"},"offset":{"type":"integer"},"limit":{"type":"integer"}},"required":["path"]}
             }
         }),
         json!({
@@ -1167,16 +1788,16 @@ fn native_tool_schemas() -> Vec<Value> {
             "type":"function",
             "function":{
                 "name":"fs_list_files",
-                "description":"List files under a path",
-                "parameters":{"type":"object","properties":{"path":{"type":"string"}}}
+                "description":"List workspace files under a path as JSON, honoring .gitignore and .dongshanignore; runs in-process without confirmation",
+                "parameters":{"type":"object","properties":{"path":{"type":"string"},"glob":{"type":"string"},"max_results":{"type":"integer"}}}
             }
         }),
         json!({
             "type":"function",
             "function":{
                 "name":"fs_grep",
-                "description":"Search pattern in files under path",
-                "parameters":{"type":"object","properties":{"pattern":{"type":"string"},"path":{"type":"string"}},"required":["pattern"]}
+                "description":"Search a regex in files under path; returns JSON matches {file,line,text}. Runs in-process without confirmation",
+                "parameters":{"type":"object","properties":{"pattern":{"type":"string"},"path":{"type":"string"},"glob":{"type":"string"},"ignore_case":{"type":"boolean"},"literal":{"type":"boolean"},"max_results":{"type":"integer"}},"required":["pattern"]}
             }
         }),
         json!({
@@ -1195,6 +1816,30 @@ fn native_tool_schemas() -> Vec<Value> {
                 "parameters":{"type":"object","properties":{"path":{"type":"string"},"recursive":{"type":"boolean"}},"required":["path"]}
             }
         }),
+        json!({
+            "type":"function",
+            "function":{
+                "name":"web_fetch",
+                "description":"Download a web page (allowlisted domains only) and return its readable text",
+                "parameters":{"type":"object","properties":{"url":{"type":"string"}},"required":["url"]}
+            }
+        }),
+        json!({
+            "type":"function",
+            "function":{
+                "name":"http_get",
+                "description":"Send a GET request to an allowlisted API and return status, content type and raw body (size-limited; credential headers are refused)",
+                "parameters":{"type":"object","properties":{"url":{"type":"string"},"headers":{"type":"object","additionalProperties":{"type":"string"}}},"required":["url"]}
+            }
+        }),
+        json!({
+            "type":"function",
+            "function":{
+                "name":"python",
+                "description":"Run a Python snippet in a restricted subprocess (workspace cwd, timeout, no network by default) and return stdout/stderr; use instead of python -c",
+                "parameters":{"type":"object","properties":{"code":{"type":"string"},"timeout_secs":{"type":"integer"}},"required":["code"]}
+            }
+        }),
         json!({
             "type":"function",
             "function":{
@@ -1225,22 +1870,20 @@ fn execute_native_function_calls(
         ));
     }
 
-    let mut parsed: Vec<(String, ToolCall)> = Vec::new();
+    let mut parsed: Vec<(String, ParsedToolCall)> = Vec::new();
     for call in calls {
-        let args = if call.arguments.trim().is_empty() {
-            Value::Object(serde_json::Map::new())
-        } else {
-            serde_json::from_str::<Value>(&call.arguments)
-                .unwrap_or_else(|_| Value::Object(serde_json::Map::new()))
-        };
-        parsed.push((
-            call.id.clone(),
-            ToolCall {
-                tool: call.name.clone(),
-                command: String::new(),
-                args,
-            },
-        ));
+        log_debug(
+            "tool-parse",
+            &format!(
+                "native call {} {} args={}",
+                call.id, call.name, call.arguments
+            ),
+        );
+        let parsed_call = ParsedToolCall::from_native(&call.name, &call.arguments);
+        if let Err(err) = &parsed_call.call {
+            log_debug("tool-parse", &format!("native call {}: {}", call.id, err));
+        }
+        parsed.push((call.id.clone(), parsed_call));
     }
 
     let mut display = String::new();
@@ -1251,7 +1894,7 @@ fn execute_native_function_calls(
 
     for (call_id, call) in parsed {
         let before_set = current_changed_file_set().unwrap_or_default();
-        let exec = execute_tool_call_with_progress(cfg, &call);
+        let exec = execute_parsed_tool_call(cfg, &call);
 
         match exec {
             Ok(out) => {
@@ -1317,127 +1960,201 @@ fn is_skipped_tool_output(output: &str) -> bool {
     lower.starts_with("skipped") || lower.starts_with("skip ")
 }
 
-fn execute_tool_call_by_name(cfg: &mut Config, call: &ToolCall) -> Result<String> {
-    let tool = call.tool.trim().to_ascii_lowercase();
-    match tool.as_str() {
-        "shell" => execute_shell_tool_call(cfg, call),
-        "fs.read_file" | "fs_read_file" => execute_native_fs_read(call),
-        "fs.create_file" | "fs_create_file" => execute_native_fs_create(call),
-        "fs.edit_file" | "fs_edit_file" => execute_native_fs_edit(call),
-        "fs.list_files" | "fs_list_files" => execute_native_fs_list(call),
-        "fs.grep" | "fs_grep" => execute_native_fs_grep(call),
-        "fs.apply_patch" | "fs_apply_patch" => execute_native_fs_apply_patch(call),
-        "fs.move" | "fs_move" => execute_native_fs_move(call),
-        "fs.delete" | "fs_delete" => execute_native_fs_delete(call),
-        "run_command" => execute_structured_run_command(cfg, call),
-        _ => Ok(format!("Skipped unsupported tool: {}", call.tool)),
+/// Runs a validated call. Unknown tools are skipped; schema violations fail with
+/// the validation message so the model can correct its arguments.
+fn execute_parsed_tool_call(cfg: &mut Config, parsed: &ParsedToolCall) -> Result<String> {
+    match &parsed.call {
+        Ok(call) => execute_tool_call_with_progress(cfg, call),
+        Err(err @ ToolCallError::Unsupported(_)) => Ok(format!("Skipped {err}")),
+        Err(err) => bail!("{err}"),
     }
 }
 
-fn tool_progress_label(call: &ToolCall) -> Option<String> {
-    let tool = call.tool.trim().to_ascii_lowercase();
-    match tool.as_str() {
-        "fs.read_file" | "fs_read_file" => {
-            let p = tool_arg_string(call, &["path", "file"])?;
-            Some(format!("reading {}", p))
-        }
-        "fs.list_files" | "fs_list_files" => {
-            let p = tool_arg_string(call, &["path"]).unwrap_or_else(|| ".".to_string());
-            Some(format!("listing {}", p))
-        }
-        "fs.grep" | "fs_grep" => {
-            let pattern = tool_arg_string(call, &["pattern", "query"])?;
-            let p = tool_arg_string(call, &["path"]).unwrap_or_else(|| ".".to_string());
-            Some(format!("searching '{}' in {}", pattern, p))
+#[tracing::instrument(name = "tool", skip_all, fields(tool = call.name()))]
+fn execute_tool_call_by_name(cfg: &mut Config, call: &ToolCall) -> Result<String> {
+    let res = dispatch_tool_call(cfg, call).map(|out| {
+        let out = fit_tool_output(cfg, call.name(), &redact_secrets(&out));
+        // Write tools only echo what the model itself produced.
+        match call {
+            ToolCall::CreateFile(_)
+            | ToolCall::EditFile(_)
+            | ToolCall::ApplyPatch(_)
+            | ToolCall::Move(_)
+            | ToolCall::Delete(_) => out,
+            _ => guard_untrusted(call.name(), &out).into_owned(),
         }
-        _ => None,
+    });
+    if let Err(err) = &res {
+        tracing::warn!(tool = call.name(), "tool failed: {err:#}");
+    }
+    record_tool_call(call.name(), &call.args_json(), &res);
+    res
+}
+
+fn dispatch_tool_call(cfg: &mut Config, call: &ToolCall) -> Result<String> {
+    let writes = matches!(
+        call,
+        ToolCall::CreateFile(_)
+            | ToolCall::EditFile(_)
+            | ToolCall::ApplyPatch(_)
+            | ToolCall::Move(_)
+            | ToolCall::Delete(_)
+    );
+    if writes && !approval_policy(cfg).file_writes {
+        return Ok(format!(
+            "Skipped {}: file writes are disabled (approval = read-only)",
+            call.name()
+        ));
+    }
+    match call {
+        ToolCall::Shell(args) => execute_shell_tool_call(cfg, &args.command),
+        ToolCall::ReadFile(args) => execute_native_fs_read(args),
+        ToolCall::CreateFile(args) => execute_native_fs_create(args),
+        ToolCall::EditFile(args) => execute_native_fs_edit(args),
+        ToolCall::ListFiles(args) => execute_native_fs_list(args),
+        ToolCall::Grep(args) => execute_native_fs_grep(args),
+        ToolCall::ApplyPatch(args) => execute_native_fs_apply_patch(args),
+        ToolCall::Move(args) => execute_native_fs_move(args),
+        ToolCall::Delete(args) => execute_native_fs_delete(args),
+        ToolCall::WebFetch(args) => execute_web_fetch(cfg, args),
+        ToolCall::HttpGet(args) => execute_http_get(cfg, args),
+        ToolCall::Python(args) => execute_python(cfg, args),
     }
 }
 
 fn execute_tool_call_with_progress(cfg: &mut Config, call: &ToolCall) -> Result<String> {
-    let progress = tool_progress_label(call);
+    let progress = call.progress_label();
     let mut clear_width = 0usize;
-    if let Some(label) = &progress {
+    if let Some(label) = progress.as_ref().filter(|_| !is_ci()) {
         let line = format!("{} {}", color_dim("tool>"), label);
         clear_width = line.chars().count();
         print!("\r{}", line);
         let _ = io::stdout().flush();
     }
-    let res = execute_tool_call_by_name(cfg, call);
+    // Tools walk directories and spawn processes; keep that off the async
+    // worker so other tasks on the runtime keep running.
+    let res = tokio::task::block_in_place(|| execute_tool_call_by_name(cfg, call));
     if clear_width > 0 {
         let width = clear_width.min(200);
         print!("\r{}\r", " ".repeat(width));
         let _ = io::stdout().flush();
     }
+    ci_event(
+        "tool",
+        json!({ "tool": call.name(), "label": progress, "ok": res.is_ok() }),
+    );
     res
 }
 
-fn execute_shell_tool_call(cfg: &mut Config, call: &ToolCall) -> Result<String> {
-    let cmd_owned = if !call.command.trim().is_empty() {
-        call.command.trim().to_string()
-    } else {
-        tool_arg_string(call, &["command", "cmd"]).unwrap_or_default()
-    };
-    let cmd = cmd_owned.trim();
+fn execute_shell_tool_call(cfg: &mut Config, command: &str) -> Result<String> {
+    let cmd = command.trim();
     if cmd.is_empty() {
         bail!("shell tool missing command");
     }
     if let Some(reason) = precheck_command(cmd) {
         return Ok(format!("Skipped command: {} ({})", cmd, reason));
     }
-    if !is_command_allowed(cfg, cmd) {
+    let policy = approval_policy(cfg);
+    let verdict = command_verdict(cfg, cmd);
+    let allowed = matches!(verdict, CommandVerdict::Allowed);
+    // Read-only asks about anything that is not plainly read-only instead of
+    // skipping it; denied commands are still skipped.
+    let ask_unsafe = !policy.file_writes && !matches!(verdict, CommandVerdict::Denied(_));
+    if !allowed && !ask_unsafe {
         return Ok(format!("Skipped unsafe command: {}", cmd));
     }
-    if cfg.auto_confirm_exec && !is_trusted_command(cfg, cmd) {
+    if let Some(target) = find_write_outside_jail(cmd) {
+        report_blocked("command write", &target);
+        return Ok(format!(
+            "Blocked command: {} (writes outside workspace: {})",
+            cmd, target
+        ));
+    }
+    let network = is_network_command(cmd);
+    if network && policy.network == NetworkAccess::Block {
+        return Ok(format!(
+            "Skipped network command: {} (approval = read-only)",
+            cmd
+        ));
+    }
+    if !allowed
+        || (policy.confirm_exec && !is_trusted_command(cfg, cmd))
+        || (network && policy.network == NetworkAccess::Ask)
+    {
         let prefix = command_prefix(cmd);
         let input = ask(&tagged_prompt(
             "exec-confirm",
-            &format!(
-                "Run command `{}` ? [y=yes]/[n=no]/[a=always `{}`]/[q=stop]: ",
-                cmd, prefix
-            ),
+            &tf("exec.confirm", &[&cmd, &prefix]),
         ))?;
         let choice = input.trim().to_ascii_lowercase();
         if choice == "q" {
             return Ok("User stopped command execution.".to_string());
         }
         if choice == "a" {
-            if !cfg
-                .auto_exec_trusted
-                .iter()
-                .any(|x| x.eq_ignore_ascii_case(&prefix))
-            {
-                cfg.auto_exec_trusted.push(prefix.clone());
-                let _ = save_config(cfg);
-            }
+            let trust = |cfg: &mut Config| {
+                if !cfg
+                    .auto_exec_trusted
+                    .iter()
+                    .any(|x| x.eq_ignore_ascii_case(&prefix))
+                {
+                    cfg.auto_exec_trusted.push(prefix.clone());
+                }
+            };
+            trust(cfg);
+            let _ = update_config(trust);
         } else if choice != "y" {
             return Ok(format!("Skipped by user: {}", cmd));
         }
     }
+    if !is_read_only_command(cmd) {
+        remember_command(cmd);
+    }
     let out = run_shell_command(cmd)?;
     Ok(format!("$ {}\n{}", cmd, out))
 }
 
-fn execute_native_fs_read(call: &ToolCall) -> Result<String> {
-    let raw = tool_arg_string(call, &["path", "file"])
-        .ok_or_else(|| anyhow::anyhow!("fs.read_file requires args.path"))?;
-    let path = resolve_native_path(&raw)?;
+fn execute_native_fs_read(args: &ReadFileArgs) -> Result<String> {
+    let path = resolve_native_path(&args.path)?;
     let text = read_text_file(&path)?;
+    let shown = display_path(&path);
+    let total = text.lines().count();
+    let start = args.offset.unwrap_or(1).max(1);
+    let limit = args
+        .limit
+        .unwrap_or(READ_MAX_LINES)
+        .clamp(1, READ_MAX_LINES);
+    if start == 1 && total <= limit {
+        return Ok(format!("Read: {}\n{}", shown, text));
+    }
+    if start > total {
+        bail!(
+            "offset {} is past the end of {} ({} lines)",
+            start,
+            shown,
+            total
+        );
+    }
+    let end = (start - 1 + limit).min(total);
+    let body = text
+        .lines()
+        .skip(start - 1)
+        .take(end - start + 1)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let more = if end < total {
+        format!("; continue with offset={}", end + 1)
+    } else {
+        String::new()
+    };
     Ok(format!(
-        "Read: {}\n{}",
-        path.display(),
-        clip_output(&text, 12000)
+        "Read: {} (lines {}-{} of {}{})\n{}",
+        shown, start, end, total, more, body
     ))
 }
 
-fn execute_native_fs_create(call: &ToolCall) -> Result<String> {
-    let raw = tool_arg_string(call, &["path", "file"])
-        .ok_or_else(|| anyhow::anyhow!("fs.create_file requires args.path"))?;
-    let content = tool_arg_string(call, &["content"])
-        .ok_or_else(|| anyhow::anyhow!("fs.create_file requires args.content"))?;
-    let overwrite = tool_arg_bool(call, &["overwrite"]).unwrap_or(true);
-    let path = resolve_native_path(&raw)?;
-    if path.exists() && !overwrite {
+fn execute_native_fs_create(args: &CreateFileArgs) -> Result<String> {
+    let path = resolve_native_path(&args.path)?;
+    if path.exists() && !args.overwrite {
         bail!(
             "target already exists and overwrite=false: {}",
             path.display()
@@ -1447,123 +2164,184 @@ fn execute_native_fs_create(call: &ToolCall) -> Result<String> {
         fs::create_dir_all(parent)
             .with_context(|| format!("Failed to create parent dir {}", parent.display()))?;
     }
-    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    let before = fs::read_to_string(&path).unwrap_or_default();
+    remember_file(&path);
+    fs::write(&path, &args.content)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    record_file_change("create", &path, &before, &args.content);
     Ok(format!("Created file: {}", path.display()))
 }
 
-fn execute_native_fs_edit(call: &ToolCall) -> Result<String> {
-    let raw = tool_arg_string(call, &["path", "file"])
-        .ok_or_else(|| anyhow::anyhow!("fs.edit_file requires args.path"))?;
-    let old_str = tool_arg_string(call, &["old_str", "old"]).unwrap_or_default();
-    let new_str = tool_arg_string(call, &["new_str", "new"])
-        .ok_or_else(|| anyhow::anyhow!("fs.edit_file requires args.new_str"))?;
-    let replace_all = tool_arg_bool(call, &["replace_all"]).unwrap_or(false);
-    let path = resolve_native_path(&raw)?;
-    let mut text =
+fn execute_native_fs_edit(args: &EditFileArgs) -> Result<String> {
+    let (old_str, new_str) = (&args.old_str, &args.new_str);
+    let path = resolve_native_path(&args.path)?;
+    let raw_text =
         fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let notebook = is_notebook(&path);
+    let mut text = if notebook {
+        render_notebook(&raw_text, false)?
+    } else {
+        raw_text.clone()
+    };
 
+    let mut fuzzy_note = None;
     if old_str.is_empty() {
-        text = new_str;
-    } else if replace_all {
-        if !text.contains(&old_str) {
-            bail!("old_str not found in {}", path.display());
-        }
-        text = text.replace(&old_str, &new_str);
-    } else if let Some(idx) = text.find(&old_str) {
-        text.replace_range(idx..idx + old_str.len(), &new_str);
+        text = new_str.clone();
     } else {
-        bail!("old_str not found in {}", path.display());
+        match apply_edit(&mut text, old_str, new_str, args.replace_all, &mut None) {
+            Ok(EditHit::Fuzzy(m)) => fuzzy_note = Some(m.describe()),
+            Ok(_) => {}
+            Err(miss) => bail!("{} in {}", miss, path.display()),
+        }
+    }
+    if notebook {
+        text = merge_notebook_edit(&raw_text, &text)?;
     }
 
-    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
-    Ok(format!("Edited file: {}", path.display()))
+    remember_file(&path);
+    fs::write(&path, &text).with_context(|| format!("Failed to write {}", path.display()))?;
+    record_file_change("edit", &path, &raw_text, &text);
+    Ok(match fuzzy_note {
+        Some(note) => format!(
+            "Edited file: {} (old_str matched fuzzily: {}; re-read the file to confirm)",
+            path.display(),
+            note
+        ),
+        None => format!("Edited file: {}", path.display()),
+    })
 }
 
-fn execute_native_fs_list(call: &ToolCall) -> Result<String> {
-    let raw = tool_arg_string(call, &["path"]).unwrap_or_else(|| ".".to_string());
-    let path = resolve_native_path(&raw)?;
-    let out = list_files_output(&path)?;
-    Ok(format!(
-        "List: {}\n{}",
-        path.display(),
-        clip_output(&out, 12000)
-    ))
+fn execute_web_fetch(cfg: &Config, args: &WebFetchArgs) -> Result<String> {
+    let url = &args.url;
+    if approval_policy(cfg).network == NetworkAccess::Block {
+        return Ok(format!("Skipped fetch: {} (approval = read-only)", url));
+    }
+    if !is_fetch_allowed(cfg, url) {
+        return Ok(format!(
+            "Skipped fetch: {} is not in fetch_allow_domains (dongshan config set --fetch-allow-domains)",
+            url
+        ));
+    }
+    let page = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(fetch_url(url, Some(&cfg.fetch_allow_domains)))
+    })?;
+    Ok(format!("Fetched: {}", page.to_context(cfg.fetch_max_chars)))
 }
 
-fn execute_native_fs_grep(call: &ToolCall) -> Result<String> {
-    let pattern = tool_arg_string(call, &["pattern", "query"])
-        .ok_or_else(|| anyhow::anyhow!("fs.grep requires args.pattern"))?;
-    let raw = tool_arg_string(call, &["path"]).unwrap_or_else(|| ".".to_string());
-    let path = resolve_native_path(&raw)?;
-    let out = grep_output(&path, &pattern)?;
-    if out.trim().is_empty() {
+fn execute_python(cfg: &Config, args: &PythonArgs) -> Result<String> {
+    let code = args.code.trim_end();
+    if code.trim().is_empty() {
+        bail!("python tool missing code");
+    }
+    let policy = approval_policy(cfg);
+    if !policy.file_writes {
+        return Ok("Skipped python snippet (approval = read-only)".to_string());
+    }
+    // A snippet can do anything a shell can, so it goes through the command
+    // rules as `python` and is never trusted: only full-auto skips the prompt.
+    if let Some(rule) = denying_rule(&cfg.auto_exec_deny, "python") {
         return Ok(format!(
-            "Grep: {} in {}\nNo matches found.",
-            pattern,
-            path.display()
+            "Skipped python snippet (denied by auto_exec_deny: {})",
+            rule
         ));
     }
-    Ok(format!(
-        "Grep: {} in {}\n{}",
-        pattern,
-        path.display(),
-        clip_output(&out, 12000)
-    ))
+    if matches!(policy.exec_mode, AutoExecMode::Custom)
+        && !matches_exec_rules(&cfg.auto_exec_allow, "python")
+    {
+        return Ok("Skipped python snippet (python is not in auto_exec_allow)".to_string());
+    }
+    if approval_level() != ApprovalLevel::FullAuto {
+        println!("{}", color_dim(&truncate_preview(code, 1200)));
+        let input = ask(&tagged_prompt(
+            "exec-confirm",
+            &tf("python.confirm", &[&code.lines().count()]),
+        ))?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            return Ok("Skipped by user: python snippet".to_string());
+        }
+    }
+    Ok(run_python(cfg, code, args.timeout_secs)?.to_context())
 }
 
-fn execute_native_fs_apply_patch(call: &ToolCall) -> Result<String> {
-    let raw = tool_arg_string(call, &["path", "file"])
-        .ok_or_else(|| anyhow::anyhow!("fs.apply_patch requires args.path"))?;
-    let path = resolve_native_path(&raw)?;
+fn execute_http_get(cfg: &Config, args: &HttpGetArgs) -> Result<String> {
+    let url = &args.url;
+    if approval_policy(cfg).network == NetworkAccess::Block {
+        return Ok(format!("Skipped http.get: {} (approval = read-only)", url));
+    }
+    if !is_http_allowed(cfg, url) {
+        return Ok(format!(
+            "Skipped http.get: {} is not in http_allow_domains (dongshan config set --http-allow-domains)",
+            url
+        ));
+    }
+    let resp = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(http_get(cfg, url, &args.headers))
+    })?;
+    Ok(resp.to_context())
+}
+
+fn execute_native_fs_list(args: &ListFilesArgs) -> Result<String> {
+    let path = resolve_native_path(&args.path)?;
+    let files = workspace_files(&path, args.glob.as_deref())?;
+    let max = tool_max_results(args.max_results, LIST_DEFAULT_RESULTS);
+    let shown: Vec<String> = files.iter().take(max).map(|f| display_path(f)).collect();
+    Ok(serde_json::to_string_pretty(&json!({
+        "path": display_path(&path),
+        "files": shown,
+        "total": files.len(),
+        "truncated": files.len() > max,
+    }))?)
+}
+
+fn execute_native_fs_grep(args: &GrepArgs) -> Result<String> {
+    let path = resolve_native_path(&args.path)?;
+    let max = tool_max_results(args.max_results, GREP_DEFAULT_RESULTS);
+    let opts = SearchOptions {
+        glob: args.glob.as_deref(),
+        ignore_case: args.ignore_case,
+        literal: args.literal,
+        max_results: max,
+    };
+    let (matches, total) = search_workspace(&path, &args.pattern, &opts)?;
+    Ok(serde_json::to_string_pretty(&json!({
+        "pattern": args.pattern,
+        "path": display_path(&path),
+        "matches": matches,
+        "total": total,
+        "truncated": total > matches.len(),
+    }))?)
+}
+
+fn tool_max_results(requested: Option<usize>, default: usize) -> usize {
+    requested.unwrap_or(default).clamp(1, TOOL_MAX_RESULTS)
+}
+
+fn execute_native_fs_apply_patch(args: &ApplyPatchArgs) -> Result<String> {
+    let path = resolve_native_path(&args.path)?;
     let text =
         fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
 
-    let edits = tool_arg_array(call, &["edits", "patches"])
-        .ok_or_else(|| anyhow::anyhow!("fs.apply_patch requires args.edits[]"))?;
+    let edits = &args.edits;
     if edits.is_empty() {
         bail!("fs.apply_patch requires at least one edit");
     }
-    let strict = tool_arg_bool(call, &["strict"]).unwrap_or(true);
-    let mut working = text;
+    let strict = args.strict;
+    let mut working = text.clone();
     let mut hits: Vec<String> = Vec::new();
     let mut misses: Vec<String> = Vec::new();
+    let mut fuzzy = 0usize;
+    // Line of the previous hit; fuzzy matches prefer candidates near it.
+    let mut last_line = None;
 
     for (idx, e) in edits.iter().enumerate() {
-        let Value::Object(obj) = e else {
-            misses.push(format!("#{} invalid edit object", idx + 1));
-            continue;
-        };
-        let old_s = obj
-            .get("old")
-            .or_else(|| obj.get("old_str"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        let new_s = obj
-            .get("new")
-            .or_else(|| obj.get("new_str"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("");
-        let replace_all = obj
-            .get("replace_all")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-        if old_s.is_empty() {
-            misses.push(format!("#{} old/old_str is empty", idx + 1));
-            continue;
-        }
-        if replace_all {
-            let count = working.matches(old_s).count();
-            if count == 0 {
-                misses.push(format!("#{} old_str not found (replace_all=true)", idx + 1));
-            } else {
-                working = working.replace(old_s, new_s);
-                hits.push(format!("#{} replace_all x{}", idx + 1, count));
+        match apply_edit(&mut working, &e.old, &e.new, e.replace_all, &mut last_line) {
+            Ok(EditHit::Exact) => hits.push(format!("#{} replaced once", idx + 1)),
+            Ok(EditHit::All(count)) => hits.push(format!("#{} replace_all x{}", idx + 1, count)),
+            Ok(EditHit::Fuzzy(m)) => {
+                hits.push(format!("#{} applied fuzzily ({})", idx + 1, m.describe()));
+                fuzzy += 1;
             }
-        } else if let Some(pos) = working.find(old_s) {
-            working.replace_range(pos..pos + old_s.len(), new_s);
-            hits.push(format!("#{} replaced once", idx + 1));
-        } else {
-            misses.push(format!("#{} old_str not found", idx + 1));
+            Err(miss) => misses.push(format!("#{} {}", idx + 1, miss)),
         }
     }
 
@@ -1595,7 +2373,9 @@ fn execute_native_fs_apply_patch(call: &ToolCall) -> Result<String> {
         bail!("{}", detail);
     }
 
-    fs::write(&path, working).with_context(|| format!("Failed to write {}", path.display()))?;
+    remember_file(&path);
+    fs::write(&path, &working).with_context(|| format!("Failed to write {}", path.display()))?;
+    record_file_change("patch", &path, &text, &working);
     let mut report = format!(
         "Applied {}/{} edit(s) to {} (strict={})",
         hits.len(),
@@ -1603,6 +2383,12 @@ fn execute_native_fs_apply_patch(call: &ToolCall) -> Result<String> {
         path.display(),
         strict
     );
+    if fuzzy > 0 {
+        report.push_str(&format!(
+            "\n{} edit(s) matched fuzzily; re-read the changed lines to confirm",
+            fuzzy
+        ));
+    }
     report.push_str("\nhits:");
     for h in hits.iter().take(20) {
         report.push_str(&format!("\n- {}", h));
@@ -1616,32 +2402,30 @@ fn execute_native_fs_apply_patch(call: &ToolCall) -> Result<String> {
     Ok(report)
 }
 
-fn execute_native_fs_move(call: &ToolCall) -> Result<String> {
-    let from_raw = tool_arg_string(call, &["from", "src", "source"])
-        .ok_or_else(|| anyhow::anyhow!("fs.move requires args.from"))?;
-    let to_raw = tool_arg_string(call, &["to", "dst", "target"])
-        .ok_or_else(|| anyhow::anyhow!("fs.move requires args.to"))?;
-    let from = resolve_native_path(&from_raw)?;
-    let to = resolve_native_path(&to_raw)?;
+fn execute_native_fs_move(args: &MoveArgs) -> Result<String> {
+    let from = resolve_native_path(&args.from)?;
+    let to = resolve_native_path(&args.to)?;
     if let Some(parent) = to.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("Failed to create parent dir {}", parent.display()))?;
     }
+    remember_file(&from);
+    remember_file(&to);
     fs::rename(&from, &to)
         .with_context(|| format!("Failed to move {} -> {}", from.display(), to.display()))?;
+    record_file_write("move-from", &from, None);
+    record_file_write("move-to", &to, None);
     Ok(format!("Moved: {} -> {}", from.display(), to.display()))
 }
 
-fn execute_native_fs_delete(call: &ToolCall) -> Result<String> {
-    let raw = tool_arg_string(call, &["path", "file", "target"])
-        .ok_or_else(|| anyhow::anyhow!("fs.delete requires args.path"))?;
-    let recursive = tool_arg_bool(call, &["recursive", "r"]).unwrap_or(false);
-    let p = resolve_native_path(&raw)?;
+fn execute_native_fs_delete(args: &DeleteArgs) -> Result<String> {
+    let p = resolve_native_path(&args.path)?;
     if !p.exists() {
         return Ok(format!("Skip delete; not found: {}", p.display()));
     }
+    remember_file(&p);
     if p.is_dir() {
-        if recursive {
+        if args.recursive {
             fs::remove_dir_all(&p)
                 .with_context(|| format!("Failed to remove dir {}", p.display()))?;
         } else {
@@ -1652,27 +2436,11 @@ fn execute_native_fs_delete(call: &ToolCall) -> Result<String> {
     } else {
         fs::remove_file(&p).with_context(|| format!("Failed to remove file {}", p.display()))?;
     }
+    record_file_write("delete", &p, None);
     Ok(format!("Deleted: {}", p.display()))
 }
 
-fn execute_structured_run_command(cfg: &mut Config, call: &ToolCall) -> Result<String> {
-    let cmd = tool_arg_string(call, &["command", "cmd"])
-        .or_else(|| {
-            if call.command.trim().is_empty() {
-                None
-            } else {
-                Some(call.command.clone())
-            }
-        })
-        .ok_or_else(|| anyhow::anyhow!("run_command requires args.command"))?;
-    let shell_call = ToolCall {
-        tool: "shell".to_string(),
-        command: cmd,
-        args: call.args.clone(),
-    };
-    execute_shell_tool_call(cfg, &shell_call)
-}
-fn resolve_native_path(raw: &str) -> Result<PathBuf> {
+pub(crate) fn resolve_native_path(raw: &str) -> Result<PathBuf> {
     let cwd = std::env::current_dir().context("Failed to get current dir")?;
     let base = if Path::new(raw).is_absolute() {
         PathBuf::from(raw)
@@ -1696,57 +2464,20 @@ fn resolve_native_path(raw: &str) -> Result<PathBuf> {
         base.clone()
     };
 
+    if is_dongshan_ignored(&normalized) {
+        bail!("{} is excluded by {}", raw, IGNORE_FILE);
+    }
     let cwd_norm = cwd
         .canonicalize()
         .with_context(|| format!("Failed to resolve cwd {}", cwd.display()))?;
-    if !normalized.starts_with(&cwd_norm) {
+    if !normalized.starts_with(&cwd_norm) && !in_extra_root(&normalized) {
+        report_blocked("file access", raw);
         bail!("path outside workspace is not allowed: {}", raw);
     }
     Ok(normalized)
 }
 
-fn tool_arg_string(call: &ToolCall, keys: &[&str]) -> Option<String> {
-    let Value::Object(map) = &call.args else {
-        return None;
-    };
-    for key in keys {
-        if let Some(v) = map.get(*key)
-            && let Some(s) = v.as_str()
-        {
-            return Some(s.to_string());
-        }
-    }
-    None
-}
-
-fn tool_arg_bool(call: &ToolCall, keys: &[&str]) -> Option<bool> {
-    let Value::Object(map) = &call.args else {
-        return None;
-    };
-    for key in keys {
-        if let Some(v) = map.get(*key)
-            && let Some(b) = v.as_bool()
-        {
-            return Some(b);
-        }
-    }
-    None
-}
-
-fn tool_arg_array(call: &ToolCall, keys: &[&str]) -> Option<Vec<Value>> {
-    let Value::Object(map) = &call.args else {
-        return None;
-    };
-    for key in keys {
-        if let Some(v) = map.get(*key)
-            && let Some(arr) = v.as_array()
-        {
-            return Some(arr.clone());
-        }
-    }
-    None
-}
-fn precheck_command(cmd: &str) -> Option<String> {
+pub(crate) fn precheck_command(cmd: &str) -> Option<String> {
     let tokens: Vec<&str> = cmd.split_whitespace().collect();
     if tokens.is_empty() {
         return Some("empty command".to_string());
@@ -1764,7 +2495,10 @@ fn precheck_command(cmd: &str) -> Option<String> {
 
     if (first == "python" || first == "python3") && lower.contains(" -c ") {
         if cmd.contains('\n') || cmd.len() > 360 {
-            return Some("python -c is too long/multiline; write .py file then run it".to_string());
+            return Some(
+                "python -c is too long/multiline; use the python tool with {code} instead"
+                    .to_string(),
+            );
         }
     }
 
@@ -1807,7 +2541,16 @@ async fn run_agent_turn(
     session: Option<&str>,
     render_markdown: bool,
 ) -> Result<()> {
-    let system = build_system_prompt(cfg, mode);
+    let mut system = build_system_prompt(cfg, mode);
+    let max_chars = cfg.repo_map_max_chars;
+    if max_chars > 0
+        && let Ok(cwd) = std::env::current_dir()
+        && let Ok(map) = run_blocking(move || build_repo_map(&cwd, max_chars)).await
+        && !map.is_empty()
+    {
+        system.push_str("\n\nRepo map (file: symbols; use it to pick files before grepping):\n");
+        system.push_str(&map);
+    }
     run_agent_turn_with_system(cfg, history, &system, session, render_markdown, true).await
 }
 
@@ -1843,6 +2586,7 @@ async fn run_agent_turn_with_system(
             .await
             {
                 Ok(()) => Ok(()),
+                Err(err) if is_budget_declined(&err) => Err(err),
                 Err(err) => {
                     cache_active_model_tool_mode(cfg, ToolCallMode::Json);
                     record_diagnostic(cfg, "native-request", &err.to_string(), session);
@@ -1875,14 +2619,14 @@ async fn run_agent_turn_with_system_native(
 ) -> Result<()> {
     let tools = native_tool_schemas();
     let mut messages = build_openai_messages(system, history);
-    let changed_baseline = current_changed_file_set().unwrap_or_default();
+    let changed_baseline = begin_turn_changes();
     let mut steps = 0usize;
     let mut unsafe_retries = 0usize;
     let mut invalid_format_retries = 0usize;
     let mut write_claim_retries = 0usize;
     let mut write_task_retries = 0usize;
     loop {
-        compact_native_messages(&mut messages, cfg.history_max_chars.max(2000));
+        compact_native_messages(&mut messages, history_budget_chars(cfg));
         println!(
             "{}",
             color_dim(&format!("(phase: reasoning step {})", steps + 1))
@@ -1891,11 +2635,13 @@ async fn run_agent_turn_with_system_native(
             "{}",
             color_rust(&format!(
                 "● assistant[{}]({})> ",
-                cfg.active_prompt, cfg.model
+                prompt_name(cfg),
+                cfg.model
             ))
         );
         let resp = call_llm_with_messages_native_tools(cfg, &messages, &tools).await?;
         let answer = resp.content.trim().to_string();
+        note_reply(&answer);
         if !answer.is_empty() {
             println!("{}", render_markdown_terminal(&answer, render_markdown));
         }
@@ -1992,16 +2738,14 @@ async fn run_agent_turn_with_system_native(
                     println!("assistant> {}", err);
                     return Ok(());
                 }
-                history.push(ChatMessage {
-                    role: "assistant".to_string(),
-                    content: answer,
-                });
+                history.push(ChatMessage::text("assistant", answer));
                 return Ok(());
             }
 
             cache_active_model_tool_mode(cfg, ToolCallMode::Json);
             if exec_result.executed_any {
-                let (verification, recovery_hint) = print_execution_and_verification(&exec_result)?;
+                let (verification, recovery_hint) =
+                    print_execution_and_verification(cfg, &exec_result, &changed_baseline).await?;
                 messages.push(json!({
                     "role":"user",
                     "content": format!(
@@ -2022,6 +2766,15 @@ async fn run_agent_turn_with_system_native(
                 continue;
             }
 
+            if exec_result.had_failures && invalid_format_retries < MAX_INVALID_FORMAT_RETRIES {
+                invalid_format_retries += 1;
+                messages.push(json!({
+                    "role":"user",
+                    "content": format!("{}\n{}", exec_result.history_text, TOOL_ERROR_RETRY_MSG)
+                }));
+                continue;
+            }
+
             if exec_result.invalid_format && invalid_format_retries < MAX_INVALID_FORMAT_RETRIES {
                 invalid_format_retries += 1;
                 messages.push(json!({
@@ -2075,7 +2828,8 @@ async fn run_agent_turn_with_system_native(
         }
 
         if exec_result.executed_any {
-            let (verification, recovery_hint) = print_execution_and_verification(&exec_result)?;
+            let (verification, recovery_hint) =
+                print_execution_and_verification(cfg, &exec_result, &changed_baseline).await?;
             messages.push(json!({
                 "role":"user",
                 "content": format!(
@@ -2095,6 +2849,15 @@ async fn run_agent_turn_with_system_native(
             continue;
         }
 
+        if exec_result.had_failures && invalid_format_retries < MAX_INVALID_FORMAT_RETRIES {
+            invalid_format_retries += 1;
+            messages.push(json!({
+                "role":"user",
+                "content": TOOL_ERROR_RETRY_MSG
+            }));
+            continue;
+        }
+
         if exec_result.skipped_any && unsafe_retries < 1 {
             unsafe_retries += 1;
             messages.push(json!({
@@ -2123,7 +2886,7 @@ fn cache_active_model_tool_mode(cfg: &mut Config, mode: ToolCallMode) {
         return;
     }
     set_model_tool_mode(cfg, &model, mode);
-    let _ = save_config(cfg);
+    let _ = update_config(|disk| set_model_tool_mode(disk, &model, mode));
 }
 
 async fn run_agent_turn_with_system_legacy(
@@ -2134,14 +2897,14 @@ async fn run_agent_turn_with_system_legacy(
     render_markdown: bool,
     allow_executor_fallback: bool,
 ) -> Result<()> {
-    let changed_baseline = current_changed_file_set().unwrap_or_default();
+    let changed_baseline = begin_turn_changes();
     let mut steps = 0usize;
     let mut unsafe_retries = 0usize;
     let mut invalid_format_retries = 0usize;
     let mut write_claim_retries = 0usize;
     let mut write_task_retries = 0usize;
     loop {
-        maybe_compact_history(history, cfg);
+        maybe_compact_history(history, cfg).await;
         println!(
             "{}",
             color_dim(&format!("(phase: reasoning step {})", steps + 1))
@@ -2150,7 +2913,8 @@ async fn run_agent_turn_with_system_legacy(
             "{}",
             color_rust(&format!(
                 "● assistant[{}]({})> ",
-                cfg.active_prompt, cfg.model
+                prompt_name(cfg),
+                cfg.model
             ))
         );
         let answer = match call_llm_with_history_stream_tools(cfg, system, history, &native_tool_schemas()).await {
@@ -2159,14 +2923,18 @@ async fn run_agent_turn_with_system_legacy(
                 record_diagnostic(cfg, "legacy-request", &err.to_string(), session);
                 println!("\n");
                 println!(
-                    "assistant> Request interrupted: {}",
-                    truncate_with_suffix(&err.to_string(), 220, " ...")
+                    "{}",
+                    tf(
+                        "chat.interrupted",
+                        &[&truncate_with_suffix(&err.to_string(), 220, " ...")]
+                    )
                 );
-                println!("assistant> You can continue chatting and send the next message.\n");
+                println!("{}\n", t("chat.continue_hint"));
                 return Ok(());
             }
         };
         println!("\n");
+        note_reply(&answer);
         if !answer.trim().is_empty() {
             println!("{}", render_markdown_terminal(&answer, render_markdown));
             println!("\n");
@@ -2191,10 +2959,7 @@ async fn run_agent_turn_with_system_legacy(
             Some(&exec_result),
             &changed_baseline,
         );
-        history.push(ChatMessage {
-            role: "assistant".to_string(),
-            content: answer.clone(),
-        });
+        history.push(ChatMessage::text("assistant", answer.clone()));
 
         if !exec_result.had_blocks {
             let changed_now = current_changed_file_set().unwrap_or_default();
@@ -2206,10 +2971,10 @@ async fn run_agent_turn_with_system_legacy(
                 &mut write_task_retries,
                 &mut write_claim_retries,
             ) {
-                history.push(ChatMessage {
-                    role: "user".to_string(),
-                    content: format!("{msg} {}", STRICT_TOOL_CALL_INSTRUCTION),
-                });
+                history.push(ChatMessage::text(
+                    "user",
+                    format!("{msg} {}", STRICT_TOOL_CALL_INSTRUCTION),
+                ));
                 continue;
             }
             if changed_delta.is_empty() && looks_like_write_request(&last_user) {
@@ -2275,16 +3040,14 @@ async fn run_agent_turn_with_system_legacy(
         }
 
         if exec_result.executed_any {
-            let (verification, recovery_hint) = print_execution_and_verification(&exec_result)?;
-            history.push(ChatMessage {
-                role: "user".to_string(),
-                content: format!(
+            let (verification, recovery_hint) =
+                print_execution_and_verification(cfg, &exec_result, &changed_baseline).await?;
+            history.push(ChatMessage::text("user", format!(
                     "{}\n{}{}\nContinue based on tool outputs above. If more execution is needed, emit JSON tool_calls. If complete, give final answer directly with short summary, changed files, and verification result.",
                     exec_result.history_text,
                     verification,
                     recovery_hint
-                ),
-            });
+                )));
             steps += 1;
             if steps >= MAX_AUTO_TOOL_STEPS {
                 println!(
@@ -2296,27 +3059,36 @@ async fn run_agent_turn_with_system_legacy(
             continue;
         }
 
+        if exec_result.had_failures && invalid_format_retries < MAX_INVALID_FORMAT_RETRIES {
+            invalid_format_retries += 1;
+            history.push(ChatMessage::text(
+                "user",
+                format!("{}\n{}", exec_result.history_text, TOOL_ERROR_RETRY_MSG),
+            ));
+            continue;
+        }
+
         if exec_result.invalid_format && invalid_format_retries < MAX_INVALID_FORMAT_RETRIES {
             invalid_format_retries += 1;
-            history.push(ChatMessage {
-                role: "user".to_string(),
-                content: format!(
+            history.push(ChatMessage::text(
+                "user",
+                format!(
                     "Your last response had invalid tool_calls format. {}",
                     STRICT_TOOL_CALL_INSTRUCTION
                 ),
-            });
+            ));
             continue;
         }
 
         if exec_result.skipped_any && unsafe_retries < 1 {
             unsafe_retries += 1;
-            history.push(ChatMessage {
-                role: "user".to_string(),
-                content: format!(
+            history.push(ChatMessage::text(
+                "user",
+                format!(
                     "Your last response used unsupported execution format or unsafe commands. {}",
                     STRICT_TOOL_CALL_INSTRUCTION
                 ),
-            });
+            ));
             continue;
         }
 
@@ -2579,7 +3351,11 @@ async fn try_executor_model_fallback(
     println!("assistant> {}", msg);
 
     *cfg = exec_cfg;
-    let _ = save_config(cfg);
+    // The executor becomes the default model unless this session picked its own.
+    if cfg.global_model.is_none() {
+        let model = cfg.model.clone();
+        let _ = update_config(|disk| set_active_model(disk, &model));
+    }
     Ok(true)
 }
 
@@ -2595,11 +3371,25 @@ fn last_user_text_from_native_messages(messages: &[Value]) -> Option<&str> {
     }
     None
 }
-fn extract_tool_calls(text: &str) -> Vec<ToolCall> {
+fn extract_tool_calls(text: &str) -> Vec<ParsedToolCall> {
     let mut out = Vec::new();
     collect_tool_calls_from_fence(text, "```json", "```", false, &mut out);
+    let from_fence = out.len();
     collect_tool_calls_from_inline_json(text, &mut out);
+    let from_inline = out.len() - from_fence;
     collect_tool_calls_from_code_execution(text, &mut out);
+    let from_code_exec = out.len() - from_fence - from_inline;
+    log_debug(
+        "tool-parse",
+        &format!(
+            "extracted {} call(s): fence={} inline_json={} code_execution={} (response {} chars)",
+            out.len(),
+            from_fence,
+            from_inline,
+            from_code_exec,
+            text.chars().count()
+        ),
+    );
     out
 }
 fn collect_tool_calls_from_fence(
@@ -2607,7 +3397,7 @@ fn collect_tool_calls_from_fence(
     open: &str,
     close: &str,
     skip_if_prev_backtick: bool,
-    out: &mut Vec<ToolCall>,
+    out: &mut Vec<ParsedToolCall>,
 ) {
     let mut i = 0usize;
     while i < text.len() {
@@ -2656,14 +3446,11 @@ async fn run_chat_turn(
     render_markdown: bool,
 ) -> Result<()> {
     let mut system = build_system_prompt(cfg, mode);
-    maybe_compact_history(history, cfg);
+    maybe_compact_history(history, cfg).await;
     println!("{}", color_dim("(phase: response)"));
     print!(
         "{}",
-        color_blue(&format!(
-            "assistant[{}]({})> ",
-            cfg.active_prompt, cfg.model
-        ))
+        color_blue(&format!("assistant[{}]({})> ", prompt_name(cfg), cfg.model))
     );
     for attempt in 0..=1usize {
         let answer = match call_llm_with_history_stream_tools(cfg, &system, history, &native_tool_schemas()).await {
@@ -2672,10 +3459,13 @@ async fn run_chat_turn(
                 record_diagnostic(cfg, "chat-lite-request", &err.to_string(), None);
                 println!("\n");
                 println!(
-                    "assistant> Request interrupted: {}",
-                    truncate_with_suffix(&err.to_string(), 220, " ...")
+                    "{}",
+                    tf(
+                        "chat.interrupted",
+                        &[&truncate_with_suffix(&err.to_string(), 220, " ...")]
+                    )
                 );
-                println!("assistant> You can continue chatting and send the next message.\n");
+                println!("{}\n", t("chat.continue_hint"));
                 return Ok(());
             }
         };
@@ -2697,21 +3487,16 @@ async fn run_chat_turn(
             }
             let msg = "Model returned tool_calls in chat mode. Use /mode agent-force for execution tasks.";
             println!("assistant> {}\n", msg);
-            history.push(ChatMessage {
-                role: "assistant".to_string(),
-                content: msg.to_string(),
-            });
+            history.push(ChatMessage::text("assistant", msg));
             return Ok(());
         }
 
+        note_reply(&answer);
         if !answer.trim().is_empty() {
             println!("{}", render_markdown_terminal(&answer, render_markdown));
             println!("\n");
         }
-        history.push(ChatMessage {
-            role: "assistant".to_string(),
-            content: answer,
-        });
+        history.push(ChatMessage::text("assistant", answer));
         return Ok(());
     }
     Ok(())
@@ -2753,19 +3538,23 @@ async fn classify_mode_with_llm(
 ) -> Result<Option<bool>> {
     let mut router_history: Vec<ChatMessage> =
         history.iter().rev().take(4).rev().cloned().collect();
-    router_history.push(ChatMessage {
-        role: "user".to_string(),
-        content: format!(
+    router_history.push(ChatMessage::text(
+        "user",
+        format!(
             "Route this request for terminal assistant mode.\n\
              Request: {input}\n\
              Output JSON only: {{\"mode\":\"agent\"|\"chat\",\"reason\":\"short\"}}"
         ),
-    });
+    ));
     let system = "You are a strict mode router for coding assistant.\n\
 Choose \"agent\" when task likely needs repo inspection, filesystem commands, file edits, test/build execution, or multi-step actions.\n\
 Choose \"chat\" for explanation-only or conceptual Q&A.\n\
 Output JSON only.";
-    let out = call_llm_with_history(cfg, system, &router_history).await?;
+    let router_cfg = match utility_model(cfg, None) {
+        Some(model) => config_for_model(cfg, model),
+        None => cfg.clone(),
+    };
+    let out = call_llm_with_history(&router_cfg, system, &router_history).await?;
     Ok(parse_router_mode(&out))
 }
 
@@ -2849,7 +3638,11 @@ fn looks_like_agent_task(input: &str) -> bool {
     en_hit || zh_hit
 }
 
-fn print_execution_and_verification(exec_result: &ExecResult) -> Result<(String, String)> {
+async fn print_execution_and_verification(
+    cfg: &Config,
+    exec_result: &ExecResult,
+    changed_baseline: &BTreeSet<String>,
+) -> Result<(String, String)> {
     println!("{}", color_dim("(phase: tool execution)"));
     let tool_calls = exec_result.display_text.matches("tool[").count();
     if exec_result.had_failures {
@@ -2858,7 +3651,9 @@ fn print_execution_and_verification(exec_result: &ExecResult) -> Result<(String,
         println!("{} tool calls executed.", tool_calls);
     }
     println!("{}", color_dim("(phase: verification)"));
-    let verification = run_auto_verification()?;
+    let verify_cfg = cfg.clone();
+    let verification = run_blocking(move || run_auto_verification(&verify_cfg)).await?;
+    note_verification(&verification);
     if !verification.trim().is_empty() && !verification.starts_with("verification: skipped") {
         println!("{} {}", color_dim("verify>"), verification);
     }
@@ -2871,6 +3666,24 @@ fn print_execution_and_verification(exec_result: &ExecResult) -> Result<(String,
             diff_preview.lines().next().unwrap_or_default()
         );
     }
+    if cfg.auto_commit && verification_passed(&verification) && is_git_repo() {
+        let paths: Vec<String> = list_workspace_changed_files()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| !changed_baseline.contains(p))
+            .collect();
+        if !paths.is_empty() {
+            match commit_agent_changes(cfg, &paths).await {
+                Ok(subject) => {
+                    println!("{} {}", color_dim("commit>"), subject);
+                    if let Ok(mut committed) = AUTO_COMMITTED.lock() {
+                        committed.extend(paths);
+                    }
+                }
+                Err(err) => println!("{} skipped ({err:#})", color_dim("commit>")),
+            }
+        }
+    }
     let recovery_hint = if exec_result.had_failures {
         "\nSome commands failed. Prefer narrower retries: check file/path existence first, then rerun minimal commands.".to_string()
     } else {
@@ -2884,20 +3697,80 @@ fn print_execution_and_verification(exec_result: &ExecResult) -> Result<(String,
     Ok((combined, recovery_hint))
 }
 
-fn run_auto_verification() -> Result<String> {
+pub(crate) fn run_auto_verification(cfg: &Config) -> Result<String> {
+    if cfg.verify_steps.iter().any(|s| !s.trim().is_empty()) {
+        return run_verification_steps(cfg);
+    }
     let Some((label, cmd)) = pick_verification_command() else {
         return Ok("verification: skipped (no supported project checker detected)".to_string());
     };
-    let out = run_shell_command(cmd)?;
-    let status = if looks_like_command_failure(&out) {
-        "failed"
-    } else {
-        "ok"
+    let (passed, out) = run_shell_command_with_status(cmd)?;
+    let failed = !passed || looks_like_command_failure(&out);
+    let status = if failed { "failed" } else { "ok" };
+    if let Ok(mut last) = LAST_VERIFICATION.lock() {
+        *last = Some(format!("{label}: {status}"));
+    }
+    let body = verification_output(cfg, failed, &out);
+    Ok(format!("verification[{label}] {status}\n$ {cmd}\n{body}"))
+}
+
+/// Whether a verification report ran at least one check and every check passed.
+fn verification_passed(report: &str) -> bool {
+    report
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("verification["))
+        .and_then(|rest| rest.split_once("] "))
+        .is_some_and(|(_, status)| status.starts_with("ok"))
+}
+
+/// For a failed check, the parsed failures (test, file:line, message) when the
+/// log is cargo/pytest/jest output; otherwise the log fitted to its budget.
+fn verification_output(cfg: &Config, failed: bool, out: &str) -> String {
+    if failed && let Some(summary) = summarize_test_failures(out) {
+        return summary;
+    }
+    fit_tool_output(cfg, "verification", out)
+}
+
+/// Runs `verify_steps` in order like a CI job and stops at the first step that
+/// exits non-zero; the report names the failed step and skips the rest.
+fn run_verification_steps(cfg: &Config) -> Result<String> {
+    let steps: Vec<&str> = cfg
+        .verify_steps
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let total = steps.len();
+    let mut lines = Vec::new();
+    let mut failure = None;
+    for (i, cmd) in steps.iter().enumerate() {
+        if failure.is_some() {
+            lines.push(format!("[{}/{}] {}: skipped", i + 1, total, cmd));
+            continue;
+        }
+        let (passed, out) = run_shell_command_with_status(cmd)?;
+        if passed {
+            lines.push(format!("[{}/{}] {}: ok", i + 1, total, cmd));
+        } else {
+            lines.push(format!("[{}/{}] {}: failed", i + 1, total, cmd));
+            failure = Some((i + 1, *cmd, out));
+        }
+    }
+    let status = match &failure {
+        Some((step, cmd, _)) => format!("failed at step {}/{} ({})", step, total, cmd),
+        None => format!("ok ({} steps)", total),
     };
-    let clipped = clip_output(&out, 5000);
-    Ok(format!(
-        "verification[{label}] {status}\n$ {cmd}\n{clipped}"
-    ))
+    if let Ok(mut last) = LAST_VERIFICATION.lock() {
+        *last = Some(format!("steps: {status}"));
+    }
+    let mut report = format!("verification[steps] {}\n{}", status, lines.join("\n"));
+    if let Some((_, cmd, out)) = failure {
+        let body = verification_output(cfg, true, &out);
+        report.push_str(&format!("\n$ {cmd}\n{body}"));
+    }
+    Ok(report)
 }
 
 fn collect_diff_preview(changed: &BTreeSet<String>) -> String {
@@ -3147,7 +4020,7 @@ fn build_diff_cache_key(changed: &BTreeSet<String>) -> String {
     out
 }
 
-fn pick_verification_command() -> Option<(&'static str, &'static str)> {
+pub(crate) fn pick_verification_command() -> Option<(&'static str, &'static str)> {
     if Path::new("Cargo.toml").exists() {
         return Some(("rust", "cargo check"));
     }
@@ -3162,7 +4035,7 @@ fn pick_verification_command() -> Option<(&'static str, &'static str)> {
     }
     None
 }
-fn collect_tool_calls_from_inline_json(text: &str, out: &mut Vec<ToolCall>) {
+fn collect_tool_calls_from_inline_json(text: &str, out: &mut Vec<ParsedToolCall>) {
     let bytes = text.as_bytes();
     let mut i = 0usize;
     while i < bytes.len() {
@@ -3186,7 +4059,7 @@ fn collect_tool_calls_from_inline_json(text: &str, out: &mut Vec<ToolCall>) {
     }
 }
 
-fn collect_tool_calls_from_code_execution(text: &str, out: &mut Vec<ToolCall>) {
+fn collect_tool_calls_from_code_execution(text: &str, out: &mut Vec<ParsedToolCall>) {
     let marker = "code_execution";
     let mut i = 0usize;
     while i < text.len() {
@@ -3213,33 +4086,38 @@ fn collect_tool_calls_from_code_execution(text: &str, out: &mut Vec<ToolCall>) {
     }
 }
 
-fn collect_tool_calls_from_python_code(code: &str, out: &mut Vec<ToolCall>) {
+fn collect_tool_calls_from_python_code(code: &str, out: &mut Vec<ParsedToolCall>) {
     if let Some((path, content)) = parse_python_write_file(code) {
-        out.push(ToolCall {
-            tool: "fs_create_file".to_string(),
-            command: String::new(),
-            args: json!({
-                "path": path,
-                "content": content,
-                "overwrite": true
+        out.push(ParsedToolCall::valid(
+            "fs_create_file",
+            ToolCall::CreateFile(CreateFileArgs {
+                path,
+                content,
+                overwrite: true,
             }),
-        });
+        ));
         return;
     }
     if let Some(path) = parse_python_read_file(code) {
-        out.push(ToolCall {
-            tool: "fs_read_file".to_string(),
-            command: String::new(),
-            args: json!({ "path": path }),
-        });
+        out.push(ParsedToolCall::valid(
+            "fs_read_file",
+            ToolCall::ReadFile(ReadFileArgs {
+                path,
+                offset: None,
+                limit: None,
+            }),
+        ));
         return;
     }
     if let Some(path) = parse_python_listdir(code) {
-        out.push(ToolCall {
-            tool: "fs_list_files".to_string(),
-            command: String::new(),
-            args: json!({ "path": path }),
-        });
+        out.push(ParsedToolCall::valid(
+            "fs_list_files",
+            ToolCall::ListFiles(ListFilesArgs {
+                path,
+                glob: None,
+                max_results: None,
+            }),
+        ));
     }
 }
 
@@ -3375,7 +4253,7 @@ fn find_matching_brace(text: &str, start: usize) -> Option<usize> {
     }
     None
 }
-fn collect_tool_calls_from_value(value: &Value, out: &mut Vec<ToolCall>) {
+fn collect_tool_calls_from_value(value: &Value, out: &mut Vec<ParsedToolCall>) {
     match value {
         Value::Array(items) => {
             for item in items {
@@ -3387,70 +4265,40 @@ fn collect_tool_calls_from_value(value: &Value, out: &mut Vec<ToolCall>) {
                 collect_tool_calls_from_value(calls, out);
                 return;
             }
-            let tool = map
-                .get("tool")
-                .or_else(|| map.get("type"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            let command = map
-                .get("command")
-                .or_else(|| map.get("cmd"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            let args = if let Some(v) = map.get("args") {
-                v.clone()
-            } else {
-                let mut m = serde_json::Map::new();
-                for (k, v) in map {
-                    if k == "tool"
-                        || k == "type"
-                        || k == "command"
-                        || k == "cmd"
-                        || k == "tool_calls"
-                    {
-                        continue;
-                    }
-                    m.insert(k.clone(), v.clone());
-                }
-                Value::Object(m)
-            };
-
-            let has_args = matches!(&args, Value::Object(m) if !m.is_empty());
-            if !tool.trim().is_empty() && (!command.trim().is_empty() || has_args) {
-                out.push(ToolCall {
-                    tool,
-                    command,
-                    args,
-                });
+            if let Some(call) = ParsedToolCall::from_json(map) {
+                out.push(call);
             }
         }
         _ => {}
     }
 }
-fn is_command_allowed(cfg: &Config, cmd: &str) -> bool {
-    if matches_list(&cfg.auto_exec_deny, cmd) {
-        return false;
+/// How the exec policy treats a command before any confirmation settings.
+pub(crate) enum CommandVerdict {
+    Allowed,
+    /// Not covered by `auto_exec` / `auto_exec_allow`.
+    NotAllowed,
+    /// Matches this `auto_exec_deny` rule; never runs.
+    Denied(String),
+}
+
+pub(crate) fn command_verdict(cfg: &Config, cmd: &str) -> CommandVerdict {
+    if let Some(rule) = denying_rule(&cfg.auto_exec_deny, cmd) {
+        return CommandVerdict::Denied(rule.clone());
     }
-    match cfg.auto_exec_mode {
+    let allowed = match approval_policy(cfg).exec_mode {
         AutoExecMode::All => true,
-        AutoExecMode::Safe => is_safe_auto_exec_command(cmd),
-        AutoExecMode::Custom => matches_list(&cfg.auto_exec_allow, cmd),
+        AutoExecMode::Safe => is_read_only_command(cmd),
+        AutoExecMode::Custom => matches_exec_rules(&cfg.auto_exec_allow, cmd),
+    };
+    if allowed {
+        CommandVerdict::Allowed
+    } else {
+        CommandVerdict::NotAllowed
     }
 }
 
-fn matches_list(list: &[String], cmd: &str) -> bool {
-    let cmd_l = cmd.to_ascii_lowercase();
-    list.iter().any(|item| {
-        let s = item.trim().to_ascii_lowercase();
-        !s.is_empty() && cmd_l.starts_with(&s)
-    })
-}
-
-fn is_trusted_command(cfg: &Config, cmd: &str) -> bool {
-    matches_list(&cfg.auto_exec_trusted, cmd)
+pub(crate) fn is_trusted_command(cfg: &Config, cmd: &str) -> bool {
+    matches_exec_rules(&cfg.auto_exec_trusted, cmd)
 }
 
 fn command_prefix(cmd: &str) -> String {
@@ -3464,31 +4312,12 @@ fn command_prefix(cmd: &str) -> String {
     first
 }
 
-fn is_safe_auto_exec_command(cmd: &str) -> bool {
-    let mut parts = cmd.split_whitespace();
-    let Some(first) = parts.next() else {
-        return false;
-    };
-    let f = first.to_ascii_lowercase();
-    if matches!(
-        f.as_str(),
-        "ls" | "dir" | "pwd" | "cat" | "type" | "rg" | "grep" | "findstr" | "tree" | "find"
-    ) {
-        return true;
-    }
-    if f == "get-childitem" || f == "get-content" || f == "get-location" {
-        return true;
-    }
-    if f == "git"
-        && let Some(second) = parts.next()
-    {
-        let s = second.to_ascii_lowercase();
-        return matches!(s.as_str(), "status" | "diff" | "log" | "show" | "branch");
-    }
-    false
+pub(crate) fn run_shell_command(cmd: &str) -> Result<String> {
+    run_shell_command_with_status(cmd).map(|(_, out)| out)
 }
 
-fn run_shell_command(cmd: &str) -> Result<String> {
+/// Like `run_shell_command`, but also reports whether the command exited successfully.
+pub(crate) fn run_shell_command_with_status(cmd: &str) -> Result<(bool, String)> {
     let short = if cmd.chars().count() > 48 {
         format!("exec {}...", prefix_chars(cmd, 48))
     } else {
@@ -3498,25 +4327,14 @@ fn run_shell_command(cmd: &str) -> Result<String> {
 
     if let Some(v) = run_translated_safe_command(cmd)? {
         working.finish();
-        return Ok(v);
+        record_command(cmd, Some(0), &v);
+        note_command(cmd, Some(0));
+        return Ok((true, v));
     }
 
-    let output = if cfg!(target_os = "windows") {
-        let normalized = normalize_windows_shell_command(cmd);
-        let wrapped = format!(
-            "$OutputEncoding = [Console]::OutputEncoding = [System.Text.UTF8Encoding]::new($false); {}",
-            normalized
-        );
-        Command::new("powershell")
-            .args(["-NoProfile", "-Command", &wrapped])
-            .output()
-            .with_context(|| format!("Failed to run command: {cmd}"))?
-    } else {
-        Command::new("sh")
-            .args(["-lc", cmd])
-            .output()
-            .with_context(|| format!("Failed to run command: {cmd}"))?
-    };
+    let output = shell_command(cmd)
+        .output()
+        .with_context(|| format!("Failed to run command: {cmd}"))?;
 
     let stdout = decode_command_output(&output.stdout);
     let stderr = decode_command_output(&output.stderr);
@@ -3534,35 +4352,11 @@ fn run_shell_command(cmd: &str) -> Result<String> {
         out = "(no output)".to_string();
     }
     working.finish();
-    Ok(out)
+    record_command(cmd, output.status.code(), &out);
+    note_command(cmd, output.status.code());
+    Ok((output.status.success(), out))
 }
 
-fn normalize_windows_shell_command(cmd: &str) -> String {
-    // Windows PowerShell 5.1 does not support "&&"; convert to sequential separator.
-    // This keeps common model-generated commands like `cd path && ls -la` runnable.
-    let mut out = String::with_capacity(cmd.len());
-    let mut chars = cmd.chars().peekable();
-    let mut in_single = false;
-    let mut in_double = false;
-    while let Some(ch) = chars.next() {
-        match ch {
-            '\'' if !in_double => {
-                in_single = !in_single;
-                out.push(ch);
-            }
-            '"' if !in_single => {
-                in_double = !in_double;
-                out.push(ch);
-            }
-            '&' if !in_single && !in_double && chars.peek() == Some(&'&') => {
-                let _ = chars.next();
-                out.push_str("; ");
-            }
-            _ => out.push(ch),
-        }
-    }
-    out
-}
 fn run_translated_safe_command(cmd: &str) -> Result<Option<String>> {
     if !cfg!(target_os = "windows") {
         return Ok(None);
@@ -3646,17 +4440,7 @@ fn limit_lines(s: &str, n: usize) -> String {
     s.lines().take(n).collect::<Vec<_>>().join("\n")
 }
 
-fn session_path(session: &str) -> Result<std::path::PathBuf> {
-    Ok(config_dir()?
-        .join("sessions")
-        .join(format!("{session}.json")))
-}
-
-fn sessions_dir() -> Result<PathBuf> {
-    Ok(config_dir()?.join("sessions"))
-}
-
-fn resolve_session_name(requested: &str) -> Result<String> {
+pub(crate) fn resolve_session_name(requested: &str) -> Result<String> {
     let name = if requested == "default" || requested == "auto" {
         workspace_session_base()?
     } else {
@@ -3705,15 +4489,11 @@ fn sanitize_session_name(name: &str) -> String {
     }
 }
 
+#[tracing::instrument(name = "session.load", skip_all, fields(session = %session))]
 fn load_session_or_default(session: &str) -> Result<Vec<ChatMessage>> {
-    let path = session_path(session)?;
-    if !path.exists() {
+    let Some(parsed) = storage().load_session(session)? else {
         return Ok(Vec::new());
-    }
-    let text =
-        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
-    let parsed: Vec<ChatMessage> = serde_json::from_str(&text)
-        .with_context(|| format!("Invalid session JSON: {}", path.display()))?;
+    };
     let repaired = parsed
         .into_iter()
         .map(|mut m| {
@@ -3724,12 +4504,8 @@ fn load_session_or_default(session: &str) -> Result<Vec<ChatMessage>> {
     Ok(repaired)
 }
 
+#[tracing::instrument(name = "session.save", skip_all, fields(session = %session, messages = messages.len()))]
 fn save_session(session: &str, messages: &[ChatMessage]) -> Result<()> {
-    let path = session_path(session)?;
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create session dir {}", parent.display()))?;
-    }
     let normalized: Vec<ChatMessage> = messages
         .iter()
         .cloned()
@@ -3738,24 +4514,110 @@ fn save_session(session: &str, messages: &[ChatMessage]) -> Result<()> {
             m
         })
         .collect();
-    let text = serde_json::to_string_pretty(&normalized)?;
-    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
-    Ok(())
+    storage().save_session(session, &normalized)
 }
 
 pub async fn run_agent_task(mut cfg: Config, session: &str, task: &str) -> Result<()> {
     let active_session = resolve_session_name(session)?;
-    println!("== dongshan agent ({active_session}) ==");
-    let mut history = load_session_or_default(&active_session)?;
-    let augmented_input = augment_user_input_with_workspace_context(task)?;
-    history.push(ChatMessage {
-        role: "user".to_string(),
-        content: augmented_input,
+    let started = Instant::now();
+    ci_event(
+        "start",
+        json!({
+            "task": task,
+            "session": active_session,
+            "model": cfg.model,
+            "approval": approval_level().as_str(),
+        }),
+    );
+    let result = run_agent_task_inner(&mut cfg, &active_session, task).await;
+    let changed = list_workspace_changed_files().unwrap_or_default();
+    // CI needs a verdict for its exit code even when the agent never verified.
+    if is_ci() && result.is_ok() && !changed.is_empty() && last_verification_status().is_none() {
+        match run_auto_verification(&cfg) {
+            Ok(report) => {
+                note_verification(&report);
+                println!("{}", report);
+            }
+            Err(err) => println!("verification: error ({err:#})"),
+        }
+    }
+    let summary = AgentRunSummary {
+        task,
+        session: &active_session,
+        duration: started.elapsed(),
+        changed_files: &changed,
+        verification: last_verification_status(),
+        error: result.as_ref().err().map(|e| format!("{e:#}")),
+    };
+    notify_agent_finished(&cfg, &summary).await;
+    record_agent_run(
+        task,
+        &changed,
+        summary.verification.as_deref(),
+        summary.error.as_deref(),
+    );
+    let verification = summary.verification.clone();
+    let _ = update_session_meta(&active_session, |meta| {
+        meta.task = Some(task.to_string());
+        meta.verification = verification;
+    });
+    let title = if result.is_ok() {
+        t("notify.agent_done")
+    } else {
+        t("notify.agent_failed")
+    };
+    notify_desktop_if_slow(&cfg, summary.duration, title, task);
+    let report = write_run_report(&RunOutcome {
+        task,
+        session: &active_session,
+        model: &cfg.model,
+        duration: summary.duration,
+        changed_files: &changed,
+        error: summary.error.as_deref(),
+        verification_failed: last_verification_failed(),
     });
+    match report {
+        Ok(Some(path)) => println!("agent> report written to {}", path.display()),
+        Ok(None) => {}
+        Err(err) => println!("agent> report failed: {err:#}"),
+    }
+    ci_event(
+        "done",
+        json!({
+            "ok": result.is_ok() && !last_verification_failed(),
+            "duration_secs": summary.duration.as_secs(),
+            "changed_files": changed,
+            "verification": summary.verification,
+            "error": summary.error,
+        }),
+    );
+    result
+}
 
-    maybe_compact_history(&mut history, &cfg);
-    run_agent_turn(&mut cfg, &mut history, "chat", Some(&active_session), true).await?;
-    save_session(&active_session, &history)?;
+async fn run_agent_task_inner(cfg: &mut Config, active_session: &str, task: &str) -> Result<()> {
+    println!("== dongshan agent ({active_session}) ==");
+    let mut history = load_session_or_default(active_session)?;
+    load_session_meta(active_session)?.apply(cfg);
+    record_session_seed(active_session, effective_seed(cfg))?;
+    set_transcript_session(active_session);
+    let augmented_input = augment_user_input_with_workspace_context(cfg, task).await?;
+    history.push(ChatMessage::text("user", augmented_input));
+
+    maybe_compact_history(&mut history, cfg).await;
+    if ensure_workspace_trust(cfg)? {
+        run_agent_turn(
+            cfg,
+            &mut history,
+            "chat",
+            Some(active_session),
+            markdown_enabled(),
+        )
+        .await?;
+    } else {
+        // Untrusted workspaces get an answer but no tools.
+        run_chat_turn(cfg, &mut history, "chat-lite", markdown_enabled()).await?;
+    }
+    save_session(active_session, &history)?;
 
     let changed = list_workspace_changed_files()?;
     if changed.is_empty() {
@@ -3769,6 +4631,153 @@ pub async fn run_agent_task(mut cfg: Config, session: &str, task: &str) -> Resul
     Ok(())
 }
 
+fn last_verification_status() -> Option<String> {
+    LAST_VERIFICATION.lock().ok().and_then(|v| v.clone())
+}
+
+/// Whether the last verification of this process ran and failed.
+pub(crate) fn last_verification_failed() -> bool {
+    last_verification_status().is_some_and(|s| s.contains(": failed"))
+}
+
+/// Prints this session's requests per turn with running token and cost totals.
+fn print_session_cost(cfg: &Config) {
+    let turns = tracked_turns();
+    if turns.is_empty() {
+        println!("{}", t("chat.cost_empty"));
+        return;
+    }
+    println!(
+        "  {:<3} {:<24} {:>4} {:>9} {:>9} {:>8}",
+        "#",
+        t("table.turn"),
+        t("table.requests"),
+        t("table.in"),
+        t("table.out"),
+        t("table.cost")
+    );
+    let (mut requests, mut prompt, mut completion, mut cost) = (0, 0, 0, 0.0);
+    let mut any_estimated = false;
+    for (i, turn) in turns.iter().enumerate() {
+        requests += turn.requests;
+        prompt += turn.prompt_tokens;
+        completion += turn.completion_tokens;
+        cost += turn.cost_usd;
+        any_estimated |= turn.estimated;
+        let label = truncate_with_suffix(&turn.label, 21, "...");
+        let mark = if turn.estimated { "~" } else { " " };
+        println!(
+            "  {:<3} {:<24} {:>4} {:>9} {:>9} {}${:.4}  {}",
+            i + 1,
+            label,
+            turn.requests,
+            turn.prompt_tokens,
+            turn.completion_tokens,
+            mark,
+            turn.cost_usd,
+            color_dim(&tf("cost.running_total", &[&format!("{:.4}", cost)]))
+        );
+    }
+    println!(
+        "{}",
+        color_bold(&format!(
+            "  {:<28} {:>4} {:>9} {:>9}  ${:.4}",
+            t("cost.session_total"),
+            requests,
+            prompt,
+            completion,
+            cost
+        ))
+    );
+    if cfg.cost_budget_usd > 0.0 {
+        let budget = format!("{:.2}", cfg.cost_budget_usd);
+        let used = format!("{:.0}", cost * 100.0 / cfg.cost_budget_usd);
+        println!("  {}", tf("cost.budget", &[&budget, &used]));
+    }
+    if any_estimated {
+        println!("{}", color_dim(t("cost.estimated_tokens")));
+    }
+    println!("{}", color_dim(t("cost.estimates_note")));
+}
+
+/// Messages listed one by one in `/tokens`; older ones are summed into one row.
+const TOKENS_RECENT_MESSAGES: usize = 12;
+
+/// Prints the estimated token cost of what the next request would send.
+fn print_token_breakdown(cfg: &Config, history: &[ChatMessage], exec_mode: ChatExecutionMode) {
+    let agent = !matches!(exec_mode, ChatExecutionMode::ChatOnly);
+    let system = build_system_prompt(cfg, if agent { "chat" } else { "chat-lite" });
+    let row = |label: &str, tokens: u64| println!("  {:<28} {:>8}", label, tokens);
+
+    println!("{}", color_dim(t("tokens.header")));
+    let mut total = estimate_tokens(&system);
+    row(t("tokens.system_prompt"), total);
+    if agent && active_effective_tool_mode(cfg) != ToolCallMode::Json {
+        let schemas = serde_json::to_string(&native_tool_schemas()).unwrap_or_default();
+        let tokens = estimate_tokens(&schemas);
+        row(t("tokens.tool_schemas"), tokens);
+        total += tokens;
+    }
+    if agent
+        && cfg.repo_map_max_chars > 0
+        && let Ok(cwd) = std::env::current_dir()
+        && let Ok(map) = build_repo_map(&cwd, cfg.repo_map_max_chars)
+        && !map.is_empty()
+    {
+        let tokens = estimate_tokens(&map);
+        row(t("tokens.repo_map"), tokens);
+        total += tokens;
+    }
+
+    let (summaries, messages): (Vec<_>, Vec<_>) = history
+        .iter()
+        .partition(|m| m.content.starts_with("[session-summary]"));
+    for m in summaries {
+        let tokens = estimate_tokens(&m.content);
+        row(t("tokens.session_summary"), tokens);
+        total += tokens;
+    }
+    let older = messages.len().saturating_sub(TOKENS_RECENT_MESSAGES);
+    if older > 0 {
+        let tokens: u64 = messages[..older]
+            .iter()
+            .map(|m| estimate_tokens(&m.content))
+            .sum();
+        row(&tf("tokens.older_messages", &[&older]), tokens);
+        total += tokens;
+    }
+    for m in &messages[older..] {
+        let preview: String = m.content.split_whitespace().collect::<Vec<_>>().join(" ");
+        let label = format!(
+            "{:<9} {}",
+            m.role,
+            truncate_with_suffix(&preview, 15, "...")
+        );
+        let tokens = estimate_tokens(&m.content);
+        row(&label, tokens);
+        total += tokens;
+    }
+
+    let window = model_context_window(cfg, &cfg.model);
+    let pct = total as f64 * 100.0 / window as f64;
+    let share = tf("tokens.window", &[&format!("{:.1}", pct), &cfg.model]);
+    let summary = format!(
+        "  {:<28} {:>8} / {} {}",
+        t("tokens.total"),
+        total,
+        window,
+        share
+    );
+    let summary = if pct >= 90.0 {
+        color_red(&summary)
+    } else if pct >= 70.0 {
+        color_yellow(&summary)
+    } else {
+        color_green(&summary)
+    };
+    println!("{}", summary);
+}
+
 fn print_status(cfg: &Config) -> Result<()> {
     let provider = cfg
         .model_profiles
@@ -3783,6 +4792,9 @@ fn print_status(cfg: &Config) -> Result<()> {
         "executor_model: {}",
         cfg.executor_model.as_deref().unwrap_or("(none)")
     );
+    if let Some(seed) = effective_seed(cfg) {
+        println!("seed: {}", seed);
+    }
     let changed = list_workspace_changed_files()?;
     println!("changed_files: {}", changed.len());
     for p in changed.iter().take(8) {
@@ -3909,48 +4921,13 @@ fn native_calls_to_values(
 
 fn tool_calls_from_text(text: &str) -> Vec<Value> {
     extract_tool_calls(text)
-        .into_iter()
-        .map(|c| {
-            json!({
-                "tool": c.tool,
-                "command": c.command,
-                "args": c.args
-            })
-        })
+        .iter()
+        .map(ParsedToolCall::to_json)
         .collect()
 }
 
-fn list_saved_sessions() -> Result<Vec<String>> {
-    let dir = sessions_dir()?;
-    if !dir.exists() {
-        return Ok(Vec::new());
-    }
-
-    let mut names = Vec::new();
-    for entry in fs::read_dir(&dir)
-        .with_context(|| format!("Failed to read session dir {}", dir.display()))?
-    {
-        let entry = entry.with_context(|| format!("Failed to read entry in {}", dir.display()))?;
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("json") {
-            continue;
-        }
-        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
-            continue;
-        };
-        names.push(stem.to_string());
-    }
-    names.sort();
-    Ok(names)
-}
-
-fn remove_session_file(session: &str) -> Result<bool> {
-    let path = session_path(session)?;
-    if !path.exists() {
-        return Ok(false);
-    }
-    fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
-    Ok(true)
+pub(crate) fn list_saved_sessions() -> Result<Vec<String>> {
+    storage().list_sessions()
 }
 
 fn list_workspace_changed_files() -> Result<Vec<String>> {
@@ -3999,6 +4976,49 @@ fn read_git_status_entries() -> Result<Vec<(String, String)>> {
     Ok(entries)
 }
 
+/// Changes against HEAD (staged and unstaged) plus a list of untracked files.
+fn collect_git_diff(path: Option<&str>) -> Result<String> {
+    if !is_git_repo() {
+        bail!("Not inside a git repository");
+    }
+    let run = |args: &[&str]| -> Result<std::process::Output> {
+        Command::new("git")
+            .args(args)
+            .args(path)
+            .output()
+            .context("Failed to run git")
+    };
+    let mut out = run(&["diff", "--no-color", "HEAD", "--"])?;
+    if !out.status.success() {
+        // No commits yet: fall back to the unstaged diff.
+        out = run(&["diff", "--no-color", "--"])?;
+    }
+    let mut diff = decode_command_output(&out.stdout);
+    let untracked = run(&["ls-files", "--others", "--exclude-standard", "--"])?;
+    let untracked = decode_command_output(&untracked.stdout);
+    if !untracked.trim().is_empty() {
+        diff.push_str("\nUntracked files:\n");
+        for file in untracked.lines() {
+            diff.push_str(&format!("  {}\n", file));
+        }
+    }
+    Ok(diff)
+}
+
+fn color_diff_line(line: &str) -> String {
+    if line.starts_with("+++") || line.starts_with("---") || line.starts_with("diff --git") {
+        color_bold(line)
+    } else if line.starts_with('+') {
+        color_green(line)
+    } else if line.starts_with('-') {
+        color_red(line)
+    } else if line.starts_with("@@") {
+        color_cyan(line)
+    } else {
+        line.to_string()
+    }
+}
+
 fn is_git_repo() -> bool {
     let out = Command::new("git")
         .args(["rev-parse", "--is-inside-work-tree"])
@@ -4044,28 +5064,18 @@ fn list_workspace_changed_files_fs() -> Result<Vec<String>> {
     Ok(changed.into_iter().collect())
 }
 
+/// Size, mtime and content digest of every workspace file, from the cached
+/// file list so unchanged directories are not walked again.
 fn collect_fs_snapshot(root: &Path) -> Result<BTreeMap<String, FsEntry>> {
     let mut out = BTreeMap::new();
-    collect_fs_snapshot_walk(root, root, &mut out)?;
-    Ok(out)
-}
-
-fn collect_fs_snapshot_walk(
-    root: &Path,
-    dir: &Path,
-    out: &mut BTreeMap<String, FsEntry>,
-) -> Result<()> {
-    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
-        let entry = entry.with_context(|| format!("Failed to read entry in {}", dir.display()))?;
-        let path = entry.path();
+    for path in indexed_files(root)? {
         let rel = path.strip_prefix(root).unwrap_or(&path);
         let rel_str = normalize_rel_path(rel);
         if should_skip_fs_path(&rel_str) {
             continue;
         }
-        let meta = match entry.metadata() {
-            Ok(m) => m,
-            Err(_) => continue,
+        let Ok(meta) = fs::metadata(&path) else {
+            continue;
         };
         let mtime_unix = meta
             .modified()
@@ -4073,30 +5083,16 @@ fn collect_fs_snapshot_walk(
             .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
             .map(|d| d.as_secs() as i64)
             .unwrap_or(0);
-        if meta.is_dir() {
-            out.insert(
-                rel_str.clone(),
-                FsEntry {
-                    is_dir: true,
-                    size: 0,
-                    mtime_unix,
-                    digest: "dir".to_string(),
-                },
-            );
-            collect_fs_snapshot_walk(root, &path, out)?;
-        } else {
-            out.insert(
-                rel_str.clone(),
-                FsEntry {
-                    is_dir: false,
-                    size: meta.len(),
-                    mtime_unix,
-                    digest: compute_fs_digest(&path, meta.len(), mtime_unix),
-                },
-            );
-        }
+        out.insert(
+            rel_str,
+            FsEntry {
+                size: meta.len(),
+                mtime_unix,
+                digest: compute_fs_digest(&path, meta.len(), mtime_unix),
+            },
+        );
     }
-    Ok(())
+    Ok(out)
 }
 
 fn normalize_rel_path(p: &Path) -> String {
@@ -4129,8 +5125,23 @@ fn compute_fs_digest(path: &Path, size: u64, mtime_unix: i64) -> String {
     format!("h:{:x}", hasher.finish())
 }
 
+/// Changed files, plus the ones `auto_commit` already committed this turn so
+/// they still count as the turn's changes.
 fn current_changed_file_set() -> Result<BTreeSet<String>> {
-    Ok(list_workspace_changed_files()?.into_iter().collect())
+    let mut changed: BTreeSet<String> = list_workspace_changed_files()?.into_iter().collect();
+    if let Ok(committed) = AUTO_COMMITTED.lock() {
+        changed.extend(committed.iter().cloned());
+    }
+    Ok(changed)
+}
+
+/// Starts a new agent turn: forgets earlier auto-commits and returns the
+/// files already changed before the turn.
+fn begin_turn_changes() -> BTreeSet<String> {
+    if let Ok(mut committed) = AUTO_COMMITTED.lock() {
+        committed.clear();
+    }
+    current_changed_file_set().unwrap_or_default()
 }
 
 fn print_changed_files_delta(before: &BTreeSet<String>) -> Result<()> {
@@ -4207,25 +5218,11 @@ fn fingerprint_for_workspace_path(rel: &str) -> String {
     format!("file:{}:{:x}", bytes.len(), hasher.finish())
 }
 
-fn guessed_changed_files_for_call(call: &ToolCall) -> Vec<String> {
-    let tool = call.tool.trim().to_ascii_lowercase();
-    let mut out = BTreeSet::new();
-    match tool.as_str() {
-        "fs.create_file" | "fs.edit_file" | "fs.apply_patch" | "fs.delete" => {
-            if let Some(path) = tool_arg_string(call, &["path", "file", "target"]) {
-                out.insert(path);
-            }
-        }
-        "fs.move" => {
-            if let Some(path) = tool_arg_string(call, &["from", "src", "source"]) {
-                out.insert(path);
-            }
-            if let Some(path) = tool_arg_string(call, &["to", "dst", "target"]) {
-                out.insert(path);
-            }
-        }
-        _ => {}
-    }
+fn guessed_changed_files_for_call(call: &ParsedToolCall) -> Vec<String> {
+    let Ok(call) = &call.call else {
+        return Vec::new();
+    };
+    let out: BTreeSet<String> = call.written_paths().into_iter().collect();
     out.into_iter().collect()
 }
 
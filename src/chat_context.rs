@@ -1,20 +1,83 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
 
 use anyhow::Result;
 
-pub fn augment_user_input_with_workspace_context(input: &str) -> Result<String> {
+use crate::config::{Config, context_budget_chars};
+use crate::file_index::indexed_files;
+use crate::fs_tools::relative_path;
+use crate::ignore_rules::is_dongshan_ignored;
+use crate::logging::log_verbose;
+use crate::packages::{detect_workspace, render_workspace, summarize_manifests};
+use crate::redact::redact_secrets;
+use crate::util::{run_blocking, truncate_with_suffix};
+use crate::workspace_index::search_index;
+
+const MAX_SCAN_FILES: usize = 3000;
+const MAX_SCAN_BYTES: u64 = 256 * 1024;
+const MAX_KEYWORDS: usize = 12;
+const INDEX_TOP_K: usize = 8;
+const SNIPPET_CONTEXT_LINES: usize = 3;
+const SNIPPET_MAX_LINES: usize = 24;
+const SELECTION_MAX_CHARS: usize = 8000;
+/// Total size of file contents kept between turns for the keyword scan.
+const MAX_CACHED_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Size, mtime and contents of a scanned file.
+type CachedText = (u64, u64, Arc<str>);
+
+static TEXT_CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedText>>> = OnceLock::new();
+
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "with", "this", "that", "from", "into", "what", "how", "why", "can",
+    "you", "please", "help", "make", "use", "add", "fix", "should", "would", "could", "about",
+    "all", "are", "not", "but", "when", "where", "which", "then", "them", "there", "file", "files",
+    "code", "project", "does", "have", "has", "will", "let", "get", "set", "new",
+];
+
+#[derive(Debug, Default, Clone)]
+struct FileCandidate {
+    score: f32,
+    reasons: Vec<String>,
+    snippet: Option<(usize, String)>,
+}
+
+/// Prefixes the user request with workspace context: the files most relevant to
+/// it (explicit paths, keyword hits and index matches), or a root overview for
+/// whole-project questions when nothing specific matched.
+pub async fn augment_user_input_with_workspace_context(
+    cfg: &Config,
+    input: &str,
+) -> Result<String> {
     let cwd = env::current_dir()?;
     let mut out = format!("Workspace CWD: {}\nUser request: {}", cwd.display(), input);
 
-    if is_project_analysis_request(input) {
-        let snapshot = build_project_snapshot(&cwd)?;
+    let selected = if cfg.auto_context_files > 0 {
+        select_relevant_files(cfg, &cwd, input, cfg.auto_context_files).await
+    } else {
+        String::new()
+    };
+    if !selected.is_empty() {
+        out = format!(
+            "Workspace CWD: {}\nRelevant files (auto-selected, read them for full content):\n{}\n\nUser request: {}",
+            cwd.display(),
+            redact_secrets(&selected),
+            input
+        );
+    } else if is_project_analysis_request(input) {
+        let root = cwd.clone();
+        let mut overview = run_blocking(move || build_root_overview(&root)).await?;
+        if let Some(max_chars) = context_budget_chars(cfg, 10) {
+            overview = truncate_with_suffix(&overview, max_chars, "\n...[snapshot truncated]");
+        }
         out = format!(
             "Workspace CWD: {}\nAuto project snapshot:\n{}\n\nUser request: {}",
             cwd.display(),
-            snapshot,
+            redact_secrets(&overview),
             input
         );
     }
@@ -22,6 +85,207 @@ pub fn augment_user_input_with_workspace_context(input: &str) -> Result<String>
     Ok(out)
 }
 
+async fn select_relevant_files(cfg: &Config, root: &Path, input: &str, limit: usize) -> String {
+    let mut candidates: BTreeMap<String, FileCandidate> = BTreeMap::new();
+
+    for token in input.split_whitespace() {
+        let token = token.trim_matches(|c: char| "`'\"()[]{}<>,;:。，：；".contains(c));
+        if token.is_empty() || !(token.contains('/') || token.contains('.')) {
+            continue;
+        }
+        let path = root.join(token);
+        if path.is_file()
+            && !is_dongshan_ignored(&path)
+            && let Some(rel) = relative_path(root, &path)
+        {
+            let c = candidates.entry(rel).or_default();
+            c.score += 100.0;
+            c.reasons.push("mentioned".to_string());
+        }
+    }
+
+    let keywords = extract_keywords(input);
+    if !keywords.is_empty() {
+        let (scan_root, scan_keywords) = (root.to_path_buf(), keywords.clone());
+        let mut scanned = candidates.clone();
+        match run_blocking(move || {
+            score_keyword_hits(&scan_root, &scan_keywords, &mut scanned);
+            Ok(scanned)
+        })
+        .await
+        {
+            Ok(scanned) => candidates = scanned,
+            Err(err) => log_verbose("context", &format!("keyword scan skipped: {err:#}")),
+        }
+    }
+
+    match search_index(cfg, root, input, INDEX_TOP_K).await {
+        Ok(hits) => {
+            for hit in hits {
+                // The index may predate the ignore rules.
+                if is_dongshan_ignored(&root.join(&hit.chunk.path)) {
+                    continue;
+                }
+                let c = candidates.entry(hit.chunk.path.clone()).or_default();
+                c.score += hit.score * 20.0;
+                if !c.reasons.iter().any(|r| r == "index") {
+                    c.reasons.push("index".to_string());
+                }
+                if c.snippet.is_none() {
+                    let text = hit
+                        .chunk
+                        .text
+                        .lines()
+                        .take(SNIPPET_MAX_LINES)
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    c.snippet = Some((hit.chunk.start_line, text));
+                }
+            }
+        }
+        Err(err) => log_verbose("context", &format!("index retrieval skipped: {}", err)),
+    }
+
+    let mut ranked: Vec<(String, FileCandidate)> = candidates
+        .into_iter()
+        .filter(|(_, c)| c.score > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| b.1.score.total_cmp(&a.1.score).then(a.0.cmp(&b.0)));
+    ranked.truncate(limit);
+
+    let max_chars = context_budget_chars(cfg, 10).unwrap_or(SELECTION_MAX_CHARS);
+    let mut out = String::new();
+    for (path, c) in ranked {
+        let mut block = format!("--- {} ({}) ---\n", path, c.reasons.join(", "));
+        if let Some((start, text)) = &c.snippet {
+            block.push_str(&format!("from line {}:\n{}\n", start, text));
+        }
+        if out.len() + block.len() > max_chars {
+            out.push_str(&format!("- {}\n", path));
+            continue;
+        }
+        out.push_str(&block);
+    }
+    log_verbose(
+        "context",
+        &format!("auto-selected files for keywords {:?}", keywords),
+    );
+    out.trim_end().to_string()
+}
+
+fn extract_keywords(input: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for word in input.split(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+        let lower = word.to_ascii_lowercase();
+        if lower.len() < 3 || STOPWORDS.contains(&lower.as_str()) {
+            continue;
+        }
+        if lower.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        if seen.insert(lower.clone()) {
+            out.push(lower);
+        }
+        if out.len() >= MAX_KEYWORDS {
+            break;
+        }
+    }
+    out
+}
+
+fn score_keyword_hits(
+    root: &Path,
+    keywords: &[String],
+    candidates: &mut BTreeMap<String, FileCandidate>,
+) {
+    let Ok(files) = indexed_files(root) else {
+        return;
+    };
+    for path in files.into_iter().take(MAX_SCAN_FILES) {
+        let Some(rel) = relative_path(root, &path) else {
+            continue;
+        };
+        let rel_lower = rel.to_ascii_lowercase();
+        let mut score = 0.0f32;
+        let mut matched = Vec::new();
+        for k in keywords {
+            if rel_lower.contains(k.as_str()) {
+                score += 5.0;
+                matched.push(k.clone());
+            }
+        }
+
+        let text = scan_text(&path);
+        let lines: Vec<&str> = text.lines().collect();
+        // ASCII lowercasing keeps byte offsets, so the lines line up.
+        let lower = text.to_ascii_lowercase();
+        let lower_lines: Vec<&str> = lower.lines().collect();
+        let mut first_hit: Option<usize> = None;
+        for k in keywords {
+            let hits = lower_lines
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| l.contains(k.as_str()))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            if hits.is_empty() {
+                continue;
+            }
+            score += hits.len().min(5) as f32;
+            if !matched.contains(k) {
+                matched.push(k.clone());
+            }
+            first_hit = Some(first_hit.map_or(hits[0], |f: usize| f.min(hits[0])));
+        }
+        if score == 0.0 {
+            continue;
+        }
+        // Files matching several distinct keywords beat files repeating one.
+        score *= matched.len() as f32;
+
+        let c = candidates.entry(rel).or_default();
+        c.score += score;
+        c.reasons.push(format!("keywords: {}", matched.join(" ")));
+        if let Some(hit) = first_hit {
+            let start = hit.saturating_sub(SNIPPET_CONTEXT_LINES);
+            let end = (hit + SNIPPET_CONTEXT_LINES + 1).min(lines.len());
+            c.snippet = Some((start + 1, lines[start..end].join("\n")));
+        }
+    }
+}
+
+/// Contents of a file small enough to scan, empty otherwise. Kept for the
+/// session by size and mtime, so later turns only re-read files that changed.
+fn scan_text(path: &Path) -> Arc<str> {
+    let meta = match fs::metadata(path) {
+        Ok(meta) if meta.is_file() && meta.len() <= MAX_SCAN_BYTES => meta,
+        _ => return Arc::from(""),
+    };
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let cache = TEXT_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Ok(guard) = cache.lock()
+        && let Some((size, time, text)) = guard.get(path)
+        && *size == meta.len()
+        && *time == mtime
+    {
+        return text.clone();
+    }
+    let text: Arc<str> = Arc::from(fs::read_to_string(path).unwrap_or_default());
+    if let Ok(mut guard) = cache.lock() {
+        let cached: u64 = guard.values().map(|(size, _, _)| size).sum();
+        if cached + meta.len() <= MAX_CACHED_BYTES || guard.contains_key(path) {
+            guard.insert(path.to_path_buf(), (meta.len(), mtime, text.clone()));
+        }
+    }
+    text
+}
+
 fn is_project_analysis_request(input: &str) -> bool {
     let t = input.to_lowercase();
     let keys = [
@@ -38,9 +302,8 @@ fn is_project_analysis_request(input: &str) -> bool {
     keys.iter().any(|k| t.contains(k))
 }
 
-fn build_project_snapshot(root: &Path) -> Result<String> {
+fn build_root_overview(root: &Path) -> Result<String> {
     let mut lines: Vec<String> = Vec::new();
-
     let root_entries = read_root_entries(root)?;
     lines.push("Root entries:".to_string());
     if root_entries.is_empty() {
@@ -53,40 +316,16 @@ fn build_project_snapshot(root: &Path) -> Result<String> {
             lines.push(format!("- ... ({} more)", root_entries.len() - 80));
         }
     }
-
-    let files = collect_files(root)?;
-    lines.push(format!("Total indexed files: {}", files.len()));
-    lines.push("Sample files:".to_string());
-    for path in files.iter().take(120) {
-        lines.push(format!("- {}", path.display()));
-    }
-    if files.len() > 120 {
-        lines.push(format!("- ... ({} more)", files.len() - 120));
-    }
-
-    let manifests = [
-        "Cargo.toml",
-        "package.json",
-        "pyproject.toml",
-        "requirements.txt",
-        "go.mod",
-        "pom.xml",
-    ];
-    lines.push("Manifest previews:".to_string());
-    let mut found_manifest = false;
-    for m in manifests {
-        let p = root.join(m);
-        if p.exists() && p.is_file() {
-            found_manifest = true;
-            let text = fs::read_to_string(&p).unwrap_or_else(|_| "<unreadable>".to_string());
-            let preview: String = text.lines().take(80).collect::<Vec<_>>().join("\n");
-            lines.push(format!("--- {} ---\n{}", m, preview));
-        }
+    if let Some(workspace) = detect_workspace(root) {
+        lines.push(render_workspace(&workspace));
     }
-    if !found_manifest {
-        lines.push("- none found in workspace root".to_string());
+    let manifests = summarize_manifests(root);
+    if !manifests.is_empty() {
+        lines.push("Manifests:".to_string());
+        lines.push(manifests);
     }
-
+    let files = indexed_files(root)?;
+    lines.push(format!("Total indexed files: {}", files.len()));
     Ok(lines.join("\n"))
 }
 
@@ -96,7 +335,7 @@ fn read_root_entries(root: &Path) -> Result<Vec<String>> {
     for entry in entries {
         let entry = entry?;
         let p = entry.path();
-        if is_ignored(&p) {
+        if is_ignored(&p) || is_dongshan_ignored(&p) {
             continue;
         }
         let name = p
@@ -114,61 +353,12 @@ fn read_root_entries(root: &Path) -> Result<Vec<String>> {
     Ok(out)
 }
 
-fn collect_files(root: &Path) -> Result<Vec<PathBuf>> {
-    if let Some(from_rg) = collect_files_by_rg(root) {
-        return Ok(from_rg);
-    }
-    collect_files_by_walk(root)
-}
-
-fn collect_files_by_rg(root: &Path) -> Option<Vec<PathBuf>> {
-    let output = Command::new("rg").arg("--files").arg(root).output().ok()?;
-    if !output.status.success() {
-        return None;
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut files = Vec::new();
-    for line in stdout.lines() {
-        if line.trim().is_empty() {
-            continue;
-        }
-        files.push(PathBuf::from(line));
-    }
-    files.sort();
-    Some(files)
-}
-
-fn collect_files_by_walk(root: &Path) -> Result<Vec<PathBuf>> {
-    let mut out = Vec::new();
-    let mut stack = vec![root.to_path_buf()];
-    while let Some(path) = stack.pop() {
-        if is_ignored(&path) {
-            continue;
-        }
-        let Ok(meta) = fs::metadata(&path) else {
-            continue;
-        };
-        if meta.is_dir() {
-            let Ok(entries) = fs::read_dir(&path) else {
-                continue;
-            };
-            for entry in entries.flatten() {
-                stack.push(entry.path());
-            }
-        } else {
-            out.push(path);
-        }
-    }
-    out.sort();
-    Ok(out)
-}
-
 fn is_ignored(path: &Path) -> bool {
     let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
         return false;
     };
     matches!(
         name,
-        ".git" | "node_modules" | "target" | ".idea" | ".vscode"
+        ".git" | ".dongshan" | "node_modules" | "target" | ".idea" | ".vscode"
     )
 }
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+
+use crate::backups::save_backup;
+use crate::transcript::record_file_write;
+
+/// Undo steps kept per process; older turns drop off.
+const MAX_CHECKPOINTS: usize = 10;
+/// Files captured from one directory delete; larger trees are not undoable.
+const MAX_DIR_FILES: usize = 500;
+
+/// What one turn changed: the original contents of every file its file tools
+/// touched (`None` = did not exist) and the shell commands it ran, whose
+/// effects are not captured.
+#[derive(Default)]
+struct Checkpoint {
+    files: BTreeMap<PathBuf, Option<Vec<u8>>>,
+    commands: Vec<String>,
+}
+
+impl Checkpoint {
+    fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.commands.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct CheckpointState {
+    pending: Checkpoint,
+    stack: Vec<Checkpoint>,
+}
+
+fn state() -> &'static Mutex<CheckpointState> {
+    static STATE: OnceLock<Mutex<CheckpointState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(CheckpointState::default()))
+}
+
+/// Closes the previous turn's checkpoint (if it changed anything) and starts a new one.
+pub fn start_turn() {
+    if let Ok(mut s) = state().lock() {
+        let pending = std::mem::take(&mut s.pending);
+        if !pending.is_empty() {
+            s.stack.push(pending);
+            if s.stack.len() > MAX_CHECKPOINTS {
+                s.stack.remove(0);
+            }
+        }
+    }
+}
+
+/// Records the current content of `path` before a tool writes, moves or deletes
+/// it. Only the first call per path in a turn counts.
+pub fn remember_file(path: &Path) {
+    let abs = absolute(path);
+    if abs.is_dir() {
+        let mut files = Vec::new();
+        collect_files(&abs, &mut files);
+        if files.len() <= MAX_DIR_FILES {
+            for file in files {
+                remember_file(&file);
+            }
+        }
+        return;
+    }
+    let Ok(mut s) = state().lock() else {
+        return;
+    };
+    s.pending
+        .files
+        .entry(abs.clone())
+        .or_insert_with(|| fs::read(&abs).ok());
+}
+
+/// Notes a shell command the turn ran, so `/undo` can say its changes were
+/// left in place.
+pub fn remember_command(cmd: &str) {
+    if let Ok(mut s) = state().lock() {
+        s.pending.commands.push(cmd.to_string());
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    std::env::current_dir()
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(read) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read.flatten() {
+        let path = entry.path();
+        if out.len() > MAX_DIR_FILES {
+            return;
+        }
+        if path.is_dir() {
+            collect_files(&path, out);
+        } else {
+            out.push(path);
+        }
+    }
+}
+
+pub enum UndoAction {
+    Restored,
+    Removed,
+}
+
+/// Result of [`undo_last_turn`].
+pub struct Undone {
+    pub files: Vec<(PathBuf, UndoAction)>,
+    /// Shell commands of the turn; whatever they changed was not reverted.
+    pub commands: Vec<String>,
+}
+
+/// Reverts the most recent turn that changed files or ran shell commands.
+/// Current contents are saved as backups first, so `dongshan restore` can
+/// bring them back.
+pub fn undo_last_turn() -> Result<Option<Undone>> {
+    let checkpoint = {
+        let mut s = state()
+            .lock()
+            .map_err(|_| anyhow::anyhow!("failed to lock checkpoint state"))?;
+        let pending = std::mem::take(&mut s.pending);
+        if pending.is_empty() {
+            s.stack.pop()
+        } else {
+            Some(pending)
+        }
+    };
+    let Some(checkpoint) = checkpoint else {
+        return Ok(None);
+    };
+    let mut report = Vec::new();
+    for (path, original) in checkpoint.files {
+        if let Ok(current) = fs::read(&path) {
+            let _ = save_backup(&path, &current);
+        }
+        match original {
+            Some(bytes) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create {}", parent.display()))?;
+                }
+                fs::write(&path, &bytes)
+                    .with_context(|| format!("Failed to restore {}", path.display()))?;
+                record_file_write("undo", &path, Some(bytes.len()));
+                report.push((path, UndoAction::Restored));
+            }
+            None => {
+                if path.is_file() {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                    record_file_write("undo", &path, None);
+                    report.push((path, UndoAction::Removed));
+                }
+            }
+        }
+    }
+    Ok(Some(Undone {
+        files: report,
+        commands: checkpoint.commands,
+    }))
+}
@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use serde_json::{Map, Value, json};
+
+use crate::diagnostics::now_unix_ts;
+
+static CI: AtomicBool = AtomicBool::new(false);
+/// The original stdout once CI mode has pointed stdout at stderr.
+static EVENTS: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Exit code of `agent --ci` when the final verification failed.
+pub const EXIT_VERIFICATION_FAILED: i32 = 2;
+/// Exit code of an `agent --isolated` run stopped with Ctrl-C (128 + SIGINT).
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Set by `agent --ci` / `--yes`: no prompts, spinners or update check, and
+/// progress events on stdout. Everything else printed from then on, including
+/// the output of child processes, goes to stderr so stdout is pure JSON lines.
+pub fn init_ci(enabled: bool) {
+    CI.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        return;
+    }
+    let _ = io::stdout().flush();
+    match redirect_stdout_to_stderr() {
+        Ok(events) => {
+            let _ = EVENTS.set(Mutex::new(events));
+        }
+        Err(err) => eprintln!("CI mode: could not move output to stderr: {}", err),
+    }
+}
+
+/// Duplicates stdout, then points the process's stdout at stderr; returns the
+/// duplicate, which still writes to the original stdout.
+#[cfg(unix)]
+fn redirect_stdout_to_stderr() -> io::Result<File> {
+    use std::os::fd::AsFd;
+
+    let events = io::stdout().as_fd().try_clone_to_owned()?;
+    // SAFETY: dup2 on the process's own standard descriptors, which stay open.
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(File::from(events))
+}
+
+#[cfg(windows)]
+fn redirect_stdout_to_stderr() -> io::Result<File> {
+    use std::os::windows::io::{AsHandle, AsRawHandle};
+    use windows_sys::Win32::System::Console::{STD_OUTPUT_HANDLE, SetStdHandle};
+
+    let events = io::stdout().as_handle().try_clone_to_owned()?;
+    // SAFETY: the stderr handle is owned by the process for its whole life.
+    if unsafe { SetStdHandle(STD_OUTPUT_HANDLE, io::stderr().as_raw_handle()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(File::from(events))
+}
+
+pub fn is_ci() -> bool {
+    CI.load(Ordering::Relaxed)
+}
+
+/// Prints one progress event as a JSON line, e.g.
+/// `{"event":"tool","ts":1700000000,"tool":"fs.edit","ok":true}`. Does
+/// nothing outside CI mode.
+pub fn ci_event(event: &str, fields: Value) {
+    if !is_ci() {
+        return;
+    }
+    let mut line = Map::new();
+    line.insert("event".to_string(), json!(event));
+    line.insert("ts".to_string(), json!(now_unix_ts()));
+    if let Value::Object(fields) = fields {
+        line.extend(fields);
+    }
+    match EVENTS.get().and_then(|events| events.lock().ok()) {
+        Some(mut events) => {
+            let _ = writeln!(events, "{}", Value::Object(line));
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(stdout, "{}", Value::Object(line));
+            let _ = stdout.flush();
+        }
+    }
+}
+
+/// Answers a prompt in CI mode: nothing is read, the empty answer takes the
+/// prompt's default (which never approves anything), and the prompt is
+/// reported as a `prompt` event.
+pub fn decline_prompt(label: &str) -> String {
+    println!("{label}");
+    ci_event(
+        "prompt",
+        json!({ "prompt": strip_ansi(label).trim(), "answer": "" }),
+    );
+    String::new()
+}
+
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
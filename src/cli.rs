@@ -1,12 +1,37 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 
-use crate::config::{AutoExecMode, ModelApiProvider, ProviderPreset};
+use crate::config::{
+    ApprovalLevel, AutoExecMode, ModelApiProvider, ProviderPreset, SessionEncryption,
+    StorageBackend, UpdateChannel,
+};
+use crate::logging::LogLevel;
 
 #[derive(Parser, Debug)]
 #[command(name = "dongshan", version, about = "A simple AI coding CLI in Rust")]
 pub struct Cli {
+    /// Increase log verbosity (-v: requests/status, -vv: request bodies, SSE events, tool parsing)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Suppress update notices and progress spinners
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Print assistant output as raw markdown (no terminal rendering)
+    #[arg(long, global = true)]
+    pub plain: bool,
+    /// Write logs to this file (JSON lines, rotated daily) instead of stderr
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+    /// Record logs at this level in `log_file` (default ~/.dongshan/logs/dongshan.log)
+    #[arg(long, value_enum, global = true)]
+    pub log_level: Option<LogLevel>,
+    /// Sampling seed for this run (overrides the `seed` config key)
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+    /// Approval preset for this run (overrides the `approval` config key)
+    #[arg(long, value_enum, global = true)]
+    pub approval: Option<ApprovalLevel>,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -22,12 +47,83 @@ pub enum Commands {
         /// Session name to persist run history
         #[arg(long, default_value = "default")]
         session: String,
+        /// Estimated USD budget for this run (overrides `cost_budget_usd`)
+        #[arg(long)]
+        budget: Option<f64>,
+        /// Run the agent's commands in this subdirectory (overrides `exec_cwd`)
+        #[arg(long)]
+        cwd: Option<PathBuf>,
+        /// Run detached and log to a job file; follow it with `dongshan jobs attach <id>`
+        #[arg(long)]
+        background: bool,
+        /// Work in a temporary git worktree and branch, then merge, keep or discard it
+        #[arg(long)]
+        isolated: bool,
+        /// Create or switch to this branch before running (names the branch with --isolated)
+        #[arg(long)]
+        branch: Option<String>,
+        /// Write a run report (task, plan, commands, changes, verification, usage) to this
+        /// file; `.json` for JSON, anything else for markdown
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// Never prompt (the approval policy decides), skip the update check and spinners,
+        /// print JSON progress events and exit with 2 when verification fails
+        #[arg(long, visible_alias = "yes")]
+        ci: bool,
+        /// Set by `--background` in the detached process
+        #[arg(long, hide = true)]
+        job_id: Option<String>,
+    },
+    /// Ask a single question and stream the answer (no session, no agent loop)
+    Ask {
+        /// Question to send
+        question: String,
+        /// Attach a file to the question (repeatable, `-` reads from stdin)
+        #[arg(short, long = "file")]
+        files: Vec<PathBuf>,
+        /// Attach an image (png/jpg/gif/webp) for vision models (repeatable)
+        #[arg(long = "image")]
+        images: Vec<PathBuf>,
+        /// Do not include workspace context in the prompt
+        #[arg(long)]
+        no_context: bool,
+    },
+    /// Send the same prompt to several catalog models and compare the answers
+    Compare {
+        /// Comma-separated catalog models, e.g. `gpt-4o-mini,deepseek-chat`
+        #[arg(long, value_delimiter = ',', required = true)]
+        models: Vec<String>,
+        /// Prompt sent to every model
+        prompt: String,
+    },
+    /// Turn a natural-language request into one shell command, confirm, and run it
+    Exec {
+        /// What you want the command to do
+        request: String,
+        /// Run without asking for confirmation (deny list still applies)
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Generate a commit message for the staged diff and run `git commit`
+    Commit {
+        /// Format the message as a Conventional Commit
+        #[arg(long)]
+        conventional: bool,
+        /// Accept the generated message without prompting
+        #[arg(short, long)]
+        yes: bool,
+        /// Print the generated message instead of committing (used by the git hook)
+        #[arg(long, conflicts_with = "yes")]
+        message_only: bool,
     },
     /// Interactive multi-turn chat
     Chat {
         /// Session name to persist chat history
         #[arg(long, default_value = "default")]
         session: String,
+        /// Estimated USD budget for this session (overrides `cost_budget_usd`)
+        #[arg(long)]
+        budget: Option<f64>,
     },
     /// Local web console for prompt/model/policy management
     Web {
@@ -38,7 +134,7 @@ pub enum Commands {
     /// Manage API settings
     Config {
         #[command(subcommand)]
-        command: ConfigCommand,
+        command: Box<ConfigCommand>,
     },
     /// Manage saved prompts and active prompt
     Prompt {
@@ -50,24 +146,75 @@ pub enum Commands {
         #[command(subcommand)]
         command: ModelsCommand,
     },
+    /// Run multi-stage prompt pipelines defined in config
+    Pipeline {
+        #[command(subcommand)]
+        command: PipelineCommand,
+    },
     /// Diagnose current model/profile/network health
     Doctor,
+    /// Check for and install a newer dongshan release
+    Update {
+        /// Switch update channel before checking: stable | beta | nightly
+        #[arg(long, value_enum)]
+        channel: Option<UpdateChannel>,
+        /// Only report whether an update is available; do not install
+        #[arg(long)]
+        check: bool,
+    },
+    /// Build and query the workspace retrieval index under `.dongshan/index`
+    Index {
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+    /// Inspect transcript logs of requests, tool calls, commands and file writes
+    Logs {
+        #[command(subcommand)]
+        command: LogsCommand,
+    },
+    /// List recent file edits and agent runs from the transcript logs
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+        /// Show at most this many (most recent) entries
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        /// Only show entries of this session
+        #[arg(long)]
+        session: Option<String>,
+    },
+    /// Summarize locally recorded requests, tokens and estimated cost
+    Stats {
+        /// Time window such as 30m, 24h, 7d, 2w, or `all`
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Basic file system tools (read/list/grep)
     Fs {
         #[command(subcommand)]
         command: FsCommand,
     },
-    /// Review a single file with AI
+    /// Review one or more files with AI
     Review {
-        /// Target source file path
-        file: PathBuf,
+        /// Target source file paths (`-` reads from stdin)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
         /// Extra requirement for the review
         #[arg(short, long)]
         prompt: Option<String>,
+        /// Attach an image such as a UI screenshot (repeatable)
+        #[arg(long = "image")]
+        images: Vec<PathBuf>,
+        /// Approximate token budget shared by all reviewed files (0 = unlimited)
+        #[arg(long, default_value_t = 32000)]
+        max_tokens: usize,
     },
     /// Edit a single file with AI instruction
     Edit {
-        /// Target source file path
+        /// Target source file path (`-` reads from stdin and prints the result)
         file: PathBuf,
         /// Instruction for the code edit
         #[arg(short, long)]
@@ -75,7 +222,178 @@ pub enum Commands {
         /// Write edited content back to the file
         #[arg(long)]
         apply: bool,
+        /// After applying, run the project's check and ask the model to fix failures
+        #[arg(long, requires = "apply")]
+        verify: bool,
+        /// Verification command to run instead of the auto-detected one (implies --verify)
+        #[arg(long, requires = "apply")]
+        verify_cmd: Option<String>,
+        /// Corrective attempts before restoring the backup
+        #[arg(long, default_value_t = 2)]
+        verify_retries: usize,
+    },
+    /// Describe the architecture and draw a Mermaid diagram of module dependencies
+    Arch {
+        /// Markdown file to write
+        #[arg(short, long, default_value = "ARCHITECTURE.md")]
+        output: PathBuf,
+        /// Only generate the diagram, without asking the model for a description
+        #[arg(long)]
+        diagram_only: bool,
     },
+    /// Insert missing doc comments (rustdoc, JSDoc, docstrings) without changing code
+    Doc {
+        /// Source file or glob, e.g. "src/**/*.rs"
+        target: String,
+        /// Apply every file's additions without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Refactor the files in a scope together, with one checkpoint to roll back
+    Refactor {
+        /// Files to refactor, as a glob (e.g. "src/payments/**")
+        #[arg(long, required_unless_present = "rollback")]
+        scope: Option<String>,
+        /// What to change
+        #[arg(short, long, required_unless_present = "rollback")]
+        instruction: Option<String>,
+        /// Apply the refactor without asking
+        #[arg(short, long)]
+        yes: bool,
+        /// Revert the last applied refactor
+        #[arg(long, conflicts_with_all = ["scope", "instruction", "yes"])]
+        rollback: bool,
+    },
+    /// Apply the same change to every file matching a pattern, one file at a time
+    Migrate {
+        /// Files to migrate, as a glob (e.g. "**/*.py")
+        #[arg(long, required_unless_present = "rollback")]
+        pattern: Option<String>,
+        /// The transformation to apply to each file
+        #[arg(short, long, required_unless_present = "rollback")]
+        instruction: Option<String>,
+        /// Command that must pass after each rewrite; `{file}` is replaced by the path
+        #[arg(long)]
+        check: Option<String>,
+        /// Extra attempts for a file whose rewrite fails
+        #[arg(long, default_value_t = 2)]
+        retries: usize,
+        /// Start without asking
+        #[arg(short, long)]
+        yes: bool,
+        /// Revert the files rewritten by the last migration
+        #[arg(long, conflicts_with_all = ["pattern", "instruction", "check", "yes"])]
+        rollback: bool,
+    },
+    /// Run the build/test command and let the model patch errors until it passes
+    Fix {
+        /// Command to repair instead of the auto-detected check (e.g. "cargo build")
+        #[arg(long)]
+        cmd: Option<String>,
+        /// Fix rounds before giving up
+        #[arg(short = 'n', long, default_value_t = 3)]
+        max_iterations: usize,
+        /// Apply proposed patches without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Explain why lines look the way they do, from their git history
+    Blame {
+        /// Lines to explain: `file:line` or `file:start-end`
+        target: String,
+    },
+    /// Print a shell snippet that sends failed commands to `dongshan ask` (`dsfix`)
+    ShellHook {
+        #[arg(value_enum)]
+        shell: HookShell,
+    },
+    /// Git integration helpers
+    Git {
+        #[command(subcommand)]
+        command: GitCommand,
+    },
+    /// Restore a file from a backup saved by `edit --apply`
+    Restore {
+        /// File to restore
+        file: PathBuf,
+        /// Backup to restore, 1 = newest (see `dongshan backups list <file>`)
+        #[arg(long, default_value_t = 1)]
+        version: usize,
+    },
+    /// List or clean backups under ~/.dongshan/backups
+    Backups {
+        #[command(subcommand)]
+        command: BackupsCommand,
+    },
+    /// Manage which workspaces may run the agent
+    Trust {
+        #[command(subcommand)]
+        command: TrustCommand,
+    },
+    /// Inspect facts remembered with /remember in chat
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommand,
+    },
+    /// Manage agent runs started with `dongshan agent --background`
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommand,
+    },
+    /// Open pull requests for agent branches
+    Pr {
+        #[command(subcommand)]
+        command: PrCommand,
+    },
+    /// Inspect, search, migrate and prune stored sessions, usage and audit events
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommand,
+    },
+}
+
+impl Commands {
+    /// Short name used to tag transcript entries and usage records.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commands::Onboard => "onboard",
+            Commands::Agent { .. } => "agent",
+            Commands::Ask { .. } => "ask",
+            Commands::Compare { .. } => "compare",
+            Commands::Exec { .. } => "exec",
+            Commands::Commit { .. } => "commit",
+            Commands::Chat { .. } => "chat",
+            Commands::Web { .. } => "web",
+            Commands::Config { .. } => "config",
+            Commands::Prompt { .. } => "prompt",
+            Commands::Models { .. } => "models",
+            Commands::Pipeline { .. } => "pipeline",
+            Commands::Doctor => "doctor",
+            Commands::Update { .. } => "update",
+            Commands::Index { .. } => "index",
+            Commands::Logs { .. } => "logs",
+            Commands::History { .. } => "history",
+            Commands::Stats { .. } => "stats",
+            Commands::Fs { .. } => "fs",
+            Commands::Review { .. } => "review",
+            Commands::Edit { .. } => "edit",
+            Commands::Arch { .. } => "arch",
+            Commands::Doc { .. } => "doc",
+            Commands::Refactor { .. } => "refactor",
+            Commands::Migrate { .. } => "migrate",
+            Commands::Fix { .. } => "fix",
+            Commands::Blame { .. } => "blame",
+            Commands::ShellHook { .. } => "shell-hook",
+            Commands::Git { .. } => "git",
+            Commands::Restore { .. } => "restore",
+            Commands::Backups { .. } => "backups",
+            Commands::Trust { .. } => "trust",
+            Commands::Memory { .. } => "memory",
+            Commands::Jobs { .. } => "jobs",
+            Commands::Pr { .. } => "pr",
+            Commands::Storage { .. } => "storage",
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -105,21 +423,27 @@ pub enum ConfigCommand {
         /// Automatically check new version on startup
         #[arg(long)]
         auto_check_update: Option<bool>,
+        /// Release channel used by update checks: stable | beta | nightly
+        #[arg(long, value_enum)]
+        update_channel: Option<UpdateChannel>,
         /// Command auto-exec policy: safe | all | custom
         #[arg(long, value_enum)]
         auto_exec_mode: Option<AutoExecMode>,
-        /// Comma-separated allowlist for `custom` mode, e.g. "rg,ls,git status"
+        /// Comma-separated allowlist for `custom` mode, e.g. "rg,ls,git status" (rules as in --auto-exec-deny)
         #[arg(long)]
         auto_exec_allow: Option<String>,
-        /// Comma-separated denylist (highest priority), e.g. "rm,del,git reset"
+        /// Comma-separated denylist (highest priority): prefixes ("rm,git reset"), flag rules matched in any order ("git push --force,rm -rf /") or "re:<regex>"
         #[arg(long)]
         auto_exec_deny: Option<String>,
         /// Ask before running non-trusted commands in chat
         #[arg(long)]
         auto_confirm_exec: Option<bool>,
-        /// Comma-separated trusted command prefixes, e.g. "rg,grep,git status"
+        /// Comma-separated trusted commands, e.g. "rg,grep,git status" (rules as in --auto-exec-deny)
         #[arg(long)]
         auto_exec_trusted: Option<String>,
+        /// Approval preset bundling the exec, file-write and network policies (`custom` uses the fields above)
+        #[arg(long, value_enum)]
+        approval: Option<ApprovalLevel>,
         /// Maximum number of chat messages kept before compaction
         #[arg(long)]
         history_max_messages: Option<usize>,
@@ -129,6 +453,180 @@ pub enum ConfigCommand {
         /// Optional executor model used as fallback when relay model fails to produce real diffs
         #[arg(long)]
         executor_model: Option<String>,
+        /// Cheaper model used to summarize history on compaction (defaults to the active model)
+        #[arg(long)]
+        summary_model: Option<String>,
+        /// Cheaper model for internal calls: history and tool-output summaries, mode routing ("" clears)
+        #[arg(long)]
+        utility_model: Option<String>,
+        /// Send prompt-caching hints (Anthropic cache_control, OpenAI prompt_cache_key)
+        #[arg(long)]
+        prompt_cache: Option<bool>,
+        /// Maximum provider requests per minute (0 = unlimited)
+        #[arg(long)]
+        rate_limit_rpm: Option<u32>,
+        /// Maximum provider requests in flight at once (0 = unlimited)
+        #[arg(long)]
+        max_concurrent_requests: Option<usize>,
+        /// Record requests, tool calls, commands and file writes to ~/.dongshan/logs
+        #[arg(long)]
+        transcript_log: Option<bool>,
+        /// Estimated USD spend per chat session or agent run before asking to continue (0 = no limit)
+        #[arg(long)]
+        cost_budget_usd: Option<f64>,
+        /// Chat interface language: auto (from LANG/LC_ALL), en, or zh-CN
+        #[arg(long)]
+        locale: Option<String>,
+        /// Shell for agent commands: auto, pwsh, powershell or cmd on Windows; auto, bash, zsh or sh elsewhere
+        #[arg(long)]
+        shell: Option<String>,
+        /// Environment variable for agent commands as NAME=value (repeatable; "NAME=" removes it)
+        #[arg(long = "exec-env")]
+        exec_env: Vec<String>,
+        /// Directory agent commands run in, relative to the workspace, e.g. "packages/api" (empty clears)
+        #[arg(long)]
+        exec_cwd: Option<String>,
+        /// PEM bundle of extra CA certificates, e.g. a TLS-intercepting proxy's (empty clears)
+        #[arg(long)]
+        tls_ca_cert_path: Option<String>,
+        /// PEM client certificate for mTLS gateways (empty clears)
+        #[arg(long)]
+        tls_client_cert: Option<String>,
+        /// PEM private key for the client certificate, if not in the same file (empty clears)
+        #[arg(long)]
+        tls_client_key: Option<String>,
+        /// Skip TLS certificate verification (debugging only)
+        #[arg(long)]
+        tls_danger_accept_invalid_certs: Option<bool>,
+        /// Sampling seed sent with every request, for reproducible runs (empty clears)
+        #[arg(long)]
+        default_seed: Option<String>,
+        /// Mask API keys, tokens and passwords in tool output and file content sent to the model
+        #[arg(long)]
+        redact_secrets: Option<bool>,
+        /// Extra regex to redact (repeatable; an empty value clears the custom list)
+        #[arg(long = "redact-pattern")]
+        redact_patterns: Vec<String>,
+        /// Warn about and fence off prompt-injection attempts in tool output, fetched pages and files
+        #[arg(long)]
+        injection_guard: Option<bool>,
+        /// Block agent file writes and shell writes outside the workspace
+        #[arg(long)]
+        write_jail: Option<bool>,
+        /// Comma-separated extra roots the agent may write to, e.g. "~/notes,/srv/shared"
+        #[arg(long)]
+        write_jail_roots: Option<String>,
+        /// Comma-separated domains the agent may fetch, e.g. "docs.rs,github.com" (`*` = any)
+        #[arg(long)]
+        fetch_allow_domains: Option<String>,
+        /// Characters of a fetched page kept in context
+        #[arg(long)]
+        fetch_max_chars: Option<usize>,
+        /// Comma-separated domains the agent's http.get tool may call (`*` = any)
+        #[arg(long)]
+        http_allow_domains: Option<String>,
+        /// Response bytes http.get keeps; the rest is cut
+        #[arg(long)]
+        http_max_bytes: Option<usize>,
+        /// Seconds before an http.get request is abandoned
+        #[arg(long)]
+        http_timeout_secs: Option<u64>,
+        /// Interpreter for the agent's python tool (default: python3, then python)
+        #[arg(long)]
+        python_bin: Option<String>,
+        /// Longest run, in seconds, of a python tool snippet
+        #[arg(long)]
+        python_timeout_secs: Option<u64>,
+        /// Let python tool snippets open network connections
+        #[arg(long)]
+        python_allow_network: Option<bool>,
+        /// URL that receives a JSON summary when `dongshan agent` finishes ("" to disable)
+        #[arg(long)]
+        notify_webhook_url: Option<String>,
+        /// Only notify for agent runs that took at least this many seconds
+        #[arg(long)]
+        notify_min_secs: Option<u64>,
+        /// Show a desktop notification when a slow reply or agent run finishes
+        #[arg(long)]
+        notifications: Option<bool>,
+        /// Seconds a reply must take before it triggers a desktop notification
+        #[arg(long)]
+        notify_after_secs: Option<u64>,
+        /// Syntax highlighting theme for code blocks, e.g. "base16-ocean.dark" or "none"
+        #[arg(long)]
+        color_theme: Option<String>,
+        /// Embedding model for `dongshan index` ("local" uses the built-in offline embedder)
+        #[arg(long)]
+        embedding_model: Option<String>,
+        /// Character budget for the symbol map added to agent context (0 disables)
+        #[arg(long)]
+        repo_map_max_chars: Option<usize>,
+        /// Number of relevant files auto-selected into each request (0 disables)
+        #[arg(long)]
+        auto_context_files: Option<usize>,
+        /// Characters of tool output kept in context; the middle of longer output is elided (0 = unlimited)
+        #[arg(long)]
+        tool_output_max_chars: Option<usize>,
+        /// Per-tool budget as tool=chars, e.g. "shell=20000" (repeatable; "tool=" removes the override)
+        #[arg(long = "tool-output-limit")]
+        tool_output_limits: Vec<String>,
+        /// Comma-separated verification commands run in order, e.g. "cargo fmt --check,cargo build,cargo test" (empty = auto-detect)
+        #[arg(long)]
+        verify_steps: Option<String>,
+        /// Commit the agent's changes with a generated `[dongshan]` message after verification passes
+        #[arg(long)]
+        auto_commit: Option<bool>,
+        /// Skip git hooks (`--no-verify`) when auto-committing
+        #[arg(long)]
+        auto_commit_no_verify: Option<bool>,
+        /// Storage backend for sessions, usage and audit events (move data with `dongshan storage migrate`)
+        #[arg(long, value_enum)]
+        storage: Option<StorageBackend>,
+        /// Delete sessions, usage and audit events older than this many days (0 = keep)
+        #[arg(long)]
+        storage_retention_days: Option<u64>,
+        /// Encrypt saved sessions at rest (re-save existing ones with `dongshan storage reencrypt`)
+        #[arg(long, value_enum)]
+        session_encryption: Option<SessionEncryption>,
+    },
+    /// Check the config for mistakes and suggest fixes
+    Lint,
+    /// Pack config, model catalog and prompts into a .tar.gz bundle
+    Export {
+        #[arg(short, long, default_value = "dongshan-bundle.tar.gz")]
+        output: PathBuf,
+        /// Keep API keys, the webhook URL and exec_env in the bundle (stripped by default)
+        #[arg(long)]
+        include_secrets: bool,
+    },
+    /// Apply a bundle made by `config export` (current config is backed up)
+    Import {
+        file: PathBuf,
+        /// Only import prompts
+        #[arg(long)]
+        skip_config: bool,
+        /// Overwrite local prompts that have the same name
+        #[arg(long)]
+        replace_prompts: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PipelineCommand {
+    /// List pipelines and their stages
+    List,
+    /// Feed input through every stage of a pipeline
+    Run {
+        name: String,
+        /// Input file (`-` for stdin); piped stdin is used when omitted
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// Write the final output to this file instead of streaming it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Also print the output of the intermediate stages
+        #[arg(long)]
+        show_stages: bool,
     },
 }
 
@@ -140,8 +638,22 @@ pub enum PromptCommand {
     Save { name: String, text: String },
     /// Remove a prompt
     Remove { name: String },
+    /// Download a prompt (or a set) from a URL or `gh:<user>/<gist id>`
+    Fetch {
+        source: String,
+        /// Save a single fetched prompt under this name
+        #[arg(long)]
+        name: Option<String>,
+        /// Save without the preview confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
     /// Set active prompt
     Use { name: String },
+    /// Use a saved prompt as the instructions for one mode: agent | chat | review | edit
+    Bind { mode: String, name: String },
+    /// Restore the built-in instructions for a mode
+    Unbind { mode: String },
     /// Show active prompt content
     Show,
     /// Set template variable used in prompt text, e.g. {{tone}}
@@ -169,6 +681,196 @@ pub enum FsCommand {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum IndexCommand {
+    /// Chunk and embed workspace files (only changed files are re-embedded)
+    Build {
+        /// Discard the existing index and embed everything again
+        #[arg(long)]
+        rebuild: bool,
+    },
+    /// Show the chunks most relevant to a query
+    Search {
+        query: String,
+        /// Number of chunks to return
+        #[arg(short = 'k', long, default_value_t = 5)]
+        top_k: usize,
+    },
+    /// Show index size, model and build time
+    Status,
+    /// Print the tree-sitter symbol map the agent sees
+    Map {
+        /// Character budget (defaults to `repo_map_max_chars`)
+        #[arg(long)]
+        max_chars: Option<usize>,
+    },
+    /// Delete the workspace index
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LogsCommand {
+    /// List transcript files under ~/.dongshan/logs
+    List,
+    /// Print entries from a transcript (latest by default)
+    Show {
+        /// Transcript date, e.g. 2026-10-16
+        #[arg(long)]
+        date: Option<String>,
+        /// Only show one entry kind: request | response | tool_call | command | file_write | agent_run
+        #[arg(long)]
+        kind: Option<String>,
+        /// Show at most this many (most recent) entries
+        #[arg(short = 'n', long, default_value_t = 50)]
+        limit: usize,
+        /// Print raw JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the last entries of the latest transcript
+    Tail {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Keep printing new entries as they are written
+        #[arg(short, long)]
+        follow: bool,
+        /// Print raw JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryCommand {
+    /// Print an entry with its exact diff; for an agent run, every edit it made
+    Show {
+        /// Entry id from `dongshan history`, e.g. 20261017-42
+        id: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Pwsh,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GitCommand {
+    /// Install a prepare-commit-msg hook that drafts messages with `dongshan commit --message-only`
+    InstallHooks {
+        /// Draft Conventional Commit messages
+        #[arg(long)]
+        conventional: bool,
+        /// Replace an existing prepare-commit-msg hook not installed by dongshan
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove the hook installed by `install-hooks`
+    UninstallHooks,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrustCommand {
+    /// Trust a workspace (default: current directory) so agent mode can run there
+    Add { path: Option<PathBuf> },
+    /// Mark a workspace as untrusted; chat stays in chat-only mode there
+    Deny { path: Option<PathBuf> },
+    /// Forget the decision for a workspace so dongshan asks again
+    Remove { path: Option<PathBuf> },
+    /// List recorded decisions
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MemoryCommand {
+    /// List global facts and the facts that apply to the current directory
+    List,
+    /// Remember a fact for the current directory, or everywhere with --global
+    Add {
+        text: Vec<String>,
+        #[arg(long)]
+        global: bool,
+    },
+    /// Forget a fact by id
+    Forget { id: u64 },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PrCommand {
+    /// Push the current branch and open a PR with a generated title and body
+    Create {
+        /// Session whose agent task and verification result go into the description
+        #[arg(long, default_value = "default")]
+        session: String,
+        /// Branch to merge into (default: the remote's default branch, main or master)
+        #[arg(long)]
+        base: Option<String>,
+        /// Remote to push to
+        #[arg(long, default_value = "origin")]
+        remote: String,
+        /// Use this title instead of the generated one
+        #[arg(long)]
+        title: Option<String>,
+        /// Open the PR as a draft
+        #[arg(long)]
+        draft: bool,
+        /// Push and open without confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JobsCommand {
+    /// List background jobs, newest first
+    List,
+    /// Follow a job's log until it finishes (Ctrl-C detaches, the job keeps running)
+    Attach { id: String },
+    /// Print a job's log so far
+    Logs { id: String },
+    /// Stop a running job and the commands it started
+    Cancel { id: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StorageCommand {
+    /// Show the active backend and what it holds
+    Info,
+    /// Copy JSON sessions, usage.jsonl and transcripts into SQLite and switch to it
+    Migrate,
+    /// Search messages of all stored sessions
+    Search {
+        query: String,
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Delete sessions, usage records and audit events older than a duration
+    Prune {
+        /// Age such as 30d, 2w or `all` (default: `storage_retention_days`)
+        #[arg(long)]
+        older_than: Option<String>,
+    },
+    /// Re-save every JSON session with the current `session_encryption` setting
+    Reencrypt,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupsCommand {
+    /// List backups of the current workspace, newest first
+    List {
+        /// Only show versions of this file
+        file: Option<PathBuf>,
+    },
+    /// Delete backups older than a duration, in every workspace
+    Clean {
+        /// Age such as 12h, 30d, 2w, or `all`
+        #[arg(long, default_value = "30d")]
+        older_than: String,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum ModelsCommand {
     /// List saved model catalog and current active model
@@ -206,5 +908,20 @@ pub enum ModelsCommand {
         api_key_env: Option<String>,
         #[arg(long)]
         api_key: Option<String>,
+        /// Comma-separated models to retry with on timeout, 429 or 5xx (empty clears)
+        #[arg(long)]
+        fallbacks: Option<String>,
+        /// Context window in tokens used to budget history and context (0 clears)
+        #[arg(long)]
+        context_window: Option<u64>,
+        /// Reasoning model: omit temperature and use max_completion_tokens
+        #[arg(long)]
+        reasoning: Option<bool>,
+        /// reasoning_effort sent to reasoning models: minimal, low, medium or high (empty clears)
+        #[arg(long)]
+        reasoning_effort: Option<String>,
+        /// Print the model's reasoning stream dimmed instead of hiding it
+        #[arg(long)]
+        show_reasoning: Option<bool>,
     },
 }
@@ -0,0 +1,142 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::backups::save_backup;
+use crate::config::{Config, build_system_prompt};
+use crate::llm::call_llm;
+use crate::packages::{detect_workspace, render_workspace, summarize_manifests};
+use crate::redact::redact_secrets;
+use crate::repo_map::{ModuleGraph, build_module_graph, build_repo_map};
+use crate::transcript::record_file_write;
+use crate::util::{color_dim, color_green};
+
+/// Larger graphs keep the modules with the most files.
+const MAX_DIAGRAM_MODULES: usize = 60;
+const ARCH_REPO_MAP_CHARS: usize = 16_000;
+
+/// Mermaid node id; module names may contain `/`, `-` or reserved words.
+fn node_id(module: &str) -> String {
+    let id: String = module
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("m_{}", id)
+}
+
+/// `graph LR` of the modules and their imports.
+fn render_mermaid(graph: &ModuleGraph) -> String {
+    let mut modules: Vec<(&String, &usize)> = graph.modules.iter().collect();
+    modules.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    modules.truncate(MAX_DIAGRAM_MODULES);
+    let shown: Vec<&String> = modules.iter().map(|(m, _)| *m).collect();
+
+    let mut out = String::from("graph LR\n");
+    for (module, files) in &modules {
+        out.push_str(&format!(
+            "    {}[\"{} ({})\"]\n",
+            node_id(module),
+            module.replace('"', "'"),
+            files
+        ));
+    }
+    for (from, to) in &graph.edges {
+        if shown.contains(&from) && shown.contains(&to) {
+            out.push_str(&format!("    {} --> {}\n", node_id(from), node_id(to)));
+        }
+    }
+    out
+}
+
+fn build_arch_prompt(root: &Path, graph: &ModuleGraph) -> Result<String> {
+    let map = build_repo_map(root, ARCH_REPO_MAP_CHARS)?;
+    let mut manifests = summarize_manifests(root);
+    if let Some(workspace) = detect_workspace(root) {
+        manifests = format!("{}\n{}", render_workspace(&workspace), manifests);
+    }
+    let edges: Vec<String> = graph
+        .edges
+        .iter()
+        .map(|(from, to)| format!("{} -> {}", from, to))
+        .collect();
+    let modules: Vec<String> = graph
+        .modules
+        .iter()
+        .map(|(m, files)| format!("{} ({} files)", m, files))
+        .collect();
+    Ok(format!(
+        "Write a high-level architecture overview of this project in Markdown for a new contributor.\n\
+         Cover: what the project does, the main modules and the responsibility of each, how a typical \
+         request or command flows through them, external dependencies that matter, and entry points.\n\
+         Use `##` headings, short paragraphs and bullet lists. Do not add a diagram; one is generated \
+         separately. Do not invent modules that are not listed.\n\n\
+         Manifests:\n{}\n\n\
+         Modules:\n{}\n\n\
+         Module imports:\n{}\n\n\
+         Symbol map:\n{}",
+        redact_secrets(&manifests),
+        modules.join("\n"),
+        if edges.is_empty() {
+            "(none detected)".to_string()
+        } else {
+            edges.join("\n")
+        },
+        map
+    ))
+}
+
+/// Writes an architecture overview of the workspace to `output`: a prose
+/// description from the model (skipped with `diagram_only`) followed by a
+/// Mermaid diagram of the module imports.
+pub async fn run_arch(cfg: &Config, output: &Path, diagram_only: bool) -> Result<()> {
+    let root = env::current_dir()?;
+    let graph = build_module_graph(&root)?;
+    if graph.modules.is_empty() {
+        bail!("No supported source files found (Rust, Python, JS/TS, Go)");
+    }
+    println!(
+        "{}",
+        color_dim(&format!(
+            "arch> {} module(s), {} import edge(s)",
+            graph.modules.len(),
+            graph.edges.len()
+        ))
+    );
+
+    let mut doc = String::from("# Architecture\n\n");
+    if !diagram_only {
+        let prompt = build_arch_prompt(&root, &graph)?;
+        let overview = call_llm(cfg, &build_system_prompt(cfg, "chat-lite"), &prompt).await?;
+        doc.push_str(overview.trim());
+        doc.push_str("\n\n");
+    }
+    doc.push_str("## Module dependencies\n\n");
+    if graph.modules.len() > MAX_DIAGRAM_MODULES {
+        doc.push_str(&format!(
+            "Showing the {} largest of {} modules.\n\n",
+            MAX_DIAGRAM_MODULES,
+            graph.modules.len()
+        ));
+    }
+    doc.push_str("```mermaid\n");
+    doc.push_str(&render_mermaid(&graph));
+    doc.push_str("```\n");
+
+    if let Ok(previous) = fs::read_to_string(output) {
+        save_backup(output, &previous)?;
+    }
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(output, &doc).with_context(|| format!("Failed to write {}", output.display()))?;
+    record_file_write("arch", output, Some(doc.len()));
+    println!(
+        "{} {}",
+        color_green("Architecture overview written to"),
+        output.display()
+    );
+    Ok(())
+}
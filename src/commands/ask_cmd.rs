@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::chat_context::augment_user_input_with_workspace_context;
+use crate::config::{Config, build_system_prompt};
+use crate::fs_tools::{is_stdin_path, read_image_data_url, read_piped_stdin, read_text_source};
+use crate::injection::guard_untrusted;
+use crate::llm::{ChatMessage, call_llm_with_history_stream};
+use crate::redact::redact_secrets;
+
+pub async fn run_ask(
+    cfg: &Config,
+    question: &str,
+    files: &[PathBuf],
+    images: &[PathBuf],
+    no_context: bool,
+) -> Result<()> {
+    let mut prompt = if no_context {
+        question.to_string()
+    } else {
+        augment_user_input_with_workspace_context(cfg, question).await?
+    };
+
+    if !files.iter().any(|f| is_stdin_path(f))
+        && let Some(piped) = read_piped_stdin()?
+    {
+        prompt.push_str(&format!(
+            "\n\nAttached document (stdin):\n```\n{}\n```",
+            guard_untrusted("stdin", &redact_secrets(&piped))
+        ));
+    }
+
+    for file in files {
+        let content = read_text_source(file)?;
+        let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+        let label = if is_stdin_path(file) {
+            "stdin".to_string()
+        } else {
+            file.display().to_string()
+        };
+        prompt.push_str(&format!(
+            "\n\nAttached file: {}\n```{}\n{}\n```",
+            label,
+            ext,
+            guard_untrusted(&label, &redact_secrets(&content))
+        ));
+    }
+
+    let images = images
+        .iter()
+        .map(|p| read_image_data_url(p))
+        .collect::<Result<Vec<_>>>()?;
+    let history = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        images,
+    }];
+    call_llm_with_history_stream(cfg, &build_system_prompt(cfg, "chat-lite"), &history).await?;
+    println!();
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::backups::{clean_backups, find_backup, list_backups, save_backup, workspace_backup_dir};
+use crate::cli::BackupsCommand;
+use crate::transcript::{record_file_write, utc_date, utc_time};
+use crate::usage::parse_since;
+use crate::util::{color_dim, color_green};
+
+pub fn handle_backups(command: BackupsCommand) -> Result<()> {
+    match command {
+        BackupsCommand::List { file } => {
+            let entries = list_backups(file.as_deref())?;
+            if entries.is_empty() {
+                println!("No backups in {}.", workspace_backup_dir()?.display());
+                return Ok(());
+            }
+            for (i, entry) in entries.iter().enumerate() {
+                let version = if file.is_some() {
+                    format!("{:>3}  ", i + 1)
+                } else {
+                    String::new()
+                };
+                println!(
+                    "{}{} {}  {}  {}",
+                    version,
+                    utc_date(entry.ts),
+                    utc_time(entry.ts),
+                    entry.rel.display(),
+                    color_dim(&format!("{} bytes", entry.size))
+                );
+            }
+        }
+        BackupsCommand::Clean { older_than } => {
+            // `all` has no age limit, so every backup is removed.
+            let cutoff = parse_since(&older_than)?.unwrap_or(u64::MAX);
+            let removed = clean_backups(cutoff)?;
+            println!(
+                "Removed {} backup file(s) older than {}",
+                removed, older_than
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Restores `file` from backup `version` (1 = newest). The current content is
+/// backed up first, so a restore can itself be undone.
+pub fn run_restore(file: &Path, version: usize) -> Result<()> {
+    let entry = find_backup(file, version)?;
+    let content = fs::read(&entry.path)
+        .with_context(|| format!("Failed to read {}", entry.path.display()))?;
+    if let Ok(current) = fs::read(file)
+        && current != content
+    {
+        save_backup(file, &current)?;
+    }
+    fs::write(file, &content).with_context(|| format!("Failed to write {}", file.display()))?;
+    record_file_write("restore", file, Some(content.len()));
+    println!(
+        "{} {} from backup of {} {}",
+        color_green("Restored"),
+        file.display(),
+        utc_date(entry.ts),
+        utc_time(entry.ts)
+    );
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::{Config, build_system_prompt};
+use crate::llm::call_llm;
+use crate::util::{color_dim, markdown_enabled, render_markdown_terminal, truncate_with_suffix};
+
+/// Lines of surrounding code shown around the requested range.
+const CONTEXT_LINES: usize = 8;
+const MAX_COMMITS: usize = 8;
+const MAX_LOG_L_COMMITS: usize = 10;
+const MAX_HISTORY_CHARS: usize = 20_000;
+
+/// Parses `path:line`, `path:start-end` or `path:start,end`.
+fn parse_target(target: &str) -> Result<(String, usize, usize)> {
+    let Some((path, range)) = target.rsplit_once(':') else {
+        bail!("Expected <file:line> or <file:start-end>, got {}", target);
+    };
+    let (start, end) = match range.split_once(['-', ',']) {
+        Some((a, b)) => (a.trim().parse::<usize>(), b.trim().parse::<usize>()),
+        None => (range.trim().parse::<usize>(), range.trim().parse::<usize>()),
+    };
+    let (Ok(start), Ok(end)) = (start, end) else {
+        bail!("Invalid line range in {}", target);
+    };
+    if path.is_empty() || start == 0 || end < start {
+        bail!("Invalid line range in {}", target);
+    }
+    Ok((path.to_string(), start, end))
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args[0]))?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Commit hashes in `git blame --porcelain` output, in order of appearance.
+/// The all-zero hash marks uncommitted lines and is skipped.
+fn blamed_commits(porcelain: &str) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    let mut out = Vec::new();
+    for line in porcelain.lines() {
+        let Some(hash) = line.split_whitespace().next() else {
+            continue;
+        };
+        if hash.len() == 40
+            && hash.chars().all(|c| c.is_ascii_hexdigit())
+            && hash.chars().any(|c| c != '0')
+            && seen.insert(hash.to_string())
+        {
+            out.push(hash.to_string());
+        }
+    }
+    out
+}
+
+fn numbered_excerpt(text: &str, start: usize, end: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let from = start.saturating_sub(CONTEXT_LINES).max(1);
+    let to = (end + CONTEXT_LINES).min(lines.len());
+    (from..=to)
+        .map(|n| {
+            let marker = if (start..=end).contains(&n) { ">" } else { " " };
+            format!("{}{:>5} | {}", marker, n, lines[n - 1])
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Explains why the given lines look the way they do, from their `git blame`
+/// commits and `git log -L` history.
+pub async fn run_blame(cfg: &Config, target: &str) -> Result<()> {
+    let (path, start, end) = parse_target(target)?;
+    let text =
+        fs::read_to_string(Path::new(&path)).with_context(|| format!("Failed to read {}", path))?;
+    let line_count = text.lines().count();
+    if end > line_count {
+        bail!("{} has only {} line(s)", path, line_count);
+    }
+    let range = format!("{},{}", start, end);
+
+    let blame = git(&["blame", "--porcelain", "-L", &range, "--", &path])?;
+    let commits = blamed_commits(&blame);
+    if commits.is_empty() {
+        bail!(
+            "Lines {}-{} of {} are not committed yet; there is no history to explain",
+            start,
+            end,
+            path
+        );
+    }
+    let annotated = git(&["blame", "--date=short", "-L", &range, "--", &path])?;
+    let mut details = String::new();
+    for hash in commits.iter().take(MAX_COMMITS) {
+        details.push_str(&git(&[
+            "show",
+            "-s",
+            "--date=short",
+            "--format=commit %h%nAuthor: %an%nDate: %ad%n%n%B",
+            hash,
+        ])?);
+        details.push('\n');
+    }
+    let line_log_arg = format!("{}:{}", range, path);
+    let line_log = git(&[
+        "log",
+        "--no-color",
+        "--date=short",
+        "--format=commit %h %ad %an%n%s",
+        &format!("-n{}", MAX_LOG_L_COMMITS),
+        "-L",
+        &line_log_arg,
+    ])
+    .unwrap_or_default();
+    println!(
+        "{}",
+        color_dim(&format!(
+            "blame> {}:{}-{}, {} commit(s)",
+            path,
+            start,
+            end,
+            commits.len()
+        ))
+    );
+
+    let prompt = format!(
+        "Explain why the marked lines of {} look the way they do: what they do, when and why \
+         they were introduced or changed, and any constraint or bug fix that shaped them.\n\
+         Base the explanation on the history below and cite commits by short hash, e.g. (abc1234). \
+         Say so when the history does not explain a choice instead of guessing.\n\n\
+         Code (marked with >):\n```\n{}\n```\n\n\
+         git blame:\n```\n{}\n```\n\n\
+         Commits:\n```\n{}\n```\n\n\
+         git log -L:\n```\n{}\n```",
+        path,
+        numbered_excerpt(&text, start, end),
+        annotated.trim_end(),
+        truncate_with_suffix(details.trim_end(), MAX_HISTORY_CHARS, "\n...[truncated]"),
+        truncate_with_suffix(line_log.trim_end(), MAX_HISTORY_CHARS, "\n...[truncated]")
+    );
+    let answer = call_llm(cfg, &build_system_prompt(cfg, "chat-lite"), &prompt).await?;
+    println!("{}", render_markdown_terminal(&answer, markdown_enabled()));
+    Ok(())
+}
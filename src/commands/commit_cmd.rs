@@ -0,0 +1,129 @@
+use std::fs;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::{Config, config_dir, current_prompt_text};
+use crate::llm::call_llm;
+use crate::util::{ask, color_dim, tagged_prompt, truncate_with_suffix};
+
+const MAX_DIFF_CHARS: usize = 24_000;
+
+pub async fn run_commit(
+    cfg: &Config,
+    conventional: bool,
+    yes: bool,
+    message_only: bool,
+) -> Result<()> {
+    let diff = staged_diff()?;
+    if diff.trim().is_empty() {
+        bail!("No staged changes. Stage files with `git add` first.");
+    }
+    if message_only {
+        println!(
+            "{}",
+            generate_commit_message(cfg, &diff, conventional).await?
+        );
+        return Ok(());
+    }
+
+    loop {
+        let message = generate_commit_message(cfg, &diff, conventional).await?;
+        println!("{}", color_dim("── proposed commit message ──"));
+        println!("{message}");
+        println!("{}", color_dim("─────────────────────────────"));
+
+        let choice = if yes {
+            "a".to_string()
+        } else {
+            ask(&tagged_prompt(
+                "commit",
+                "[a=accept]/[e=edit]/[r=regenerate]/[q=quit] (default a): ",
+            ))?
+            .trim()
+            .to_ascii_lowercase()
+        };
+        match choice.as_str() {
+            "" | "a" | "y" => return git_commit_with_message(&message, false),
+            "e" => return git_commit_with_message(&message, true),
+            "r" => continue,
+            _ => {
+                println!("Commit cancelled.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub fn staged_diff() -> Result<String> {
+    let output = Command::new("git")
+        .args(["diff", "--cached", "--no-color", "--stat", "--patch"])
+        .output()
+        .context("Failed to run git diff --cached")?;
+    if !output.status.success() {
+        bail!(
+            "git diff --cached failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub async fn generate_commit_message(
+    cfg: &Config,
+    diff: &str,
+    conventional: bool,
+) -> Result<String> {
+    let style = if conventional {
+        "Use Conventional Commits: `type(scope): subject` where type is one of \
+         feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert."
+    } else {
+        "Use a plain imperative subject line, e.g. `Fix crash when config is missing`."
+    };
+    let system = format!(
+        "{}\nYou write git commit messages.\n\
+         Output only the commit message: a subject line of at most 72 characters, \
+         a blank line, then a short body wrapped at 72 columns explaining what changed and why.\n\
+         {}\nNo markdown fences, no surrounding quotes.",
+        current_prompt_text(cfg),
+        style
+    );
+    let prompt = format!(
+        "Write a commit message for this staged diff:\n\n{}",
+        truncate_with_suffix(diff, MAX_DIFF_CHARS, "\n[diff truncated]")
+    );
+    let answer = call_llm(cfg, &system, &prompt).await?;
+    Ok(clean_commit_message(&answer))
+}
+
+fn clean_commit_message(answer: &str) -> String {
+    let mut text = answer.trim();
+    if let Some(rest) = text.strip_prefix("```") {
+        let rest = rest.split_once('\n').map(|(_, body)| body).unwrap_or(rest);
+        text = rest.trim_end().trim_end_matches("```").trim();
+    }
+    text.trim_matches('"').trim().to_string()
+}
+
+fn git_commit_with_message(message: &str, edit: bool) -> Result<()> {
+    let path = config_dir()?.join("COMMIT_MSG");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, format!("{}\n", message))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let mut cmd = Command::new("git");
+    cmd.arg("commit");
+    if edit {
+        cmd.arg("-e");
+    }
+    cmd.arg("-F").arg(&path);
+    let status = cmd.status().context("Failed to run git commit")?;
+    let _ = fs::remove_file(&path);
+    if !status.success() {
+        bail!("git commit exited with {}", status);
+    }
+    Ok(())
+}
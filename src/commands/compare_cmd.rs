@@ -0,0 +1,206 @@
+use std::env;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use tokio::task::JoinSet;
+
+use crate::config::{Config, build_system_prompt, config_for_model, ensure_model_catalog};
+use crate::i18n::{t, tf};
+use crate::llm::call_llm_with_usage;
+use crate::usage::{TokenUsage, estimate_cost};
+use crate::util::{WorkingStatus, color_bold, color_cyan, color_dim, color_red};
+
+/// Narrower columns than this fall back to printing answers one after another.
+const MIN_COLUMN_WIDTH: usize = 30;
+const DEFAULT_TERMINAL_WIDTH: usize = 120;
+
+struct CompareResult {
+    model: String,
+    elapsed: Duration,
+    outcome: Result<(String, TokenUsage)>,
+}
+
+/// Sends `prompt` to every model concurrently and prints the answers side by
+/// side with latency, token and cost stats.
+pub async fn run_compare(cfg: &Config, models: &[String], prompt: &str) -> Result<()> {
+    let mut catalog_cfg = cfg.clone();
+    ensure_model_catalog(&mut catalog_cfg);
+    let mut unique: Vec<String> = Vec::new();
+    for m in models.iter().map(|m| m.trim()).filter(|m| !m.is_empty()) {
+        if !catalog_cfg.model_catalog.iter().any(|c| c == m) {
+            bail!(
+                "Model not in catalog: {}. Use `dongshan models add {}` first.",
+                m,
+                m
+            );
+        }
+        if !unique.iter().any(|u| u == m) {
+            unique.push(m.to_string());
+        }
+    }
+    if unique.len() < 2 {
+        bail!("Compare needs at least two models, e.g. --models gpt-4o-mini,deepseek-chat");
+    }
+    if prompt.trim().is_empty() {
+        bail!("Prompt is empty");
+    }
+
+    let system_prompt = build_system_prompt(cfg, "chat-lite");
+    let working = WorkingStatus::start(tf("status.comparing", &[&unique.len()]));
+    let mut tasks = JoinSet::new();
+    for (idx, model) in unique.iter().enumerate() {
+        let model_cfg = config_for_model(&catalog_cfg, model);
+        let system_prompt = system_prompt.clone();
+        let prompt = prompt.to_string();
+        tasks.spawn(async move {
+            let start = Instant::now();
+            let outcome = call_llm_with_usage(&model_cfg, &system_prompt, &prompt).await;
+            let result = CompareResult {
+                model: model_cfg.model,
+                elapsed: start.elapsed(),
+                outcome,
+            };
+            (idx, result)
+        });
+    }
+    let mut slots: Vec<Option<CompareResult>> = unique.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        let (idx, result) = joined?;
+        slots[idx] = Some(result);
+    }
+    working.finish();
+
+    let results: Vec<CompareResult> = slots.into_iter().flatten().collect();
+    print_stats(&catalog_cfg, &results);
+    println!();
+    print_answers(&results);
+    Ok(())
+}
+
+fn print_stats(cfg: &Config, results: &[CompareResult]) {
+    println!(
+        "{}",
+        color_bold(&format!(
+            "  {:<28} {:>8} {:>9} {:>9} {:>10}",
+            t("table.model"),
+            t("table.latency"),
+            t("table.in"),
+            t("table.out"),
+            t("table.cost")
+        ))
+    );
+    for r in results {
+        match &r.outcome {
+            Ok((_, usage)) => {
+                let mark = if usage.estimated { "~" } else { "" };
+                println!(
+                    "  {:<28} {:>7.2}s {:>9} {:>9} {:>10}",
+                    r.model,
+                    r.elapsed.as_secs_f64(),
+                    format!("{mark}{}", usage.prompt_tokens),
+                    format!("{mark}{}", usage.completion_tokens),
+                    format!("${:.4}", estimate_cost(cfg, &r.model, usage))
+                );
+            }
+            Err(_) => println!(
+                "  {:<28} {:>7.2}s {}",
+                r.model,
+                r.elapsed.as_secs_f64(),
+                color_red(t("compare.failed"))
+            ),
+        }
+    }
+}
+
+fn answer_text(r: &CompareResult) -> String {
+    match &r.outcome {
+        Ok((answer, _)) => answer.clone(),
+        Err(err) => tf("compare.error", &[&format!("{:#}", err)]),
+    }
+}
+
+fn print_answers(results: &[CompareResult]) {
+    let width = env::var("COLUMNS")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH);
+    let gap = 3;
+    let col_width = width.saturating_sub(gap * (results.len() - 1)) / results.len();
+    if col_width < MIN_COLUMN_WIDTH {
+        for r in results {
+            println!("{}", color_cyan(&format!("── {} ──", r.model)));
+            println!("{}", answer_text(r));
+            println!();
+        }
+        return;
+    }
+
+    let columns: Vec<Vec<String>> = results
+        .iter()
+        .map(|r| wrap_lines(&answer_text(r), col_width))
+        .collect();
+    let header = results
+        .iter()
+        .map(|r| pad(&clip(&r.model, col_width), col_width))
+        .collect::<Vec<_>>()
+        .join(&" ".repeat(gap));
+    println!("{}", color_cyan(header.trim_end()));
+    let rule = results
+        .iter()
+        .map(|_| "─".repeat(col_width))
+        .collect::<Vec<_>>()
+        .join(&" ".repeat(gap));
+    println!("{}", color_dim(&rule));
+    let rows = columns.iter().map(|c| c.len()).max().unwrap_or(0);
+    let sep = format!(" {} ", color_dim("│"));
+    for i in 0..rows {
+        let line = columns
+            .iter()
+            .map(|c| pad(c.get(i).map(String::as_str).unwrap_or(""), col_width))
+            .collect::<Vec<_>>()
+            .join(&sep);
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Hard-wraps each line at `width` characters, preferring to break on spaces.
+fn wrap_lines(text: &str, width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let line = line.replace('\t', "    ");
+        let mut chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            out.push(String::new());
+            continue;
+        }
+        while chars.len() > width {
+            let cut = chars[..width]
+                .iter()
+                .rposition(|c| *c == ' ')
+                .filter(|&i| i > width / 2)
+                .unwrap_or(width);
+            out.push(
+                chars[..cut]
+                    .iter()
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string(),
+            );
+            chars.drain(..cut);
+            while chars.first() == Some(&' ') {
+                chars.remove(0);
+            }
+        }
+        out.push(chars.into_iter().collect());
+    }
+    out
+}
+
+fn clip(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    format!("{}{}", text, " ".repeat(width.saturating_sub(len)))
+}
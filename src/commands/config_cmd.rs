@@ -1,10 +1,19 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 
+use crate::bundle::{export_bundle, import_bundle};
 use crate::cli::ConfigCommand;
 use crate::config::{
     Config, add_model_with_active_profile, apply_preset, config_path, ensure_model_catalog,
     load_config_or_default, save_config, set_active_model, update_active_model_profile,
 };
+use crate::config_lint::{Severity, lint_config};
+use crate::exec_rules::validate_exec_rule;
+use crate::highlight::available_themes;
+use crate::i18n::validate_locale_setting;
+use crate::redact::validate_redact_pattern;
+use crate::shell::validate_shell_setting;
+use crate::tls::load_tls;
+use crate::tool_output::canonical_tool_name;
 
 pub fn handle_config(command: ConfigCommand) -> Result<()> {
     match command {
@@ -33,14 +42,61 @@ pub fn handle_config(command: ConfigCommand) -> Result<()> {
             api_key,
             allow_nsfw,
             auto_check_update,
+            update_channel,
             auto_exec_mode,
             auto_exec_allow,
             auto_exec_deny,
             auto_confirm_exec,
             auto_exec_trusted,
+            approval,
             history_max_messages,
             history_max_chars,
             executor_model,
+            summary_model,
+            utility_model,
+            prompt_cache,
+            rate_limit_rpm,
+            max_concurrent_requests,
+            transcript_log,
+            cost_budget_usd,
+            locale,
+            shell,
+            exec_env,
+            exec_cwd,
+            tls_ca_cert_path,
+            tls_client_cert,
+            tls_client_key,
+            tls_danger_accept_invalid_certs,
+            default_seed,
+            redact_secrets,
+            redact_patterns,
+            injection_guard,
+            write_jail,
+            write_jail_roots,
+            fetch_allow_domains,
+            fetch_max_chars,
+            http_allow_domains,
+            http_max_bytes,
+            http_timeout_secs,
+            python_bin,
+            python_timeout_secs,
+            python_allow_network,
+            notify_webhook_url,
+            notify_min_secs,
+            notifications,
+            notify_after_secs,
+            color_theme,
+            embedding_model,
+            repo_map_max_chars,
+            auto_context_files,
+            tool_output_max_chars,
+            tool_output_limits,
+            verify_steps,
+            auto_commit,
+            auto_commit_no_verify,
+            storage,
+            storage_retention_days,
+            session_encryption,
         } => {
             let mut cfg = load_config_or_default()?;
             if let Some(v) = model {
@@ -66,20 +122,26 @@ pub fn handle_config(command: ConfigCommand) -> Result<()> {
             if let Some(v) = auto_check_update {
                 cfg.auto_check_update = v;
             }
+            if let Some(v) = update_channel {
+                cfg.update_channel = v;
+            }
             if let Some(v) = auto_exec_mode {
                 cfg.auto_exec_mode = v;
             }
             if let Some(v) = auto_exec_allow {
-                cfg.auto_exec_allow = parse_csv_list(&v);
+                cfg.auto_exec_allow = parse_exec_rules(&v)?;
             }
             if let Some(v) = auto_exec_deny {
-                cfg.auto_exec_deny = parse_csv_list(&v);
+                cfg.auto_exec_deny = parse_exec_rules(&v)?;
             }
             if let Some(v) = auto_confirm_exec {
                 cfg.auto_confirm_exec = v;
             }
             if let Some(v) = auto_exec_trusted {
-                cfg.auto_exec_trusted = parse_csv_list(&v);
+                cfg.auto_exec_trusted = parse_exec_rules(&v)?;
+            }
+            if let Some(v) = approval {
+                cfg.approval = v;
             }
             if let Some(v) = history_max_messages {
                 cfg.history_max_messages = v.max(4);
@@ -96,11 +158,320 @@ pub fn handle_config(command: ConfigCommand) -> Result<()> {
                     add_model_with_active_profile(&mut cfg, name);
                 }
             }
+            if let Some(v) = summary_model {
+                let name = v.trim();
+                if name.is_empty() {
+                    cfg.summary_model = None;
+                } else {
+                    cfg.summary_model = Some(name.to_string());
+                    add_model_with_active_profile(&mut cfg, name);
+                }
+            }
+            if let Some(v) = utility_model {
+                let name = v.trim();
+                if name.is_empty() {
+                    cfg.utility_model = None;
+                } else {
+                    cfg.utility_model = Some(name.to_string());
+                    add_model_with_active_profile(&mut cfg, name);
+                }
+            }
+            if let Some(v) = prompt_cache {
+                cfg.prompt_cache = v;
+            }
+            if let Some(v) = rate_limit_rpm {
+                cfg.rate_limit_rpm = v;
+            }
+            if let Some(v) = max_concurrent_requests {
+                cfg.max_concurrent_requests = v;
+            }
+            if let Some(v) = transcript_log {
+                cfg.transcript_log = v;
+            }
+            if let Some(v) = cost_budget_usd {
+                if v < 0.0 {
+                    bail!("cost_budget_usd must be >= 0");
+                }
+                cfg.cost_budget_usd = v;
+            }
+            if let Some(v) = locale {
+                validate_locale_setting(&v)?;
+                cfg.locale = v.trim().to_string();
+            }
+            if let Some(v) = shell {
+                validate_shell_setting(&v)?;
+                cfg.shell = v.trim().to_string();
+            }
+            for entry in exec_env {
+                let Some((name, value)) = entry.split_once('=') else {
+                    bail!("Invalid --exec-env {}: expected NAME=value", entry);
+                };
+                let name = name.trim();
+                if name.is_empty() {
+                    bail!("Invalid --exec-env {}: empty variable name", entry);
+                }
+                if value.is_empty() {
+                    cfg.exec_env.remove(name);
+                } else {
+                    cfg.exec_env.insert(name.to_string(), value.to_string());
+                }
+            }
+            if let Some(v) = exec_cwd {
+                let v = v.trim();
+                cfg.exec_cwd = if v.is_empty() {
+                    None
+                } else {
+                    Some(v.to_string())
+                };
+            }
+            let tls_changed = tls_ca_cert_path.is_some()
+                || tls_client_cert.is_some()
+                || tls_client_key.is_some()
+                || tls_danger_accept_invalid_certs.is_some();
+            for (value, slot) in [
+                (tls_ca_cert_path, &mut cfg.tls.ca_cert_path),
+                (tls_client_cert, &mut cfg.tls.client_cert),
+                (tls_client_key, &mut cfg.tls.client_key),
+            ] {
+                if let Some(v) = value {
+                    let v = v.trim();
+                    *slot = if v.is_empty() {
+                        None
+                    } else {
+                        Some(v.to_string())
+                    };
+                }
+            }
+            if let Some(v) = tls_danger_accept_invalid_certs {
+                cfg.tls.danger_accept_invalid_certs = v;
+            }
+            if tls_changed {
+                load_tls(&cfg.tls)?;
+            }
+            if let Some(v) = default_seed {
+                let v = v.trim();
+                cfg.seed = if v.is_empty() {
+                    None
+                } else {
+                    Some(v.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid --default-seed {}: expected a number", v)
+                    })?)
+                };
+            }
+            if let Some(v) = redact_secrets {
+                cfg.redact_secrets = v;
+            }
+            if let Some(v) = write_jail {
+                cfg.write_jail = v;
+            }
+            if let Some(v) = write_jail_roots {
+                cfg.write_jail_roots = parse_csv_list(&v);
+            }
+            for pattern in redact_patterns {
+                if pattern.is_empty() {
+                    cfg.redact_patterns.clear();
+                } else {
+                    validate_redact_pattern(&pattern)?;
+                    if !cfg.redact_patterns.contains(&pattern) {
+                        cfg.redact_patterns.push(pattern);
+                    }
+                }
+            }
+            if let Some(v) = injection_guard {
+                cfg.injection_guard = v;
+            }
+            if let Some(v) = fetch_allow_domains {
+                cfg.fetch_allow_domains = parse_csv_list(&v);
+            }
+            if let Some(v) = fetch_max_chars {
+                cfg.fetch_max_chars = v;
+            }
+            if let Some(v) = http_allow_domains {
+                cfg.http_allow_domains = parse_csv_list(&v);
+            }
+            if let Some(v) = http_max_bytes {
+                cfg.http_max_bytes = v;
+            }
+            if let Some(v) = http_timeout_secs {
+                if v == 0 {
+                    bail!("http_timeout_secs must be greater than 0");
+                }
+                cfg.http_timeout_secs = v;
+            }
+            if let Some(v) = python_bin {
+                let v = v.trim();
+                cfg.python_bin = (!v.is_empty()).then(|| v.to_string());
+            }
+            if let Some(v) = python_timeout_secs {
+                if v == 0 {
+                    bail!("python_timeout_secs must be greater than 0");
+                }
+                cfg.python_timeout_secs = v;
+            }
+            if let Some(v) = python_allow_network {
+                cfg.python_allow_network = v;
+            }
+            if let Some(v) = notify_webhook_url {
+                let v = v.trim();
+                if v.is_empty() {
+                    cfg.notify_webhook_url = None;
+                } else if v.starts_with("https://") || v.starts_with("http://") {
+                    cfg.notify_webhook_url = Some(v.to_string());
+                } else {
+                    bail!("notify_webhook_url must be an http(s) URL");
+                }
+            }
+            if let Some(v) = notify_min_secs {
+                cfg.notify_min_secs = v;
+            }
+            if let Some(v) = notifications {
+                cfg.notifications = v;
+            }
+            if let Some(v) = notify_after_secs {
+                cfg.notify_after_secs = v;
+            }
+            if let Some(v) = color_theme {
+                let themes = available_themes();
+                if !themes.iter().any(|t| t == v.trim()) {
+                    bail!(
+                        "Unknown color theme: {}. Available: {}",
+                        v,
+                        themes.join(", ")
+                    );
+                }
+                cfg.color_theme = v.trim().to_string();
+            }
+            if let Some(v) = auto_context_files {
+                cfg.auto_context_files = v;
+            }
+            if let Some(v) = tool_output_max_chars {
+                cfg.tool_output_max_chars = v;
+            }
+            for entry in tool_output_limits {
+                let Some((tool, chars)) = entry.split_once('=') else {
+                    bail!("Invalid --tool-output-limit {}: expected tool=chars", entry);
+                };
+                let tool = canonical_tool_name(tool);
+                if chars.trim().is_empty() {
+                    cfg.tool_output_limits.remove(&tool);
+                } else {
+                    let chars = chars.trim().parse::<usize>().map_err(|_| {
+                        anyhow::anyhow!(
+                            "Invalid --tool-output-limit {}: chars must be a number",
+                            entry
+                        )
+                    })?;
+                    cfg.tool_output_limits.insert(tool, chars);
+                }
+            }
+            if let Some(v) = verify_steps {
+                cfg.verify_steps = parse_csv_list(&v);
+            }
+            if let Some(v) = auto_commit {
+                cfg.auto_commit = v;
+            }
+            if let Some(v) = auto_commit_no_verify {
+                cfg.auto_commit_no_verify = v;
+            }
+            if let Some(v) = storage {
+                cfg.storage = v;
+            }
+            if let Some(v) = storage_retention_days {
+                cfg.storage_retention_days = v;
+            }
+            if let Some(v) = session_encryption {
+                cfg.session_encryption = v;
+            }
+            if let Some(v) = repo_map_max_chars {
+                cfg.repo_map_max_chars = v;
+            }
+            if let Some(v) = embedding_model {
+                let v = v.trim();
+                cfg.embedding_model = if v.is_empty() {
+                    None
+                } else {
+                    Some(v.to_string())
+                };
+            }
             ensure_model_catalog(&mut cfg);
             save_config(&cfg)?;
             println!("Config updated:");
             println!("{}", toml::to_string_pretty(&cfg)?);
         }
+        ConfigCommand::Lint => {
+            let cfg = load_config_or_default()?;
+            let findings = lint_config(&cfg);
+            for f in &findings {
+                let tag = match f.severity {
+                    Severity::Error => "[error]",
+                    Severity::Warning => "[warn]",
+                };
+                println!("{} {}: {}", tag, f.field, f.message);
+                println!("        fix: {}", f.fix);
+            }
+            let errors = findings
+                .iter()
+                .filter(|f| f.severity == Severity::Error)
+                .count();
+            if findings.is_empty() {
+                println!("[ok] {} looks good", config_path()?.display());
+            } else {
+                println!(
+                    "{} error(s), {} warning(s) in {}",
+                    errors,
+                    findings.len() - errors,
+                    config_path()?.display()
+                );
+            }
+            if errors > 0 {
+                bail!("config lint found {} error(s)", errors);
+            }
+        }
+        ConfigCommand::Export {
+            output,
+            include_secrets,
+        } => {
+            let summary = export_bundle(&output, include_secrets)?;
+            println!(
+                "Exported config, {} model(s) and {} prompt(s) to {}",
+                summary.models,
+                summary.prompts,
+                output.display()
+            );
+            if summary.secrets_included {
+                println!("Warning: the bundle contains API keys; do not share it.");
+            } else {
+                println!("Secrets were stripped (use --include-secrets to keep them).");
+            }
+        }
+        ConfigCommand::Import {
+            file,
+            skip_config,
+            replace_prompts,
+        } => {
+            let summary = import_bundle(&file, skip_config, replace_prompts)?;
+            if summary.config_replaced {
+                println!("Config imported to {}", config_path()?.display());
+                if let Some(backup) = &summary.config_backup {
+                    println!("Previous config saved as {}", backup);
+                }
+                if !summary.secrets_included {
+                    println!("Bundle had no secrets; local API keys and exec_env were kept.");
+                }
+            }
+            println!(
+                "Prompts: {} added, {} replaced, {} skipped",
+                summary.prompts_added.len(),
+                summary.prompts_replaced.len(),
+                summary.prompts_skipped.len()
+            );
+            if !summary.prompts_skipped.is_empty() {
+                println!(
+                    "Skipped (already exist with different text, use --replace-prompts): {}",
+                    summary.prompts_skipped.join(", ")
+                );
+            }
+        }
     }
 
     Ok(())
@@ -113,3 +484,11 @@ fn parse_csv_list(s: &str) -> Vec<String> {
         .map(|x| x.to_string())
         .collect()
 }
+
+fn parse_exec_rules(s: &str) -> Result<Vec<String>> {
+    let rules = parse_csv_list(s);
+    for rule in &rules {
+        validate_exec_rule(rule)?;
+    }
+    Ok(rules)
+}
@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use super::fix_cmd::{StagedFiles, parse_patch_reply, print_diff, stage_patches};
+use crate::backups::save_backup;
+use crate::chat::resolve_native_path;
+use crate::config::{Config, build_system_prompt};
+use crate::fs_tools::{display_path, workspace_files};
+use crate::llm::call_llm;
+use crate::transcript::record_file_change;
+use crate::util::{ask, color_dim, color_green, color_yellow, tagged_prompt};
+
+/// Larger files are skipped; the whole file has to fit in one request.
+const MAX_DOC_FILE_CHARS: usize = 60_000;
+const MAX_DOC_FILES: usize = 50;
+
+struct DocStyle {
+    /// How the prompt names the comment style.
+    label: &'static str,
+    /// Prefixes every inserted line must start with (after indentation).
+    line_prefixes: &'static [&'static str],
+    /// Comment lines the compiler reads as directives; never inserted.
+    directive_prefixes: &'static [&'static str],
+    /// Python: inserted lines form triple-quoted docstrings instead.
+    docstring: bool,
+}
+
+const BLOCK_PREFIXES: &[&str] = &["/**", "*", "*/"];
+/// `//go:build`, `//go:generate`, `//go:embed`, ... plus the older cgo and
+/// build-constraint forms.
+const GO_DIRECTIVES: &[&str] = &["//go:", "// +build", "//line ", "//export ", "//extern "];
+
+fn doc_style(path: &Path) -> Option<DocStyle> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let (label, line_prefixes, docstring) = match ext.as_str() {
+        "rs" => ("rustdoc `///` comments", &["///", "//!"][..], false),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => {
+            ("JSDoc `/** ... */` blocks", BLOCK_PREFIXES, false)
+        }
+        "java" | "kt" | "kts" | "scala" | "php" => {
+            ("Javadoc-style `/** ... */` blocks", BLOCK_PREFIXES, false)
+        }
+        "c" | "h" | "cc" | "cpp" | "hpp" => {
+            ("Doxygen `/** ... */` comments", BLOCK_PREFIXES, false)
+        }
+        "cs" => ("XML doc comments (`///`)", &["///"][..], false),
+        "swift" => ("`///` doc comments", &["///"][..], false),
+        "go" => (
+            "Go doc comments (`// Name ...` directly above the declaration)",
+            &["//"][..],
+            false,
+        ),
+        "py" | "pyi" => ("docstrings", &[][..], true),
+        _ => return None,
+    };
+    let directive_prefixes = if ext == "go" { GO_DIRECTIVES } else { &[] };
+    Some(DocStyle {
+        label,
+        line_prefixes,
+        directive_prefixes,
+        docstring,
+    })
+}
+
+fn is_docstring_run(run: &[&str]) -> bool {
+    let text = run.join("\n");
+    let text = text.trim();
+    let body = text
+        .strip_prefix('r')
+        .or_else(|| text.strip_prefix('u'))
+        .unwrap_or(text);
+    ["\"\"\"", "'''"]
+        .iter()
+        .any(|q| body.len() >= 6 && body.starts_with(q) && body.ends_with(q))
+}
+
+/// Checks that `new` is `old` with only doc lines added: every line of `old`
+/// appears in order, and each added run is a comment (or docstring) block.
+fn check_doc_only(style: &DocStyle, old: &str, new: &str) -> Result<()> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let mut k = 0;
+    let mut runs: Vec<Vec<&str>> = Vec::new();
+    let mut run: Vec<&str> = Vec::new();
+    for line in new.lines() {
+        if k < old_lines.len() && line == old_lines[k] {
+            k += 1;
+            if !run.is_empty() {
+                runs.push(std::mem::take(&mut run));
+            }
+        } else {
+            run.push(line);
+        }
+    }
+    if !run.is_empty() {
+        runs.push(run);
+    }
+    if k < old_lines.len() {
+        bail!("edit changes existing code near `{}`", old_lines[k].trim());
+    }
+    for run in &runs {
+        let ok = if style.docstring {
+            run.iter().all(|l| l.trim().is_empty()) || is_docstring_run(run)
+        } else {
+            run.iter().all(|l| {
+                let l = l.trim_start();
+                l.is_empty()
+                    || (style.line_prefixes.iter().any(|p| l.starts_with(p))
+                        && !style.directive_prefixes.iter().any(|p| l.starts_with(p)))
+            })
+        };
+        if !ok {
+            let line = run.iter().find(|l| !l.trim().is_empty()).unwrap_or(&"");
+            bail!(
+                "edit adds a line that is not a doc comment: `{}`",
+                line.trim()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn build_doc_prompt(path: &Path, style: &DocStyle, text: &str) -> String {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+    let placement = if style.docstring {
+        "A docstring goes as the first statement inside the def/class, indented like its body."
+    } else {
+        "A comment goes on the lines directly above the item (above any attributes/decorators)."
+    };
+    format!(
+        "Add missing {} to the public items of this file (modules, types, functions, methods, \
+         constants) that have none. Do not change, reorder or reformat any existing line; only \
+         insert comment lines. {}\n\
+         Keep each comment to one or two sentences about what the item does or returns, not how.\n\
+         Reply with ONLY a JSON array, no markdown and no explanation:\n\
+         [{{\"path\": \"{}\", \"edits\": [{{\"old\": \"existing line(s) next to the insertion\", \"new\": \"the same line(s) with the comment added\"}}]}}]\n\
+         Each `old` must be copied exactly, including indentation, and be unique in the file. \
+         Reply with [] if every public item is already documented.\n\n\
+         File: {}\n```{}\n{}\n```",
+        style.label,
+        placement,
+        display_path(path),
+        display_path(path),
+        ext,
+        text
+    )
+}
+
+/// Asks for the doc comments of one file and returns the staged change, checked
+/// as a whole so fuzzily applied edits cannot slip in code changes.
+async fn propose_docs(cfg: &Config, path: &Path, style: &DocStyle) -> Result<StagedFiles> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if text.chars().count() > MAX_DOC_FILE_CHARS {
+        bail!("larger than {} chars", MAX_DOC_FILE_CHARS);
+    }
+    let reply = call_llm(
+        cfg,
+        &build_system_prompt(cfg, "edit"),
+        &build_doc_prompt(path, style, &text),
+    )
+    .await?;
+    let patches = parse_patch_reply(&reply)?;
+    for patch in &patches {
+        if resolve_native_path(&patch.path)? != path {
+            bail!("model tried to edit another file ({})", patch.path);
+        }
+    }
+    let staged = stage_patches(&patches)?;
+    for (original, updated) in staged.values() {
+        check_doc_only(style, original, updated)?;
+    }
+    Ok(staged)
+}
+
+fn doc_targets(target: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(target);
+    let files = if path.is_file() {
+        if doc_style(path).is_none() {
+            bail!("Unsupported file type for doc comments: {}", target);
+        }
+        vec![resolve_native_path(target)?]
+    } else {
+        workspace_files(Path::new("."), Some(target))?
+            .into_iter()
+            .filter(|f| doc_style(f).is_some())
+            .map(|f| resolve_native_path(&f.to_string_lossy()))
+            .collect::<Result<_>>()?
+    };
+    if files.is_empty() {
+        bail!("No supported source files match {}", target);
+    }
+    if files.len() > MAX_DOC_FILES {
+        bail!(
+            "{} matches {} files (limit {}); use a narrower glob",
+            target,
+            files.len(),
+            MAX_DOC_FILES
+        );
+    }
+    Ok(files)
+}
+
+/// Inserts missing doc comments into `target` (a file or a glob such as
+/// `src/**/*.rs`). Each file's additions are shown as a diff and only lines
+/// that are doc comments are accepted.
+pub async fn run_doc(cfg: &Config, target: &str, yes: bool) -> Result<()> {
+    let files = doc_targets(target)?;
+    let mut apply_all = yes;
+    let (mut documented, mut unchanged, mut skipped) = (0, 0, 0);
+    for file in &files {
+        let Some(style) = doc_style(file) else {
+            continue;
+        };
+        println!("{}", color_dim(&format!("doc> {}", display_path(file))));
+        let staged = match propose_docs(cfg, file, &style).await {
+            Ok(staged) => staged,
+            Err(err) => {
+                println!(
+                    "{}",
+                    color_yellow(&format!("Skipped {}: {:#}", display_path(file), err))
+                );
+                skipped += 1;
+                continue;
+            }
+        };
+        if staged.is_empty() {
+            println!("Nothing to add.");
+            unchanged += 1;
+            continue;
+        }
+        for (path, (original, updated)) in &staged {
+            print_diff(path, original, updated);
+        }
+        if !apply_all {
+            let answer = ask(&tagged_prompt(
+                "doc",
+                "[y=apply]/[n=skip]/[a=apply all]/[q=quit] (default n): ",
+            ))?;
+            match answer.trim().to_ascii_lowercase().as_str() {
+                "y" => {}
+                "a" => apply_all = true,
+                "q" => break,
+                _ => {
+                    skipped += 1;
+                    continue;
+                }
+            }
+        }
+        for (path, (original, updated)) in &staged {
+            save_backup(path, original)?;
+            fs::write(path, updated)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            record_file_change("doc", path, original, updated);
+        }
+        documented += 1;
+    }
+    println!(
+        "{}",
+        color_green(&format!(
+            "Documented {} file(s); {} already complete, {} skipped.",
+            documented, unchanged, skipped
+        ))
+    );
+    Ok(())
+}
@@ -1,9 +1,10 @@
 use anyhow::{Result, bail};
-use reqwest::Client;
 use serde_json::json;
 use std::time::Duration;
 
 use crate::config::{load_config_or_default, resolve_api_key};
+use crate::llm::apply_reasoning_params;
+use crate::tls::client_builder;
 
 pub async fn run_doctor() -> Result<()> {
     let cfg = load_config_or_default()?;
@@ -26,7 +27,7 @@ pub async fn run_doctor() -> Result<()> {
     }
     println!("[ok] API key resolved");
 
-    let client = Client::builder().timeout(Duration::from_secs(12)).build()?;
+    let client = client_builder()?.timeout(Duration::from_secs(12)).build()?;
 
     let models_url = derive_models_url(&profile.base_url);
     let models_resp = client
@@ -54,12 +55,13 @@ pub async fn run_doctor() -> Result<()> {
         }
     }
 
-    let body = json!({
+    let mut body = json!({
         "model": cfg.model,
         "messages": [{"role":"user","content":"ping"}],
         "temperature": 0,
         "max_tokens": 8
     });
+    apply_reasoning_params(&cfg, &mut body);
 
     let chat_resp = client
         .post(&profile.base_url)
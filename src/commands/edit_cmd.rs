@@ -1,178 +1,206 @@
 use std::fs;
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 
+use crate::backups::save_backup;
+use crate::change_diff::{ChangeReport, build_change_report, render_change_diff};
+use crate::chat::{pick_verification_command, run_shell_command_with_status};
 use crate::config::{Config, build_system_prompt};
-use crate::fs_tools::read_text_file;
+use crate::fs_tools::{is_extracted_document, is_stdin_path, read_text_source};
 use crate::llm::call_llm;
-use crate::util::backup_path;
+use crate::notebook::{is_notebook, merge_notebook_edit, render_notebook};
+use crate::test_failures::summarize_test_failures;
+use crate::transcript::{record_file_change, record_file_write};
+use crate::util::{color_dim, color_green, color_yellow, truncate_with_suffix};
+
+/// Verification errors sent back to the model are clipped to this many chars.
+const MAX_VERIFY_OUTPUT_CHARS: usize = 6000;
+const MAX_RETRY_DIFF_CHARS: usize = 8000;
+
+/// Post-apply check for `edit --verify`.
+pub struct EditVerify {
+    /// Command to run; `None` picks the project's checker like the agent does.
+    pub command: Option<String>,
+    /// Corrective model round-trips allowed after the first failure.
+    pub retries: usize,
+}
 
-pub async fn run_edit(cfg: &Config, file: &Path, instruction: &str, apply: bool) -> Result<()> {
-    let original = read_text_file(file)?;
+pub async fn run_edit(
+    cfg: &Config,
+    file: &Path,
+    instruction: &str,
+    apply: bool,
+    verify: Option<EditVerify>,
+) -> Result<()> {
+    let from_stdin = is_stdin_path(file);
+    if from_stdin && apply {
+        bail!("--apply cannot write back to stdin; redirect stdout instead");
+    }
+    if verify.is_some() && !apply {
+        bail!("--verify needs --apply");
+    }
+    let verify_cmd = match &verify {
+        Some(v) => Some(match v.command.as_deref().map(str::trim) {
+            Some(cmd) if !cmd.is_empty() => cmd.to_string(),
+            _ => match pick_verification_command() {
+                Some((_, cmd)) => cmd.to_string(),
+                None => {
+                    bail!("No verification command detected for this project; pass --verify-cmd")
+                }
+            },
+        }),
+        None => None,
+    };
+    if apply && is_extracted_document(file) {
+        bail!(
+            "--apply cannot write back to {}: PDF/DOCX are read as extracted text only",
+            file.display()
+        );
+    }
+    // Notebooks are edited in their percent-cell text form and merged back into the JSON.
+    let notebook_raw = if is_notebook(file) && !from_stdin {
+        Some(
+            fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?,
+        )
+    } else {
+        None
+    };
+    let original = match &notebook_raw {
+        Some(raw) => render_notebook(raw, false)?,
+        None => read_text_source(file)?,
+    };
     let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+    let notebook_hint = if notebook_raw.is_some() {
+        "This is a Jupyter notebook shown as cells headed `# %% [n] type`. Keep the header of every \
+         cell you keep, start new cells with `# %% code` or `# %% markdown`, and drop a cell by \
+         removing it with its header.\n"
+    } else {
+        ""
+    };
 
     let prompt = format!(
         "Edit this file according to the instruction.\n\
-         Return ONLY the full updated file content with no markdown and no explanation.\n\n\
+         Return ONLY the full updated file content with no markdown and no explanation.\n{}\n\
          Instruction:\n{}\n\n\
          File: {}\n```{}\n{}\n```",
+        notebook_hint,
         instruction,
         file.display(),
         ext,
         original
     );
 
-    let edited = call_llm(cfg, &build_system_prompt(cfg, "edit"), &prompt).await?;
+    let mut edited = call_llm(cfg, &build_system_prompt(cfg, "edit"), &prompt).await?;
 
+    if from_stdin {
+        println!("{edited}");
+        return Ok(());
+    }
     if !apply {
         println!("{edited}");
         println!("\nDry run only. Use --apply to write changes.");
         return Ok(());
     }
 
-    let report = build_change_report(&original, &edited);
-    let backup = backup_path(file);
-    fs::write(&backup, original)?;
-    fs::write(file, edited)?;
+    let previous = notebook_raw.clone().unwrap_or_else(|| original.clone());
+    let backup = save_backup(file, &previous)?;
+    write_edit(file, notebook_raw.as_deref(), &edited)?;
 
     println!("Updated {}", file.display());
     println!("Backup  {}", backup.display());
-    print_change_report(file, &report);
-    Ok(())
-}
+    print_change_report(file, &build_change_report(&original, &edited));
 
-#[derive(Debug, Clone, Copy)]
-struct ChangeChunk {
-    old_start: usize,
-    old_len: usize,
-    new_start: usize,
-    new_len: usize,
-}
-
-#[derive(Debug, Default)]
-struct ChangeReport {
-    chunks: Vec<ChangeChunk>,
-    inserted_lines: usize,
-    deleted_lines: usize,
-}
-
-fn build_change_report(original: &str, edited: &str) -> ChangeReport {
-    let old_lines: Vec<&str> = original.lines().collect();
-    let new_lines: Vec<&str> = edited.lines().collect();
-    let mut i = 0usize;
-    let mut j = 0usize;
-    let mut chunks = Vec::new();
-
-    while i < old_lines.len() && j < new_lines.len() {
-        if old_lines[i] == new_lines[j] {
-            i += 1;
-            j += 1;
-            continue;
-        }
-
-        let (di, dj) = find_next_anchor(&old_lines, &new_lines, i, j);
-        if di.is_none() || dj.is_none() {
-            chunks.push(ChangeChunk {
-                old_start: i + 1,
-                old_len: old_lines.len() - i,
-                new_start: j + 1,
-                new_len: new_lines.len() - j,
-            });
-            i = old_lines.len();
-            j = new_lines.len();
-            break;
+    let (Some(cmd), Some(verify)) = (verify_cmd, verify) else {
+        return Ok(());
+    };
+    // Any failure from here on puts the original content back.
+    let restored = |err: anyhow::Error| -> anyhow::Error {
+        match restore_previous(file, &previous) {
+            Ok(()) => err.context(format!("restored {} from backup", file.display())),
+            Err(restore_err) => err.context(format!(
+                "could not restore {} ({:#}); see `dongshan restore`",
+                file.display(),
+                restore_err
+            )),
         }
-
-        let di = di.unwrap_or(0);
-        let dj = dj.unwrap_or(0);
-        if di > 0 || dj > 0 {
-            chunks.push(ChangeChunk {
-                old_start: i + 1,
-                old_len: di,
-                new_start: j + 1,
-                new_len: dj,
-            });
+    };
+    let mut attempt = 0;
+    loop {
+        println!("{}", color_dim(&format!("verify> {}", cmd)));
+        let (passed, output) = run_shell_command_with_status(&cmd).map_err(restored)?;
+        if passed {
+            println!("{}", color_green("Verification passed"));
+            return Ok(());
         }
-        i += di;
-        j += dj;
-    }
-
-    if i < old_lines.len() || j < new_lines.len() {
-        chunks.push(ChangeChunk {
-            old_start: i + 1,
-            old_len: old_lines.len().saturating_sub(i),
-            new_start: j + 1,
-            new_len: new_lines.len().saturating_sub(j),
-        });
-    }
-
-    let mut merged = Vec::new();
-    for chunk in chunks {
-        if let Some(last) = merged.last_mut()
-            && are_adjacent(*last, chunk)
-        {
-            let last_old_end = last.old_start + last.old_len;
-            let last_new_end = last.new_start + last.new_len;
-            let chunk_old_end = chunk.old_start + chunk.old_len;
-            let chunk_new_end = chunk.new_start + chunk.new_len;
-            last.old_len = chunk_old_end
-                .saturating_sub(last.old_start)
-                .max(last_old_end - last.old_start);
-            last.new_len = chunk_new_end
-                .saturating_sub(last.new_start)
-                .max(last_new_end - last.new_start);
-            continue;
+        let log = truncate_with_suffix(output.trim(), MAX_VERIFY_OUTPUT_CHARS, "...\n[truncated]");
+        println!("{}", log);
+        // The model gets the parsed failures when the log format is known.
+        let errors = summarize_test_failures(&output).unwrap_or(log);
+        if attempt >= verify.retries {
+            restore_previous(file, &previous)?;
+            bail!(
+                "Verification still failing after {} corrective attempt(s); restored {} from backup",
+                verify.retries,
+                file.display()
+            );
         }
-        merged.push(chunk);
-    }
-
-    let inserted_lines = merged.iter().map(|c| c.new_len).sum::<usize>();
-    let deleted_lines = merged.iter().map(|c| c.old_len).sum::<usize>();
-
-    ChangeReport {
-        chunks: merged,
-        inserted_lines,
-        deleted_lines,
+        attempt += 1;
+        println!(
+            "{}",
+            color_yellow(&format!(
+                "Verification failed; asking the model for a fix ({}/{})",
+                attempt, verify.retries
+            ))
+        );
+        let diff = truncate_with_suffix(
+            &render_change_diff(&original, &edited),
+            MAX_RETRY_DIFF_CHARS,
+            "...\n[truncated]",
+        );
+        let prompt = format!(
+            "Your edit was applied but `{}` fails.\n\
+             Fix the file so the check passes while still following the instruction.\n\
+             Return ONLY the full updated file content with no markdown and no explanation.\n{}\n\
+             Instruction:\n{}\n\n\
+             Diff of your edit against the original:\n```diff\n{}\n```\n\n\
+             Check output:\n```\n{}\n```\n\n\
+             Current file: {}\n```{}\n{}\n```",
+            cmd,
+            notebook_hint,
+            instruction,
+            diff,
+            errors,
+            file.display(),
+            ext,
+            edited
+        );
+        edited = call_llm(cfg, &build_system_prompt(cfg, "edit"), &prompt)
+            .await
+            .map_err(restored)?;
+        write_edit(file, notebook_raw.as_deref(), &edited).map_err(restored)?;
+        print_change_report(file, &build_change_report(&original, &edited));
     }
 }
 
-fn find_next_anchor(
-    old_lines: &[&str],
-    new_lines: &[&str],
-    i: usize,
-    j: usize,
-) -> (Option<usize>, Option<usize>) {
-    const LOOKAHEAD: usize = 80;
-    let mut best: Option<(usize, usize, usize)> = None;
-    let old_max = (old_lines.len() - i).min(LOOKAHEAD + 1);
-    let new_max = (new_lines.len() - j).min(LOOKAHEAD + 1);
-
-    for di in 0..old_max {
-        for dj in 0..new_max {
-            if old_lines[i + di] != new_lines[j + dj] {
-                continue;
-            }
-            let score = di + dj;
-            match best {
-                None => best = Some((score, di, dj)),
-                Some((best_score, _, _)) if score < best_score => best = Some((score, di, dj)),
-                _ => {}
-            }
-        }
-    }
-
-    if let Some((_, di, dj)) = best {
-        (Some(di), Some(dj))
-    } else {
-        (None, None)
-    }
+/// Puts the content from before the edit back after a failed verification.
+fn restore_previous(file: &Path, previous: &str) -> Result<()> {
+    fs::write(file, previous).with_context(|| format!("Failed to write {}", file.display()))?;
+    record_file_write("edit", file, Some(previous.len()));
+    Ok(())
 }
 
-fn are_adjacent(left: ChangeChunk, right: ChangeChunk) -> bool {
-    let left_old_end = left.old_start + left.old_len;
-    let left_new_end = left.new_start + left.new_len;
-    right.old_start <= left_old_end + 1 && right.new_start <= left_new_end + 1
+/// Writes edited text to `file`, merging it into the notebook JSON when editing a notebook.
+fn write_edit(file: &Path, notebook_raw: Option<&str>, edited: &str) -> Result<()> {
+    let updated = match notebook_raw {
+        Some(raw) => merge_notebook_edit(raw, edited)?,
+        None => edited.to_string(),
+    };
+    let before = fs::read_to_string(file).unwrap_or_default();
+    fs::write(file, &updated)?;
+    record_file_change("edit", file, &before, &updated);
+    Ok(())
 }
 
 fn fmt_range(start: usize, len: usize) -> String {
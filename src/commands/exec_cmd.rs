@@ -0,0 +1,89 @@
+use anyhow::{Result, bail};
+
+use crate::approval::{NetworkAccess, approval_policy, is_network_command};
+use crate::chat::{
+    CommandVerdict, command_verdict, is_trusted_command, precheck_command, run_shell_command,
+};
+use crate::chat_context::augment_user_input_with_workspace_context;
+use crate::config::{Config, current_prompt_text};
+use crate::llm::call_llm;
+use crate::util::{ask, color_cyan, tagged_prompt};
+use crate::write_jail::{find_write_outside_jail, report_blocked};
+
+pub async fn run_exec(cfg: &Config, request: &str, yes: bool) -> Result<()> {
+    let shell = if cfg!(target_os = "windows") {
+        "PowerShell on Windows"
+    } else {
+        "sh on a Unix-like system"
+    };
+    let system = format!(
+        "{}\nTranslate the user's request into exactly one shell command for {}.\n\
+         Output only the command on a single line: no markdown fences, no explanation.\n\
+         Prefer read-only commands unless the request clearly asks for changes.",
+        current_prompt_text(cfg),
+        shell
+    );
+    let user = augment_user_input_with_workspace_context(cfg, request).await?;
+    let answer = call_llm(cfg, &system, &user).await?;
+    let cmd = extract_command(&answer);
+    if cmd.is_empty() {
+        bail!("Model did not return a command: {}", answer.trim());
+    }
+
+    println!("{} {}", color_cyan("$"), cmd);
+    if let Some(reason) = precheck_command(&cmd) {
+        bail!("Refusing to run command ({})", reason);
+    }
+    let verdict = command_verdict(cfg, &cmd);
+    if let CommandVerdict::Denied(rule) = &verdict {
+        bail!("Command blocked by auto_exec_deny `{}`: {}", rule, cmd);
+    }
+    if let Some(target) = find_write_outside_jail(&cmd) {
+        report_blocked("command write", &target);
+        bail!(
+            "Refusing to run command (writes outside workspace: {})",
+            target
+        );
+    }
+
+    let policy = approval_policy(cfg);
+    let network = is_network_command(&cmd);
+    if network && policy.network == NetworkAccess::Block {
+        bail!("Refusing to run network command under approval = read-only");
+    }
+    let allowed = matches!(verdict, CommandVerdict::Allowed);
+    let pre_approved = allowed
+        && (!policy.confirm_exec || is_trusted_command(cfg, &cmd))
+        && !(network && policy.network == NetworkAccess::Ask);
+    // Under read-only, `--yes` covers read-only commands only.
+    let yes = yes && (policy.file_writes || allowed);
+    if !yes && !pre_approved {
+        let input = ask(&tagged_prompt(
+            "exec-confirm",
+            "Run this command? [y=yes]/[n=no]: ",
+        ))?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Skipped.");
+            return Ok(());
+        }
+    }
+
+    let out = run_shell_command(&cmd)?;
+    println!("{}", out.trim_end());
+    Ok(())
+}
+
+fn extract_command(answer: &str) -> String {
+    let mut text = answer.trim();
+    if let Some(rest) = text.strip_prefix("```") {
+        let rest = rest.split_once('\n').map(|(_, body)| body).unwrap_or(rest);
+        text = rest.trim_end().trim_end_matches("```").trim();
+    }
+    text.lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty())
+        .unwrap_or_default()
+        .trim_start_matches("$ ")
+        .trim_matches('`')
+        .to_string()
+}
@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+
+use crate::backups::save_backup;
+use crate::change_diff::render_change_diff;
+use crate::chat::{pick_verification_command, resolve_native_path, run_shell_command_with_status};
+use crate::config::{Config, build_system_prompt};
+use crate::fs_tools::display_path;
+use crate::fuzzy_patch::{EditHit, apply_edit};
+use crate::llm::call_llm;
+use crate::test_failures::{parse_test_failures, summarize_test_failures};
+use crate::tool_calls::ApplyPatchArgs;
+use crate::transcript::record_file_change;
+use crate::util::{
+    ask, color_dim, color_green, color_red, color_yellow, tagged_prompt, truncate_with_suffix,
+};
+
+const MAX_ERROR_CHARS: usize = 8000;
+/// Files named in the errors that are sent to the model, in order of appearance.
+const MAX_CONTEXT_FILES: usize = 6;
+const MAX_FILE_CHARS: usize = 40_000;
+
+/// Files the log points at, in order of first mention, that exist under the
+/// workspace. Parsed failure locations come first, then any `path:line` text.
+fn files_in_errors(output: &str) -> Vec<PathBuf> {
+    static PATH_LINE: OnceLock<Regex> = OnceLock::new();
+    let path_line = PATH_LINE.get_or_init(|| {
+        Regex::new(r"([A-Za-z0-9_./\\-]+\.[A-Za-z0-9]+):\d+").expect("valid regex")
+    });
+    let mut candidates: Vec<String> = parse_test_failures(output)
+        .into_iter()
+        .filter_map(|f| f.location)
+        .collect();
+    candidates.extend(path_line.captures_iter(output).map(|c| c[1].to_string()));
+
+    let mut out: Vec<PathBuf> = Vec::new();
+    for raw in candidates {
+        let file = raw
+            .split(':')
+            .next()
+            .unwrap_or(&raw)
+            .trim_start_matches("./");
+        if file.is_empty() || !Path::new(file).is_file() {
+            continue;
+        }
+        let Ok(path) = resolve_native_path(file) else {
+            continue;
+        };
+        if !out.contains(&path) {
+            out.push(path);
+        }
+        if out.len() >= MAX_CONTEXT_FILES {
+            break;
+        }
+    }
+    out
+}
+
+fn build_fix_prompt(cmd: &str, errors: &str, files: &[(PathBuf, String)]) -> String {
+    let mut prompt = format!(
+        "`{}` fails. Fix the code so it passes.\n\
+         Reply with ONLY a JSON array, no markdown and no explanation:\n\
+         [{{\"path\": \"src/lib.rs\", \"edits\": [{{\"old\": \"exact text to replace\", \"new\": \"replacement\"}}]}}]\n\
+         Each `old` must match the current file exactly, including whitespace, and be unique in it. \
+         Keep changes minimal and do not touch unrelated code. \
+         If the failure cannot be fixed by editing these files, reply with [].\n\n\
+         Errors:\n```\n{}\n```",
+        cmd, errors
+    );
+    for (path, text) in files {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+        prompt.push_str(&format!(
+            "\n\nFile: {}\n```{}\n{}\n```",
+            display_path(path),
+            ext,
+            truncate_with_suffix(text, MAX_FILE_CHARS, "\n...[truncated]")
+        ));
+    }
+    prompt
+}
+
+/// Pulls the JSON array out of the reply, tolerating a surrounding code fence.
+pub(super) fn parse_patch_reply(reply: &str) -> Result<Vec<ApplyPatchArgs>> {
+    let start = reply.find('[');
+    let end = reply.rfind(']');
+    let (Some(start), Some(end)) = (start, end) else {
+        bail!("Model reply has no JSON patch list");
+    };
+    if end < start {
+        bail!("Model reply has no JSON patch list");
+    }
+    serde_json::from_str(&reply[start..=end]).context("Model reply is not a valid patch list")
+}
+
+/// Original and patched text of each changed file.
+pub(super) type StagedFiles = BTreeMap<PathBuf, (String, String)>;
+
+/// Applies every edit to an in-memory copy with the same matching as
+/// `fs.apply_patch` (unique exact match, else fuzzy); any miss rejects the
+/// whole file so a half-applied fix is never written.
+pub(super) fn stage_patches(patches: &[ApplyPatchArgs]) -> Result<StagedFiles> {
+    let mut staged = StagedFiles::new();
+    for patch in patches {
+        let path = resolve_native_path(&patch.path)?;
+        let (original, working) = match staged.remove(&path) {
+            Some(entry) => entry,
+            None => {
+                let text = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                (text.clone(), text)
+            }
+        };
+        let mut working = working;
+        let mut last_line = None;
+        for (i, edit) in patch.edits.iter().enumerate() {
+            match apply_edit(
+                &mut working,
+                &edit.old,
+                &edit.new,
+                edit.replace_all,
+                &mut last_line,
+            ) {
+                Ok(EditHit::Fuzzy(m)) => println!(
+                    "{}",
+                    color_yellow(&format!(
+                        "Edit #{} for {} applied fuzzily ({})",
+                        i + 1,
+                        patch.path,
+                        m.describe()
+                    ))
+                ),
+                Ok(_) => {}
+                Err(miss) => bail!("Edit #{} for {}: {}", i + 1, patch.path, miss),
+            }
+        }
+        staged.insert(path, (original, working));
+    }
+    staged.retain(|_, (original, working)| original != working);
+    Ok(staged)
+}
+
+pub(super) fn print_diff(path: &Path, original: &str, updated: &str) {
+    println!("{}", color_dim(&format!("── {}", display_path(path))));
+    for line in render_change_diff(original, updated).lines() {
+        if line.starts_with('+') {
+            println!("{}", color_green(line));
+        } else if line.starts_with('-') {
+            println!("{}", color_red(line));
+        } else {
+            println!("{}", color_dim(line));
+        }
+    }
+}
+
+/// Runs `cmd` (or the project's check) and, while it fails, asks the model for
+/// patches to the files named in the errors, applies them after confirmation
+/// and runs it again, for at most `max_iterations` rounds.
+pub async fn run_fix(
+    cfg: &Config,
+    cmd: Option<&str>,
+    max_iterations: usize,
+    yes: bool,
+) -> Result<()> {
+    let cmd = match cmd.map(str::trim).filter(|c| !c.is_empty()) {
+        Some(cmd) => cmd.to_string(),
+        None => match pick_verification_command() {
+            Some((_, cmd)) => cmd.to_string(),
+            None => bail!("No build/test command detected for this project; pass --cmd"),
+        },
+    };
+    let mut touched: Vec<PathBuf> = Vec::new();
+    let mut iteration = 0;
+    loop {
+        println!("{}", color_dim(&format!("fix> {}", cmd)));
+        let (passed, output) = run_shell_command_with_status(&cmd)?;
+        if passed {
+            if iteration == 0 {
+                println!("{}", color_green("Command already passes; nothing to fix."));
+            } else {
+                println!(
+                    "{}",
+                    color_green(&format!("Fixed after {} iteration(s).", iteration))
+                );
+            }
+            return Ok(());
+        }
+        let log = truncate_with_suffix(output.trim(), MAX_ERROR_CHARS, "...\n[truncated]");
+        println!("{}", log);
+        if iteration >= max_iterations {
+            let mut message = format!("`{}` still fails after {} iteration(s)", cmd, iteration);
+            if !touched.is_empty() {
+                message.push_str("; previous versions are saved, see `dongshan backups list`");
+            }
+            bail!("{}", message);
+        }
+        iteration += 1;
+
+        let files = files_in_errors(&output);
+        if files.is_empty() {
+            bail!(
+                "Could not find any workspace file in the errors; fix it manually or use `dongshan edit`"
+            );
+        }
+        let mut context = Vec::new();
+        for path in &files {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            context.push((path.clone(), text));
+        }
+        let errors = summarize_test_failures(&output).unwrap_or(log);
+        println!(
+            "{}",
+            color_yellow(&format!(
+                "Asking the model for a fix ({}/{}), context: {}",
+                iteration,
+                max_iterations,
+                files
+                    .iter()
+                    .map(|p| display_path(p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        );
+        let reply = call_llm(
+            cfg,
+            &build_system_prompt(cfg, "edit"),
+            &build_fix_prompt(&cmd, &errors, &context),
+        )
+        .await?;
+        let staged = stage_patches(&parse_patch_reply(&reply)?)?;
+        if staged.is_empty() {
+            bail!("The model proposed no changes; `{}` still fails", cmd);
+        }
+        for (path, (original, updated)) in &staged {
+            print_diff(path, original, updated);
+        }
+        if !yes {
+            let answer = ask(&tagged_prompt(
+                "fix",
+                &format!("Apply changes to {} file(s)? [y/N] ", staged.len()),
+            ))?;
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                println!("Fix cancelled; no files were changed.");
+                return Ok(());
+            }
+        }
+        for (path, (original, updated)) in &staged {
+            save_backup(path, original)?;
+            fs::write(path, updated)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            record_file_change("fix", path, original, updated);
+            if !touched.contains(path) {
+                touched.push(path.clone());
+            }
+            println!("Updated {}", display_path(path));
+        }
+    }
+}
@@ -1,9 +1,7 @@
 use anyhow::Result;
 
 use crate::cli::FsCommand;
-use crate::fs_tools::{
-    grep_recursive, list_files_recursive, read_text_file, try_rg_files, try_rg_grep,
-};
+use crate::fs_tools::{grep_output, list_files_recursive, read_text_file, try_rg_files};
 
 pub fn handle_fs(command: FsCommand) -> Result<()> {
     match command {
@@ -17,9 +15,7 @@ pub fn handle_fs(command: FsCommand) -> Result<()> {
             }
         }
         FsCommand::Grep { pattern, path } => {
-            if !try_rg_grep(&path, &pattern)? {
-                grep_recursive(&path, &pattern)?;
-            }
+            print!("{}", grep_output(&path, &pattern)?);
         }
     }
     Ok(())
@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::cli::GitCommand;
+use crate::util::color_green;
+
+/// Marks hooks written by dongshan so they can be replaced or removed safely.
+const HOOK_MARKER: &str = "# dongshan: prepare-commit-msg";
+const HOOK_NAME: &str = "prepare-commit-msg";
+
+pub fn handle_git(command: GitCommand) -> Result<()> {
+    match command {
+        GitCommand::InstallHooks {
+            conventional,
+            force,
+        } => {
+            let path = hooks_dir()?.join(HOOK_NAME);
+            if let Ok(existing) = fs::read_to_string(&path)
+                && !existing.contains(HOOK_MARKER)
+                && !force
+            {
+                bail!(
+                    "{} already exists and was not installed by dongshan; use --force to replace it",
+                    path.display()
+                );
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            fs::write(&path, hook_script(conventional))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            make_executable(&path)?;
+            println!("{} {}", color_green("Installed"), path.display());
+            println!(
+                "`git commit` now pre-fills the message from the staged diff; \
+                 `git commit -m` and merges are left alone."
+            );
+        }
+        GitCommand::UninstallHooks => {
+            let path = hooks_dir()?.join(HOOK_NAME);
+            match fs::read_to_string(&path) {
+                Ok(existing) if existing.contains(HOOK_MARKER) => {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                    println!("Removed {}", path.display());
+                }
+                Ok(_) => bail!(
+                    "{} was not installed by dongshan; leaving it in place",
+                    path.display()
+                ),
+                Err(_) => println!("No dongshan hook installed."),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the hooks directory, honouring `core.hooksPath` and worktrees.
+fn hooks_dir() -> Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()
+        .context("Failed to run git rev-parse")?;
+    if !output.status.success() {
+        bail!(
+            "Not a git repository: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+/// The hook prepends the generated message to git's template, so comment
+/// lines and `commit -v` diffs stay intact. Failures never block the commit.
+fn hook_script(conventional: bool) -> String {
+    let flags = if conventional { " --conventional" } else { "" };
+    format!(
+        "#!/bin/sh\n\
+         {HOOK_MARKER} (installed by `dongshan git install-hooks`)\n\
+         # Skip when a message was given (-m/-F), for merges, squashes and amends.\n\
+         case \"$2\" in message|merge|squash|commit) exit 0 ;; esac\n\
+         command -v dongshan >/dev/null 2>&1 || exit 0\n\
+         msg=$(dongshan --quiet commit --message-only{flags} </dev/null 2>/dev/null) || exit 0\n\
+         [ -n \"$msg\" ] || exit 0\n\
+         {{ printf '%s\\n' \"$msg\"; cat \"$1\"; }} > \"$1.dongshan\" && mv \"$1.dongshan\" \"$1\"\n"
+    )
+}
+
+#[cfg(unix)]
+fn make_executable(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make {} executable", path.display()))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &std::path::Path) -> Result<()> {
+    Ok(())
+}
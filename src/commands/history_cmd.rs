@@ -0,0 +1,258 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use crate::cli::HistoryCommand;
+use crate::transcript::{list_transcripts, logs_dir, utc_date, utc_time};
+use crate::util::{
+    color_cyan, color_dim, color_green, color_red, color_yellow, truncate_with_suffix,
+};
+
+/// A `file_write` or `agent_run` transcript entry. Its id is
+/// `<YYYYMMDD>-<line>`, which stays valid because transcripts are append-only.
+struct HistoryEntry {
+    id: String,
+    line: usize,
+    value: Value,
+}
+
+impl HistoryEntry {
+    fn str(&self, key: &str) -> &str {
+        self.value
+            .get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    }
+
+    fn ts(&self) -> u64 {
+        self.value.get("ts").and_then(Value::as_u64).unwrap_or(0)
+    }
+
+    fn pid(&self) -> u64 {
+        self.value.get("pid").and_then(Value::as_u64).unwrap_or(0)
+    }
+
+    fn is_run(&self) -> bool {
+        self.str("kind") == "agent_run"
+    }
+
+    fn files(&self) -> Vec<&str> {
+        self.value
+            .get("files")
+            .and_then(Value::as_array)
+            .map(|files| files.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default()
+    }
+}
+
+pub fn handle_history(
+    command: Option<HistoryCommand>,
+    limit: usize,
+    session: Option<&str>,
+) -> Result<()> {
+    match command {
+        Some(HistoryCommand::Show { id }) => show(&id),
+        None => list(limit, session),
+    }
+}
+
+fn list(limit: usize, session: Option<&str>) -> Result<()> {
+    let mut shown = 0;
+    for path in list_transcripts()?.iter().rev() {
+        let entries = read_entries(path)?;
+        for entry in entries.iter().rev() {
+            if shown >= limit {
+                return Ok(());
+            }
+            if session.is_some_and(|s| entry.str("session") != s) {
+                continue;
+            }
+            print_row(entry);
+            shown += 1;
+        }
+    }
+    if shown == 0 {
+        println!(
+            "No edits or agent runs recorded in {}. Enable with `dongshan config set --transcript-log true`.",
+            logs_dir()?.display()
+        );
+    }
+    Ok(())
+}
+
+fn print_row(entry: &HistoryEntry) {
+    let ts = entry.ts();
+    let detail = if entry.is_run() {
+        let files = entry.files();
+        let verification = match entry.value.get("verification").and_then(Value::as_str) {
+            Some(v) if v.ends_with("failed") => color_red(v),
+            Some(v) => color_green(v),
+            None => color_dim("not verified"),
+        };
+        let status = if entry.value.get("error").is_some_and(|e| !e.is_null()) {
+            format!("{} ", color_red("error"))
+        } else {
+            String::new()
+        };
+        format!(
+            "{}{} file(s), {}  {}",
+            status,
+            files.len(),
+            verification,
+            preview(entry.str("task"))
+        )
+    } else {
+        format!("{} {}", entry.str("op"), entry.str("path"))
+    };
+    let kind = if entry.is_run() {
+        color_yellow(&format!("{:<6}", "agent"))
+    } else {
+        color_cyan(&format!("{:<6}", "edit"))
+    };
+    let session = match entry.str("session") {
+        "" => String::new(),
+        s => color_dim(&format!("  [{}]", s)),
+    };
+    println!(
+        "{:<13} {} {} {}{}",
+        entry.id,
+        color_dim(&format!("{} {}", utc_date(ts), utc_time(ts))),
+        kind,
+        detail,
+        session
+    );
+}
+
+fn show(id: &str) -> Result<()> {
+    let (path, line) = resolve_id(id)?;
+    let entries = read_entries(&path)?;
+    let Some(pos) = entries.iter().position(|e| e.line == line) else {
+        bail!("No edit or agent run with id {}", id);
+    };
+    let entry = &entries[pos];
+    let ts = entry.ts();
+    println!("id:       {}", entry.id);
+    println!("time:     {} {} UTC", utc_date(ts), utc_time(ts));
+    println!("command:  {}", entry.str("command"));
+    if !entry.str("session").is_empty() {
+        println!("session:  {}", entry.str("session"));
+    }
+    if !entry.is_run() {
+        println!("op:       {} {}", entry.str("op"), entry.str("path"));
+        print_diff(entry);
+        return Ok(());
+    }
+    println!("task:     {}", entry.str("task"));
+    println!(
+        "verified: {}",
+        entry
+            .value
+            .get("verification")
+            .and_then(Value::as_str)
+            .unwrap_or("not run")
+    );
+    if let Some(error) = entry.value.get("error").and_then(Value::as_str) {
+        println!("error:    {}", color_red(error));
+    }
+    let files = entry.files();
+    println!("files:    {}", files.len());
+    for file in files {
+        println!("  {}", file);
+    }
+    // Edits the same process wrote since its previous run belong to this one.
+    let pid = entry.pid();
+    let edits: Vec<&HistoryEntry> = entries[..pos]
+        .iter()
+        .rev()
+        .filter(|e| e.pid() == pid)
+        .take_while(|e| !e.is_run())
+        .collect();
+    for edit in edits.into_iter().rev() {
+        println!(
+            "\n{}",
+            color_dim(&format!(
+                "── {} {} {} ({})",
+                edit.id,
+                edit.str("op"),
+                edit.str("path"),
+                utc_time(edit.ts())
+            ))
+        );
+        print_diff(edit);
+    }
+    Ok(())
+}
+
+fn print_diff(entry: &HistoryEntry) {
+    let Some(diff) = entry.value.get("diff").and_then(Value::as_str) else {
+        println!("{}", color_dim("(no diff recorded for this write)"));
+        return;
+    };
+    if diff.is_empty() {
+        println!("{}", color_dim("(content unchanged)"));
+    }
+    for line in diff.lines() {
+        if line.starts_with('+') {
+            println!("{}", color_green(line));
+        } else if line.starts_with('-') {
+            println!("{}", color_red(line));
+        } else {
+            println!("{}", color_dim(line));
+        }
+    }
+}
+
+/// History entries of one transcript in file order.
+fn read_entries(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let day = transcript_day(path).unwrap_or_default();
+    let mut entries = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let kind = value
+            .get("kind")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if kind != "file_write" && kind != "agent_run" {
+            continue;
+        }
+        entries.push(HistoryEntry {
+            id: format!("{}-{}", day, idx + 1),
+            line: idx + 1,
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+/// `20261017` for `transcript-2026-10-17.jsonl`.
+fn transcript_day(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let date = name.strip_prefix("transcript-")?.strip_suffix(".jsonl")?;
+    Some(date.replace('-', ""))
+}
+
+fn resolve_id(id: &str) -> Result<(PathBuf, usize)> {
+    let parsed = id
+        .split_once('-')
+        .and_then(|(day, line)| Some((day, line.parse::<usize>().ok()?)));
+    let Some((day, line)) = parsed else {
+        bail!("Invalid history id {} (expected e.g. 20261017-42)", id);
+    };
+    let path = list_transcripts()?
+        .into_iter()
+        .find(|p| transcript_day(p).as_deref() == Some(day));
+    match path {
+        Some(path) => Ok((path, line)),
+        None => bail!("No transcript for {} in {}", day, logs_dir()?.display()),
+    }
+}
+
+fn preview(text: &str) -> String {
+    truncate_with_suffix(&text.replace('\n', " "), 80, "...")
+}
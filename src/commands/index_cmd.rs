@@ -0,0 +1,89 @@
+use std::env;
+
+use anyhow::Result;
+
+use crate::cli::IndexCommand;
+use crate::config::Config;
+use crate::repo_map::build_repo_map;
+use crate::util::{WorkingStatus, color_cyan, color_dim, truncate_with_suffix};
+use crate::workspace_index::{
+    build_index, clear_index, embedding_model, index_dir, load_index, search_index,
+};
+
+pub async fn handle_index(cfg: &Config, command: IndexCommand) -> Result<()> {
+    let root = env::current_dir()?;
+    match command {
+        IndexCommand::Build { rebuild } => {
+            let working = WorkingStatus::start("indexing workspace");
+            let stats = build_index(cfg, &root, rebuild).await;
+            working.finish();
+            let stats = stats?;
+            println!(
+                "Indexed {} files ({} unchanged), embedded {} chunks, {} chunks total.",
+                stats.files, stats.reused_files, stats.embedded_chunks, stats.total_chunks
+            );
+            println!("Model: {}", embedding_model(cfg));
+            println!("Index: {}", index_dir(&root).display());
+        }
+        IndexCommand::Search { query, top_k } => {
+            if load_index(&root)?.is_none() {
+                println!("No index found. Run `dongshan index build` first.");
+                return Ok(());
+            }
+            let hits = search_index(cfg, &root, &query, top_k).await?;
+            if hits.is_empty() {
+                println!("No matching chunks.");
+            }
+            for hit in hits {
+                println!(
+                    "{} {}",
+                    color_cyan(&format!(
+                        "{}:{}-{}",
+                        hit.chunk.path, hit.chunk.start_line, hit.chunk.end_line
+                    )),
+                    color_dim(&format!("score {:.3}", hit.score))
+                );
+                let preview: String = hit
+                    .chunk
+                    .text
+                    .lines()
+                    .take(6)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                println!("{}\n", truncate_with_suffix(&preview, 600, " ..."));
+            }
+        }
+        IndexCommand::Status => match load_index(&root)? {
+            Some(index) => {
+                println!("Index: {}", index_dir(&root).display());
+                println!("Model: {}", index.model);
+                println!("Files: {}", index.files.len());
+                println!("Chunks: {}", index.chunks.len());
+                println!("Built at (unix): {}", index.built_at);
+                if index.model != embedding_model(cfg) {
+                    println!(
+                        "Configured embedding model is '{}'; run `dongshan index build` to re-embed.",
+                        embedding_model(cfg)
+                    );
+                }
+            }
+            None => println!("No index found. Run `dongshan index build` first."),
+        },
+        IndexCommand::Map { max_chars } => {
+            let map = build_repo_map(&root, max_chars.unwrap_or(cfg.repo_map_max_chars))?;
+            if map.is_empty() {
+                println!("No supported source files found.");
+            } else {
+                println!("{map}");
+            }
+        }
+        IndexCommand::Clear => {
+            if clear_index(&root)? {
+                println!("Removed {}", index_dir(&root).display());
+            } else {
+                println!("No index to remove.");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::cli::JobsCommand;
+use crate::jobs::{JobRecord, JobStatus, cancel_job, job_log_path, list_jobs, load_job};
+use crate::transcript::{utc_date, utc_time};
+use crate::util::{color_dim, color_green, color_red, color_yellow, truncate_preview};
+
+pub async fn handle_jobs(command: JobsCommand) -> Result<()> {
+    match command {
+        JobsCommand::List => {
+            let jobs = list_jobs()?;
+            if jobs.is_empty() {
+                println!(
+                    "No background jobs. Start one with `dongshan agent --background <task>`."
+                );
+                return Ok(());
+            }
+            for job in jobs {
+                println!(
+                    "{:<14} {} {}  {}",
+                    job.id,
+                    status_label(job.status),
+                    color_dim(&format!(
+                        "{} {}",
+                        utc_date(job.started),
+                        utc_time(job.started)
+                    )),
+                    truncate_preview(&job.task, 60)
+                );
+            }
+        }
+        JobsCommand::Logs { id } => {
+            load_job(&id)?;
+            copy_log(&id, 0)?;
+        }
+        JobsCommand::Attach { id } => attach(&id).await?,
+        JobsCommand::Cancel { id } => {
+            let job = cancel_job(&id)?;
+            println!("Cancelled job {} (pid {})", job.id, job.pid);
+        }
+    }
+    Ok(())
+}
+
+fn status_label(status: JobStatus) -> String {
+    let name = format!("{:<10}", status.name());
+    match status {
+        JobStatus::Running => color_yellow(&name),
+        JobStatus::Done => color_green(&name),
+        JobStatus::Failed | JobStatus::Cancelled => color_red(&name),
+    }
+}
+
+/// Prints the log from byte `offset` on and returns the new end offset.
+fn copy_log(id: &str, offset: u64) -> Result<u64> {
+    let path = job_log_path(id)?;
+    let mut file =
+        File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    let mut out = io::stdout();
+    out.write_all(&buf)?;
+    out.flush()?;
+    Ok(offset + buf.len() as u64)
+}
+
+/// Follows the job log until the job ends; Ctrl-C detaches without stopping it.
+async fn attach(id: &str) -> Result<()> {
+    let mut offset = 0;
+    loop {
+        let job = load_job(id)?;
+        offset = copy_log(id, offset)?;
+        if job.status != JobStatus::Running {
+            copy_log(id, offset)?;
+            print_result(&job);
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+fn print_result(job: &JobRecord) {
+    let line = match &job.error {
+        Some(err) => format!("job {} {}: {}", job.id, job.status.name(), err),
+        None => format!("job {} {}", job.id, job.status.name()),
+    };
+    println!("\n{}", color_dim(&line));
+}
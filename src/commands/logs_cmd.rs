@@ -0,0 +1,195 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use crate::cli::LogsCommand;
+use crate::transcript::{list_transcripts, logs_dir, utc_time};
+use crate::util::{color_cyan, color_dim, color_green, color_red, truncate_with_suffix};
+
+pub fn handle_logs(command: LogsCommand) -> Result<()> {
+    match command {
+        LogsCommand::List => {
+            let files = list_transcripts()?;
+            if files.is_empty() {
+                println!(
+                    "No transcripts in {}. Enable with `dongshan config set --transcript-log true`.",
+                    logs_dir()?.display()
+                );
+            }
+            for path in files {
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                println!("{}  {} bytes", path.display(), size);
+            }
+        }
+        LogsCommand::Show {
+            date,
+            kind,
+            limit,
+            json,
+        } => {
+            let path = resolve_transcript(date.as_deref())?;
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let lines: Vec<&str> = text
+                .lines()
+                .filter(|l| kind_matches(l, kind.as_deref()))
+                .collect();
+            let start = lines.len().saturating_sub(limit);
+            for line in &lines[start..] {
+                print_entry(line, json);
+            }
+        }
+        LogsCommand::Tail {
+            lines,
+            follow,
+            json,
+        } => {
+            let path = resolve_transcript(None)?;
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let all: Vec<&str> = text.lines().collect();
+            for line in &all[all.len().saturating_sub(lines)..] {
+                print_entry(line, json);
+            }
+            if follow {
+                follow_file(&path, text.len() as u64, json)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve_transcript(date: Option<&str>) -> Result<PathBuf> {
+    let files = list_transcripts()?;
+    let found = match date {
+        Some(d) => files.into_iter().find(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.contains(d))
+        }),
+        None => files.into_iter().last(),
+    };
+    match found {
+        Some(p) => Ok(p),
+        None => bail!(
+            "No transcript found in {}. Enable with `dongshan config set --transcript-log true`.",
+            logs_dir()?.display()
+        ),
+    }
+}
+
+fn kind_matches(line: &str, kind: Option<&str>) -> bool {
+    let Some(kind) = kind else {
+        return true;
+    };
+    serde_json::from_str::<Value>(line)
+        .ok()
+        .and_then(|v| v.get("kind").and_then(|k| k.as_str()).map(|k| k == kind))
+        .unwrap_or(false)
+}
+
+fn follow_file(path: &PathBuf, mut offset: u64, json: bool) -> Result<()> {
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        let len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if len <= offset {
+            continue;
+        }
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let reader = BufReader::new(file);
+        for line in reader.lines() {
+            let line = line?;
+            offset += line.len() as u64 + 1;
+            print_entry(&line, json);
+        }
+    }
+}
+
+fn print_entry(line: &str, json: bool) {
+    if json {
+        println!("{line}");
+        return;
+    }
+    let Ok(v) = serde_json::from_str::<Value>(line) else {
+        return;
+    };
+    let s = |key: &str| v.get(key).and_then(|x| x.as_str()).unwrap_or_default();
+    let ts = v.get("ts").and_then(|x| x.as_u64()).unwrap_or(0);
+    let kind = s("kind");
+    let detail = match kind {
+        "start" => format!("{} in {}", s("command"), s("cwd")),
+        "request" => {
+            let body = v.get("body");
+            let model = body
+                .and_then(|b| b.get("model"))
+                .and_then(|m| m.as_str())
+                .unwrap_or_default();
+            let last = body
+                .and_then(|b| b.get("messages"))
+                .and_then(|m| m.as_array())
+                .and_then(|m| m.last())
+                .and_then(|m| m.get("content"))
+                .map(|c| {
+                    c.as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| c.to_string())
+                })
+                .unwrap_or_default();
+            format!("{} <- {}", model, preview(&last))
+        }
+        "response" => format!("{} -> {}", s("model"), preview(s("content"))),
+        "tool_call" => {
+            let ok = v.get("ok").and_then(|x| x.as_bool()).unwrap_or(false);
+            let status = if ok {
+                color_green("ok")
+            } else {
+                color_red("err")
+            };
+            format!(
+                "{} {} [{}] {}",
+                s("tool"),
+                v.get("args")
+                    .map(|a| preview(&a.to_string()))
+                    .unwrap_or_default(),
+                status,
+                preview(s("output"))
+            )
+        }
+        "command" => format!(
+            "$ {} (exit {}) {}",
+            s("cmd"),
+            v.get("exit_code")
+                .and_then(|x| x.as_i64())
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| "?".to_string()),
+            preview(s("output"))
+        ),
+        "file_write" => format!("{} {}", s("op"), s("path")),
+        "agent_run" => format!(
+            "{} [{}] {}",
+            preview(s("task")),
+            v.get("verification")
+                .and_then(|x| x.as_str())
+                .unwrap_or("not verified"),
+            s("session")
+        ),
+        _ => preview(line),
+    };
+    println!(
+        "{} {} {}",
+        color_dim(&utc_time(ts)),
+        color_cyan(&format!("{:<10}", kind)),
+        detail
+    );
+}
+
+fn preview(text: &str) -> String {
+    truncate_with_suffix(&text.replace('\n', " "), 140, "...")
+}
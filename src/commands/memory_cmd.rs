@@ -0,0 +1,51 @@
+use anyhow::{Result, bail};
+
+use crate::cli::MemoryCommand;
+use crate::memory::{MemoryEntry, active_memories, forget, remember};
+use crate::transcript::utc_date;
+use crate::util::{color_cyan, color_dim};
+
+pub fn handle_memory(command: MemoryCommand) -> Result<()> {
+    match command {
+        MemoryCommand::List => {
+            let (global, workspace) = active_memories()?;
+            if global.is_empty() && workspace.is_empty() {
+                println!("Nothing remembered yet. Use /remember <fact> in chat.");
+                return Ok(());
+            }
+            print_section("global", &global);
+            print_section("workspace", &workspace);
+        }
+        MemoryCommand::Add { text, global } => {
+            let text = text.join(" ");
+            if text.trim().is_empty() {
+                bail!("Nothing to remember: pass the fact as text");
+            }
+            let id = remember(&text, global)?;
+            println!("Remembered #{}", id);
+        }
+        MemoryCommand::Forget { id } => {
+            if forget(id)? {
+                println!("Forgot #{}", id);
+            } else {
+                println!("No memory with id {}.", id);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_section(title: &str, entries: &[MemoryEntry]) {
+    if entries.is_empty() {
+        return;
+    }
+    println!("{}", color_cyan(title));
+    for entry in entries {
+        println!(
+            "  #{:<4} {}  {}",
+            entry.id,
+            entry.text,
+            color_dim(&utc_date(entry.ts))
+        );
+    }
+}
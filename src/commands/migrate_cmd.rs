@@ -0,0 +1,288 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Result, bail};
+
+use crate::backups::{rollback_checkpoint, save_backup, save_checkpoint};
+use crate::change_diff::render_change_diff;
+use crate::chat::{resolve_native_path, run_shell_command_with_status};
+use crate::config::{Config, build_system_prompt};
+use crate::fs_tools::{display_path, workspace_files};
+use crate::llm::call_llm;
+use crate::shell::active_shell;
+use crate::test_failures::summarize_test_failures;
+use crate::transcript::{record_file_change, utc_date, utc_time};
+use crate::util::{
+    ask, color_bold, color_dim, color_green, color_red, color_yellow, tagged_prompt,
+    truncate_with_suffix,
+};
+
+const MAX_MIGRATE_FILES: usize = 500;
+/// Files are rewritten whole, so each one has to fit in a single request.
+const MAX_MIGRATE_FILE_CHARS: usize = 60_000;
+const MAX_CHECK_OUTPUT_CHARS: usize = 4000;
+const PROGRESS_WIDTH: usize = 24;
+
+/// Per-file settings shared by every file of one run.
+pub struct MigrateOptions {
+    /// Command run after each rewrite; `{file}` is replaced by the path.
+    pub check: Option<String>,
+    /// Extra model round-trips for a file after its first attempt fails.
+    pub retries: usize,
+    pub yes: bool,
+}
+
+enum Outcome {
+    Migrated { added: usize, removed: usize },
+    Unchanged,
+    Failed(String),
+}
+
+fn progress_bar(done: usize, total: usize) -> String {
+    let filled = (done * PROGRESS_WIDTH).checked_div(total).unwrap_or(0);
+    format!(
+        "[{}{}] {}/{}",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_WIDTH - filled),
+        done,
+        total
+    )
+}
+
+fn strip_fence(reply: &str) -> String {
+    let text = reply.trim();
+    match text.strip_prefix("```") {
+        Some(rest) => {
+            let rest = rest.split_once('\n').map(|(_, body)| body).unwrap_or(rest);
+            rest.trim_end().trim_end_matches("```").to_string()
+        }
+        None => reply.to_string(),
+    }
+}
+
+fn build_migrate_prompt(path: &Path, instruction: &str, text: &str, feedback: &str) -> String {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+    format!(
+        "Apply this migration to the file.\n\
+         Return ONLY the full updated file content with no markdown and no explanation. \
+         If the file needs no change, return it unchanged.\n\n\
+         Migration:\n{}\n{}\n\
+         File: {}\n```{}\n{}\n```",
+        instruction,
+        feedback,
+        display_path(path),
+        ext,
+        text
+    )
+}
+
+/// Rewrites one file, retrying with the error when the request, the reply or
+/// the check fails. A file that never passes is left as it was.
+async fn migrate_file(
+    cfg: &Config,
+    path: &Path,
+    instruction: &str,
+    opts: &MigrateOptions,
+) -> (Outcome, Option<String>) {
+    let original = match fs::read_to_string(path) {
+        Ok(text) if text.chars().count() > MAX_MIGRATE_FILE_CHARS => {
+            return (
+                Outcome::Failed(format!("larger than {} chars", MAX_MIGRATE_FILE_CHARS)),
+                None,
+            );
+        }
+        Ok(text) => text,
+        Err(err) => return (Outcome::Failed(format!("read failed: {}", err)), None),
+    };
+    let system = build_system_prompt(cfg, "edit");
+    let mut feedback = String::new();
+    let mut last_error = String::new();
+    let mut backed_up = false;
+    for attempt in 0..=opts.retries {
+        if attempt > 0 {
+            println!(
+                "{}",
+                color_yellow(&format!(
+                    "  retry {}/{}: {}",
+                    attempt,
+                    opts.retries,
+                    last_error.lines().next().unwrap_or_default()
+                ))
+            );
+        }
+        let prompt = build_migrate_prompt(path, instruction, &original, &feedback);
+        let mut updated = match call_llm(cfg, &system, &prompt).await {
+            Ok(reply) => strip_fence(&reply),
+            Err(err) => {
+                last_error = format!("request failed: {:#}", err);
+                continue;
+            }
+        };
+        if updated.trim().is_empty() {
+            last_error = "model returned an empty file".to_string();
+            feedback = "\nYour previous reply was empty; return the whole file.\n".to_string();
+            continue;
+        }
+        if original.ends_with('\n') && !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        if updated == original {
+            return (Outcome::Unchanged, None);
+        }
+        // Kept even when the run is interrupted before the checkpoint is saved.
+        if !backed_up {
+            if let Err(err) = save_backup(path, &original) {
+                return (Outcome::Failed(format!("backup failed: {:#}", err)), None);
+            }
+            backed_up = true;
+        }
+        if let Err(err) = fs::write(path, &updated) {
+            return (Outcome::Failed(format!("write failed: {}", err)), None);
+        }
+        if let Some(check) = &opts.check {
+            let cmd = check.replace("{file}", &active_shell().quote(&display_path(path)));
+            let (passed, output) = match run_shell_command_with_status(&cmd) {
+                Ok(result) => result,
+                Err(err) => (false, format!("{:#}", err)),
+            };
+            if !passed {
+                let _ = fs::write(path, &original);
+                let errors = summarize_test_failures(&output).unwrap_or_else(|| {
+                    truncate_with_suffix(output.trim(), MAX_CHECK_OUTPUT_CHARS, "...[truncated]")
+                });
+                last_error = format!("`{}` failed", cmd);
+                feedback = format!(
+                    "\nYour previous rewrite failed `{}`:\n```\n{}\n```\nFix that while applying the migration.\n",
+                    cmd, errors
+                );
+                continue;
+            }
+        }
+        record_file_change("migrate", path, &original, &updated);
+        let diff = render_change_diff(&original, &updated);
+        let added = diff.lines().filter(|l| l.starts_with('+')).count();
+        let removed = diff.lines().filter(|l| l.starts_with('-')).count();
+        return (Outcome::Migrated { added, removed }, Some(original));
+    }
+    (Outcome::Failed(last_error), None)
+}
+
+/// Applies one instruction to every file matching `pattern`, one request per
+/// file, and prints a summary. Rewritten files share one rollback checkpoint.
+pub async fn run_migrate(
+    cfg: &Config,
+    pattern: &str,
+    instruction: &str,
+    opts: &MigrateOptions,
+) -> Result<()> {
+    let files: Vec<PathBuf> = workspace_files(Path::new("."), Some(pattern))?
+        .iter()
+        .map(|f| resolve_native_path(&f.to_string_lossy()))
+        .collect::<Result<_>>()?;
+    if files.is_empty() {
+        bail!("No files match {}", pattern);
+    }
+    if files.len() > MAX_MIGRATE_FILES {
+        bail!(
+            "{} matches {} files (limit {}); narrow the pattern",
+            pattern,
+            files.len(),
+            MAX_MIGRATE_FILES
+        );
+    }
+    println!(
+        "{}",
+        color_dim(&format!(
+            "migrate> {} file(s) match {}",
+            files.len(),
+            pattern
+        ))
+    );
+    if !opts.yes {
+        let answer = ask(&tagged_prompt(
+            "migrate",
+            &format!("Rewrite up to {} file(s)? [y/N] ", files.len()),
+        ))?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Migration cancelled.");
+            return Ok(());
+        }
+    }
+
+    let started = Instant::now();
+    let total = files.len();
+    let mut originals: Vec<(PathBuf, String)> = Vec::new();
+    let mut failed: Vec<(PathBuf, String)> = Vec::new();
+    let mut unchanged = 0;
+    for (i, path) in files.iter().enumerate() {
+        println!(
+            "{} {}",
+            color_dim(&progress_bar(i, total)),
+            display_path(path)
+        );
+        let (outcome, original) = migrate_file(cfg, path, instruction, opts).await;
+        match outcome {
+            Outcome::Migrated { added, removed } => {
+                println!(
+                    "  {}",
+                    color_green(&format!("migrated +{} -{}", added, removed))
+                );
+            }
+            Outcome::Unchanged => {
+                println!("  {}", color_dim("no change needed"));
+                unchanged += 1;
+            }
+            Outcome::Failed(reason) => {
+                println!("  {}", color_red(&format!("failed: {}", reason)));
+                failed.push((path.clone(), reason));
+            }
+        }
+        if let Some(original) = original {
+            originals.push((path.clone(), original));
+        }
+    }
+    println!("{}", color_dim(&progress_bar(total, total)));
+
+    if !originals.is_empty() {
+        save_checkpoint("migrate", instruction, &originals)?;
+    }
+    println!("{}", color_bold("Migration summary"));
+    println!("- migrated:  {}", originals.len());
+    println!("- unchanged: {}", unchanged);
+    println!("- failed:    {}", failed.len());
+    println!("- time:      {}s", started.elapsed().as_secs());
+    for (path, reason) in &failed {
+        println!("  {} {}: {}", color_red("x"), display_path(path), reason);
+    }
+    if !originals.is_empty() {
+        println!(
+            "{}",
+            color_dim("Undo every rewritten file with `dongshan migrate --rollback`.")
+        );
+    }
+    if !failed.is_empty() {
+        bail!("{} of {} file(s) failed to migrate", failed.len(), total);
+    }
+    Ok(())
+}
+
+/// Reverts the files rewritten by the newest migration.
+pub fn run_migrate_rollback() -> Result<()> {
+    let Some(checkpoint) = rollback_checkpoint("migrate")? else {
+        println!("No migration to roll back in this workspace.");
+        return Ok(());
+    };
+    println!(
+        "{} {} file(s) from {} {} ({})",
+        color_green("Rolled back"),
+        checkpoint.files.len(),
+        utc_date(checkpoint.ts),
+        utc_time(checkpoint.ts),
+        checkpoint.label
+    );
+    for rel in &checkpoint.files {
+        println!("- {}", rel.display());
+    }
+    Ok(())
+}
@@ -1,17 +1,65 @@
+mod arch_cmd;
+mod ask_cmd;
+mod backups_cmd;
+mod blame_cmd;
+mod commit_cmd;
+mod compare_cmd;
 mod config_cmd;
+mod doc_cmd;
 mod doctor_cmd;
 mod edit_cmd;
+mod exec_cmd;
+mod fix_cmd;
 mod fs_cmd;
+mod git_cmd;
+mod history_cmd;
+mod index_cmd;
+mod jobs_cmd;
+mod logs_cmd;
+mod memory_cmd;
+mod migrate_cmd;
 mod models_cmd;
 mod onboard_cmd;
+mod pipeline_cmd;
+mod pr_cmd;
 mod prompt_cmd;
+mod refactor_cmd;
 mod review_cmd;
+mod shell_hook_cmd;
+mod stats_cmd;
+mod storage_cmd;
+mod trust_cmd;
+mod update_cmd;
 
+pub use arch_cmd::run_arch;
+pub use ask_cmd::run_ask;
+pub use backups_cmd::{handle_backups, run_restore};
+pub use blame_cmd::run_blame;
+pub use commit_cmd::{generate_commit_message, run_commit};
+pub use compare_cmd::run_compare;
 pub use config_cmd::handle_config;
+pub use doc_cmd::run_doc;
 pub use doctor_cmd::run_doctor;
-pub use edit_cmd::run_edit;
+pub use edit_cmd::{EditVerify, run_edit};
+pub use exec_cmd::run_exec;
+pub use fix_cmd::run_fix;
 pub use fs_cmd::handle_fs;
+pub use git_cmd::handle_git;
+pub use history_cmd::handle_history;
+pub use index_cmd::handle_index;
+pub use jobs_cmd::handle_jobs;
+pub use logs_cmd::handle_logs;
+pub use memory_cmd::handle_memory;
+pub use migrate_cmd::{MigrateOptions, run_migrate, run_migrate_rollback};
 pub use models_cmd::handle_models;
 pub use onboard_cmd::run_onboard;
+pub use pipeline_cmd::handle_pipeline;
+pub use pr_cmd::{PrOptions, run_pr_create};
 pub use prompt_cmd::handle_prompt;
+pub use refactor_cmd::{run_refactor, run_refactor_rollback};
 pub use review_cmd::run_review;
+pub use shell_hook_cmd::run_shell_hook;
+pub use stats_cmd::run_stats;
+pub use storage_cmd::handle_storage;
+pub use trust_cmd::handle_trust;
+pub use update_cmd::run_update;
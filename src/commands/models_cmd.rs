@@ -2,10 +2,13 @@ use anyhow::{Result, bail};
 
 use crate::cli::ModelsCommand;
 use crate::config::{
-    add_model_with_active_profile, ensure_model_catalog, load_config_or_default, remove_model,
-    save_config, set_active_model, upsert_model_profile,
+    add_model_with_active_profile, ensure_model_catalog, load_config_or_default,
+    model_context_window, remove_model, save_config, set_active_model, set_model_context_window,
+    set_model_fallbacks, upsert_model_profile,
 };
 
+const REASONING_EFFORTS: &[&str] = &["minimal", "low", "medium", "high"];
+
 pub fn handle_models(command: ModelsCommand) -> Result<()> {
     let mut cfg = load_config_or_default()?;
     ensure_model_catalog(&mut cfg);
@@ -69,6 +72,23 @@ pub fn handle_models(command: ModelsCommand) -> Result<()> {
             println!("  tool_mode: {:?}", p.tool_mode);
             println!("  base_url: {}", p.base_url);
             println!("  api_key_env: {}", p.api_key_env);
+            if !p.fallbacks.is_empty() {
+                println!("  fallbacks: {}", p.fallbacks.join(" -> "));
+            }
+            if p.reasoning {
+                println!(
+                    "  reasoning: yes (effort: {}, shown: {})",
+                    p.reasoning_effort.as_deref().unwrap_or("default"),
+                    if p.show_reasoning { "yes" } else { "no" }
+                );
+            }
+            match p.context_window {
+                Some(window) => println!("  context_window: {}", window),
+                None => println!(
+                    "  context_window: {} (guessed)",
+                    model_context_window(&cfg, &target)
+                ),
+            }
             println!(
                 "  api_key: {}",
                 if p.api_key.as_ref().is_some_and(|v| !v.trim().is_empty()) {
@@ -88,17 +108,68 @@ pub fn handle_models(command: ModelsCommand) -> Result<()> {
             base_url,
             api_key_env,
             api_key,
+            fallbacks,
+            context_window,
+            reasoning,
+            reasoning_effort,
+            show_reasoning,
         } => {
             if provider.is_none()
                 && base_url.is_none()
                 && api_key_env.is_none()
                 && api_key.is_none()
+                && fallbacks.is_none()
+                && context_window.is_none()
+                && reasoning.is_none()
+                && reasoning_effort.is_none()
+                && show_reasoning.is_none()
+            {
+                bail!(
+                    "Nothing to set. Provide at least one of --provider/--base-url/--api-key-env/--api-key/--fallbacks/--context-window/--reasoning/--reasoning-effort/--show-reasoning."
+                );
+            }
+            if let Some(v) = reasoning_effort.as_deref().map(str::trim)
+                && !v.is_empty()
+                && !REASONING_EFFORTS.contains(&v)
             {
                 bail!(
-                    "Nothing to set. Provide at least one of --provider/--base-url/--api-key-env/--api-key."
+                    "Unknown reasoning effort: {}. Available: {}",
+                    v,
+                    REASONING_EFFORTS.join(", ")
                 );
             }
             upsert_model_profile(&mut cfg, &name, base_url, api_key_env, api_key, provider);
+            if let Some(v) = fallbacks {
+                let list: Vec<String> = v
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|m| !m.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if let Some(m) = list.iter().find(|m| !cfg.model_catalog.contains(m)) {
+                    bail!(
+                        "Fallback model not in catalog: {}. Use `dongshan models add {}` first.",
+                        m,
+                        m
+                    );
+                }
+                set_model_fallbacks(&mut cfg, &name, list);
+            }
+            if let Some(window) = context_window {
+                set_model_context_window(&mut cfg, &name, (window > 0).then_some(window));
+            }
+            if let Some(p) = cfg.model_profiles.get_mut(&name) {
+                if let Some(v) = reasoning {
+                    p.reasoning = v;
+                }
+                if let Some(v) = reasoning_effort {
+                    let v = v.trim();
+                    p.reasoning_effort = (!v.is_empty()).then(|| v.to_string());
+                }
+                if let Some(v) = show_reasoning {
+                    p.show_reasoning = v;
+                }
+            }
             save_config(&cfg)?;
             println!("Profile updated for model: {}", name);
         }
@@ -3,7 +3,6 @@ use std::env;
 use std::time::Duration;
 
 use anyhow::{Result, bail};
-use reqwest::Client;
 use serde_json::Value;
 
 use crate::config::{
@@ -12,6 +11,7 @@ use crate::config::{
     set_active_model, update_active_model_profile, upsert_model_profile,
 };
 use crate::prompt_store::{list_prompt_names, save_prompt};
+use crate::tls::client_builder;
 use crate::util::{ask, tagged_prompt};
 
 pub async fn run_onboard() -> Result<()> {
@@ -237,7 +237,7 @@ async fn fetch_provider_models_online(
     provider: ProviderPreset,
     cfg: &Config,
 ) -> Result<Option<Vec<String>>> {
-    let client = Client::builder().timeout(Duration::from_secs(6)).build()?;
+    let client = client_builder()?.timeout(Duration::from_secs(6)).build()?;
     let (url, needs_auth) = match provider {
         ProviderPreset::Openrouter => ("https://openrouter.ai/api/v1/models".to_string(), false),
         _ => (cfg.base_url.replace("/chat/completions", "/models"), true),
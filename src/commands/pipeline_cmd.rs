@@ -0,0 +1,153 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::cli::PipelineCommand;
+use crate::config::{
+    Config, PipelineStage, config_for_model, ensure_model_catalog, load_config_or_default,
+    render_prompt_vars,
+};
+use crate::fs_tools::{read_piped_stdin, read_text_source};
+use crate::llm::{ChatMessage, call_llm, call_llm_with_history_stream};
+use crate::prompt_store::get_prompt;
+use crate::redact::redact_secrets;
+use crate::util::{color_bold, color_dim};
+
+pub async fn handle_pipeline(command: PipelineCommand) -> Result<()> {
+    let cfg = load_config_or_default()?;
+    match command {
+        PipelineCommand::List => {
+            if cfg.pipelines.is_empty() {
+                println!("No pipelines. Define them under [pipelines] in config.toml.");
+            }
+            for (name, stages) in &cfg.pipelines {
+                let chain: Vec<String> = stages.iter().map(stage_label).collect();
+                println!("- {}: {}", name, chain.join(" -> "));
+            }
+        }
+        PipelineCommand::Run {
+            name,
+            input,
+            output,
+            show_stages,
+        } => {
+            let text = match input {
+                Some(path) => read_text_source(&path)?,
+                None => read_piped_stdin()?
+                    .context("No input: pass --input <file> or pipe text on stdin")?,
+            };
+            run_pipeline(&cfg, &name, &text, output.as_deref(), show_stages).await?;
+        }
+    }
+    Ok(())
+}
+
+fn stage_label(stage: &PipelineStage) -> String {
+    let prompt = stage.prompt.as_deref().unwrap_or("(active prompt)");
+    match &stage.model {
+        Some(model) => format!("{}@{}", prompt, model),
+        None => prompt.to_string(),
+    }
+}
+
+/// Resolves every stage's model and system prompt before the first request,
+/// so a typo in the last stage does not waste the earlier calls.
+fn prepare_stages(cfg: &Config, stages: &[PipelineStage]) -> Result<Vec<(Config, String)>> {
+    let mut catalog_cfg = cfg.clone();
+    ensure_model_catalog(&mut catalog_cfg);
+    let mut out = Vec::new();
+    for (i, stage) in stages.iter().enumerate() {
+        let stage_cfg = match stage.model.as_deref().map(str::trim) {
+            Some(model) if !model.is_empty() => {
+                if !catalog_cfg.model_catalog.iter().any(|m| m == model) {
+                    bail!(
+                        "Stage {} uses model {} which is not in the catalog. Use `dongshan models add {}` first.",
+                        i + 1,
+                        model,
+                        model
+                    );
+                }
+                config_for_model(&catalog_cfg, model)
+            }
+            _ => cfg.clone(),
+        };
+        let prompt_name = stage.prompt.as_deref().unwrap_or(&cfg.active_prompt);
+        let system = get_prompt(prompt_name)?.with_context(|| {
+            format!(
+                "Stage {} uses prompt {} which does not exist (see `dongshan prompt list`)",
+                i + 1,
+                prompt_name
+            )
+        })?;
+        out.push((stage_cfg, render_prompt_vars(&system, &cfg.prompt_vars)));
+    }
+    Ok(out)
+}
+
+fn stage_message(stage: &PipelineStage, input: &str) -> String {
+    match stage.instruction.as_deref().map(str::trim) {
+        Some(instruction) if !instruction.is_empty() => {
+            format!("{}\n\nInput:\n{}", instruction, input)
+        }
+        _ => input.to_string(),
+    }
+}
+
+/// Runs the stages in order, each getting the previous stage's output. The
+/// last stage streams to the terminal unless `output` is given.
+pub async fn run_pipeline(
+    cfg: &Config,
+    name: &str,
+    input: &str,
+    output: Option<&Path>,
+    show_stages: bool,
+) -> Result<()> {
+    let Some(stages) = cfg.pipelines.get(name) else {
+        let known: Vec<&str> = cfg.pipelines.keys().map(String::as_str).collect();
+        bail!(
+            "Pipeline not found: {}. Defined: {}",
+            name,
+            if known.is_empty() {
+                "(none)".to_string()
+            } else {
+                known.join(", ")
+            }
+        );
+    };
+    if stages.is_empty() {
+        bail!("Pipeline {} has no stages", name);
+    }
+    if input.trim().is_empty() {
+        bail!("Pipeline input is empty");
+    }
+    let prepared = prepare_stages(cfg, stages)?;
+
+    let mut current = redact_secrets(input).into_owned();
+    let total = stages.len();
+    for (i, (stage, (stage_cfg, system))) in stages.iter().zip(&prepared).enumerate() {
+        let label = format!("stage {}/{} {}", i + 1, total, stage_label(stage));
+        let message = stage_message(stage, &current);
+        let last = i + 1 == total;
+        eprintln!("{}", color_dim(&format!("pipeline> {}", label)));
+        if last && output.is_none() {
+            let history = vec![ChatMessage::text("user", message)];
+            call_llm_with_history_stream(stage_cfg, system, &history).await?;
+            println!();
+            return Ok(());
+        }
+        current = call_llm(stage_cfg, system, &message)
+            .await
+            .with_context(|| format!("Pipeline {} failed at {}", name, label))?;
+        if show_stages && !last {
+            println!("{}", color_bold(&format!("== {}", label)));
+            println!("{}\n", current.trim_end());
+        }
+    }
+
+    if let Some(path) = output {
+        fs::write(path, &current).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Pipeline {} output written to {}", name, path.display());
+    }
+    Ok(())
+}
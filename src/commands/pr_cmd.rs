@@ -0,0 +1,211 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+
+use crate::chat::resolve_session_name;
+use crate::config::Config;
+use crate::llm::call_llm;
+use crate::session_meta::load_session_meta;
+use crate::task_branch::default_branch;
+use crate::tls::client_builder;
+use crate::util::{ask, color_dim, tagged_prompt, truncate_with_suffix};
+use crate::worktree::{git, git_ok};
+
+const MAX_LOG_CHARS: usize = 6_000;
+
+/// Options for `dongshan pr create`.
+pub struct PrOptions {
+    pub session: String,
+    pub base: Option<String>,
+    pub remote: String,
+    pub title: Option<String>,
+    pub draft: bool,
+    pub yes: bool,
+}
+
+pub async fn run_pr_create(cfg: &Config, opts: &PrOptions) -> Result<()> {
+    let here = Path::new(".");
+    let branch = git_ok(here, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .context("Not on a branch; check out the branch to open a PR for")?;
+    let base = match &opts.base {
+        Some(base) => base.clone(),
+        None => default_branch("")
+            .map(|b| b.trim_start_matches("origin/").to_string())
+            .ok_or_else(|| anyhow!("Could not detect the base branch; pass --base"))?,
+    };
+    if base == branch {
+        bail!(
+            "{} is the base branch; switch to a feature branch first",
+            branch
+        );
+    }
+    let remote_ref = format!("{}/{}", opts.remote, base);
+    let compare = if git(here, &["rev-parse", "--verify", "--quiet", &remote_ref])?
+        .status
+        .success()
+    {
+        remote_ref
+    } else {
+        base.clone()
+    };
+    let range = format!("{}...HEAD", compare);
+    let commits = git_ok(here, &["log", "--oneline", "--no-decorate", &range])?;
+    if commits.is_empty() {
+        bail!("{} has no commits ahead of {}", branch, base);
+    }
+    let stat = git_ok(here, &["diff", "--stat", &range])?;
+
+    let session = resolve_session_name(&opts.session)?;
+    let meta = load_session_meta(&session)?;
+    let (generated_title, body) = generate_pr_text(
+        cfg,
+        meta.task.as_deref(),
+        &commits,
+        &stat,
+        meta.verification.as_deref(),
+    )
+    .await?;
+    let title = opts.title.clone().unwrap_or(generated_title);
+    println!("{}", color_dim("── pull request ──"));
+    println!("{} -> {}\n{}\n\n{}", branch, base, title, body);
+    println!("{}", color_dim("──────────────────"));
+    if !opts.yes {
+        let choice = ask(&tagged_prompt(
+            "pr",
+            "Push and open this pull request? [y/N]: ",
+        ))?;
+        if !choice.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    let status = Command::new("git")
+        .args(["push", "-u", &opts.remote, &branch])
+        .status()
+        .context("Failed to run git push")?;
+    if !status.success() {
+        bail!("git push exited with {}", status);
+    }
+    let url = open_pull_request(opts, &branch, &base, &title, &body).await?;
+    println!("Opened {}", url);
+    Ok(())
+}
+
+/// Title and markdown body from the session task, commits, diff stat and
+/// verification result.
+async fn generate_pr_text(
+    cfg: &Config,
+    task: Option<&str>,
+    commits: &str,
+    stat: &str,
+    verification: Option<&str>,
+) -> Result<(String, String)> {
+    let system = "You write GitHub pull request descriptions.\n\
+        Output a title of at most 72 characters on the first line, a blank line, then a markdown body with \
+        a short summary paragraph, a `## Changes` list grouped by area, and a `## Verification` section that \
+        reports the given result (say it was not run when none is given).\n\
+        No code fences around the output, no surrounding quotes.";
+    let prompt = format!(
+        "Task: {}\n\nCommits:\n{}\n\nChanged files:\n{}\n\nVerification: {}",
+        task.map(str::trim)
+            .filter(|t| !t.is_empty())
+            .unwrap_or("(not recorded)"),
+        truncate_with_suffix(commits, MAX_LOG_CHARS, "\n..."),
+        truncate_with_suffix(stat, MAX_LOG_CHARS, "\n..."),
+        verification.unwrap_or("not run")
+    );
+    let answer = call_llm(cfg, system, &prompt).await?;
+    let answer = answer.trim();
+    let (title, body) = answer.split_once('\n').unwrap_or((answer, ""));
+    let title = title
+        .trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_matches('"');
+    if title.is_empty() {
+        bail!("The model returned an empty pull request title");
+    }
+    Ok((title.to_string(), body.trim().to_string()))
+}
+
+/// Opens the PR through the GitHub API when a token is set and the remote is
+/// on github.com, else through `gh pr create`. Returns the PR URL.
+async fn open_pull_request(
+    opts: &PrOptions,
+    branch: &str,
+    base: &str,
+    title: &str,
+    body: &str,
+) -> Result<String> {
+    let token = ["GITHUB_TOKEN", "GH_TOKEN"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()));
+    let remote_url = git_ok(Path::new("."), &["remote", "get-url", &opts.remote])?;
+    if let (Some(token), Some((owner, repo))) = (token, github_repo(&remote_url)) {
+        let url = format!("https://api.github.com/repos/{owner}/{repo}/pulls");
+        let resp = client_builder()?
+            .build()?
+            .post(&url)
+            .bearer_auth(token.trim())
+            .header("User-Agent", "dongshan-cli")
+            .header("Accept", "application/vnd.github+json")
+            .json(&json!({
+                "title": title,
+                "body": body,
+                "head": branch,
+                "base": base,
+                "draft": opts.draft,
+            }))
+            .send()
+            .await
+            .context("GitHub API request failed")?;
+        let status = resp.status();
+        let value: Value = resp.json().await.unwrap_or_default();
+        if !status.is_success() {
+            let message = value
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            bail!("GitHub API returned {}: {}", status, message);
+        }
+        return Ok(value
+            .get("html_url")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string());
+    }
+    let mut cmd = Command::new("gh");
+    cmd.args([
+        "pr", "create", "--head", branch, "--base", base, "--title", title, "--body", body,
+    ]);
+    if opts.draft {
+        cmd.arg("--draft");
+    }
+    let out = cmd.output().map_err(|_| {
+        anyhow!("Set GITHUB_TOKEN for a github.com remote, or install the GitHub CLI (`gh`)")
+    })?;
+    if !out.status.success() {
+        bail!(
+            "gh pr create failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// `(owner, repo)` for `https://github.com/o/r(.git)` and `git@github.com:o/r(.git)` remotes.
+fn github_repo(url: &str) -> Option<(String, String)> {
+    let path = url
+        .strip_prefix("https://github.com/")
+        .or_else(|| url.strip_prefix("ssh://git@github.com/"))
+        .or_else(|| url.strip_prefix("git@github.com:"))?;
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    let (owner, repo) = path.split_once('/')?;
+    if owner.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    Some((owner.to_string(), repo.to_string()))
+}
@@ -1,25 +1,37 @@
 use anyhow::{Result, bail};
 
 use crate::cli::PromptCommand;
-use crate::config::{current_prompt_text, load_config_or_default, save_config};
-use crate::prompt_store::{list_prompt_names, remove_prompt, save_prompt};
-use crate::util::truncate_preview;
+use crate::config::{PROMPT_MODES, current_prompt_text, load_config_or_default, save_config};
+use crate::diagnostics::now_unix_ts;
+use crate::prompt_fetch::fetch_prompts;
+use crate::prompt_store::{
+    PromptSource, get_prompt, list_prompt_names, list_prompts, remove_prompt, save_prompt,
+    save_prompt_with_source,
+};
+use crate::util::{ask, truncate_preview};
+
+/// Lines of each fetched prompt shown before asking to save.
+const FETCH_PREVIEW_LINES: usize = 8;
 
 pub fn handle_prompt(command: PromptCommand) -> Result<()> {
     let mut cfg = load_config_or_default()?;
     match command {
         PromptCommand::List => {
             println!("Active: {}", cfg.active_prompt);
-            for name in list_prompt_names()? {
+            for (mode, name) in &cfg.mode_prompts {
+                println!("Mode {}: {}", mode, name);
+            }
+            for doc in list_prompts()? {
+                let name = doc.name();
                 let text = if name == cfg.active_prompt {
                     current_prompt_text(&cfg)
                 } else {
                     String::new()
                 };
-                let preview = if text.is_empty() {
-                    "(stored in prompts folder)".to_string()
-                } else {
-                    truncate_preview(&text, 90)
+                let preview = match (text.is_empty(), doc.source()) {
+                    (false, _) => truncate_preview(&text, 90),
+                    (true, Some(source)) => format!("(fetched from {})", source.url),
+                    (true, None) => "(stored in prompts folder)".to_string(),
                 };
                 println!("- {}: {}", name, preview);
             }
@@ -48,6 +60,21 @@ pub fn handle_prompt(command: PromptCommand) -> Result<()> {
             save_config(&cfg)?;
             println!("Active prompt updated.");
         }
+        PromptCommand::Bind { mode, name } => {
+            let mode = validate_prompt_mode(&mode)?;
+            if !list_prompt_names()?.iter().any(|p| p == &name) {
+                bail!("Prompt not found: {name}");
+            }
+            cfg.mode_prompts.insert(mode.clone(), name.clone());
+            save_config(&cfg)?;
+            println!("Mode `{mode}` now uses prompt `{name}`.");
+        }
+        PromptCommand::Unbind { mode } => {
+            let mode = validate_prompt_mode(&mode)?;
+            cfg.mode_prompts.remove(&mode);
+            save_config(&cfg)?;
+            println!("Mode `{mode}` uses its default instructions.");
+        }
         PromptCommand::Show => {
             let text = current_prompt_text(&cfg);
             println!("Active prompt: {}", cfg.active_prompt);
@@ -63,6 +90,52 @@ pub fn handle_prompt(command: PromptCommand) -> Result<()> {
             save_config(&cfg)?;
             println!("Prompt variable removed.");
         }
+        PromptCommand::Fetch { source, name, yes } => {
+            let prompts = tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(fetch_prompts(&source, name.as_deref()))
+            })?;
+            for p in &prompts {
+                let state = match get_prompt(&p.name)? {
+                    Some(existing) if existing == p.content => "unchanged",
+                    Some(_) => "replaces existing",
+                    None => "new",
+                };
+                println!(
+                    "== {} ({}, {} lines) from {}",
+                    p.name,
+                    state,
+                    p.content.lines().count(),
+                    p.url
+                );
+                for line in p.content.lines().take(FETCH_PREVIEW_LINES) {
+                    println!("  | {}", line);
+                }
+                if p.content.lines().count() > FETCH_PREVIEW_LINES {
+                    println!("  | ...");
+                }
+            }
+            if !yes {
+                let answer = ask(&format!("Save {} prompt(s)? [y/N]: ", prompts.len()))?;
+                if !answer.trim().eq_ignore_ascii_case("y") {
+                    println!("Nothing saved.");
+                    return Ok(());
+                }
+            }
+            let fetched_unix = now_unix_ts();
+            for p in &prompts {
+                save_prompt_with_source(
+                    &p.name,
+                    &p.content,
+                    Some(PromptSource {
+                        url: p.url.clone(),
+                        fetched_unix,
+                    }),
+                )?;
+            }
+            let names: Vec<&str> = prompts.iter().map(|p| p.name.as_str()).collect();
+            println!("Saved: {}", names.join(", "));
+            println!("Activate with `dongshan prompt use {}`.", names[0]);
+        }
         PromptCommand::VarList => {
             if cfg.prompt_vars.is_empty() {
                 println!("No prompt variables.");
@@ -75,3 +148,15 @@ pub fn handle_prompt(command: PromptCommand) -> Result<()> {
     }
     Ok(())
 }
+
+fn validate_prompt_mode(mode: &str) -> Result<String> {
+    let mode = mode.trim().to_ascii_lowercase();
+    if !PROMPT_MODES.contains(&mode.as_str()) {
+        bail!(
+            "Unknown mode: {}. Available: {}",
+            mode,
+            PROMPT_MODES.join(", ")
+        );
+    }
+    Ok(mode)
+}
@@ -0,0 +1,209 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use super::fix_cmd::{print_diff, stage_patches};
+use crate::backups::{rollback_checkpoint, save_checkpoint};
+use crate::chat::resolve_native_path;
+use crate::config::{Config, build_system_prompt};
+use crate::fs_tools::{display_path, workspace_files};
+use crate::llm::call_llm;
+use crate::tool_calls::ApplyPatchArgs;
+use crate::transcript::{record_file_change, utc_date, utc_time};
+use crate::util::{ask, color_bold, color_dim, color_green, tagged_prompt};
+
+const MAX_REFACTOR_FILES: usize = 40;
+/// Combined size of the scoped files sent in the planning request.
+const MAX_REFACTOR_CHARS: usize = 200_000;
+
+#[derive(Deserialize)]
+struct RefactorPlan {
+    #[serde(default)]
+    plan: Vec<String>,
+    #[serde(default)]
+    patches: Vec<ApplyPatchArgs>,
+}
+
+fn scoped_files(scope: &str) -> Result<Vec<(PathBuf, String)>> {
+    let files = workspace_files(Path::new("."), Some(scope))?;
+    if files.is_empty() {
+        bail!("No files match scope {}", scope);
+    }
+    if files.len() > MAX_REFACTOR_FILES {
+        bail!(
+            "Scope {} matches {} files (limit {}); narrow it",
+            scope,
+            files.len(),
+            MAX_REFACTOR_FILES
+        );
+    }
+    let mut out = Vec::new();
+    let mut total = 0;
+    for file in files {
+        let path = resolve_native_path(&file.to_string_lossy())?;
+        // Binary files cannot be patched; they are left out of the request.
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        total += text.chars().count();
+        if total > MAX_REFACTOR_CHARS {
+            bail!(
+                "Scope {} is larger than {} chars; narrow it",
+                scope,
+                MAX_REFACTOR_CHARS
+            );
+        }
+        out.push((path, text));
+    }
+    Ok(out)
+}
+
+fn build_refactor_prompt(scope: &str, instruction: &str, files: &[(PathBuf, String)]) -> String {
+    let mut prompt = format!(
+        "Refactor the files in scope `{}` according to the instruction.\n\
+         Keep behavior unchanged unless the instruction says otherwise, and update every call site \
+         within the scope. Only the files below may be edited.\n\
+         Reply with ONLY a JSON object, no markdown and no explanation:\n\
+         {{\"plan\": [\"one line per step\"], \"patches\": [{{\"path\": \"src/a.rs\", \"edits\": [{{\"old\": \"exact text to replace\", \"new\": \"replacement\"}}]}}]}}\n\
+         Each `old` must match the current file exactly, including whitespace, and be unique in it.\n\n\
+         Instruction:\n{}",
+        scope, instruction
+    );
+    for (path, text) in files {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+        prompt.push_str(&format!(
+            "\n\nFile: {}\n```{}\n{}\n```",
+            display_path(path),
+            ext,
+            text
+        ));
+    }
+    prompt
+}
+
+fn parse_refactor_reply(reply: &str) -> Result<RefactorPlan> {
+    let (Some(start), Some(end)) = (reply.find('{'), reply.rfind('}')) else {
+        bail!("Model reply has no JSON refactor plan");
+    };
+    if end < start {
+        bail!("Model reply has no JSON refactor plan");
+    }
+    serde_json::from_str(&reply[start..=end]).context("Model reply is not a valid refactor plan")
+}
+
+/// Writes every staged file; when one write fails the files already written
+/// are put back, so the tree is never left half-refactored.
+fn write_all(staged: &[(PathBuf, String, String)]) -> Result<()> {
+    for (i, (path, _, updated)) in staged.iter().enumerate() {
+        if let Err(err) = fs::write(path, updated) {
+            for (done, original, _) in &staged[..i] {
+                let _ = fs::write(done, original);
+            }
+            return Err(err).with_context(|| {
+                format!(
+                    "Failed to write {}; earlier files were restored",
+                    path.display()
+                )
+            });
+        }
+    }
+    for (path, original, updated) in staged {
+        record_file_change("refactor", path, original, updated);
+    }
+    Ok(())
+}
+
+/// Plans a refactor of the files matching `scope`, previews the per-file
+/// diffs and applies them together behind one rollback checkpoint.
+pub async fn run_refactor(cfg: &Config, scope: &str, instruction: &str, yes: bool) -> Result<()> {
+    let files = scoped_files(scope)?;
+    println!(
+        "{}",
+        color_dim(&format!("refactor> {} file(s) in {}", files.len(), scope))
+    );
+    let reply = call_llm(
+        cfg,
+        &build_system_prompt(cfg, "edit"),
+        &build_refactor_prompt(scope, instruction, &files),
+    )
+    .await?;
+    let plan = parse_refactor_reply(&reply)?;
+    for patch in &plan.patches {
+        let path = resolve_native_path(&patch.path)?;
+        if !files.iter().any(|(f, _)| *f == path) {
+            bail!(
+                "The plan edits {}, which is outside scope {}",
+                patch.path,
+                scope
+            );
+        }
+    }
+    let staged: Vec<(PathBuf, String, String)> = stage_patches(&plan.patches)?
+        .into_iter()
+        .map(|(path, (original, updated))| (path, original, updated))
+        .collect();
+
+    if !plan.plan.is_empty() {
+        println!("{}", color_bold("Plan:"));
+        for (i, step) in plan.plan.iter().enumerate() {
+            println!("  {}. {}", i + 1, step);
+        }
+    }
+    if staged.is_empty() {
+        println!("The model proposed no changes.");
+        return Ok(());
+    }
+    for (path, original, updated) in &staged {
+        print_diff(path, original, updated);
+    }
+    if !yes {
+        let answer = ask(&tagged_prompt(
+            "refactor",
+            &format!("Apply the refactor to {} file(s)? [y/N] ", staged.len()),
+        ))?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Refactor cancelled; no files were changed.");
+            return Ok(());
+        }
+    }
+
+    let originals: Vec<(PathBuf, String)> = staged
+        .iter()
+        .map(|(path, original, _)| (path.clone(), original.clone()))
+        .collect();
+    save_checkpoint("refactor", instruction, &originals)?;
+    write_all(&staged)?;
+    println!(
+        "{}",
+        color_green(&format!(
+            "Refactored {} file(s). Undo with `dongshan refactor --rollback`.",
+            staged.len()
+        ))
+    );
+    for (path, _, _) in &staged {
+        println!("- {}", display_path(path));
+    }
+    Ok(())
+}
+
+/// Reverts the newest checkpoint saved by a refactor.
+pub fn run_refactor_rollback() -> Result<()> {
+    let Some(checkpoint) = rollback_checkpoint("refactor")? else {
+        println!("No refactor to roll back in this workspace.");
+        return Ok(());
+    };
+    println!(
+        "{} {} file(s) from {} {} ({})",
+        color_green("Rolled back"),
+        checkpoint.files.len(),
+        utc_date(checkpoint.ts),
+        utc_time(checkpoint.ts),
+        checkpoint.label
+    );
+    for rel in &checkpoint.files {
+        println!("- {}", rel.display());
+    }
+    Ok(())
+}
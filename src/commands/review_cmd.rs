@@ -1,31 +1,155 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 
 use crate::config::{Config, build_system_prompt};
-use crate::fs_tools::read_text_file;
-use crate::llm::call_llm;
+use crate::fs_tools::{is_stdin_path, read_image_data_url, read_text_source};
+use crate::llm::{ChatMessage, call_llm_with_history};
+use crate::util::{color_dim, markdown_enabled, render_markdown_terminal, truncate_with_suffix};
 
-pub async fn run_review(cfg: &Config, file: &Path, extra_prompt: Option<String>) -> Result<()> {
-    let code = read_text_file(file)?;
+/// Rough chars-per-token ratio used to turn the token budget into text length.
+const CHARS_PER_TOKEN: usize = 4;
+
+struct ReviewTarget {
+    label: String,
+    ext: String,
+    code: String,
+}
+
+pub async fn run_review(
+    cfg: &Config,
+    files: &[PathBuf],
+    extra_prompt: Option<String>,
+    images: &[PathBuf],
+    max_tokens: usize,
+) -> Result<()> {
+    if files.iter().filter(|f| is_stdin_path(f)).count() > 1 {
+        bail!("stdin (`-`) can only be reviewed once");
+    }
+    let mut targets = files
+        .iter()
+        .map(|f| read_target(f))
+        .collect::<Result<Vec<_>>>()?;
+    fit_to_budget(&mut targets, max_tokens.saturating_mul(CHARS_PER_TOKEN));
+
+    let images = images
+        .iter()
+        .map(|p| read_image_data_url(p))
+        .collect::<Result<Vec<_>>>()?;
+    let system_prompt = build_system_prompt(cfg, "review");
+
+    if let [target] = targets.as_slice() {
+        let answer = review_one(
+            cfg,
+            &system_prompt,
+            target,
+            extra_prompt.as_deref(),
+            &images,
+        )
+        .await?;
+        println!("{}", render_markdown_terminal(&answer, markdown_enabled()));
+        return Ok(());
+    }
+
+    let total = targets.len();
+    let mut report = format!("# Review report ({} files)\n", total);
+    let mut failed = 0;
+    for (i, target) in targets.iter().enumerate() {
+        eprintln!(
+            "{}",
+            color_dim(&format!("[{}/{}] reviewing {}", i + 1, total, target.label))
+        );
+        let result = review_one(
+            cfg,
+            &system_prompt,
+            target,
+            extra_prompt.as_deref(),
+            &images,
+        )
+        .await;
+        report.push_str(&format!("\n## {}\n\n", target.label));
+        match result {
+            Ok(answer) => report.push_str(answer.trim()),
+            Err(err) => {
+                failed += 1;
+                report.push_str(&format!("Review failed: {:#}", err));
+            }
+        }
+        report.push('\n');
+    }
+    println!("{}", render_markdown_terminal(&report, markdown_enabled()));
+    if failed == total {
+        bail!("All {} file reviews failed", total);
+    }
+    Ok(())
+}
+
+fn read_target(file: &Path) -> Result<ReviewTarget> {
+    let code = read_text_source(file)?;
     let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("txt");
+    let label = if is_stdin_path(file) {
+        "stdin".to_string()
+    } else {
+        file.display().to_string()
+    };
+    Ok(ReviewTarget {
+        label,
+        ext: ext.to_string(),
+        code,
+    })
+}
+
+/// Shares the character budget across files: small files keep their full
+/// text and whatever they leave unused goes to the larger ones.
+fn fit_to_budget(targets: &mut [ReviewTarget], max_chars: usize) {
+    if max_chars == 0 {
+        return;
+    }
+    let mut order: Vec<usize> = (0..targets.len()).collect();
+    order.sort_by_key(|&i| targets[i].code.chars().count());
+    let mut remaining = max_chars;
+    for (n, &i) in order.iter().enumerate() {
+        let share = remaining / (order.len() - n);
+        let len = targets[i].code.chars().count();
+        if len > share {
+            targets[i].code = truncate_with_suffix(
+                &targets[i].code,
+                share,
+                "\n... [truncated to fit the review token budget]",
+            );
+            remaining -= share;
+        } else {
+            remaining -= len;
+        }
+    }
+}
 
+async fn review_one(
+    cfg: &Config,
+    system_prompt: &str,
+    target: &ReviewTarget,
+    extra_prompt: Option<&str>,
+    images: &[String],
+) -> Result<String> {
     let mut user_prompt = format!(
         "Please review this code. Focus on correctness, bugs, risks, and missing tests.\n\
          Provide concise findings with severity and actionable suggestions.\n\n\
          File: {}\n```{}\n{}\n```",
-        file.display(),
-        ext,
-        code
+        target.label, target.ext, target.code
     );
 
     if let Some(p) = extra_prompt {
         user_prompt.push_str("\n\nExtra requirement:\n");
-        user_prompt.push_str(&p);
+        user_prompt.push_str(p);
     }
 
-    let answer = call_llm(cfg, &build_system_prompt(cfg, "review"), &user_prompt).await?;
-
-    println!("{answer}");
-    Ok(())
+    if !images.is_empty() {
+        user_prompt.push_str("\n\nAttached images show the related UI or error output.");
+    }
+    let history = [ChatMessage {
+        role: "user".to_string(),
+        content: user_prompt,
+        images: images.to_vec(),
+    }];
+    call_llm_with_history(cfg, system_prompt, &history).await
 }
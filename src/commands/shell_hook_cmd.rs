@@ -0,0 +1,117 @@
+use crate::cli::HookShell;
+
+const BASH_HOOK: &str = r#"# dongshan shell hook (bash). Add to ~/.bashrc:
+#   eval "$(dongshan shell-hook bash)"
+# After a command fails, run `dsfix` or press Ctrl-X Ctrl-F to ask dongshan why.
+__dongshan_failed=""
+__dongshan_failed_status=0
+__dongshan_prompt_hook() {
+  local status=$?
+  if [ "$status" -ne 0 ]; then
+    __dongshan_failed=$(HISTTIMEFORMAT= history 1 | sed -e 's/^ *[0-9]* *//')
+    __dongshan_failed_status=$status
+  else
+    __dongshan_failed=""
+  fi
+  return $status
+}
+case ";$PROMPT_COMMAND;" in
+  *";__dongshan_prompt_hook;"*) ;;
+  *) PROMPT_COMMAND="__dongshan_prompt_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+dsfix() {
+  if [ -z "$__dongshan_failed" ]; then
+    echo "dsfix: no failed command recorded" >&2
+    return 1
+  fi
+  local cmd="$__dongshan_failed" reply
+  local question="This shell command failed with exit code $__dongshan_failed_status. Diagnose the cause and suggest a fix.
+\$ $cmd"
+  read -r -p "Re-run \`$cmd\` to capture its output? [y/N] " reply
+  case "$reply" in
+    [yY]*) eval "$cmd" 2>&1 | tail -n 200 | dongshan ask "$question" ;;
+    *) dongshan ask "$question" </dev/null ;;
+  esac
+}
+bind -x '"\C-x\C-f": dsfix' 2>/dev/null
+"#;
+
+const ZSH_HOOK: &str = r#"# dongshan shell hook (zsh). Add to ~/.zshrc:
+#   eval "$(dongshan shell-hook zsh)"
+# After a command fails, run `dsfix` or press Ctrl-X Ctrl-F to ask dongshan why.
+typeset -g __dongshan_failed=""
+typeset -g __dongshan_failed_status=0
+__dongshan_precmd() {
+  local status_code=$?
+  if (( status_code != 0 )); then
+    __dongshan_failed=$(fc -ln -1)
+    __dongshan_failed_status=$status_code
+  else
+    __dongshan_failed=""
+  fi
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd __dongshan_precmd
+dsfix() {
+  if [[ -z "$__dongshan_failed" ]]; then
+    echo "dsfix: no failed command recorded" >&2
+    return 1
+  fi
+  local cmd="$__dongshan_failed" reply
+  local question="This shell command failed with exit code $__dongshan_failed_status. Diagnose the cause and suggest a fix.
+\$ $cmd"
+  read -r "reply?Re-run \`$cmd\` to capture its output? [y/N] "
+  case "$reply" in
+    [yY]*) eval "$cmd" 2>&1 | tail -n 200 | dongshan ask "$question" ;;
+    *) dongshan ask "$question" </dev/null ;;
+  esac
+}
+__dongshan_fix_widget() {
+  zle -I
+  dsfix </dev/tty
+  zle reset-prompt
+}
+zle -N __dongshan_fix_widget
+bindkey '^X^F' __dongshan_fix_widget
+"#;
+
+const PWSH_HOOK: &str = r#"# dongshan shell hook (PowerShell). Add to $PROFILE:
+#   dongshan shell-hook pwsh | Out-String | Invoke-Expression
+# After a command fails, run `dsfix` or press Ctrl+X Ctrl+F to ask dongshan why.
+function global:dsfix {
+    $last = Get-History -Count 1
+    if (-not $last) {
+        Write-Warning "dsfix: no command in history"
+        return
+    }
+    $cmd = $last.CommandLine
+    $question = "This shell command failed with exit code $global:LASTEXITCODE. Diagnose the cause and suggest a fix.`n`$ $cmd"
+    if ($Error.Count -gt 0) {
+        $question += "`nLast PowerShell error: $($Error[0])"
+    }
+    $reply = Read-Host "Re-run ``$cmd`` to capture its output? [y/N]"
+    if ($reply -match '^[yY]') {
+        $output = Invoke-Expression $cmd 2>&1 | Out-String
+        (($output -split "`n") | Select-Object -Last 200) -join "`n" | dongshan ask $question
+    } else {
+        dongshan ask $question
+    }
+}
+if (Get-Module PSReadLine) {
+    Set-PSReadLineKeyHandler -Chord 'Ctrl+x,Ctrl+f' -ScriptBlock {
+        [Microsoft.PowerShell.PSConsoleReadLine]::RevertLine()
+        [Microsoft.PowerShell.PSConsoleReadLine]::Insert('dsfix')
+        [Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine()
+    }
+}
+"#;
+
+/// Prints the integration snippet for `shell`; users `eval` it from their rc file.
+pub fn run_shell_hook(shell: HookShell) {
+    let snippet = match shell {
+        HookShell::Bash => BASH_HOOK,
+        HookShell::Zsh => ZSH_HOOK,
+        HookShell::Pwsh => PWSH_HOOK,
+    };
+    print!("{}", snippet);
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::usage::{UsageRecord, load_usage_records, parse_since};
+use crate::util::{color_bold, color_dim};
+
+const TOP_PROMPTS: usize = 5;
+
+#[derive(Debug, Default, Clone, Serialize)]
+struct Totals {
+    requests: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cached_tokens: u64,
+    estimated_requests: u64,
+    cost_usd: f64,
+}
+
+impl Totals {
+    fn add(&mut self, r: &UsageRecord) {
+        self.requests += 1;
+        self.prompt_tokens += r.prompt_tokens;
+        self.completion_tokens += r.completion_tokens;
+        self.cached_tokens += r.cached_tokens;
+        if r.estimated {
+            self.estimated_requests += 1;
+        }
+        self.cost_usd += r.cost_usd;
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    since: String,
+    total: Totals,
+    by_model: BTreeMap<String, Totals>,
+    by_command: BTreeMap<String, Totals>,
+    top_prompts: Vec<(String, u64)>,
+}
+
+pub fn run_stats(since: &str, json: bool) -> Result<()> {
+    let since_ts = parse_since(since)?;
+    let records = load_usage_records(since_ts)?;
+    let summary = summarize(since, &records);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+    if records.is_empty() {
+        println!("No usage recorded in the last {}.", since);
+        return Ok(());
+    }
+
+    println!("{}", color_bold(&format!("Usage (since {})", since)));
+    print_row("total", &summary.total);
+    println!();
+    println!("{}", color_bold("By model"));
+    for (model, totals) in &summary.by_model {
+        print_row(model, totals);
+    }
+    println!();
+    println!("{}", color_bold("By command"));
+    for (command, totals) in &summary.by_command {
+        print_row(command, totals);
+    }
+    println!();
+    println!("{}", color_bold("Most-used prompts"));
+    for (prompt, count) in &summary.top_prompts {
+        println!("  {:<28} {} requests", prompt, count);
+    }
+    if summary.total.estimated_requests > 0 {
+        println!();
+        println!(
+            "{}",
+            color_dim(&format!(
+                "{} requests had no provider usage; their tokens are estimated from text length.",
+                summary.total.estimated_requests
+            ))
+        );
+    }
+    println!(
+        "{}",
+        color_dim("Costs are estimates; set `model_prices` in config.toml to override.")
+    );
+    Ok(())
+}
+
+fn summarize(since: &str, records: &[UsageRecord]) -> Summary {
+    let mut total = Totals::default();
+    let mut by_model: BTreeMap<String, Totals> = BTreeMap::new();
+    let mut by_command: BTreeMap<String, Totals> = BTreeMap::new();
+    let mut prompts: BTreeMap<String, u64> = BTreeMap::new();
+    for r in records {
+        total.add(r);
+        by_model.entry(r.model.clone()).or_default().add(r);
+        let command = if r.command.is_empty() {
+            "unknown"
+        } else {
+            r.command.as_str()
+        };
+        by_command.entry(command.to_string()).or_default().add(r);
+        *prompts.entry(r.prompt.clone()).or_default() += 1;
+    }
+    let mut top_prompts: Vec<(String, u64)> = prompts.into_iter().collect();
+    top_prompts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_prompts.truncate(TOP_PROMPTS);
+    Summary {
+        since: since.to_string(),
+        total,
+        by_model,
+        by_command,
+        top_prompts,
+    }
+}
+
+fn print_row(label: &str, t: &Totals) {
+    println!(
+        "  {:<28} {:>6} req  {:>10} in  {:>9} out  {:>9} cached  ${:.4}",
+        label, t.requests, t.prompt_tokens, t.completion_tokens, t.cached_tokens, t.cost_usd
+    );
+}
@@ -0,0 +1,158 @@
+use std::fs;
+
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+use crate::cli::StorageCommand;
+use crate::config::{
+    SessionEncryption, StorageBackend, config_dir, load_config_or_default, save_config,
+};
+use crate::diagnostics::now_unix_ts;
+use crate::session_crypt::session_encryption;
+use crate::session_meta::{sessions_with_meta, update_session_meta};
+use crate::sqlite_store::{SqliteStorage, database_path};
+use crate::storage::{
+    JsonStorage, Storage, backend_storage, prune_storage, storage, storage_backend,
+};
+use crate::transcript::list_transcripts;
+use crate::usage::parse_since;
+use crate::util::{color_cyan, color_dim};
+
+pub fn handle_storage(command: StorageCommand) -> Result<()> {
+    match command {
+        StorageCommand::Info => {
+            let cfg = load_config_or_default()?;
+            let location = match storage_backend() {
+                StorageBackend::Json => config_dir()?,
+                StorageBackend::Sqlite => database_path()?,
+            };
+            println!(
+                "backend:   {:?} ({})",
+                storage_backend(),
+                location.display()
+            );
+            println!("sessions:  {}", storage().list_sessions()?.len());
+            println!("usage:     {} record(s)", storage().load_usage(None)?.len());
+            if storage_backend() == StorageBackend::Sqlite {
+                println!("events:    {}", SqliteStorage.count("events")?);
+            }
+            match cfg.storage_retention_days {
+                0 => println!("retention: keep everything"),
+                days => println!("retention: {} day(s)", days),
+            }
+            match session_encryption() {
+                SessionEncryption::Off => println!("encryption: off"),
+                mode if storage_backend() == StorageBackend::Sqlite => println!(
+                    "encryption: {:?} (JSON session files only; the database is not encrypted)",
+                    mode
+                ),
+                mode => println!("encryption: {:?}", mode),
+            }
+        }
+        StorageCommand::Migrate => migrate()?,
+        StorageCommand::Search { query, limit } => {
+            let hits = storage().search_messages(&query, limit)?;
+            if hits.is_empty() {
+                println!("No messages match {:?}.", query);
+            }
+            for hit in hits {
+                println!(
+                    "{} {} {}",
+                    color_cyan(&format!("{}#{}", hit.session, hit.index)),
+                    color_dim(&hit.role),
+                    hit.snippet
+                );
+            }
+        }
+        StorageCommand::Prune { older_than } => {
+            let cutoff = match older_than {
+                // `all` has no age limit, so everything is removed.
+                Some(raw) => parse_since(&raw)?.unwrap_or(u64::MAX),
+                None => match load_config_or_default()?.storage_retention_days {
+                    0 => bail!(
+                        "Pass --older-than or set a retention with `dongshan config set --storage-retention-days <n>`"
+                    ),
+                    days => now_unix_ts().saturating_sub(days * 86_400),
+                },
+            };
+            let report = prune_storage(cutoff)?;
+            println!(
+                "Removed {} session(s), {} usage record(s), {} audit event(s)",
+                report.sessions.len(),
+                report.usage,
+                report.events
+            );
+        }
+        StorageCommand::Reencrypt => {
+            // Always the JSON files: with SQLite active they are the leftover copies.
+            let json = backend_storage(StorageBackend::Json);
+            let sessions = json.list_sessions()?;
+            for name in &sessions {
+                let messages = json.load_session(name)?.unwrap_or_default();
+                json.save_session(name, &messages)?;
+            }
+            // Metadata files exist for sessions of either backend.
+            for name in sessions_with_meta()? {
+                update_session_meta(&name, |_| {})?;
+            }
+            let state = match session_encryption() {
+                SessionEncryption::Off => "plain JSON",
+                SessionEncryption::Passphrase => "encrypted with the passphrase key",
+                SessionEncryption::Keyring => "encrypted with the OS keyring key",
+            };
+            println!("Re-saved {} session(s), {}.", sessions.len(), state);
+        }
+    }
+    Ok(())
+}
+
+/// Copies everything the JSON backend holds into SQLite and makes SQLite the
+/// active backend. Sessions are replaced; usage and events are only copied
+/// into empty tables, so running it twice does not double-count.
+fn migrate() -> Result<()> {
+    let json = backend_storage(StorageBackend::Json);
+    let sqlite = SqliteStorage;
+    let sessions = json.list_sessions()?;
+    for name in &sessions {
+        let messages = json.load_session(name)?.unwrap_or_default();
+        let updated_at = JsonStorage::session_mtime(name).unwrap_or_else(now_unix_ts);
+        sqlite.import_session(name, &messages, updated_at)?;
+    }
+    println!("sessions: {} copied", sessions.len());
+
+    if sqlite.count("usage")? > 0 {
+        println!("usage:    skipped (the database already has usage records)");
+    } else {
+        let records = json.load_usage(None)?;
+        for record in &records {
+            sqlite.append_usage(record)?;
+        }
+        println!("usage:    {} record(s) copied", records.len());
+    }
+
+    if sqlite.count("events")? > 0 {
+        println!("events:   skipped (the database already has audit events)");
+    } else {
+        let mut events = 0;
+        for path in list_transcripts()? {
+            let text = fs::read_to_string(&path).unwrap_or_default();
+            for entry in text
+                .lines()
+                .filter_map(|l| serde_json::from_str::<Value>(l).ok())
+            {
+                sqlite.record_event(&entry)?;
+                events += 1;
+            }
+        }
+        println!("events:   {} copied", events);
+    }
+
+    let mut cfg = load_config_or_default()?;
+    cfg.storage = StorageBackend::Sqlite;
+    save_config(&cfg)?;
+    println!(
+        "Now using {}. The JSON files were left in place; switch back with `dongshan config set --storage json`.",
+        database_path()?.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::cli::TrustCommand;
+use crate::transcript::{utc_date, utc_time};
+use crate::trust::{list_workspace_trust, remove_workspace_trust, set_workspace_trust};
+use crate::util::{color_dim, color_green, color_red};
+
+pub fn handle_trust(command: TrustCommand) -> Result<()> {
+    match command {
+        TrustCommand::Add { path } => {
+            let path = resolve(path)?;
+            set_workspace_trust(&path, true)?;
+            println!("{} {}", color_green("Trusted"), path.display());
+        }
+        TrustCommand::Deny { path } => {
+            let path = resolve(path)?;
+            set_workspace_trust(&path, false)?;
+            println!(
+                "{} {} (chat-only mode)",
+                color_red("Untrusted"),
+                path.display()
+            );
+        }
+        TrustCommand::Remove { path } => {
+            let path = resolve(path)?;
+            if remove_workspace_trust(&path)? {
+                println!(
+                    "Forgot the decision for {}; you will be asked again.",
+                    path.display()
+                );
+            } else {
+                println!("No decision recorded for {}.", path.display());
+            }
+        }
+        TrustCommand::List => {
+            let entries = list_workspace_trust()?;
+            if entries.is_empty() {
+                println!("No workspace decisions recorded.");
+            }
+            for (path, entry) in entries {
+                let state = if entry.trusted {
+                    color_green("trusted  ")
+                } else {
+                    color_red("untrusted")
+                };
+                println!(
+                    "{}  {}  {}",
+                    state,
+                    path,
+                    color_dim(&format!("{} {}", utc_date(entry.ts), utc_time(entry.ts)))
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve(path: Option<PathBuf>) -> Result<PathBuf> {
+    match path {
+        Some(p) => Ok(p),
+        None => std::env::current_dir().context("Failed to resolve current directory"),
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+
+use crate::config::{UpdateChannel, load_config_or_default, save_config};
+use crate::updater::{
+    current_version, fetch_latest_release, install_release, is_remote_newer, print_update_available,
+};
+
+pub async fn run_update(channel: Option<UpdateChannel>, check_only: bool) -> Result<()> {
+    let mut cfg = load_config_or_default()?;
+    if let Some(v) = channel
+        && v != cfg.update_channel
+    {
+        cfg.update_channel = v;
+        save_config(&cfg)?;
+        println!("Update channel switched to {}", v.as_str());
+    }
+
+    let current = current_version();
+    println!(
+        "Checking {} channel (current {})...",
+        cfg.update_channel.as_str(),
+        current
+    );
+    let latest = fetch_latest_release(cfg.update_channel).await?;
+    if !is_remote_newer(current, &latest.version) {
+        println!("dongshan is up to date ({}).", current);
+        return Ok(());
+    }
+
+    print_update_available(current, &latest, cfg.update_channel);
+    if check_only {
+        return Ok(());
+    }
+    install_release(&latest)
+}
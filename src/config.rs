@@ -1,13 +1,20 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, bail};
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
-use crate::prompt_store::{ensure_default_prompt, get_prompt_or_default};
+use crate::atomic_file::{with_file_lock, write_atomic, write_locked};
+use crate::highlight::DEFAULT_COLOR_THEME;
+use crate::memory::memory_prompt_block;
+use crate::prompt_store::{ensure_default_prompt, get_prompt, get_prompt_or_default};
+use crate::shell::exec_cwd;
+use crate::tls::TlsConfig;
+use crate::usage::ModelPrice;
+use crate::util::truncate_with_suffix;
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 pub enum ProviderPreset {
@@ -49,6 +56,75 @@ pub enum AutoExecMode {
     Custom,
 }
 
+/// Named bundle of the command, file-write and network policies.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApprovalLevel {
+    /// Use auto_exec_mode, auto_confirm_exec and the other fields as set
+    #[default]
+    Custom,
+    /// Read-only commands only; no file writes, python or network commands
+    ReadOnly,
+    /// File edits apply directly; untrusted and network commands ask first
+    AutoEdit,
+    /// Everything runs without asking (the deny list and write jail still apply)
+    FullAuto,
+}
+
+impl ApprovalLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApprovalLevel::Custom => "custom",
+            ApprovalLevel::ReadOnly => "read-only",
+            ApprovalLevel::AutoEdit => "auto-edit",
+            ApprovalLevel::FullAuto => "full-auto",
+        }
+    }
+}
+
+/// Where sessions, usage records and audit events are kept.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// JSON files under ~/.dongshan (sessions/, usage.jsonl, logs/)
+    #[default]
+    Json,
+    /// One SQLite database, ~/.dongshan/dongshan.db
+    Sqlite,
+}
+
+/// How session files are encrypted at rest.
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionEncryption {
+    /// Plain JSON
+    #[default]
+    Off,
+    /// Key derived from a passphrase (`DONGSHAN_SESSION_PASSPHRASE` or prompted)
+    Passphrase,
+    /// Random key kept in the OS keychain / credential store
+    Keyring,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl UpdateChannel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+            UpdateChannel::Nightly => "nightly",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelProfile {
     #[serde(default = "default_model_provider")]
@@ -59,6 +135,38 @@ pub struct ModelProfile {
     pub api_key_env: String,
     #[serde(default)]
     pub api_key: Option<String>,
+    /// Models tried in order when this one fails with a timeout, 429 or 5xx.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<String>,
+    /// Context window in tokens; history and injected context are budgeted
+    /// against it instead of the fixed char limits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+    /// Reasoning model (o1/o3, deepseek-reasoner): requests omit `temperature`
+    /// and use `max_completion_tokens`.
+    #[serde(default)]
+    pub reasoning: bool,
+    /// Sent as `reasoning_effort` (low, medium, high) for reasoning models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
+    /// Print streamed `reasoning_content` dimmed instead of hiding it.
+    #[serde(default)]
+    pub show_reasoning: bool,
+}
+
+/// One stage of a prompt pipeline. It gets the previous stage's output (or the
+/// pipeline input) as its user message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStage {
+    /// Stored prompt used as the system prompt; the active prompt when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Catalog model for this stage; the active model when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Task text placed before the input, e.g. "Critique this summary."
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instruction: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,11 +183,18 @@ pub struct Config {
     #[serde(default = "default_active_prompt")]
     pub active_prompt: String,
     #[serde(default)]
+    pub mode_prompts: BTreeMap<String, String>,
+    /// Named prompt chains for `dongshan pipeline run`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pipelines: BTreeMap<String, Vec<PipelineStage>>,
+    #[serde(default)]
     pub prompt_vars: BTreeMap<String, String>,
     #[serde(default = "default_allow_nsfw")]
     pub allow_nsfw: bool,
     #[serde(default = "default_auto_check_update")]
     pub auto_check_update: bool,
+    #[serde(default)]
+    pub update_channel: UpdateChannel,
     #[serde(default = "default_auto_exec_mode")]
     pub auto_exec_mode: AutoExecMode,
     #[serde(default)]
@@ -90,6 +205,9 @@ pub struct Config {
     pub auto_confirm_exec: bool,
     #[serde(default)]
     pub auto_exec_trusted: Vec<String>,
+    /// Preset that overrides the exec fields above unless `custom`.
+    #[serde(default)]
+    pub approval: ApprovalLevel,
     #[serde(default = "default_history_max_messages")]
     pub history_max_messages: usize,
     #[serde(default = "default_history_max_chars")]
@@ -98,6 +216,131 @@ pub struct Config {
     pub model_catalog: Vec<String>,
     #[serde(default)]
     pub executor_model: Option<String>,
+    #[serde(default)]
+    pub summary_model: Option<String>,
+    #[serde(default)]
+    pub utility_model: Option<String>,
+    #[serde(default = "default_prompt_cache")]
+    pub prompt_cache: bool,
+    #[serde(default)]
+    pub rate_limit_rpm: u32,
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    #[serde(default)]
+    pub transcript_log: bool,
+    #[serde(default)]
+    pub model_prices: BTreeMap<String, ModelPrice>,
+    #[serde(default)]
+    pub cost_budget_usd: f64,
+    #[serde(default = "default_locale")]
+    pub locale: String,
+    /// Shell for agent and `exec` commands; `auto` prefers PowerShell 7 on Windows.
+    #[serde(default = "default_shell")]
+    pub shell: String,
+    /// Extra environment variables for agent and `exec` commands.
+    #[serde(default)]
+    pub exec_env: BTreeMap<String, String>,
+    /// Directory agent and `exec` commands run in, relative to the workspace.
+    #[serde(default)]
+    pub exec_cwd: Option<String>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Sampling seed sent to providers, for reproducible runs.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default = "default_redact_secrets")]
+    pub redact_secrets: bool,
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    /// Flag instruction-like text in tool output and files before it reaches
+    /// the model.
+    #[serde(default = "default_injection_guard")]
+    pub injection_guard: bool,
+    #[serde(default = "default_write_jail")]
+    pub write_jail: bool,
+    #[serde(default)]
+    pub write_jail_roots: Vec<String>,
+    #[serde(default)]
+    pub fetch_allow_domains: Vec<String>,
+    #[serde(default = "default_fetch_max_chars")]
+    pub fetch_max_chars: usize,
+    #[serde(default)]
+    pub http_allow_domains: Vec<String>,
+    #[serde(default = "default_http_max_bytes")]
+    pub http_max_bytes: usize,
+    #[serde(default = "default_http_timeout_secs")]
+    pub http_timeout_secs: u64,
+    #[serde(default)]
+    pub python_bin: Option<String>,
+    #[serde(default = "default_python_timeout_secs")]
+    pub python_timeout_secs: u64,
+    #[serde(default)]
+    pub python_allow_network: bool,
+    #[serde(default)]
+    pub notify_webhook_url: Option<String>,
+    #[serde(default)]
+    pub notify_min_secs: u64,
+    #[serde(default)]
+    pub notifications: bool,
+    #[serde(default = "default_notify_after_secs")]
+    pub notify_after_secs: u64,
+    #[serde(default)]
+    pub log_file: Option<String>,
+    #[serde(default = "default_color_theme")]
+    pub color_theme: String,
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    #[serde(default = "default_repo_map_max_chars")]
+    pub repo_map_max_chars: usize,
+    #[serde(default = "default_auto_context_files")]
+    pub auto_context_files: usize,
+    /// Characters of tool output kept in context before it is elided.
+    #[serde(default = "default_tool_output_max_chars")]
+    pub tool_output_max_chars: usize,
+    /// Per-tool overrides of `tool_output_max_chars`, keyed by tool name
+    /// (`shell`, `fs.read_file`, `fs.grep`, `verification`, ...).
+    #[serde(default)]
+    pub tool_output_limits: BTreeMap<String, usize>,
+    /// Commands the agent's verification phase runs in order, stopping at the
+    /// first failure. Empty means one check detected from the project files.
+    #[serde(default)]
+    pub verify_steps: Vec<String>,
+    /// Commit the agent's changes after each step whose verification passes.
+    #[serde(default)]
+    pub auto_commit: bool,
+    /// Pass `--no-verify` to auto-commits so git hooks are skipped.
+    #[serde(default)]
+    pub auto_commit_no_verify: bool,
+    #[serde(default)]
+    pub storage: StorageBackend,
+    /// Sessions, usage records and audit events older than this many days are
+    /// deleted once a day; 0 keeps everything.
+    #[serde(default)]
+    pub storage_retention_days: u64,
+    /// Encrypts `sessions/*.json` on save; encrypted files are decrypted on
+    /// load whatever this is set to.
+    #[serde(default)]
+    pub session_encryption: SessionEncryption,
+    /// Session-scoped `/system` overrides, loaded from session metadata and
+    /// never written to config.toml.
+    #[serde(skip)]
+    pub system_override: Option<String>,
+    #[serde(skip)]
+    pub system_append: Option<String>,
+    /// Global model and prompt while a session uses its own (`/model use`,
+    /// `/prompt use` in chat); `save_config` writes these instead.
+    #[serde(skip)]
+    pub global_model: Option<String>,
+    #[serde(skip)]
+    pub global_prompt: Option<String>,
+    /// Set for chat turns that ask for a review, so the project review rules
+    /// are added to the system prompt.
+    #[serde(skip)]
+    pub review_turn: bool,
+    /// Prompt picked with `/as <prompt>` for the current chat turn; it stands
+    /// in for `active_prompt` and any `/system` override without touching them.
+    #[serde(skip)]
+    pub turn_prompt: Option<String>,
 }
 
 impl Default for Config {
@@ -112,6 +355,11 @@ impl Default for Config {
                 base_url: base_url.clone(),
                 api_key_env: api_key_env.clone(),
                 api_key: None,
+                fallbacks: Vec::new(),
+                context_window: None,
+                reasoning: false,
+                reasoning_effort: None,
+                show_reasoning: false,
             },
         );
 
@@ -123,18 +371,72 @@ impl Default for Config {
             model_profiles,
             prompts: default_prompts(),
             active_prompt: default_active_prompt(),
+            mode_prompts: BTreeMap::new(),
+            pipelines: BTreeMap::new(),
             prompt_vars: BTreeMap::new(),
             allow_nsfw: true,
             auto_check_update: true,
+            update_channel: UpdateChannel::Stable,
             auto_exec_mode: AutoExecMode::Safe,
             auto_exec_allow: Vec::new(),
             auto_exec_deny: Vec::new(),
             auto_confirm_exec: true,
             auto_exec_trusted: vec!["rg".to_string(), "grep".to_string()],
+            approval: ApprovalLevel::Custom,
             history_max_messages: default_history_max_messages(),
             history_max_chars: default_history_max_chars(),
             model_catalog: vec![model],
             executor_model: None,
+            summary_model: None,
+            utility_model: None,
+            prompt_cache: true,
+            rate_limit_rpm: 0,
+            max_concurrent_requests: default_max_concurrent_requests(),
+            transcript_log: false,
+            model_prices: BTreeMap::new(),
+            cost_budget_usd: 0.0,
+            locale: default_locale(),
+            shell: default_shell(),
+            exec_env: BTreeMap::new(),
+            exec_cwd: None,
+            tls: TlsConfig::default(),
+            seed: None,
+            redact_secrets: true,
+            redact_patterns: Vec::new(),
+            injection_guard: true,
+            write_jail: true,
+            write_jail_roots: Vec::new(),
+            fetch_allow_domains: Vec::new(),
+            fetch_max_chars: default_fetch_max_chars(),
+            http_allow_domains: Vec::new(),
+            http_max_bytes: default_http_max_bytes(),
+            http_timeout_secs: default_http_timeout_secs(),
+            python_bin: None,
+            python_timeout_secs: default_python_timeout_secs(),
+            python_allow_network: false,
+            notify_webhook_url: None,
+            notify_min_secs: 0,
+            notifications: false,
+            notify_after_secs: default_notify_after_secs(),
+            log_file: None,
+            color_theme: default_color_theme(),
+            embedding_model: None,
+            repo_map_max_chars: default_repo_map_max_chars(),
+            auto_context_files: default_auto_context_files(),
+            tool_output_max_chars: default_tool_output_max_chars(),
+            tool_output_limits: BTreeMap::new(),
+            verify_steps: Vec::new(),
+            auto_commit: false,
+            auto_commit_no_verify: false,
+            storage: StorageBackend::Json,
+            storage_retention_days: 0,
+            session_encryption: SessionEncryption::Off,
+            system_override: None,
+            system_append: None,
+            global_model: None,
+            global_prompt: None,
+            review_turn: false,
+            turn_prompt: None,
         }
     }
 }
@@ -159,6 +461,54 @@ fn default_auto_confirm_exec() -> bool {
     true
 }
 
+fn default_locale() -> String {
+    "auto".to_string()
+}
+
+fn default_shell() -> String {
+    "auto".to_string()
+}
+
+fn default_redact_secrets() -> bool {
+    true
+}
+
+fn default_injection_guard() -> bool {
+    true
+}
+
+fn default_write_jail() -> bool {
+    true
+}
+
+fn default_fetch_max_chars() -> usize {
+    12000
+}
+
+fn default_http_max_bytes() -> usize {
+    256 * 1024
+}
+
+fn default_http_timeout_secs() -> u64 {
+    15
+}
+
+fn default_python_timeout_secs() -> u64 {
+    30
+}
+
+fn default_notify_after_secs() -> u64 {
+    30
+}
+
+fn default_prompt_cache() -> bool {
+    true
+}
+
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
 fn default_history_max_messages() -> usize {
     24
 }
@@ -167,6 +517,22 @@ fn default_history_max_chars() -> usize {
     50_000
 }
 
+fn default_repo_map_max_chars() -> usize {
+    6_000
+}
+
+fn default_auto_context_files() -> usize {
+    6
+}
+
+fn default_tool_output_max_chars() -> usize {
+    12000
+}
+
+fn default_color_theme() -> String {
+    DEFAULT_COLOR_THEME.to_string()
+}
+
 pub fn default_prompts() -> BTreeMap<String, String> {
     let mut map = BTreeMap::new();
     map.insert(
@@ -230,6 +596,11 @@ pub fn apply_preset(cfg: &mut Config, provider: ProviderPreset) {
             base_url,
             api_key_env,
             api_key: cfg.api_key.clone(),
+            fallbacks: Vec::new(),
+            context_window: None,
+            reasoning: false,
+            reasoning_effort: None,
+            show_reasoning: false,
         },
     );
     ensure_model_catalog(cfg);
@@ -274,15 +645,23 @@ pub fn config_path() -> Result<PathBuf> {
 pub fn load_config_or_default() -> Result<Config> {
     let path = config_path()?;
     if !path.exists() {
-        let mut cfg = Config::default();
-        ensure_model_catalog(&mut cfg);
-        apply_active_model_profile(&mut cfg);
+        let cfg = default_config();
         save_config(&cfg)?;
         return Ok(cfg);
     }
+    read_config(&path)
+}
 
+fn default_config() -> Config {
+    let mut cfg = Config::default();
+    ensure_model_catalog(&mut cfg);
+    apply_active_model_profile(&mut cfg);
+    cfg
+}
+
+fn read_config(path: &Path) -> Result<Config> {
     let text =
-        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
     let mut cfg: Config =
         toml::from_str(&text).with_context(|| format!("Invalid config: {}", path.display()))?;
     let _ = ensure_default_prompt();
@@ -296,19 +675,37 @@ pub fn load_config_or_default() -> Result<Config> {
 
 pub fn save_config(cfg: &Config) -> Result<()> {
     let path = config_path()?;
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create config dir {}", parent.display()))?;
-    }
+    write_locked(&path, config_toml(cfg)?)
+}
+
+/// Re-reads config.toml under its lock, applies `change` and writes it back,
+/// so a long-running chat or `dongshan web` only changes what it means to and
+/// keeps edits other processes saved since it loaded the config.
+pub fn update_config(change: impl FnOnce(&mut Config)) -> Result<()> {
+    let path = config_path()?;
+    with_file_lock(&path, || {
+        let mut cfg = if path.exists() {
+            read_config(&path)?
+        } else {
+            default_config()
+        };
+        change(&mut cfg);
+        write_atomic(&path, config_toml(&cfg)?)
+    })
+}
 
+fn config_toml(cfg: &Config) -> Result<String> {
     let mut to_save = cfg.clone();
     ensure_model_catalog(&mut to_save);
     update_active_model_profile(&mut to_save);
+    if let Some(model) = &cfg.global_model {
+        to_save.model = model.clone();
+    }
+    if let Some(prompt) = &cfg.global_prompt {
+        to_save.active_prompt = prompt.clone();
+    }
     apply_active_model_profile(&mut to_save);
-
-    let text = toml::to_string_pretty(&to_save)?;
-    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
-    Ok(())
+    Ok(toml::to_string_pretty(&to_save)?)
 }
 
 pub fn ensure_model_catalog(cfg: &mut Config) {
@@ -326,6 +723,11 @@ pub fn ensure_model_catalog(cfg: &mut Config) {
         base_url: cfg.base_url.clone(),
         api_key_env: cfg.api_key_env.clone(),
         api_key: cfg.api_key.clone(),
+        fallbacks: Vec::new(),
+        context_window: None,
+        reasoning: false,
+        reasoning_effort: None,
+        show_reasoning: false,
     };
 
     let mut seen = BTreeSet::new();
@@ -396,6 +798,27 @@ pub fn update_active_model_profile(cfg: &mut Config) {
             base_url: cfg.base_url.clone(),
             api_key_env: cfg.api_key_env.clone(),
             api_key: cfg.api_key.clone(),
+            fallbacks: cfg
+                .model_profiles
+                .get(&cfg.model)
+                .map(|p| p.fallbacks.clone())
+                .unwrap_or_default(),
+            context_window: cfg
+                .model_profiles
+                .get(&cfg.model)
+                .and_then(|p| p.context_window),
+            reasoning: cfg
+                .model_profiles
+                .get(&cfg.model)
+                .is_some_and(|p| p.reasoning),
+            reasoning_effort: cfg
+                .model_profiles
+                .get(&cfg.model)
+                .and_then(|p| p.reasoning_effort.clone()),
+            show_reasoning: cfg
+                .model_profiles
+                .get(&cfg.model)
+                .is_some_and(|p| p.show_reasoning),
         },
     );
 }
@@ -422,13 +845,21 @@ pub fn add_model_with_active_profile(cfg: &mut Config, model: &str) {
     let template = cfg
         .model_profiles
         .get(&cfg.model)
-        .cloned()
+        .map(|p| ModelProfile {
+            fallbacks: Vec::new(),
+            ..p.clone()
+        })
         .unwrap_or(ModelProfile {
             provider: ModelApiProvider::Openai,
             tool_mode: ToolCallMode::Auto,
             base_url: cfg.base_url.clone(),
             api_key_env: cfg.api_key_env.clone(),
             api_key: cfg.api_key.clone(),
+            fallbacks: Vec::new(),
+            context_window: None,
+            reasoning: false,
+            reasoning_effort: None,
+            show_reasoning: false,
         });
     cfg.model_profiles
         .entry(name.to_string())
@@ -473,6 +904,11 @@ pub fn upsert_model_profile(
             base_url: cfg.base_url.clone(),
             api_key_env: cfg.api_key_env.clone(),
             api_key: cfg.api_key.clone(),
+            fallbacks: Vec::new(),
+            context_window: None,
+            reasoning: false,
+            reasoning_effort: None,
+            show_reasoning: false,
         });
 
     if let Some(v) = provider {
@@ -502,6 +938,52 @@ pub fn upsert_model_profile(
     }
 }
 
+/// Copy of `cfg` targeting `model` with that model's profile endpoint and key.
+pub fn config_for_model(cfg: &Config, model: &str) -> Config {
+    let mut out = cfg.clone();
+    out.model = model.to_string();
+    if let Some(profile) = cfg.model_profiles.get(model) {
+        out.base_url = profile.base_url.clone();
+        out.api_key_env = profile.api_key_env.clone();
+        out.api_key = profile.api_key.clone();
+    }
+    out
+}
+
+/// Model for internal bookkeeping calls (summaries, mode routing): `preferred`
+/// when set, else `utility_model`. `None` means the active model.
+pub fn utility_model<'a>(cfg: &'a Config, preferred: Option<&'a str>) -> Option<&'a str> {
+    [preferred, cfg.utility_model.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|m| !m.is_empty())
+}
+
+/// Fallback models configured for the active model, without duplicates or the model itself.
+pub fn model_fallbacks(cfg: &Config) -> Vec<String> {
+    let mut seen = BTreeSet::from([cfg.model.clone()]);
+    cfg.model_profiles
+        .get(&cfg.model)
+        .map(|p| p.fallbacks.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty() && seen.insert(m.clone()))
+        .collect()
+}
+
+pub fn set_model_fallbacks(cfg: &mut Config, model: &str, fallbacks: Vec<String>) {
+    let name = model.trim();
+    if name.is_empty() {
+        return;
+    }
+    add_model_with_active_profile(cfg, name);
+    if let Some(p) = cfg.model_profiles.get_mut(name) {
+        p.fallbacks = fallbacks;
+    }
+}
+
 pub fn resolve_api_key(cfg: &Config) -> Result<String> {
     if let Some(p) = cfg.model_profiles.get(&cfg.model) {
         if let Ok(v) = env::var(&p.api_key_env) {
@@ -550,6 +1032,69 @@ pub fn active_effective_tool_mode(cfg: &Config) -> ToolCallMode {
     effective_tool_mode(cfg, &cfg.model)
 }
 
+/// Context window (in tokens) of `model`: the profile's `context_window` when
+/// set, otherwise a best-effort guess from the model name.
+pub fn model_context_window(cfg: &Config, model: &str) -> u64 {
+    if let Some(window) = cfg
+        .model_profiles
+        .get(model)
+        .and_then(|p| p.context_window)
+        .filter(|w| *w > 0)
+    {
+        return window;
+    }
+    let m = model.to_ascii_lowercase();
+    if m.contains("gemini") || m.contains("gpt-4.1") {
+        1_000_000
+    } else if m.contains("gpt-5") {
+        400_000
+    } else if m.contains("claude") || m.starts_with("o3") || m.starts_with("o4") {
+        200_000
+    } else if m.contains("gpt-3.5") {
+        16_000
+    } else {
+        // gpt-4o, deepseek, qwen, glm, kimi and most hosted models.
+        128_000
+    }
+}
+
+/// Tokens kept free for the reply: a quarter of the window, at most 16k.
+fn response_reserve_tokens(window: u64) -> u64 {
+    (window / 4).min(16_384)
+}
+
+/// Character budget for `percent` of the active model's prompt space (its
+/// configured `context_window` minus the reply reserve), at ~4 chars per token.
+/// `None` when the model has no configured window, so callers keep their
+/// fixed limits.
+pub fn context_budget_chars(cfg: &Config, percent: u64) -> Option<usize> {
+    let window = cfg
+        .model_profiles
+        .get(&cfg.model)?
+        .context_window
+        .filter(|w| *w > 0)?;
+    let prompt_tokens = window - response_reserve_tokens(window);
+    Some((prompt_tokens * percent / 100 * 4) as usize)
+}
+
+/// Chars of chat history kept before older messages are compacted.
+pub fn history_budget_chars(cfg: &Config) -> usize {
+    context_budget_chars(cfg, 60)
+        .unwrap_or(cfg.history_max_chars)
+        .max(2000)
+}
+
+pub fn set_model_context_window(cfg: &mut Config, model: &str, window: Option<u64>) {
+    let name = model.trim();
+    if name.is_empty() {
+        return;
+    }
+    add_model_with_active_profile(cfg, name);
+    if let Some(p) = cfg.model_profiles.get_mut(name) {
+        p.context_window = window;
+    }
+}
+
 pub fn set_model_tool_mode(cfg: &mut Config, model: &str, mode: ToolCallMode) {
     let name = model.trim();
     if name.is_empty() {
@@ -571,14 +1116,52 @@ pub fn render_prompt_vars(input: &str, vars: &BTreeMap<String, String>) -> Strin
 }
 
 pub fn current_prompt_text(cfg: &Config) -> String {
-    let raw = get_prompt_or_default(&cfg.active_prompt)
+    let raw = get_prompt_or_default(prompt_name(cfg))
         .unwrap_or_else(|_| default_prompts()["default"].clone());
     render_prompt_vars(&raw, &cfg.prompt_vars)
 }
 
+/// The prompt in use: the `/as` prompt during such a turn, else `active_prompt`.
+pub fn prompt_name(cfg: &Config) -> &str {
+    cfg.turn_prompt.as_deref().unwrap_or(&cfg.active_prompt)
+}
+
+/// Modes whose instructions can be overridden with `prompts.<mode>` in config
+/// or by binding a prompt-store entry via `dongshan prompt bind <mode> <name>`.
+pub const PROMPT_MODES: &[&str] = &["agent", "chat", "review", "edit"];
+
+/// Maps an internal `build_system_prompt` mode to its user-facing override key.
+fn prompt_mode_key(mode: &str) -> &str {
+    match mode {
+        "chat" => "agent",
+        "chat-lite" => "chat",
+        other => other,
+    }
+}
+
+/// User override for a mode: a bound prompt-store entry wins over inline `prompts.<mode>`.
+fn mode_prompt_override(cfg: &Config, mode: &str) -> Option<String> {
+    let key = prompt_mode_key(mode);
+    let text = cfg
+        .mode_prompts
+        .get(key)
+        .and_then(|name| get_prompt(name).ok().flatten())
+        .or_else(|| cfg.prompts.get(key).cloned())?;
+    if text.trim().is_empty() {
+        return None;
+    }
+    Some(render_prompt_vars(&text, &cfg.prompt_vars))
+}
+
 pub fn build_system_prompt(cfg: &Config, mode: &str) -> String {
-    let mut prompt = current_prompt_text(cfg);
-    if mode == "review" {
+    let mut prompt = match &cfg.system_override {
+        Some(text) if cfg.turn_prompt.is_none() => text.clone(),
+        _ => current_prompt_text(cfg),
+    };
+    if let Some(text) = mode_prompt_override(cfg, mode) {
+        prompt.push('\n');
+        prompt.push_str(text.trim());
+    } else if mode == "review" {
         prompt.push_str("\nYou are a senior code reviewer.");
     } else if mode == "edit" {
         prompt.push_str("\nYou are a careful code editor.");
@@ -590,6 +1173,10 @@ pub fn build_system_prompt(cfg: &Config, mode: &str) -> String {
         prompt.push_str("\nYou are in terminal coding assistant chat mode.");
         prompt.push_str("\nWork as an agent loop: understand task -> inspect code -> edit -> verify -> summarize.");
         prompt.push_str("\nBefore using tools, briefly state intent in one line.");
+    }
+    // The tool protocol is required for the agent loop to work, so it is kept
+    // even when the agent instructions are overridden.
+    if mode == "chat" {
         match active_effective_tool_mode(cfg) {
             ToolCallMode::Json => {
                 prompt.push_str("\nTool protocol mode: json.");
@@ -604,16 +1191,22 @@ pub fn build_system_prompt(cfg: &Config, mode: &str) -> String {
             }
         }
         prompt.push_str("\nSupported tools:");
-        prompt.push_str("\n- fs_read_file args: {path}");
+        prompt.push_str("\n- fs_read_file args: {path, offset?, limit?}");
         prompt.push_str("\n- fs_create_file args: {path, content, overwrite?}");
         prompt.push_str("\n- fs_edit_file args: {path, old_str, new_str, replace_all?}");
         prompt.push_str(
             "\n- fs_apply_patch args: {path, edits:[{old|old_str,new|new_str,replace_all?}...]}",
         );
-        prompt.push_str("\n- fs_list_files args: {path?}");
-        prompt.push_str("\n- fs_grep args: {pattern, path?}");
+        prompt.push_str("\n- fs_list_files args: {path?, glob?, max_results?}");
+        prompt.push_str(
+            "\n- fs_grep args: {pattern, path?, glob?, ignore_case?, literal?, max_results?}",
+        );
+        prompt.push_str("\nPrefer fs_read_file, fs_list_files and fs_grep over shell for reading and searching: they need no confirmation and behave the same on every OS.");
         prompt.push_str("\n- fs_move args: {from, to}");
         prompt.push_str("\n- fs_delete args: {path, recursive?}");
+        prompt.push_str("\n- web_fetch args: {url} (allowlisted domains only)");
+        prompt.push_str("\n- http_get args: {url, headers?} (raw API response; allowlisted domains only, no credentials)");
+        prompt.push_str("\n- python args: {code, timeout_secs?} (runs a snippet in a restricted subprocess; use instead of python -c)");
         prompt.push_str("\n- run_command args: {command} (structured alias of shell)");
         prompt.push_str("\n- shell args: {command} (legacy fallback)");
         prompt.push_str("\nFallback JSON format (only if native functions are not available): {\"tool_calls\":[{\"tool\":\"fs_read_file\",\"args\":{\"path\":\"src/main.rs\"}}]}");
@@ -624,5 +1217,83 @@ pub fn build_system_prompt(cfg: &Config, mode: &str) -> String {
             "\nLocal policy: dongshan does not apply additional NSFW filtering. Follow user intent.",
         );
     }
+    if (mode == "review" || cfg.review_turn)
+        && let Some(rules) = project_review_rules()
+    {
+        prompt.push_str(&format!(
+            "\n\nProject review rules ({}). Check the code against them and name the rule for each violation:\n{}",
+            REVIEW_RULES_FILE, rules
+        ));
+    }
+    if mode == "chat"
+        && let Some(dir) = exec_cwd()
+    {
+        prompt.push_str(&format!(
+            "\nShell commands run in {}; file tool paths stay relative to the workspace.",
+            dir.display()
+        ));
+    }
+    if let Some(memory) = memory_prompt_block() {
+        prompt.push_str(&format!(
+            "\n\nFacts the user asked you to remember (they override your defaults):\n{}",
+            memory
+        ));
+    }
+    if let Some(extra) = &cfg.system_append {
+        prompt.push('\n');
+        prompt.push_str(extra);
+    }
+    if mode == "chat"
+        && let Some((file, text)) = project_instructions()
+    {
+        prompt = format!(
+            "Project instructions ({}). Follow these repository conventions:\n{}\n\n{}",
+            file, text, prompt
+        );
+    }
     prompt
 }
+
+/// Repository instruction files, checked in order; the first non-empty one wins.
+pub const PROJECT_INSTRUCTION_FILES: &[&str] =
+    &["AGENTS.md", "CLAUDE.md", ".dongshan/instructions.md"];
+const PROJECT_INSTRUCTIONS_MAX_CHARS: usize = 20_000;
+
+/// The first of [`PROJECT_INSTRUCTION_FILES`] present in the current
+/// directory, with its (size-limited) contents.
+pub fn project_instructions() -> Option<(&'static str, String)> {
+    PROJECT_INSTRUCTION_FILES.iter().find_map(|file| {
+        let text = fs::read_to_string(file).ok()?;
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        Some((
+            *file,
+            truncate_with_suffix(
+                text,
+                PROJECT_INSTRUCTIONS_MAX_CHARS,
+                "\n...[project instructions truncated]",
+            ),
+        ))
+    })
+}
+
+/// Team conventions, forbidden patterns and style rules for reviews, kept in
+/// the workspace.
+pub const REVIEW_RULES_FILE: &str = ".dongshan/review-rules.md";
+const REVIEW_RULES_MAX_CHARS: usize = 20_000;
+
+/// Contents of [`REVIEW_RULES_FILE`] in the current directory, if present.
+pub fn project_review_rules() -> Option<String> {
+    let text = fs::read_to_string(REVIEW_RULES_FILE).ok()?;
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(truncate_with_suffix(
+        text,
+        REVIEW_RULES_MAX_CHARS,
+        "\n...[review rules truncated]",
+    ))
+}
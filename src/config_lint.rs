@@ -0,0 +1,392 @@
+use std::env;
+
+use crate::config::{AutoExecMode, Config};
+use crate::exec_rules::{rule_matches, validate_exec_rule};
+use crate::prompt_store::list_prompt_names;
+use crate::tls::load_tls;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct LintFinding {
+    pub severity: Severity,
+    /// Config key the finding is about, e.g. `model_profiles.gpt-4o.base_url`.
+    pub field: String,
+    pub message: String,
+    /// Command or edit that resolves it.
+    pub fix: String,
+}
+
+fn finding(
+    severity: Severity,
+    field: impl Into<String>,
+    message: String,
+    fix: String,
+) -> LintFinding {
+    LintFinding {
+        severity,
+        field: field.into(),
+        message,
+        fix,
+    }
+}
+
+/// Checks that cannot be expressed by the TOML schema alone. Errors are
+/// settings that break requests; warnings are likely mistakes.
+pub fn lint_config(cfg: &Config) -> Vec<LintFinding> {
+    let mut out = Vec::new();
+    lint_endpoints(cfg, &mut out);
+    lint_api_keys(cfg, &mut out);
+    lint_models(cfg, &mut out);
+    lint_exec_rules(cfg, &mut out);
+    lint_prompts(cfg, &mut out);
+    lint_domains(cfg, &mut out);
+    lint_tls(cfg, &mut out);
+    out.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    out
+}
+
+fn lint_endpoints(cfg: &Config, out: &mut Vec<LintFinding>) {
+    let mut urls = vec![("base_url".to_string(), cfg.base_url.as_str(), None)];
+    for (name, profile) in &cfg.model_profiles {
+        urls.push((
+            format!("model_profiles.{name}.base_url"),
+            profile.base_url.as_str(),
+            Some(name.as_str()),
+        ));
+    }
+    for (field, url, model) in urls {
+        let fix = match model {
+            Some(m) => format!(
+                "dongshan models set-profile {m} --base-url https://host/v1/chat/completions"
+            ),
+            None => "dongshan config set --base-url https://host/v1/chat/completions".to_string(),
+        };
+        match reqwest::Url::parse(url.trim()) {
+            Err(err) => out.push(finding(
+                Severity::Error,
+                field,
+                format!("`{}` is not a valid URL ({})", url, err),
+                fix,
+            )),
+            Ok(parsed) if !matches!(parsed.scheme(), "http" | "https") => out.push(finding(
+                Severity::Error,
+                field,
+                format!("scheme `{}` is not http(s)", parsed.scheme()),
+                fix,
+            )),
+            Ok(parsed) if parsed.host_str().is_none() => out.push(finding(
+                Severity::Error,
+                field,
+                format!("`{}` has no host", url),
+                fix,
+            )),
+            Ok(parsed) => {
+                let local = matches!(
+                    parsed.host_str(),
+                    Some("localhost" | "127.0.0.1" | "::1" | "[::1]")
+                );
+                if parsed.scheme() == "http" && !local {
+                    out.push(finding(
+                        Severity::Warning,
+                        field,
+                        format!("`{}` sends the API key over plain http", url),
+                        fix,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn lint_api_keys(cfg: &Config, out: &mut Vec<LintFinding>) {
+    let has_env =
+        |name: &str| !name.trim().is_empty() && env::var(name).is_ok_and(|v| !v.trim().is_empty());
+    let has_inline = |key: &Option<String>| key.as_deref().is_some_and(|k| !k.trim().is_empty());
+    let top_level_ok = has_env(&cfg.api_key_env) || has_inline(&cfg.api_key);
+    for (name, profile) in &cfg.model_profiles {
+        if has_env(&profile.api_key_env) || has_inline(&profile.api_key) || top_level_ok {
+            continue;
+        }
+        let severity = if *name == cfg.model {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        out.push(finding(
+            severity,
+            format!("model_profiles.{name}.api_key_env"),
+            format!(
+                "env var `{}` is not set and the profile has no api_key",
+                profile.api_key_env
+            ),
+            format!(
+                "export {}=... (or `dongshan models set-profile {} --api-key ...`)",
+                if profile.api_key_env.trim().is_empty() {
+                    "API_KEY"
+                } else {
+                    profile.api_key_env.as_str()
+                },
+                name
+            ),
+        ));
+    }
+    if !cfg.model_profiles.contains_key(&cfg.model) && !top_level_ok {
+        out.push(finding(
+            Severity::Error,
+            "api_key_env",
+            format!(
+                "env var `{}` is not set and no api_key is configured",
+                cfg.api_key_env
+            ),
+            format!(
+                "export {}=... (or `dongshan config set --api-key ...`)",
+                cfg.api_key_env
+            ),
+        ));
+    }
+}
+
+fn lint_models(cfg: &Config, out: &mut Vec<LintFinding>) {
+    let known = |m: &str| cfg.model_profiles.contains_key(m);
+    if !cfg.model_profiles.is_empty() && !known(&cfg.model) {
+        out.push(finding(
+            Severity::Error,
+            "model",
+            format!("active model `{}` has no profile", cfg.model),
+            format!("dongshan models add {}", cfg.model),
+        ));
+    }
+    for model in &cfg.model_catalog {
+        if !known(model) {
+            out.push(finding(
+                Severity::Warning,
+                "model_catalog",
+                format!("`{}` is in the catalog but has no profile", model),
+                format!("dongshan models add {model}  (or `dongshan models remove {model}`)"),
+            ));
+        }
+    }
+    let refs = [
+        ("executor_model", cfg.executor_model.as_deref()),
+        ("summary_model", cfg.summary_model.as_deref()),
+        ("utility_model", cfg.utility_model.as_deref()),
+    ];
+    for (field, model) in refs {
+        if let Some(m) = model.map(str::trim).filter(|m| !m.is_empty())
+            && !known(m)
+        {
+            out.push(finding(
+                Severity::Warning,
+                field,
+                format!(
+                    "`{}` has no profile; requests fall back to the active one",
+                    m
+                ),
+                format!("dongshan models add {m}"),
+            ));
+        }
+    }
+    for (pipeline, stages) in &cfg.pipelines {
+        for (i, stage) in stages.iter().enumerate() {
+            if let Some(m) = stage.model.as_deref()
+                && !known(m)
+            {
+                out.push(finding(
+                    Severity::Error,
+                    format!("pipelines.{pipeline}[{i}].model"),
+                    format!("`{}` has no profile", m),
+                    format!("dongshan models add {m}"),
+                ));
+            }
+        }
+    }
+    for (name, profile) in &cfg.model_profiles {
+        for fallback in &profile.fallbacks {
+            if fallback == name {
+                out.push(finding(
+                    Severity::Warning,
+                    format!("model_profiles.{name}.fallbacks"),
+                    format!("`{}` lists itself as a fallback", name),
+                    format!("dongshan models set-profile {name} --fallbacks <other models>"),
+                ));
+            } else if !known(fallback) {
+                out.push(finding(
+                    Severity::Warning,
+                    format!("model_profiles.{name}.fallbacks"),
+                    format!("fallback `{}` has no profile", fallback),
+                    format!("dongshan models add {fallback}  (or edit `--fallbacks` with `dongshan models set-profile {name}`)"),
+                ));
+            }
+        }
+    }
+}
+
+/// True when `deny` blocks every command `allow` admits, i.e. matches the
+/// allow entry itself. Regex allow entries cannot be compared.
+fn shadows(deny: &str, allow: &str) -> bool {
+    !allow.trim().starts_with("re:") && rule_matches(deny, allow)
+}
+
+fn lint_exec_rules(cfg: &Config, out: &mut Vec<LintFinding>) {
+    let lists = [
+        ("auto_exec_allow", &cfg.auto_exec_allow),
+        ("auto_exec_deny", &cfg.auto_exec_deny),
+        ("auto_exec_trusted", &cfg.auto_exec_trusted),
+    ];
+    for (field, list) in lists {
+        for rule in list {
+            if let Err(err) = validate_exec_rule(rule) {
+                out.push(finding(
+                    Severity::Error,
+                    field,
+                    format!("{:#}; the rule is ignored", err),
+                    format!("fix or remove `{}` in config.toml", rule),
+                ));
+            }
+        }
+    }
+    for allow in &cfg.auto_exec_allow {
+        if let Some(deny) = cfg.auto_exec_deny.iter().find(|d| shadows(d, allow)) {
+            out.push(finding(
+                Severity::Warning,
+                "auto_exec_allow",
+                format!("`{}` is allowed but `{}` in auto_exec_deny always wins", allow, deny),
+                "drop one of them: dongshan config set --auto-exec-allow ... / --auto-exec-deny ..."
+                    .to_string(),
+            ));
+        }
+    }
+    for trusted in &cfg.auto_exec_trusted {
+        if let Some(deny) = cfg.auto_exec_deny.iter().find(|d| shadows(d, trusted)) {
+            out.push(finding(
+                Severity::Warning,
+                "auto_exec_trusted",
+                format!(
+                    "`{}` is trusted but `{}` in auto_exec_deny blocks it",
+                    trusted, deny
+                ),
+                "remove it from auto_exec_trusted in config.toml".to_string(),
+            ));
+        }
+    }
+    if matches!(cfg.auto_exec_mode, AutoExecMode::Custom) && cfg.auto_exec_allow.is_empty() {
+        out.push(finding(
+            Severity::Warning,
+            "auto_exec_allow",
+            "auto_exec_mode is custom but the allow list is empty, so no command can run"
+                .to_string(),
+            "dongshan config set --auto-exec-allow \"cargo,git status\"".to_string(),
+        ));
+    }
+    if !matches!(cfg.auto_exec_mode, AutoExecMode::Custom) && !cfg.auto_exec_allow.is_empty() {
+        out.push(finding(
+            Severity::Warning,
+            "auto_exec_allow",
+            format!(
+                "auto_exec_allow is ignored while auto_exec_mode is {:?}",
+                cfg.auto_exec_mode
+            )
+            .to_ascii_lowercase(),
+            "dongshan config set --auto-exec-mode custom".to_string(),
+        ));
+    }
+}
+
+fn lint_prompts(cfg: &Config, out: &mut Vec<LintFinding>) {
+    let Ok(names) = list_prompt_names() else {
+        return;
+    };
+    if !names.contains(&cfg.active_prompt) {
+        out.push(finding(
+            Severity::Error,
+            "active_prompt",
+            format!("prompt `{}` does not exist", cfg.active_prompt),
+            format!(
+                "dongshan prompt use <name>  (available: {})",
+                names.join(", ")
+            ),
+        ));
+    }
+    for (mode, prompt) in &cfg.mode_prompts {
+        if !names.contains(prompt) {
+            out.push(finding(
+                Severity::Warning,
+                format!("mode_prompts.{mode}"),
+                format!("prompt `{}` bound to mode {} does not exist", prompt, mode),
+                format!("dongshan prompt unbind {mode}"),
+            ));
+        }
+    }
+    for (pipeline, stages) in &cfg.pipelines {
+        for (i, stage) in stages.iter().enumerate() {
+            if let Some(prompt) = &stage.prompt
+                && !names.contains(prompt)
+            {
+                out.push(finding(
+                    Severity::Error,
+                    format!("pipelines.{pipeline}[{i}].prompt"),
+                    format!("prompt `{}` does not exist", prompt),
+                    format!(
+                        "dongshan prompt save {prompt} \"...\"  (or fix the stage in config.toml)"
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn lint_domains(cfg: &Config, out: &mut Vec<LintFinding>) {
+    let lists = [
+        (
+            "fetch_allow_domains",
+            &cfg.fetch_allow_domains,
+            "--fetch-allow-domains",
+        ),
+        (
+            "http_allow_domains",
+            &cfg.http_allow_domains,
+            "--http-allow-domains",
+        ),
+    ];
+    for (field, domains, flag) in lists {
+        for d in domains {
+            if d.contains("://") || d.contains('/') {
+                out.push(finding(
+                    Severity::Warning,
+                    field,
+                    format!(
+                        "`{}` looks like a URL; entries are host names and never match",
+                        d
+                    ),
+                    format!("dongshan config set {flag} \"docs.rs,github.com\""),
+                ));
+            }
+        }
+    }
+}
+
+fn lint_tls(cfg: &Config, out: &mut Vec<LintFinding>) {
+    if let Err(err) = load_tls(&cfg.tls) {
+        out.push(finding(
+            Severity::Error,
+            "tls",
+            format!("{:#}; every HTTP request will fail", err),
+            "dongshan config set --tls-ca-cert-path <pem> (\"\" clears), or fix the [tls] paths"
+                .to_string(),
+        ));
+    }
+    if cfg.tls.danger_accept_invalid_certs {
+        out.push(finding(
+            Severity::Warning,
+            "tls.danger_accept_invalid_certs",
+            "certificate verification is off, so API keys can be intercepted".to_string(),
+            "trust the proxy CA with --tls-ca-cert-path, then dongshan config set --tls-danger-accept-invalid-certs false"
+                .to_string(),
+        ));
+    }
+}
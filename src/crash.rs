@@ -0,0 +1,138 @@
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::fs;
+use std::panic::{AssertUnwindSafe, PanicHookInfo, catch_unwind};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::config::config_dir;
+use crate::diagnostics::now_unix_ts;
+use crate::logging::{log_verbose, recent_log_lines};
+use crate::redact::redact_secrets_always;
+
+/// Flag names whose value is replaced in the recorded command line.
+const SECRET_FLAG_WORDS: &[&str] = &["key", "token", "secret", "password"];
+
+static COMMAND: OnceLock<String> = OnceLock::new();
+
+thread_local! {
+    /// Set while `catch_expected_panic` runs on this thread.
+    static EXPECTED_PANIC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs a panic hook that, after the usual panic message, writes a report
+/// to `~/.dongshan/crash/` and tells the user where it is.
+pub fn install_crash_handler(command: &str) {
+    let _ = COMMAND.set(command.to_string());
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if EXPECTED_PANIC.with(Cell::get) {
+            log_verbose("crash", &format!("caught panic: {info}"));
+            return;
+        }
+        default_hook(info);
+        match write_crash_report(info) {
+            Some(path) => eprintln!(
+                "\ndongshan crashed. A report was saved to {}\nPlease attach it when filing a bug: https://github.com/KonshinHaoshin/dongshan-cli/issues",
+                path.display()
+            ),
+            None => eprintln!("\ndongshan crashed (the crash report could not be written)."),
+        }
+    }));
+}
+
+/// Runs `f`, turning a panic into `Err` without the panic message or a crash
+/// report. For third-party code known to panic on bad input.
+pub fn catch_expected_panic<T>(f: impl FnOnce() -> T) -> std::thread::Result<T> {
+    let outer = EXPECTED_PANIC.with(|flag| flag.replace(true));
+    let result = catch_unwind(AssertUnwindSafe(f));
+    EXPECTED_PANIC.with(|flag| flag.set(outer));
+    result
+}
+
+fn write_crash_report(info: &PanicHookInfo<'_>) -> Option<PathBuf> {
+    let dir = config_dir().ok()?.join("crash");
+    fs::create_dir_all(&dir).ok()?;
+    let ts = now_unix_ts();
+    let path = dir.join(format!("crash-{ts}.txt"));
+    let report = redact_secrets_always(&build_report(info, ts));
+    fs::write(&path, report).ok()?;
+    Some(path)
+}
+
+fn build_report(info: &PanicHookInfo<'_>, ts: u64) -> String {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "(non-string panic payload)".to_string());
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let thread = std::thread::current();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# dongshan crash report");
+    let _ = writeln!(out, "version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        out,
+        "os: {} {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::FAMILY
+    );
+    let _ = writeln!(out, "time_unix: {}", ts);
+    let _ = writeln!(
+        out,
+        "command: {}",
+        COMMAND.get().map(String::as_str).unwrap_or("unknown")
+    );
+    let _ = writeln!(out, "args: {}", sanitized_args().join(" "));
+    let _ = writeln!(out, "thread: {}", thread.name().unwrap_or("unnamed"));
+    let _ = writeln!(out, "panic: {}", message);
+    let _ = writeln!(out, "location: {}", location);
+
+    let _ = writeln!(out, "\n## Recent log");
+    let lines = recent_log_lines();
+    if lines.is_empty() {
+        let _ = writeln!(out, "(empty)");
+    }
+    for line in lines {
+        let _ = writeln!(out, "{}", line);
+    }
+
+    let _ = writeln!(out, "\n## Backtrace\n{}", Backtrace::force_capture());
+    out
+}
+
+/// Command-line arguments with values of secret-looking flags masked, e.g.
+/// `--api-key sk-...` becomes `--api-key ***`.
+fn sanitized_args() -> Vec<String> {
+    let mut out = Vec::new();
+    let mut mask_next = false;
+    for arg in std::env::args().skip(1) {
+        if mask_next {
+            out.push("***".to_string());
+            mask_next = false;
+            continue;
+        }
+        let lower = arg.to_ascii_lowercase();
+        let is_secret_flag =
+            lower.starts_with("--") && SECRET_FLAG_WORDS.iter().any(|w| lower.contains(w));
+        if is_secret_flag {
+            match arg.split_once('=') {
+                Some((flag, _)) => out.push(format!("{flag}=***")),
+                None => {
+                    out.push(arg);
+                    mask_next = true;
+                }
+            }
+        } else {
+            out.push(arg);
+        }
+    }
+    out
+}
@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::logging::log_verbose;
+
+/// One entry of `auto_exec_allow`, `auto_exec_deny` or `auto_exec_trusted`:
+///
+/// - `re:<regex>` matches anywhere in the command, case-insensitively.
+/// - An entry with flags, e.g. `git push --force` or `rm -rf /`, matches a
+///   command starting with the words before the first flag that also passes
+///   every later word in any position. `-rf` is also found as `-fr` or
+///   `-r -f`, and `--force` as `--force=...`.
+/// - Anything else is a plain prefix, e.g. `git status`.
+enum ExecRule {
+    Regex(Regex),
+    Args {
+        command: Vec<String>,
+        args: Vec<String>,
+    },
+    Prefix(String),
+}
+
+impl ExecRule {
+    fn parse(entry: &str) -> Result<Option<Self>> {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            return Ok(None);
+        }
+        if let Some(pattern) = entry.strip_prefix("re:") {
+            let re = Regex::new(&format!("(?i){}", pattern.trim()))
+                .with_context(|| format!("Invalid command pattern: {}", entry))?;
+            return Ok(Some(Self::Regex(re)));
+        }
+        let words: Vec<&str> = entry.split_whitespace().collect();
+        match words.iter().skip(1).position(|w| w.starts_with('-')) {
+            Some(pos) => Ok(Some(Self::Args {
+                command: words[..=pos]
+                    .iter()
+                    .map(|w| w.to_ascii_lowercase())
+                    .collect(),
+                args: words[pos + 1..].iter().map(|w| w.to_string()).collect(),
+            })),
+            None => Ok(Some(Self::Prefix(entry.to_ascii_lowercase()))),
+        }
+    }
+
+    fn matches(&self, cmd: &str) -> bool {
+        match self {
+            Self::Regex(re) => re.is_match(cmd),
+            Self::Prefix(prefix) => cmd.trim().to_ascii_lowercase().starts_with(prefix),
+            Self::Args { command, args } => {
+                let words: Vec<String> = cmd.split_whitespace().map(clean_word).collect();
+                if words.len() < command.len() {
+                    return false;
+                }
+                let (head, rest) = words.split_at(command.len());
+                head.iter()
+                    .enumerate()
+                    .all(|(i, w)| command_word(w, i == 0) == command[i])
+                    && args.iter().all(|arg| has_arg(rest, arg))
+            }
+        }
+    }
+}
+
+/// Rejects `re:` entries that do not compile, for `config set`.
+pub fn validate_exec_rule(entry: &str) -> Result<()> {
+    ExecRule::parse(entry).map(|_| ())
+}
+
+fn parse_rules(list: &[String]) -> Vec<(&String, ExecRule)> {
+    list.iter()
+        .filter_map(|entry| match ExecRule::parse(entry) {
+            Ok(rule) => rule.map(|r| (entry, r)),
+            Err(err) => {
+                log_verbose("exec", &format!("skipping rule: {err:#}"));
+                None
+            }
+        })
+        .collect()
+}
+
+/// True when the rule `entry` admits the whole command.
+pub fn rule_matches(entry: &str, cmd: &str) -> bool {
+    matches!(ExecRule::parse(entry), Ok(Some(rule)) if rule.matches(cmd))
+}
+
+/// True when some rule in `list` admits the whole command.
+pub fn matches_exec_rules(list: &[String], cmd: &str) -> bool {
+    parse_rules(list).iter().any(|(_, rule)| rule.matches(cmd))
+}
+
+/// The deny entry blocking `cmd`. Each part of a compound command
+/// (`a && b`, `a | b`, `a; b`) is checked on its own, so `cd x && rm -rf /`
+/// is caught by `rm -rf /`.
+pub fn denying_rule<'a>(list: &'a [String], cmd: &str) -> Option<&'a String> {
+    let rules = parse_rules(list);
+    rules
+        .iter()
+        .find(|(_, rule)| {
+            rule.matches(cmd)
+                || cmd
+                    .split(['&', '|', ';', '\n'])
+                    .filter(|seg| !seg.trim().is_empty())
+                    .any(|seg| rule.matches(seg))
+        })
+        .map(|(entry, _)| *entry)
+}
+
+fn clean_word(word: &str) -> String {
+    word.trim_matches(['"', '\'', '`']).to_string()
+}
+
+/// Lowercased, and for the program name without its directory (`/bin/rm`).
+fn command_word(word: &str, program: bool) -> String {
+    let word = word.to_ascii_lowercase();
+    if program {
+        word.rsplit(['/', '\\']).next().unwrap_or(&word).to_string()
+    } else {
+        word
+    }
+}
+
+fn has_arg(words: &[String], arg: &str) -> bool {
+    if let Some(long) = arg.strip_prefix("--") {
+        return words.iter().any(|w| {
+            w.strip_prefix("--")
+                .is_some_and(|f| f == long || f.starts_with(&format!("{long}=")))
+        });
+    }
+    if let Some(short) = arg.strip_prefix('-')
+        && !short.is_empty()
+        && short.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return short.chars().all(|c| {
+            words.iter().any(|w| {
+                w.strip_prefix('-').is_some_and(|cluster| {
+                    !cluster.starts_with('-')
+                        && cluster.chars().all(|c| c.is_ascii_alphanumeric())
+                        && cluster.contains(c)
+                })
+            })
+        });
+    }
+    words.iter().any(|w| w == arg)
+}
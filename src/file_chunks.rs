@@ -0,0 +1,322 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::config::{Config, config_for_model, context_budget_chars, utility_model};
+use crate::injection::guard_untrusted;
+use crate::llm::{ApiError, call_llm_with_usage};
+use crate::logging::log_verbose;
+use crate::redact::redact_secrets;
+use crate::usage::check_budget;
+use crate::util::{WorkingStatus, truncate_with_suffix};
+
+/// Files up to this many chars are sent whole when the model's context
+/// window is unknown.
+const DEFAULT_DIRECT_MAX_CHARS: usize = 100_000;
+/// Size of one chunk sent to the summary model.
+const CHUNK_CHARS: usize = 24_000;
+/// Chunks past this many are left out and reported as not analyzed.
+const MAX_CHUNKS: usize = 64;
+/// Each chunk summary is clipped to this many chars.
+const MAX_CHUNK_SUMMARY_CHARS: usize = 2_000;
+/// Chunk requests in flight at once.
+const MAX_PARALLEL_CHUNKS: usize = 4;
+/// Tries per chunk while the provider answers 429, waiting twice as long
+/// after each.
+const RATE_LIMIT_TRIES: u32 = 4;
+const RATE_LIMIT_FIRST_WAIT: Duration = Duration::from_secs(2);
+
+const NOTHING_RELEVANT: &str = "nothing relevant";
+
+/// One piece of a file, with its 1-based line range.
+struct Chunk {
+    index: usize,
+    first_line: usize,
+    last_line: usize,
+    text: String,
+}
+
+struct ChunkSummary {
+    index: usize,
+    first_line: usize,
+    last_line: usize,
+    text: String,
+}
+
+/// Result of summarizing a large file chunk by chunk against one request.
+pub struct ChunkedFile {
+    model: String,
+    total_chunks: usize,
+    /// Summaries of the chunks that had something for the request.
+    used: Vec<ChunkSummary>,
+    /// Chunks whose summary came back "nothing relevant".
+    irrelevant: Vec<usize>,
+    failed: Vec<usize>,
+    /// First line left out because the file had more than `MAX_CHUNKS` chunks.
+    unread_from_line: Option<usize>,
+}
+
+/// True when `content` is too large to send whole: over half the model's
+/// context window, or `DEFAULT_DIRECT_MAX_CHARS` when the window is unknown.
+pub fn needs_chunking(cfg: &Config, content: &str) -> bool {
+    let limit = context_budget_chars(cfg, 50).unwrap_or(DEFAULT_DIRECT_MAX_CHARS);
+    content.chars().count() > limit
+}
+
+/// Map step of large-file analysis: every chunk goes to `summary_model` (or
+/// `utility_model`, else the active model) with the user's request, and
+/// comes back as the parts relevant to it. Fails only when every chunk does.
+pub async fn summarize_file_chunks(
+    cfg: &Config,
+    path: &str,
+    request: &str,
+    content: &str,
+) -> Result<ChunkedFile> {
+    let model = utility_model(cfg, cfg.summary_model.as_deref())
+        .unwrap_or(&cfg.model)
+        .to_string();
+    let summary_cfg = config_for_model(cfg, &model);
+    // One check up front: the chunk requests run together, so a prompt from
+    // inside them would interleave.
+    check_budget(cfg)?;
+    let mut chunks = split_chunks(&redact_secrets(content), CHUNK_CHARS);
+    let unread_from_line = chunks.get(MAX_CHUNKS).map(|c| c.first_line);
+    chunks.truncate(MAX_CHUNKS);
+    let total_chunks = chunks.len();
+
+    let system = format!(
+        "You read one chunk of a large file for a coding assistant that cannot see the whole file.\n\
+         Extract only what helps answer the user's request: relevant facts, errors, identifiers, values \
+         and line numbers. At most 12 short lines. Reply exactly \"{NOTHING_RELEVANT}\" when the chunk \
+         has nothing for the request. No preamble."
+    );
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("txt")
+        .to_string();
+    let working = WorkingStatus::start(format!("summarizing {total_chunks} chunks of {path}"));
+    let slots = Arc::new(Semaphore::new(MAX_PARALLEL_CHUNKS));
+    let mut tasks = JoinSet::new();
+    for chunk in chunks {
+        let summary_cfg = summary_cfg.clone();
+        let slots = slots.clone();
+        let system = system.clone();
+        let user = format!(
+            "User request:\n{}\n\nFile: {}, chunk {}/{} (lines {}-{}):\n```{}\n{}\n```",
+            request,
+            path,
+            chunk.index,
+            total_chunks,
+            chunk.first_line,
+            chunk.last_line,
+            ext,
+            guard_untrusted(path, &chunk.text)
+        );
+        tasks.spawn(async move {
+            let _slot = slots.acquire_owned().await;
+            let res = summarize_chunk(&summary_cfg, &system, &user, chunk.index).await;
+            (chunk, res)
+        });
+    }
+    let mut used = Vec::new();
+    let mut irrelevant = Vec::new();
+    let mut failed = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (chunk, res) = joined?;
+        match res {
+            Ok(text) if is_irrelevant(&text) => irrelevant.push(chunk.index),
+            Ok(text) => used.push(ChunkSummary {
+                index: chunk.index,
+                first_line: chunk.first_line,
+                last_line: chunk.last_line,
+                text: truncate_with_suffix(text.trim(), MAX_CHUNK_SUMMARY_CHARS, " ..."),
+            }),
+            Err(err) => {
+                log_verbose(
+                    "file-chunks",
+                    &format!("chunk {} summary failed: {err:#}", chunk.index),
+                );
+                failed.push(chunk.index);
+            }
+        }
+    }
+    working.finish();
+    if failed.len() == total_chunks {
+        bail!("Failed to summarize {path}: every chunk request failed");
+    }
+    used.sort_by_key(|s| s.index);
+    irrelevant.sort_unstable();
+    failed.sort_unstable();
+    Ok(ChunkedFile {
+        model,
+        total_chunks,
+        used,
+        irrelevant,
+        failed,
+        unread_from_line,
+    })
+}
+
+/// One map request, retried with backoff while the provider rate-limits.
+async fn summarize_chunk(cfg: &Config, system: &str, user: &str, index: usize) -> Result<String> {
+    let mut wait = RATE_LIMIT_FIRST_WAIT;
+    let mut tries = 1;
+    loop {
+        // No spinner per request; the status line covers them all.
+        match call_llm_with_usage(cfg, system, user).await {
+            Err(err) if tries < RATE_LIMIT_TRIES && is_rate_limited(&err) => {
+                log_verbose(
+                    "file-chunks",
+                    &format!(
+                        "chunk {index} rate limited, retrying in {}s",
+                        wait.as_secs()
+                    ),
+                );
+                tokio::time::sleep(wait).await;
+                wait *= 2;
+                tries += 1;
+            }
+            res => return res.map(|(text, _)| text),
+        }
+    }
+}
+
+fn is_rate_limited(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.downcast_ref::<ApiError>()
+            .is_some_and(|api| api.status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+    })
+}
+
+impl ChunkedFile {
+    /// Which chunks were used, skipped or missed, for the user and the model.
+    pub fn note(&self) -> String {
+        let mut parts = vec![format!(
+            "{} chunks summarized with {}",
+            self.total_chunks, self.model
+        )];
+        if self.used.is_empty() {
+            parts.push("no chunk had anything relevant".to_string());
+        } else {
+            let used: Vec<String> = self
+                .used
+                .iter()
+                .map(|s| format!("{} (lines {}-{})", s.index, s.first_line, s.last_line))
+                .collect();
+            parts.push(format!("used chunks {}", used.join(", ")));
+        }
+        if !self.irrelevant.is_empty() {
+            parts.push(format!(
+                "nothing relevant in {}",
+                chunk_list(&self.irrelevant)
+            ));
+        }
+        if !self.failed.is_empty() {
+            parts.push(format!("summary failed for {}", chunk_list(&self.failed)));
+        }
+        if let Some(line) = self.unread_from_line {
+            parts.push(format!(
+                "lines {line}+ not analyzed (over {MAX_CHUNKS} chunks)"
+            ));
+        }
+        parts.join("; ")
+    }
+
+    /// The relevant chunk summaries as markdown sections.
+    pub fn render_summaries(&self) -> String {
+        self.used
+            .iter()
+            .map(|s| {
+                format!(
+                    "### Chunk {} (lines {}-{})\n{}",
+                    s.index, s.first_line, s.last_line, s.text
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+fn is_irrelevant(summary: &str) -> bool {
+    summary
+        .trim()
+        .trim_matches(['"', '.'])
+        .eq_ignore_ascii_case(NOTHING_RELEVANT)
+}
+
+/// Sorted chunk numbers with runs collapsed, e.g. "chunk(s) 1-6, 8, 10-15".
+fn chunk_list(indexes: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &i in indexes {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == i => *end = i,
+            _ => ranges.push((i, i)),
+        }
+    }
+    let list: Vec<String> = ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect();
+    format!("chunk(s) {}", list.join(", "))
+}
+
+/// Splits `text` into chunks of at most `max_chars` at line breaks. A line
+/// longer than that (minified or generated code) is cut by characters.
+fn split_chunks(text: &str, max_chars: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0usize;
+    let mut first_line = 1usize;
+    let mut last_line = 0usize;
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line_chars = line.chars().count() + 1;
+        if current_chars > 0 && current_chars + line_chars > max_chars {
+            push_chunk(
+                &mut chunks,
+                std::mem::take(&mut current),
+                first_line,
+                last_line,
+            );
+            current_chars = 0;
+        }
+        if line_chars > max_chars {
+            let chars: Vec<char> = line.chars().collect();
+            for piece in chars.chunks(max_chars) {
+                push_chunk(&mut chunks, piece.iter().collect(), line_no, line_no);
+            }
+            continue;
+        }
+        if current_chars == 0 {
+            first_line = line_no;
+        }
+        current.push_str(line);
+        current.push('\n');
+        current_chars += line_chars;
+        last_line = line_no;
+    }
+    if current_chars > 0 {
+        push_chunk(&mut chunks, current, first_line, last_line);
+    }
+    chunks
+}
+
+fn push_chunk(chunks: &mut Vec<Chunk>, text: String, first_line: usize, last_line: usize) {
+    chunks.push(Chunk {
+        index: chunks.len() + 1,
+        first_line,
+        last_line,
+        text,
+    });
+}
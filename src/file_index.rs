@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result, bail};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::atomic_file::write_atomic;
+use crate::fs_tools::{is_ignored_dir, workspace_files};
+use crate::ignore_rules::is_dongshan_ignored;
+use crate::logging::log_verbose;
+
+/// Bumped when the cache layout changes; older caches are rebuilt.
+const CACHE_VERSION: u32 = 1;
+
+/// Files whose edits can hide or reveal everything below their directory.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".dongshanignore"];
+
+/// Modification time as (seconds, nanoseconds) since the epoch.
+type Stamp = (u64, u32);
+
+/// The workspace file list, one record per directory so a refresh only
+/// re-reads directories whose mtime moved.
+#[derive(Default, Serialize, Deserialize)]
+struct FileIndex {
+    version: u32,
+    /// Keyed by `/`-separated path relative to the workspace, `""` for the root.
+    dirs: BTreeMap<String, DirRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DirRecord {
+    mtime: Stamp,
+    /// Latest mtime of the directory's ignore files.
+    ignore: Option<Stamp>,
+    files: Vec<String>,
+    dirs: Vec<String>,
+}
+
+/// The index of the current process, kept between messages.
+static SESSION: Mutex<Option<(PathBuf, FileIndex)>> = Mutex::new(None);
+
+fn cache_file(workspace: &Path) -> PathBuf {
+    workspace.join(".dongshan").join("cache").join("files.json")
+}
+
+/// Files under `root`, sorted, with the same filtering as `workspace_files`.
+/// Inside the current workspace they come from the cached file list, which is
+/// built once, stored in `.dongshan/cache/files.json` and refreshed by
+/// re-reading only directories that changed. Other paths are walked.
+pub fn indexed_files(root: &Path) -> Result<Vec<PathBuf>> {
+    if !root.exists() {
+        bail!("Path does not exist: {}", root.display());
+    }
+    let cached = locate(root).and_then(|(workspace, prefix)| {
+        with_index(&workspace, |index| files_under(index, &prefix))
+    });
+    let Some(rels) = cached else {
+        return workspace_files(root, None);
+    };
+    let mut out: Vec<PathBuf> = rels.into_iter().map(|rel| root.join(rel)).collect();
+    out.sort();
+    Ok(out)
+}
+
+/// Subdirectory and file names of `dir` (relative to the workspace) from the
+/// cached file list; `None` when the directory is not indexed.
+pub fn indexed_dir_entries(dir: &Path) -> Option<(Vec<String>, Vec<String>)> {
+    let (workspace, rel) = locate(dir)?;
+    with_index(&workspace, |index| {
+        let record = index.dirs.get(&rel)?;
+        Some((record.dirs.clone(), record.files.clone()))
+    })
+}
+
+/// The current workspace and `path` relative to it, when `path` lies inside.
+fn locate(path: &Path) -> Option<(PathBuf, String)> {
+    let cwd = std::env::current_dir().ok()?;
+    let workspace = cwd.canonicalize().unwrap_or(cwd);
+    let abs = workspace.join(path).canonicalize().ok()?;
+    let rel = abs.strip_prefix(&workspace).ok()?;
+    let rel = rel.to_string_lossy().replace('\\', "/");
+    Some((workspace, rel))
+}
+
+/// Runs `f` on the refreshed index of `workspace`, loading it from the disk
+/// cache the first time.
+fn with_index<T>(workspace: &Path, f: impl FnOnce(&FileIndex) -> Option<T>) -> Option<T> {
+    let mut session = SESSION.lock().ok()?;
+    if session.as_ref().is_none_or(|(root, _)| root != workspace) {
+        *session = Some((workspace.to_path_buf(), load_cache(workspace)));
+    }
+    let (_, index) = session.as_mut()?;
+    if refresh(workspace, index)
+        && let Err(err) = save_cache(workspace, index)
+    {
+        log_verbose("file-index", &format!("{err:#}"));
+    }
+    f(index)
+}
+
+fn load_cache(workspace: &Path) -> FileIndex {
+    fs::read(cache_file(workspace))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<FileIndex>(&data).ok())
+        .filter(|index| index.version == CACHE_VERSION)
+        .unwrap_or(FileIndex {
+            version: CACHE_VERSION,
+            dirs: BTreeMap::new(),
+        })
+}
+
+fn save_cache(workspace: &Path, index: &FileIndex) -> Result<()> {
+    let path = cache_file(workspace);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    write_atomic(&path, serde_json::to_vec(index)?)
+}
+
+/// Brings the index up to date and returns whether anything changed. A
+/// directory whose mtime moved is re-read on its own; one whose ignore files
+/// changed, or that is new, is walked with everything below it.
+fn refresh(workspace: &Path, index: &mut FileIndex) -> bool {
+    let mut changed = false;
+    let mut stack = vec![String::new()];
+    while let Some(rel) = stack.pop() {
+        let path = workspace.join(&rel);
+        let Some(mtime) = stamp(&path) else {
+            changed |= remove_tree(index, &rel);
+            continue;
+        };
+        let ignore = ignore_stamp(&path);
+        match index.dirs.get(&rel) {
+            Some(record) if record.mtime == mtime && record.ignore == ignore => {
+                stack.extend(record.dirs.iter().map(|d| child_key(&rel, d)));
+            }
+            Some(record) if record.ignore == ignore => {
+                let old_dirs = record.dirs.clone();
+                let scanned = scan(workspace, &rel, Some(1));
+                let Some(fresh) = scanned.into_values().next() else {
+                    continue;
+                };
+                for gone in old_dirs.iter().filter(|d| !fresh.dirs.contains(d)) {
+                    remove_tree(index, &child_key(&rel, gone));
+                }
+                stack.extend(fresh.dirs.iter().map(|d| child_key(&rel, d)));
+                index.dirs.insert(rel, fresh);
+                changed = true;
+            }
+            _ => {
+                remove_tree(index, &rel);
+                index.dirs.extend(scan(workspace, &rel, None));
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Walks `rel` with the filtering of `workspace_files`, returning a record for
+/// it and, without a depth limit, for every directory below it.
+fn scan(workspace: &Path, rel: &str, max_depth: Option<usize>) -> BTreeMap<String, DirRecord> {
+    let root = workspace.join(rel);
+    let mut records = BTreeMap::new();
+    records.insert(rel.to_string(), new_record(&root));
+    let walker = WalkBuilder::new(&root)
+        .hidden(false)
+        .parents(true)
+        .require_git(false)
+        .max_depth(max_depth)
+        .filter_entry(|e| {
+            !is_ignored_dir(e.path())
+                && e.file_name() != ".dongshan"
+                && !is_dongshan_ignored(e.path())
+        })
+        .build();
+    for entry in walker.flatten() {
+        if entry.depth() == 0 {
+            continue;
+        }
+        let Some(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            continue;
+        };
+        let name = name.to_string_lossy().to_string();
+        let parent_key = key(workspace, parent);
+        if file_type.is_dir() {
+            if max_depth.is_none() {
+                records.insert(key(workspace, path), new_record(path));
+            }
+            if let Some(record) = records.get_mut(&parent_key) {
+                record.dirs.push(name);
+            }
+        } else if file_type.is_file()
+            && let Some(record) = records.get_mut(&parent_key)
+        {
+            record.files.push(name);
+        }
+    }
+    records
+}
+
+fn new_record(dir: &Path) -> DirRecord {
+    DirRecord {
+        mtime: stamp(dir).unwrap_or_default(),
+        ignore: ignore_stamp(dir),
+        files: Vec::new(),
+        dirs: Vec::new(),
+    }
+}
+
+/// Every file below `prefix`, relative to it; `None` when `prefix` is not an
+/// indexed directory.
+fn files_under(index: &FileIndex, prefix: &str) -> Option<Vec<String>> {
+    index.dirs.get(prefix)?;
+    let mut out = Vec::new();
+    let mut stack = vec![(prefix.to_string(), String::new())];
+    while let Some((key, rel)) = stack.pop() {
+        let Some(record) = index.dirs.get(&key) else {
+            continue;
+        };
+        out.extend(record.files.iter().map(|f| child_key(&rel, f)));
+        for dir in &record.dirs {
+            stack.push((child_key(&key, dir), child_key(&rel, dir)));
+        }
+    }
+    Some(out)
+}
+
+/// Drops the records of `rel` and everything below it.
+fn remove_tree(index: &mut FileIndex, rel: &str) -> bool {
+    let before = index.dirs.len();
+    index.dirs.retain(|key, _| {
+        !(rel.is_empty()
+            || key == rel
+            || key
+                .strip_prefix(rel)
+                .is_some_and(|rest| rest.starts_with('/')))
+    });
+    index.dirs.len() != before
+}
+
+fn child_key(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{parent}/{name}")
+    }
+}
+
+fn key(workspace: &Path, path: &Path) -> String {
+    path.strip_prefix(workspace)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = fs::metadata(path).ok()?;
+    if !meta.is_dir() {
+        return None;
+    }
+    let since = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((since.as_secs(), since.subsec_nanos()))
+}
+
+fn ignore_stamp(dir: &Path) -> Option<Stamp> {
+    IGNORE_FILES
+        .iter()
+        .filter_map(|name| {
+            let since = fs::metadata(dir.join(name))
+                .ok()?
+                .modified()
+                .ok()?
+                .duration_since(UNIX_EPOCH)
+                .ok()?;
+            Some((since.as_secs(), since.subsec_nanos()))
+        })
+        .max()
+}
@@ -1,76 +1,225 @@
 use std::fs;
+use std::io::{self, IsTerminal, Read};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ignore::WalkBuilder;
+use ignore::overrides::OverrideBuilder;
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
 
+use crate::crash::catch_expected_panic;
+use crate::file_index::indexed_files;
+use crate::ignore_rules::{is_dongshan_ignored, rg_ignore_args};
+use crate::notebook::render_notebook;
+use crate::util::truncate_with_suffix;
+
+/// Path argument that means "read from stdin" for file-taking commands.
+pub const STDIN_PATH: &str = "-";
+
+/// Largest image accepted as an attachment; vision APIs reject bigger payloads.
+const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Binary document formats that are read by extracting their text.
+const EXTRACTED_DOCUMENTS: &[&str] = &["pdf", "docx"];
+
+/// Reads a text file; PDF and DOCX files are returned as their extracted text
+/// and Jupyter notebooks as percent-format cells with their outputs.
 pub fn read_text_file(path: &Path) -> Result<String> {
     if !path.exists() {
         bail!("File does not exist: {}", path.display());
     }
+    match lower_extension(path).as_str() {
+        "pdf" => return extract_pdf_text(path),
+        "docx" => return extract_docx_text(path),
+        "ipynb" => {
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            return render_notebook(&raw, true);
+        }
+        _ => {}
+    }
     let text =
         fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
     Ok(text)
 }
 
-pub fn try_rg_files(path: &Path) -> Result<bool> {
-    let output = Command::new("rg").arg("--files").arg(path).output();
-    let Ok(output) = output else {
-        return Ok(false);
-    };
-    if !output.status.success() {
-        return Ok(false);
+/// True for formats whose text is extracted on read and cannot be written back.
+pub fn is_extracted_document(path: &Path) -> bool {
+    EXTRACTED_DOCUMENTS.contains(&lower_extension(path).as_str())
+}
+
+fn lower_extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn extract_pdf_text(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    // pdf-extract panics on some malformed files instead of returning an error.
+    let text = catch_expected_panic(|| pdf_extract::extract_text_from_mem(&bytes))
+        .map_err(|_| anyhow::anyhow!("PDF parser crashed on {}", path.display()))?
+        .with_context(|| format!("Failed to extract text from {}", path.display()))?;
+    if text.trim().is_empty() {
+        bail!(
+            "No extractable text in {} (scanned PDFs need OCR first)",
+            path.display()
+        );
     }
-    print!("{}", String::from_utf8_lossy(&output.stdout));
-    Ok(true)
+    Ok(text)
 }
 
-pub fn try_rg_grep(path: &Path, pattern: &str) -> Result<bool> {
-    let output = Command::new("rg").arg("-n").arg(pattern).arg(path).output();
-    let Ok(output) = output else {
-        return Ok(false);
-    };
-    if !output.status.success() {
-        return Ok(false);
+fn extract_docx_text(path: &Path) -> Result<String> {
+    let file =
+        fs::File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Not a valid .docx archive: {}", path.display()))?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .with_context(|| format!("Missing word/document.xml in {}", path.display()))?
+        .read_to_string(&mut xml)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(docx_xml_to_text(&xml))
+}
+
+/// Keeps `<w:t>` runs, turning paragraphs, breaks and tabs into plain-text layout.
+fn docx_xml_to_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut in_text = false;
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        if in_text {
+            out.push_str(&unescape_xml(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        let name = tag
+            .trim_start_matches('/')
+            .split([' ', '/'])
+            .next()
+            .unwrap_or_default();
+        let closing = tag.starts_with('/');
+        match name {
+            "w:t" => in_text = !closing && !tag.ends_with('/'),
+            "w:p" if closing => out.push('\n'),
+            "w:br" | "w:cr" => out.push('\n'),
+            "w:tab" => out.push('\t'),
+            "w:tc" if closing => out.push('\t'),
+            _ => {}
+        }
+        rest = &rest[start + end + 1..];
     }
-    print!("{}", String::from_utf8_lossy(&output.stdout));
-    Ok(true)
+    out
 }
 
-pub fn list_files_output(path: &Path) -> Result<String> {
-    if let Some(out) = rg_files_output(path)? {
-        return Ok(out);
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub fn is_stdin_path(path: &Path) -> bool {
+    path.as_os_str() == STDIN_PATH
+}
+
+/// Reads a file, or all of stdin when the path is `-`.
+pub fn read_text_source(path: &Path) -> Result<String> {
+    if is_stdin_path(path) {
+        return read_stdin_text();
     }
-    list_files_recursive_output(path)
+    read_text_file(path)
 }
 
-pub fn grep_output(path: &Path, pattern: &str) -> Result<String> {
-    if let Some(out) = rg_grep_output(path, pattern)? {
-        return Ok(out);
+/// Reads an image as a base64 `data:` URL for multi-part vision content.
+pub fn read_image_data_url(path: &Path) -> Result<String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let mime = match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => bail!(
+            "Unsupported image type: {} (use png, jpg, gif or webp)",
+            path.display()
+        ),
+    };
+    if !path.exists() {
+        bail!("File does not exist: {}", path.display());
     }
-    grep_recursive_output(path, pattern)
+    let len = fs::metadata(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .len();
+    if len > MAX_IMAGE_BYTES {
+        bail!(
+            "Image too large: {} ({} bytes, limit {})",
+            path.display(),
+            len,
+            MAX_IMAGE_BYTES
+        );
+    }
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(format!("data:{};base64,{}", mime, STANDARD.encode(bytes)))
 }
 
-pub fn rg_files_output(path: &Path) -> Result<Option<String>> {
-    let output = Command::new("rg").arg("--files").arg(path).output();
-    let Ok(output) = output else {
+pub fn read_stdin_text() -> Result<String> {
+    let mut buf = Vec::new();
+    io::stdin()
+        .read_to_end(&mut buf)
+        .context("Failed to read stdin")?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+/// Returns piped stdin content, or `None` when stdin is an interactive terminal
+/// or the pipe is empty.
+pub fn read_piped_stdin() -> Result<Option<String>> {
+    if io::stdin().is_terminal() {
         return Ok(None);
-    };
-    if !output.status.success() {
+    }
+    let text = read_stdin_text()?;
+    if text.trim().is_empty() {
         return Ok(None);
     }
-    Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+    Ok(Some(text))
 }
 
-pub fn rg_grep_output(path: &Path, pattern: &str) -> Result<Option<String>> {
-    let output = Command::new("rg").arg("-n").arg(pattern).arg(path).output();
+pub fn try_rg_files(path: &Path) -> Result<bool> {
+    let output = Command::new("rg")
+        .arg("--files")
+        .args(rg_ignore_args())
+        .arg(path)
+        .output();
     let Ok(output) = output else {
-        return Ok(None);
+        return Ok(false);
     };
     if !output.status.success() {
-        return Ok(None);
+        return Ok(false);
+    }
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    Ok(true)
+}
+
+/// The `/list` output, from the cached workspace file list.
+pub fn list_files_output(path: &Path) -> Result<String> {
+    let mut out = String::new();
+    for file in indexed_files(path)? {
+        out.push_str(&format!("{}\n", file.display()));
     }
-    Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+    Ok(out)
 }
 
 pub fn list_files_recursive(root: &Path) -> Result<()> {
@@ -79,12 +228,6 @@ pub fn list_files_recursive(root: &Path) -> Result<()> {
     Ok(())
 }
 
-pub fn grep_recursive(root: &Path, pattern: &str) -> Result<()> {
-    let out = grep_recursive_output(root, pattern)?;
-    print!("{out}");
-    Ok(())
-}
-
 fn list_files_recursive_output(root: &Path) -> Result<String> {
     if !root.exists() {
         bail!("Path does not exist: {}", root.display());
@@ -98,58 +241,194 @@ fn list_files_recursive_output(root: &Path) -> Result<String> {
     Ok(out)
 }
 
-fn grep_recursive_output(root: &Path, pattern: &str) -> Result<String> {
+/// Files under `root`, sorted. Directories are read in parallel.
+fn walk(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut out = walk_dir(root)?;
+    out.sort();
+    Ok(out)
+}
+
+fn walk_dir(path: &Path) -> Result<Vec<PathBuf>> {
+    if is_ignored_dir(path) || is_dongshan_ignored(path) {
+        return Ok(Vec::new());
+    }
+    let metadata = fs::metadata(path)
+        .with_context(|| format!("Failed to read metadata {}", path.display()))?;
+    if !metadata.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = fs::read_dir(path)
+        .with_context(|| format!("Failed to read dir {}", path.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    let nested = entries
+        .par_iter()
+        .map(|entry| walk_dir(entry))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(nested.into_iter().flatten().collect())
+}
+
+/// Files searched by `search_workspace` are skipped above this size.
+const MAX_SEARCH_FILE_BYTES: u64 = 4 * 1024 * 1024;
+/// Matched lines are clipped to this many chars.
+const MAX_MATCH_LINE_CHARS: usize = 300;
+
+/// Files under `root` the agent may see, sorted. Honors .gitignore,
+/// .dongshanignore and the usual build directories; `glob` (e.g. `*.rs`)
+/// narrows by path. Paths are relative to the current directory and use `/`
+/// on every platform, so results do not depend on ripgrep or the OS.
+pub fn workspace_files(root: &Path, glob: Option<&str>) -> Result<Vec<PathBuf>> {
     if !root.exists() {
         bail!("Path does not exist: {}", root.display());
     }
-    let pattern_lower = pattern.to_lowercase();
-    let mut out = String::new();
-    for entry in walk(root)? {
-        if !entry.is_file() {
-            continue;
-        }
-        let Ok(content) = fs::read_to_string(&entry) else {
-            continue;
-        };
-        for (idx, line) in content.lines().enumerate() {
-            if line.to_lowercase().contains(&pattern_lower) {
-                out.push_str(&format!(
-                    "{}:{}:{}\n",
-                    entry.display(),
-                    idx + 1,
-                    line.trim()
-                ));
-            }
-        }
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(false)
+        .parents(true)
+        .require_git(false)
+        .filter_entry(|e| !is_ignored_dir(e.path()) && !is_dongshan_ignored(e.path()));
+    if let Some(glob) = glob.map(str::trim).filter(|g| !g.is_empty()) {
+        let overrides = OverrideBuilder::new(root)
+            .add(glob)
+            .and_then(|b| b.build())
+            .with_context(|| format!("Invalid glob: {glob}"))?;
+        builder.overrides(overrides);
     }
+    let mut out: Vec<PathBuf> = builder
+        .build()
+        .flatten()
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .map(|e| e.into_path())
+        .collect();
+    out.sort();
     Ok(out)
 }
 
-fn walk(root: &Path) -> Result<Vec<PathBuf>> {
-    let mut out = Vec::new();
-    let mut stack = vec![root.to_path_buf()];
-    while let Some(path) = stack.pop() {
-        if is_ignored_dir(&path) {
-            continue;
-        }
-        let metadata = fs::metadata(&path)
-            .with_context(|| format!("Failed to read metadata {}", path.display()))?;
-        if metadata.is_dir() {
-            let entries = fs::read_dir(&path)
-                .with_context(|| format!("Failed to read dir {}", path.display()))?;
-            for entry in entries {
-                let entry = entry?;
-                stack.push(entry.path());
+/// `path` relative to the current directory with `/` separators; absolute
+/// when it lies elsewhere.
+pub fn display_path(path: &Path) -> String {
+    let cwd = std::env::current_dir()
+        .ok()
+        .and_then(|c| c.canonicalize().ok());
+    match cwd.as_deref().and_then(|c| path.strip_prefix(c).ok()) {
+        Some(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+        Some(rel) => rel.to_string_lossy().replace('\\', "/"),
+        None => path.to_string_lossy().replace('\\', "/"),
+    }
+}
+
+/// `path` under `root` with `/` separators and no leading `./`; `None` for
+/// `root` itself.
+pub fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    let s = rel.to_string_lossy().replace('\\', "/");
+    let s = s.trim_start_matches("./").to_string();
+    if s.is_empty() { None } else { Some(s) }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GrepMatch {
+    pub file: String,
+    pub line: usize,
+    pub text: String,
+}
+
+pub struct SearchOptions<'a> {
+    pub glob: Option<&'a str>,
+    pub ignore_case: bool,
+    /// Match `pattern` as plain text instead of a regex.
+    pub literal: bool,
+    pub max_results: usize,
+}
+
+/// Matching lines `/grep` and `fs grep` print before they stop.
+const MAX_GREP_RESULTS: usize = 2000;
+/// Files searched in parallel at a time; matches keep path order.
+const SEARCH_BATCH_FILES: usize = 256;
+
+/// Regex search over `workspace_files`, backing `fs.grep`, `/grep` and
+/// `fs grep`. Returns at most `max_results` matches and the total number
+/// found; binary and very large files are skipped. Files are searched in
+/// parallel, batch by batch, so the order is the same on every run.
+pub fn search_workspace(
+    root: &Path,
+    pattern: &str,
+    opts: &SearchOptions,
+) -> Result<(Vec<GrepMatch>, usize)> {
+    let source = if opts.literal {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    let re = RegexBuilder::new(&source)
+        .case_insensitive(opts.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid regex {pattern:?} (set literal=true for plain text)"))?;
+    let mut matches = Vec::new();
+    let mut total = 0usize;
+    for batch in workspace_files(root, opts.glob)?.chunks(SEARCH_BATCH_FILES) {
+        let found: Vec<Vec<GrepMatch>> = batch
+            .par_iter()
+            .map(|file| search_file(file, &re))
+            .collect();
+        for m in found.into_iter().flatten() {
+            total += 1;
+            if matches.len() < opts.max_results {
+                matches.push(m);
             }
-        } else {
-            out.push(path);
         }
     }
-    out.sort();
+    Ok((matches, total))
+}
+
+fn search_file(file: &Path, re: &Regex) -> Vec<GrepMatch> {
+    if fs::metadata(file).map_or(true, |m| m.len() > MAX_SEARCH_FILE_BYTES) {
+        return Vec::new();
+    }
+    let Ok(bytes) = fs::read(file) else {
+        return Vec::new();
+    };
+    if bytes.iter().take(8192).any(|b| *b == 0) {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| re.is_match(line))
+        .map(|(idx, line)| GrepMatch {
+            file: display_path(file),
+            line: idx + 1,
+            text: truncate_with_suffix(line.trim(), MAX_MATCH_LINE_CHARS, "..."),
+        })
+        .collect()
+}
+
+/// `file:line:text` lines for `/grep` and `fs grep`, from [`search_workspace`].
+/// The pattern is a regex, case-insensitive unless it contains an uppercase
+/// letter.
+pub fn grep_output(root: &Path, pattern: &str) -> Result<String> {
+    let opts = SearchOptions {
+        glob: None,
+        ignore_case: !pattern.chars().any(char::is_uppercase),
+        literal: false,
+        max_results: MAX_GREP_RESULTS,
+    };
+    // Absolute, so `display_path` prints paths relative to the workspace.
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let (matches, total) = search_workspace(&root, pattern, &opts)?;
+    let mut out = String::new();
+    for m in &matches {
+        out.push_str(&format!("{}:{}:{}\n", m.file, m.line, m.text));
+    }
+    if total > matches.len() {
+        out.push_str(&format!(
+            "... results capped at {MAX_GREP_RESULTS} of {total} matches; narrow the pattern or path\n"
+        ));
+    }
     Ok(out)
 }
 
-fn is_ignored_dir(path: &Path) -> bool {
+pub fn is_ignored_dir(path: &Path) -> bool {
     let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
         return false;
     };
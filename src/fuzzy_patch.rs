@@ -0,0 +1,246 @@
+/// Lowest share of matching lines for a fuzzy hunk match.
+const FUZZY_MIN_SIMILARITY: f64 = 0.75;
+/// Old text needs this many non-blank lines before fuzzy matching is tried;
+/// shorter snippets match too many places.
+const FUZZY_MIN_LINES: usize = 3;
+
+/// How an edit's old text was located when it did not match exactly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FuzzyKind {
+    /// Same lines once indentation and runs of whitespace are ignored.
+    Whitespace,
+    /// Most lines match; the rest drifted. Holds the line similarity (0..=1).
+    Similar(f64),
+}
+
+/// Whole-line region of the file that stands in for the old text.
+#[derive(Debug, Clone)]
+pub struct FuzzyMatch {
+    /// Byte range in the file, without the last line's line ending.
+    pub range: std::ops::Range<usize>,
+    /// 1-based first line.
+    pub line: usize,
+    pub kind: FuzzyKind,
+}
+
+impl FuzzyMatch {
+    /// Short note for tool reports, e.g. "line 40, whitespace-insensitive".
+    pub fn describe(&self) -> String {
+        match self.kind {
+            FuzzyKind::Whitespace => format!("line {}, whitespace-insensitive", self.line),
+            FuzzyKind::Similar(score) => {
+                format!("line {}, {:.0}% of lines matched", self.line, score * 100.0)
+            }
+        }
+    }
+}
+
+fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Finds where `old` most likely sits in `text` when an exact search failed:
+/// first ignoring whitespace, then allowing a few drifted lines. Among equally
+/// good candidates the one closest to `near_line` (0-based) wins; without a
+/// clear winner there is no match.
+pub fn find_fuzzy(text: &str, old: &str, near_line: Option<usize>) -> Option<FuzzyMatch> {
+    let old_lines: Vec<String> = old
+        .trim_end_matches(['\n', '\r'])
+        .lines()
+        .map(normalize)
+        .collect();
+    if old_lines.iter().all(|l| l.is_empty()) {
+        return None;
+    }
+    let spans = line_spans(text);
+    let file_lines: Vec<String> = spans.iter().map(|&(s, e)| normalize(&text[s..e])).collect();
+    let n = old_lines.len();
+
+    let exact: Vec<(usize, usize)> = (0..file_lines.len().saturating_sub(n - 1))
+        .filter(|&i| file_lines[i..i + n] == old_lines[..])
+        .map(|i| (i, n))
+        .collect();
+    if let Some((start, len)) = pick(exact, near_line) {
+        return Some(build(&spans, start, len, FuzzyKind::Whitespace));
+    }
+
+    if old_lines.iter().filter(|l| !l.is_empty()).count() < FUZZY_MIN_LINES {
+        return None;
+    }
+    let mut candidates = Vec::new();
+    for len in [n, n + 1, n.saturating_sub(1)] {
+        if len == 0 || len > file_lines.len() {
+            continue;
+        }
+        for i in 0..=file_lines.len() - len {
+            let score = similarity(&old_lines, &file_lines[i..i + len]);
+            if score >= FUZZY_MIN_SIMILARITY {
+                candidates.push(((i, len), score));
+            }
+        }
+    }
+    let best = candidates.iter().map(|c| c.1).fold(0.0, f64::max);
+    let best_spans = candidates
+        .iter()
+        .filter(|c| c.1 == best)
+        .map(|c| c.0)
+        .collect();
+    let (start, len) = pick(best_spans, near_line)?;
+    Some(build(&spans, start, len, FuzzyKind::Similar(best)))
+}
+
+/// The only candidate `(start, len)`, or the one strictly closest to `near_line`.
+fn pick(mut items: Vec<(usize, usize)>, near_line: Option<usize>) -> Option<(usize, usize)> {
+    items.sort_by_key(|c| c.0);
+    items.dedup_by_key(|c| c.0);
+    match items.len() {
+        0 => None,
+        1 => Some(items[0]),
+        _ => {
+            let near = near_line?;
+            items.sort_by_key(|c| c.0.abs_diff(near));
+            (items[0].0.abs_diff(near) < items[1].0.abs_diff(near)).then_some(items[0])
+        }
+    }
+}
+
+/// 2 * LCS / (a + b) over normalized lines.
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    let mut prev = vec![0usize; b.len() + 1];
+    for x in a {
+        let mut cur = vec![0usize; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            cur[j + 1] = if x == y {
+                prev[j] + 1
+            } else {
+                prev[j + 1].max(cur[j])
+            };
+        }
+        prev = cur;
+    }
+    2.0 * prev[b.len()] as f64 / (a.len() + b.len()) as f64
+}
+
+/// (start, end) byte offsets of each line, without its line ending.
+fn line_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\n', '\r']);
+        spans.push((start, start + body.len()));
+        start += line.len();
+    }
+    spans
+}
+
+fn build(spans: &[(usize, usize)], start: usize, len: usize, kind: FuzzyKind) -> FuzzyMatch {
+    FuzzyMatch {
+        range: spans[start].0..spans[start + len - 1].1,
+        line: start + 1,
+        kind,
+    }
+}
+
+/// Replacement for a fuzzy match: `new` re-indented from the old text's first
+/// line indentation to the matched region's, with the file's line endings.
+pub fn fit_replacement(text: &str, m: &FuzzyMatch, old: &str, new: &str) -> String {
+    let indent = |s: &str| -> String {
+        s.lines()
+            .find(|l| !l.trim().is_empty())
+            .map(|l| l[..l.len() - l.trim_start().len()].to_string())
+            .unwrap_or_default()
+    };
+    let from = indent(old);
+    let to = indent(&text[m.range.clone()]);
+    let new = if old.ends_with('\n') {
+        new.strip_suffix('\n').unwrap_or(new)
+    } else {
+        new
+    };
+    let eol = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    new.lines()
+        .map(|line| match line.strip_prefix(from.as_str()) {
+            Some(rest) if from != to => format!("{to}{rest}"),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(eol)
+}
+
+/// 0-based line of byte offset `pos`.
+pub fn line_of(text: &str, pos: usize) -> usize {
+    text[..pos.min(text.len())].matches('\n').count()
+}
+
+/// Where an edit applied by [`apply_edit`] landed.
+#[derive(Debug, Clone)]
+pub enum EditHit {
+    /// The single exact occurrence of the old text.
+    Exact,
+    /// `replace_all`: every one of this many occurrences.
+    All(usize),
+    Fuzzy(FuzzyMatch),
+}
+
+/// Why [`apply_edit`] left the text unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditMiss {
+    Empty,
+    NotFound,
+    /// The old text occurs this many times and the edit is not `replace_all`.
+    Ambiguous(usize),
+}
+
+impl std::fmt::Display for EditMiss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditMiss::Empty => write!(f, "old text is empty"),
+            EditMiss::NotFound => write!(f, "old text not found"),
+            EditMiss::Ambiguous(n) => write!(
+                f,
+                "old text matches {} places; include more surrounding lines",
+                n
+            ),
+        }
+    }
+}
+
+/// Applies one `old` -> `new` edit to `text`. Without `replace_all` the old
+/// text must occur exactly once; when it does not occur at all the edit falls
+/// back to [`find_fuzzy`] near `near_line`, which is moved to the hit's line
+/// so a sequence of edits keeps its place in the file.
+pub fn apply_edit(
+    text: &mut String,
+    old: &str,
+    new: &str,
+    replace_all: bool,
+    near_line: &mut Option<usize>,
+) -> Result<EditHit, EditMiss> {
+    if old.is_empty() {
+        return Err(EditMiss::Empty);
+    }
+    let count = text.matches(old).count();
+    if replace_all {
+        if count == 0 {
+            return Err(EditMiss::NotFound);
+        }
+        *text = text.replace(old, new);
+        return Ok(EditHit::All(count));
+    }
+    match count {
+        0 => {
+            let m = find_fuzzy(text, old, *near_line).ok_or(EditMiss::NotFound)?;
+            let replacement = fit_replacement(text, &m, old, new);
+            *near_line = Some(m.line - 1);
+            text.replace_range(m.range.clone(), &replacement);
+            Ok(EditHit::Fuzzy(m))
+        }
+        1 => {
+            let pos = text.find(old).ok_or(EditMiss::NotFound)?;
+            *near_line = Some(line_of(text, pos));
+            text.replace_range(pos..pos + old.len(), new);
+            Ok(EditHit::Exact)
+        }
+        n => Err(EditMiss::Ambiguous(n)),
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::{OnceLock, RwLock};
+
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+
+use crate::util::colors_enabled;
+
+pub const DEFAULT_COLOR_THEME: &str = "base16-ocean.dark";
+/// Theme name that turns code highlighting off while keeping other colors.
+pub const NO_COLOR_THEME: &str = "none";
+
+static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+static ACTIVE_THEME: RwLock<Option<String>> = RwLock::new(None);
+
+fn syntaxes() -> &'static SyntaxSet {
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+pub fn set_color_theme(name: &str) {
+    if let Ok(mut guard) = ACTIVE_THEME.write() {
+        *guard = Some(name.trim().to_string());
+    }
+}
+
+pub fn available_themes() -> Vec<String> {
+    let mut names: Vec<String> = themes().themes.keys().cloned().collect();
+    names.push(NO_COLOR_THEME.to_string());
+    names
+}
+
+fn active_theme() -> Option<&'static Theme> {
+    let name = ACTIVE_THEME
+        .read()
+        .ok()
+        .and_then(|g| g.clone())
+        .unwrap_or_else(|| DEFAULT_COLOR_THEME.to_string());
+    if name.eq_ignore_ascii_case(NO_COLOR_THEME) {
+        return None;
+    }
+    let set = themes();
+    set.themes
+        .get(&name)
+        .or_else(|| set.themes.get(DEFAULT_COLOR_THEME))
+}
+
+/// Per-code-block syntax highlighter driven one line at a time, so it can be
+/// fed from streamed output.
+pub struct CodeHighlighter {
+    lines: HighlightLines<'static>,
+}
+
+impl CodeHighlighter {
+    /// Returns `None` when colors are disabled, the theme is `none`, or the
+    /// language tag is unknown.
+    pub fn for_lang(lang: &str) -> Option<Self> {
+        if !colors_enabled() {
+            return None;
+        }
+        let theme = active_theme()?;
+        let token = lang.split_whitespace().next().unwrap_or("");
+        if token.is_empty() {
+            return None;
+        }
+        let ss = syntaxes();
+        let syntax = ss
+            .find_syntax_by_token(token)
+            .or_else(|| ss.find_syntax_by_extension(token))?;
+        Some(Self {
+            lines: HighlightLines::new(syntax, theme),
+        })
+    }
+
+    pub fn highlight_line(&mut self, line: &str) -> String {
+        let with_newline = format!("{line}\n");
+        match self.lines.highlight_line(&with_newline, syntaxes()) {
+            Ok(ranges) => {
+                let escaped = as_24_bit_terminal_escaped(&ranges, false);
+                format!("{}\x1b[0m", escaped.trim_end_matches('\n'))
+            }
+            Err(_) => line.to_string(),
+        }
+    }
+}
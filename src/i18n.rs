@@ -0,0 +1,567 @@
+use std::env;
+use std::sync::OnceLock;
+
+use anyhow::{Result, bail};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    ZhCn,
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Accepted values for the `locale` config key.
+pub const LOCALE_SETTINGS: &[&str] = &["auto", "en", "zh-CN"];
+
+/// (key, English, Simplified Chinese). `{}` placeholders are filled in order by [`tf`].
+const MESSAGES: &[(&str, &str, &str)] = &[
+    ("banner.tagline", "AI Coding Assistant", "AI 编程助手"),
+    ("banner.session", "session", "会话"),
+    ("banner.model", "model", "模型"),
+    ("banner.mode", "mode", "模式"),
+    ("help.help", "show this message", "显示本帮助"),
+    ("help.exit", "quit", "退出"),
+    (
+        "help.status",
+        "show model/tool/error status",
+        "查看模型/工具/错误状态",
+    ),
+    (
+        "help.render",
+        "toggle terminal markdown rendering",
+        "切换终端 Markdown 渲染",
+    ),
+    ("help.new", "start a new session", "新建会话"),
+    (
+        "help.clear",
+        "clear current session history",
+        "清空当前会话历史",
+    ),
+    (
+        "help.session_list",
+        "list saved sessions",
+        "列出已保存的会话",
+    ),
+    ("help.session_use", "switch session", "切换会话"),
+    ("help.session_rm", "delete session", "删除会话"),
+    (
+        "help.mode_show",
+        "show current execution mode",
+        "查看当前执行模式",
+    ),
+    ("help.mode_set", "switch execution mode", "切换执行模式"),
+    ("help.read", "read a file into context", "读取文件到上下文"),
+    ("help.askfile", "ask about a file", "针对文件提问"),
+    (
+        "help.fetch",
+        "fetch a web page into context",
+        "抓取网页内容到上下文",
+    ),
+    (
+        "help.image",
+        "attach an image for vision models",
+        "附加图片（视觉模型）",
+    ),
+    (
+        "help.diff",
+        "show git changes, optionally add them to context",
+        "查看 git 改动，可选加入上下文",
+    ),
+    (
+        "help.undo",
+        "revert files changed by the last agent turn",
+        "撤销上一轮 agent 改动的文件",
+    ),
+    (
+        "help.compact",
+        "summarize older messages now, with optional guidance",
+        "立即压缩较早的消息，可附加要求",
+    ),
+    (
+        "help.tokens",
+        "estimated token breakdown of the current context",
+        "估算当前上下文各部分的 token 数",
+    ),
+    (
+        "help.system",
+        "view or override the system prompt for this session",
+        "查看或覆盖当前会话的系统提示词",
+    ),
+    (
+        "help.cost",
+        "token and cost totals of this session",
+        "当前会话的 token 与费用统计",
+    ),
+    (
+        "help.trust",
+        "review the auto-exec policy and trust this workspace",
+        "查看自动执行策略并信任当前工作区",
+    ),
+    (
+        "help.remember",
+        "remember a fact for this workspace, or everywhere",
+        "记住一条事实（当前工作区或全局）",
+    ),
+    (
+        "help.forget",
+        "forget a remembered fact",
+        "删除一条记住的事实",
+    ),
+    (
+        "help.retry",
+        "drop the last reply and ask again, optionally with another model",
+        "丢弃上一条回复并重新请求，可指定其他模型",
+    ),
+    ("help.list", "list files", "列出文件"),
+    ("help.grep", "search files", "搜索文件"),
+    ("help.prompt_show", "show active prompt", "查看当前 prompt"),
+    ("help.prompt_list", "list prompts", "列出 prompt"),
+    ("help.prompt_use", "switch prompt", "切换 prompt"),
+    (
+        "help.as",
+        "send one message under another prompt",
+        "用另一个 prompt 发送单条消息",
+    ),
+    ("help.model_list", "list available models", "列出可用模型"),
+    ("help.model_use", "switch model", "切换模型"),
+    (
+        "help.compare",
+        "ask several models the same prompt",
+        "用同一问题对比多个模型",
+    ),
+    (
+        "help.editor",
+        "compose the next message in $EDITOR",
+        "在 $EDITOR 中编写下一条消息",
+    ),
+    (
+        "help.multiline",
+        "multi-line input: end with an empty line, or close the ``` fence",
+        "多行输入：以空行结束，或闭合 ``` 代码块",
+    ),
+    (
+        "chat.new_session",
+        "Started new session: {}",
+        "已新建会话: {}",
+    ),
+    (
+        "chat.cleared",
+        "Session history cleared.",
+        "会话历史已清空。",
+    ),
+    (
+        "chat.no_sessions",
+        "No saved sessions.",
+        "没有已保存的会话。",
+    ),
+    ("chat.saved_sessions", "Saved sessions:", "已保存的会话:"),
+    (
+        "chat.switched",
+        "Switched session: {} ({} messages)",
+        "已切换会话: {}（{} 条消息）",
+    ),
+    (
+        "chat.cannot_remove_active",
+        "Cannot remove current active session: {}",
+        "不能删除当前会话: {}",
+    ),
+    ("chat.diff_empty", "No changes.", "没有改动。"),
+    (
+        "chat.compact_nothing",
+        "History is too short to compact.",
+        "历史消息太少，无需压缩。",
+    ),
+    (
+        "chat.compacted",
+        "Compacted {} messages into a summary ({} -> {} chars).",
+        "已将 {} 条消息压缩为摘要（{} -> {} 字符）。",
+    ),
+    (
+        "chat.remembered",
+        "Remembered #{}; it is added to the system prompt from now on.",
+        "已记住 #{}，之后会加入系统提示词。",
+    ),
+    ("chat.forgot", "Forgot #{}.", "已删除 #{}。"),
+    (
+        "chat.forget_missing",
+        "No memory with id {}. See `dongshan memory list`.",
+        "没有 id 为 {} 的记忆，可用 `dongshan memory list` 查看。",
+    ),
+    (
+        "chat.undo_nothing",
+        "Nothing to undo: no agent file changes recorded in this session.",
+        "没有可撤销的内容：本次会话尚未记录 agent 的文件改动。",
+    ),
+    (
+        "chat.undo_commands",
+        "Not undone: that turn also ran these shell commands; check their changes with /diff or git:",
+        "未撤销：该轮还执行了以下 shell 命令，其改动请用 /diff 或 git 检查：",
+    ),
+    (
+        "chat.undo_done",
+        "Reverted {} file(s) from the last agent turn (previous contents saved to backups):",
+        "已撤销上一轮 agent 改动的 {} 个文件（当前内容已保存到备份）：",
+    ),
+    (
+        "chat.system_set",
+        "System prompt replaced for session '{}' (mode and tool instructions still apply).",
+        "已替换会话 '{}' 的系统提示词（模式与工具说明仍会保留）。",
+    ),
+    (
+        "chat.system_append",
+        "Text appended to the system prompt of session '{}'.",
+        "已向会话 '{}' 的系统提示词追加内容。",
+    ),
+    (
+        "chat.system_reset",
+        "Session '{}' uses the configured system prompt again.",
+        "会话 '{}' 已恢复使用配置中的系统提示词。",
+    ),
+    (
+        "chat.session_model",
+        "Model switched to '{}' for session '{}' (global default unchanged).",
+        "模型已切换为 '{}'，仅作用于会话 '{}'（全局默认不变）。",
+    ),
+    (
+        "chat.persona",
+        "(as '{}' for this message)",
+        "（本条消息使用 '{}'）",
+    ),
+    (
+        "chat.persona_missing",
+        "Prompt not found: {}. See /prompt list.",
+        "未找到提示词：{}。可用 /prompt list 查看。",
+    ),
+    (
+        "chat.persona_usage",
+        "Usage: /as <prompt> <message>",
+        "用法：/as <prompt> <消息>",
+    ),
+    (
+        "chat.session_prompt",
+        "Prompt switched to '{}' for session '{}' (global default unchanged).",
+        "提示词已切换为 '{}'，仅作用于会话 '{}'（全局默认不变）。",
+    ),
+    (
+        "chat.cost_empty",
+        "No model requests in this session yet.",
+        "当前会话还没有模型请求。",
+    ),
+    (
+        "chat.retry_nothing",
+        "Nothing to retry: no previous request in this session.",
+        "没有可重试的请求：当前会话中还没有提问。",
+    ),
+    (
+        "chat.retrying",
+        "Retrying last request with {} ({} message(s) dropped)...",
+        "正在使用 {} 重试上一次请求（已丢弃 {} 条消息）...",
+    ),
+    (
+        "chat.image_usage",
+        "Usage: /image <path> [question]",
+        "用法：/image <路径> [问题]",
+    ),
+    (
+        "chat.image_attached",
+        "Attached {}. Ask a follow-up question to discuss it.",
+        "已附加 {}，可以继续提问来讨论它。",
+    ),
+    (
+        "chat.fetch_usage",
+        "Usage: /fetch <url>",
+        "用法：/fetch <url>",
+    ),
+    (
+        "chat.fetched",
+        "Fetched {} ({} chars, content hidden). Ask a follow-up question to use it.",
+        "已抓取 {}（{} 字符，内容未显示），可以继续提问来使用它。",
+    ),
+    (
+        "chat.remember_usage",
+        "Usage: /remember [--global] <fact>",
+        "用法：/remember [--global] <事实>",
+    ),
+    (
+        "chat.forget_usage",
+        "Usage: /forget <id>",
+        "用法：/forget <id>",
+    ),
+    (
+        "chat.retry_usage",
+        "Usage: /retry [--model <name>]",
+        "用法：/retry [--model <模型名>]",
+    ),
+    (
+        "chat.model_not_in_catalog",
+        "Model not in catalog: {}",
+        "模型不在模型列表中：{}",
+    ),
+    (
+        "chat.system_header",
+        "system prompt ({}, {}):",
+        "系统提示词（{}，{}）：",
+    ),
+    ("chat.system_source_prompt", "prompt '{}'", "提示词 '{}'"),
+    (
+        "chat.system_source_override",
+        "session override",
+        "会话覆盖",
+    ),
+    (
+        "chat.system_source_append",
+        "prompt '{}' + session append",
+        "提示词 '{}' + 会话追加",
+    ),
+    (
+        "chat.system_text_usage",
+        "Usage: /system {} <text>",
+        "用法：/system {} <文本>",
+    ),
+    (
+        "chat.system_usage",
+        "Usage: /system show|set <text>|append <text>|reset",
+        "用法：/system show|set <文本>|append <文本>|reset",
+    ),
+    (
+        "chat.compare_usage",
+        "Usage: /compare <model-a,model-b,...> <prompt>",
+        "用法：/compare <模型a,模型b,...> <问题>",
+    ),
+    (
+        "chat.diff_add_prompt",
+        "add this diff to context? [y/N]: ",
+        "将该 diff 加入上下文？[y/N]: ",
+    ),
+    (
+        "chat.diff_added",
+        "Added diff to context ({} chars).",
+        "已将 diff 加入上下文（{} 字符）。",
+    ),
+    (
+        "chat.editor_sent",
+        "(sending {} lines from editor)",
+        "（发送编辑器中的 {} 行内容）",
+    ),
+    (
+        "chat.editor_empty",
+        "Editor message was empty; nothing sent.",
+        "编辑器内容为空，未发送。",
+    ),
+    ("chat.removed", "Removed session: {}", "已删除会话: {}"),
+    ("chat.not_found", "Session not found: {}", "会话不存在: {}"),
+    (
+        "chat.interrupted",
+        "assistant> Request interrupted: {}",
+        "assistant> 请求中断: {}",
+    ),
+    (
+        "chat.continue_hint",
+        "assistant> You can continue chatting and send the next message.",
+        "assistant> 可以继续对话，直接发送下一条消息。",
+    ),
+    (
+        "status.collecting_context",
+        "collecting workspace context",
+        "正在收集工作区上下文",
+    ),
+    ("status.waiting", "waiting response", "等待响应"),
+    ("status.compacting", "compacting session", "正在压缩会话"),
+    (
+        "status.summarizing_output",
+        "summarizing tool output",
+        "正在摘要工具输出",
+    ),
+    ("status.fetching", "fetching {}", "正在抓取 {}"),
+    ("status.comparing", "asking {} models", "正在询问 {} 个模型"),
+    ("table.model", "model", "模型"),
+    ("table.latency", "latency", "耗时"),
+    ("table.turn", "turn", "轮次"),
+    ("table.requests", "req", "请求"),
+    ("table.in", "in", "输入"),
+    ("table.out", "out", "输出"),
+    ("table.cost", "cost", "费用"),
+    ("compare.failed", "failed", "失败"),
+    ("compare.error", "error: {}", "错误：{}"),
+    ("cost.running_total", "total ${}", "累计 ${}"),
+    ("cost.session_total", "session total", "会话合计"),
+    (
+        "cost.budget",
+        "budget: ${} ({}% used)",
+        "预算：${}（已用 {}%）",
+    ),
+    (
+        "cost.estimated_tokens",
+        "~ some requests had no provider usage; tokens are estimated.",
+        "~ 部分请求没有服务商返回的用量，token 数为估算值。",
+    ),
+    (
+        "cost.estimates_note",
+        "Costs are estimates; set `model_prices` in config.toml to override.",
+        "费用为估算值；可在 config.toml 中设置 `model_prices` 覆盖。",
+    ),
+    (
+        "tokens.header",
+        "Estimated tokens (~4 bytes/token; providers count differently)",
+        "估算 token 数（约 4 字节/token，各服务商计算方式不同）",
+    ),
+    ("tokens.system_prompt", "system prompt", "系统提示词"),
+    ("tokens.tool_schemas", "tool schemas", "工具定义"),
+    ("tokens.repo_map", "pinned: repo map", "固定：仓库地图"),
+    (
+        "tokens.session_summary",
+        "pinned: session summary",
+        "固定：会话摘要",
+    ),
+    (
+        "tokens.older_messages",
+        "{} older messages",
+        "{} 条较早的消息",
+    ),
+    ("tokens.total", "total", "合计"),
+    (
+        "tokens.window",
+        "({}% of the {} window)",
+        "（{}%，{} 的上下文窗口）",
+    ),
+    (
+        "notify.turn_done",
+        "dongshan: reply ready",
+        "dongshan：回复已完成",
+    ),
+    (
+        "notify.agent_done",
+        "dongshan: agent run finished",
+        "dongshan：agent 任务已完成",
+    ),
+    (
+        "notify.agent_failed",
+        "dongshan: agent run failed",
+        "dongshan：agent 任务失败",
+    ),
+    (
+        "python.confirm",
+        "Run this Python snippet ({} lines) in the sandbox? [y/N]: ",
+        "在沙箱中运行这段 Python 代码（{} 行）？[y/N]: ",
+    ),
+    (
+        "exec.confirm",
+        "Run command `{}` ? [y=yes]/[n=no]/[a=always `{}`]/[q=stop]: ",
+        "执行命令 `{}` ？[y=是]/[n=否]/[a=始终允许 `{}`]/[q=停止]: ",
+    ),
+    (
+        "budget.confirm",
+        "Estimated spend ~${} reached the ${} budget. Continue for another ${}? [y/N]: ",
+        "估算花费约 ${} 已达到 ${} 预算。是否再追加 ${} 继续？[y/N]: ",
+    ),
+    (
+        "budget.stopped",
+        "Stopped: cost budget of ${} reached",
+        "已停止: 达到 ${} 的费用预算",
+    ),
+    (
+        "trust.header",
+        "Workspace {} is not trusted yet. In agent mode dongshan may act here as follows:",
+        "工作区 {} 尚未受信任。agent 模式下 dongshan 可以在此执行以下操作：",
+    ),
+    (
+        "trust.confirm",
+        "Trust this workspace and allow agent mode? [y/N]: ",
+        "是否信任此工作区并允许 agent 模式？[y/N]: ",
+    ),
+    (
+        "trust.denied",
+        "Workspace not trusted: chat-only mode. Approve later with /trust or `dongshan trust add`.",
+        "工作区未受信任：仅限对话模式。之后可通过 /trust 或 `dongshan trust add` 授权。",
+    ),
+    (
+        "trust.granted",
+        "Workspace trusted; agent mode is available (/mode agent-auto).",
+        "工作区已受信任，可使用 agent 模式（/mode agent-auto）。",
+    ),
+    (
+        "trust.noninteractive",
+        "Workspace {} is not trusted; running chat-only. Trust it with `dongshan trust add`.",
+        "工作区 {} 未受信任，仅以对话模式运行。可使用 `dongshan trust add` 信任。",
+    ),
+];
+
+/// Resolves the configured locale (`auto`, `en`, `zh-CN`); call once at startup.
+pub fn init_locale(setting: &str) {
+    let locale = parse_locale(setting).unwrap_or_else(detect_locale);
+    let _ = LOCALE.set(locale);
+}
+
+pub fn locale() -> Locale {
+    LOCALE.get().copied().unwrap_or(Locale::En)
+}
+
+/// Validates a `locale` config value.
+pub fn validate_locale_setting(setting: &str) -> Result<()> {
+    let s = setting.trim();
+    if s.eq_ignore_ascii_case("auto") || parse_locale(s).is_some() {
+        return Ok(());
+    }
+    bail!(
+        "Unknown locale: {}. Available: {}",
+        setting,
+        LOCALE_SETTINGS.join(", ")
+    )
+}
+
+fn parse_locale(setting: &str) -> Option<Locale> {
+    let s = setting.trim().to_ascii_lowercase().replace('_', "-");
+    if s == "en" || s.starts_with("en-") {
+        Some(Locale::En)
+    } else if s == "zh" || s.starts_with("zh-") {
+        Some(Locale::ZhCn)
+    } else {
+        None
+    }
+}
+
+/// Picks a locale from the usual POSIX variables, in precedence order.
+fn detect_locale() -> Locale {
+    for var in ["LC_ALL", "LC_MESSAGES", "LANG", "LANGUAGE"] {
+        let Ok(value) = env::var(var) else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        return if value.to_ascii_lowercase().starts_with("zh") {
+            Locale::ZhCn
+        } else {
+            Locale::En
+        };
+    }
+    Locale::En
+}
+
+/// Looks up a message for the active locale; unknown keys are returned as-is.
+pub fn t(key: &'static str) -> &'static str {
+    let Some((_, en, zh)) = MESSAGES.iter().find(|(k, _, _)| *k == key) else {
+        return key;
+    };
+    match locale() {
+        Locale::En => en,
+        Locale::ZhCn => zh,
+    }
+}
+
+/// Like [`t`], filling each `{}` with the next argument.
+pub fn tf(key: &'static str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    for (i, piece) in t(key).split("{}").enumerate() {
+        if i > 0
+            && let Some(arg) = args.next()
+        {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(piece);
+    }
+    out
+}
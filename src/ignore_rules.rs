@@ -0,0 +1,85 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::logging::log_verbose;
+
+/// Workspace file in gitignore syntax. Matching paths are left out of file
+/// listings, grep, project snapshots, context retrieval and agent file tools.
+pub const IGNORE_FILE: &str = ".dongshanignore";
+
+/// Rules of the workspace the process started in; `None` without an ignore file.
+fn rules() -> Option<&'static Gitignore> {
+    static RULES: OnceLock<Option<Gitignore>> = OnceLock::new();
+    RULES
+        .get_or_init(|| {
+            let cwd = std::env::current_dir().ok()?;
+            let root = cwd.canonicalize().unwrap_or(cwd);
+            let file = root.join(IGNORE_FILE);
+            if !file.is_file() {
+                return None;
+            }
+            let mut builder = GitignoreBuilder::new(&root);
+            if let Some(err) = builder.add(&file) {
+                log_verbose("ignore", &format!("{}: {}", file.display(), err));
+            }
+            builder.build().ok()
+        })
+        .as_ref()
+}
+
+/// Path of the active `.dongshanignore`, if the workspace has one.
+pub fn ignore_file_path() -> Option<PathBuf> {
+    rules().map(|r| r.path().join(IGNORE_FILE))
+}
+
+/// Extra `rg` arguments that apply the ignore file.
+pub fn rg_ignore_args() -> Vec<String> {
+    match ignore_file_path() {
+        Some(path) => vec![
+            "--ignore-file".to_string(),
+            path.to_string_lossy().to_string(),
+        ],
+        None => Vec::new(),
+    }
+}
+
+/// True when `path` (relative to the current directory or absolute) or one of
+/// its parents matches `.dongshanignore`.
+pub fn is_dongshan_ignored(path: &Path) -> bool {
+    let Some(rules) = rules() else {
+        return false;
+    };
+    let abs = absolute(path);
+    if abs == rules.path() || !abs.starts_with(rules.path()) {
+        return false;
+    }
+    rules
+        .matched_path_or_any_parents(&abs, abs.is_dir())
+        .is_ignore()
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    if let Ok(canonical) = joined.canonicalize() {
+        return canonical;
+    }
+    let mut out = PathBuf::new();
+    for comp in joined.components() {
+        match comp {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
@@ -0,0 +1,333 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ApprovalLevel, config_dir};
+use crate::diagnostics::now_unix_ts;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A detached `dongshan agent --background` run. Stored next to its log in
+/// `~/.dongshan/jobs/`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: String,
+    pub task: String,
+    pub session: String,
+    pub cwd: String,
+    pub pid: u32,
+    /// Start time of `pid` as the OS reports it, so a reused pid is not
+    /// mistaken for the job.
+    #[serde(default)]
+    pub pid_start: Option<String>,
+    pub started: u64,
+    #[serde(default)]
+    pub finished: Option<u64>,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Agent options forwarded to the detached process. It always runs with
+/// `--ci`: the caller resolves workspace trust before starting it.
+pub struct BackgroundAgent<'a> {
+    pub task: &'a str,
+    pub session: &'a str,
+    pub budget: Option<f64>,
+    pub cwd: Option<&'a str>,
+    pub seed: Option<u64>,
+    pub isolated: bool,
+    pub branch: Option<&'a str>,
+    pub report: Option<&'a Path>,
+    pub approval: Option<ApprovalLevel>,
+}
+
+fn jobs_dir() -> Result<PathBuf> {
+    Ok(config_dir()?.join("jobs"))
+}
+
+fn record_path(id: &str) -> Result<PathBuf> {
+    Ok(jobs_dir()?.join(format!("{id}.json")))
+}
+
+pub fn job_log_path(id: &str) -> Result<PathBuf> {
+    Ok(jobs_dir()?.join(format!("{id}.log")))
+}
+
+fn save_job(job: &JobRecord) -> Result<()> {
+    let path = record_path(&job.id)?;
+    let text = serde_json::to_string_pretty(job)?;
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn read_job(id: &str) -> Result<Option<JobRecord>> {
+    let path = record_path(id)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let job = serde_json::from_str(&text)
+        .with_context(|| format!("Invalid job file: {}", path.display()))?;
+    Ok(Some(job))
+}
+
+/// Loads a job and marks it failed when its process is gone without reporting.
+pub fn load_job(id: &str) -> Result<JobRecord> {
+    let Some(mut job) = read_job(id)? else {
+        bail!("No job with id {}. See `dongshan jobs list`.", id);
+    };
+    refresh(&mut job)?;
+    Ok(job)
+}
+
+/// All jobs, newest first.
+pub fn list_jobs() -> Result<Vec<JobRecord>> {
+    let dir = jobs_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut jobs = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if let Some(mut job) = read_job(id)? {
+            refresh(&mut job)?;
+            jobs.push(job);
+        }
+    }
+    jobs.sort_by(|a, b| b.started.cmp(&a.started).then(b.id.cmp(&a.id)));
+    Ok(jobs)
+}
+
+fn refresh(job: &mut JobRecord) -> Result<()> {
+    if job.status == JobStatus::Running && !job_alive(job) {
+        job.status = JobStatus::Failed;
+        job.finished = Some(now_unix_ts());
+        job.error = Some("process exited without reporting a result".to_string());
+        save_job(job)?;
+    }
+    Ok(())
+}
+
+fn new_job_id() -> Result<String> {
+    let base = now_unix_ts().to_string();
+    let mut id = base.clone();
+    let mut n = 1;
+    while record_path(&id)?.exists() {
+        n += 1;
+        id = format!("{base}-{n}");
+    }
+    Ok(id)
+}
+
+/// Starts the agent in a detached process that logs to the job file and
+/// returns the new job.
+pub fn start_background_job(agent: &BackgroundAgent) -> Result<JobRecord> {
+    let dir = jobs_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let id = new_job_id()?;
+    let log_path = job_log_path(&id)?;
+    let log = File::create(&log_path)
+        .with_context(|| format!("Failed to create {}", log_path.display()))?;
+    let exe = std::env::current_exe().context("Failed to locate the dongshan executable")?;
+    let mut cmd = Command::new(exe);
+    cmd.args([
+        "agent",
+        agent.task,
+        "--session",
+        agent.session,
+        "--job-id",
+        &id,
+        "--ci",
+    ]);
+    if let Some(budget) = agent.budget {
+        cmd.args(["--budget", &budget.to_string()]);
+    }
+    if let Some(cwd) = agent.cwd {
+        cmd.args(["--cwd", cwd]);
+    }
+    if let Some(seed) = agent.seed {
+        cmd.args(["--seed", &seed.to_string()]);
+    }
+    if agent.isolated {
+        cmd.arg("--isolated");
+    }
+    if let Some(branch) = agent.branch {
+        cmd.args(["--branch", branch]);
+    }
+    if let Some(report) = agent.report {
+        cmd.arg("--report").arg(report);
+    }
+    if let Some(level) = agent.approval {
+        cmd.args(["--approval", level.as_str()]);
+    }
+    cmd.env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    detach(&mut cmd);
+    let child = cmd.spawn().context("Failed to start background agent")?;
+    let job = JobRecord {
+        id,
+        task: agent.task.to_string(),
+        session: agent.session.to_string(),
+        cwd: std::env::current_dir()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default(),
+        pid: child.id(),
+        pid_start: process_start(child.id()),
+        started: now_unix_ts(),
+        finished: None,
+        status: JobStatus::Running,
+        error: None,
+    };
+    save_job(&job)?;
+    Ok(job)
+}
+
+/// Called by the detached process when the agent run ends.
+pub fn finish_job(id: &str, error: Option<String>) -> Result<()> {
+    let Some(mut job) = read_job(id)? else {
+        return Ok(());
+    };
+    if job.status == JobStatus::Cancelled {
+        return Ok(());
+    }
+    job.status = if error.is_some() {
+        JobStatus::Failed
+    } else {
+        JobStatus::Done
+    };
+    job.finished = Some(now_unix_ts());
+    job.error = error;
+    save_job(&job)
+}
+
+/// Stops a running job and its child processes.
+pub fn cancel_job(id: &str) -> Result<JobRecord> {
+    let mut job = load_job(id)?;
+    if job.status != JobStatus::Running {
+        bail!("Job {} is already {}", id, job.status.name());
+    }
+    kill_process_tree(&job)?;
+    job.status = JobStatus::Cancelled;
+    job.finished = Some(now_unix_ts());
+    save_job(&job)?;
+    Ok(job)
+}
+
+#[cfg(unix)]
+fn detach(cmd: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    // Own process group: closing the terminal does not stop the job, and
+    // cancel can signal the agent together with the commands it started.
+    cmd.process_group(0);
+}
+
+#[cfg(windows)]
+fn detach(cmd: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(not(any(unix, windows)))]
+fn detach(_cmd: &mut Command) {}
+
+fn quiet(cmd: &mut Command) -> bool {
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+fn process_alive(pid: u32) -> bool {
+    if cfg!(target_os = "windows") {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
+    } else {
+        quiet(Command::new("kill").args(["-0", &pid.to_string()]))
+    }
+}
+
+/// Start time of a running process, `None` when it is gone or unknown.
+fn process_start(pid: u32) -> Option<String> {
+    let out = if cfg!(target_os = "windows") {
+        Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                &format!("(Get-Process -Id {pid}).StartTime.ToFileTimeUtc()"),
+            ])
+            .output()
+    } else {
+        Command::new("ps")
+            .args(["-o", "lstart=", "-p", &pid.to_string()])
+            .output()
+    }
+    .ok()?;
+    let start = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !start.is_empty()).then_some(start)
+}
+
+/// True while the job's own process runs. Records from before start times
+/// were kept only check the pid.
+fn job_alive(job: &JobRecord) -> bool {
+    match &job.pid_start {
+        Some(start) => process_start(job.pid).as_ref() == Some(start),
+        None => process_alive(job.pid),
+    }
+}
+
+fn kill_process_tree(job: &JobRecord) -> Result<()> {
+    if !job_alive(job) {
+        bail!(
+            "Process {} is no longer job {}; nothing was stopped",
+            job.pid,
+            job.id
+        );
+    }
+    let pid = job.pid;
+    let killed = if cfg!(target_os = "windows") {
+        quiet(Command::new("taskkill").args(["/PID", &pid.to_string(), "/T", "/F"]))
+    } else {
+        quiet(Command::new("kill").args(["-TERM", "--", &format!("-{pid}")]))
+            || quiet(Command::new("kill").args(["-TERM", &pid.to_string()]))
+    };
+    if !killed && process_alive(pid) {
+        bail!("Failed to stop process {}", pid);
+    }
+    Ok(())
+}
@@ -0,0 +1,344 @@
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::config::Configurer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{CompletionType, Editor, Helper};
+
+use crate::chat::list_saved_sessions;
+use crate::config::{config_dir, ensure_model_catalog, load_config_or_default};
+use crate::diagnostics::now_unix_ts;
+use crate::file_index::indexed_dir_entries;
+use crate::prompt_store::list_prompt_names;
+use crate::util::ask_or_eof;
+
+const HISTORY_MAX_ENTRIES: usize = 2000;
+
+/// Slash commands offered by tab completion, with their fixed subcommands.
+const SLASH_COMMANDS: &[(&str, &[&str])] = &[
+    ("/help", &[]),
+    ("/exit", &[]),
+    ("/status", &[]),
+    ("/render", &["show", "on", "off"]),
+    ("/new", &[]),
+    ("/clear", &[]),
+    ("/session", &["list", "use", "rm"]),
+    ("/mode", &["show", "chat", "agent-auto", "agent-force"]),
+    ("/read", &[]),
+    ("/askfile", &[]),
+    ("/image", &[]),
+    ("/fetch", &[]),
+    ("/diff", &[]),
+    ("/undo", &[]),
+    ("/compact", &[]),
+    ("/tokens", &[]),
+    ("/cost", &[]),
+    ("/trust", &[]),
+    ("/remember", &["--global"]),
+    ("/forget", &[]),
+    ("/retry", &["--model"]),
+    ("/system", &["show", "set", "append", "reset"]),
+    ("/list", &[]),
+    ("/grep", &[]),
+    ("/prompt", &["show", "list", "use"]),
+    ("/as", &[]),
+    ("/model", &["list", "use"]),
+    ("/compare", &[]),
+    ("/editor", &[]),
+];
+
+/// Commands whose argument at the given word index is a filesystem path.
+const PATH_ARGS: &[(&str, usize)] = &[
+    ("/read", 1),
+    ("/askfile", 1),
+    ("/image", 1),
+    ("/diff", 1),
+    ("/list", 1),
+    ("/grep", 2),
+];
+
+/// Interactive line reader for chat input: arrow-key history, Ctrl+R search and
+/// unicode-aware editing. History lives in `~/.dongshan/history` and is shared by
+/// every session. Falls back to a plain stdin read when stdin is not a terminal.
+pub struct LineEditor {
+    editor: Option<Editor<ChatHelper, FileHistory>>,
+    history_path: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        if !io::stdin().is_terminal() {
+            return Self {
+                editor: None,
+                history_path: None,
+            };
+        }
+        let Ok(mut editor) = Editor::<ChatHelper, FileHistory>::new() else {
+            return Self {
+                editor: None,
+                history_path: None,
+            };
+        };
+        let _ = editor.set_max_history_size(HISTORY_MAX_ENTRIES);
+        let _ = editor.set_history_ignore_dups(true);
+        editor.set_history_ignore_space(true);
+        editor.set_completion_type(CompletionType::List);
+        // Pasted text is inserted as a whole, newlines included, and sent as one message.
+        editor.enable_bracketed_paste(true);
+        editor.set_helper(Some(ChatHelper::default()));
+        let history_path = history_path().ok();
+        if let Some(path) = &history_path
+            && path.exists()
+        {
+            let _ = editor.load_history(path);
+        }
+        Self {
+            editor: Some(editor),
+            history_path,
+        }
+    }
+
+    /// Returns `None` on EOF (Ctrl+D). Ctrl+C clears the current line.
+    /// Input continues over several lines while a ``` fence is open, or after
+    /// a line ending in `\` until an empty line; pastes arrive as one message.
+    pub fn read_line(&mut self, label: &str) -> Result<Option<String>> {
+        let Some(editor) = self.editor.as_mut() else {
+            let Some(mut input) = ask_or_eof(label)? else {
+                return Ok(None);
+            };
+            while !is_input_complete(&input) {
+                let Some(next) = ask_or_eof("")? else {
+                    break;
+                };
+                input.push('\n');
+                input.push_str(&next);
+            }
+            return Ok(Some(finish_multiline(&input)));
+        };
+        match editor.readline(label) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                    if let Some(path) = &self.history_path {
+                        let _ = editor.append_history(path);
+                    }
+                }
+                Ok(Some(finish_multiline(&line)))
+            }
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(err) => Err(err).context("Failed to read input"),
+        }
+    }
+}
+
+/// Opens `$VISUAL`/`$EDITOR` (notepad or vi by default) on a temp file seeded
+/// with `initial` and returns the saved text, or `None` when it is left empty.
+pub fn compose_in_editor(initial: &str) -> Result<Option<String>> {
+    let editor = env::var("VISUAL")
+        .ok()
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(target_os = "windows") {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        });
+    let path = env::temp_dir().join(format!(
+        "dongshan-message-{}-{}.md",
+        std::process::id(),
+        now_unix_ts()
+    ));
+    fs::write(&path, initial).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    // Editors such as `code --wait` carry their own arguments.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to launch editor `{}`", editor));
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let status = status?;
+    if !status.success() {
+        bail!("Editor `{}` exited with {}", editor, status);
+    }
+    let text = text.with_context(|| format!("Failed to read {}", path.display()))?;
+    let text = text.trim_end().to_string();
+    Ok((!text.trim().is_empty()).then_some(text))
+}
+
+pub fn history_path() -> Result<PathBuf> {
+    let dir = config_dir()?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir.join("history"))
+}
+
+#[derive(Default)]
+struct ChatHelper {
+    files: FilenameCompleter,
+}
+
+impl Completer for ChatHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let head = &line[..pos];
+        if !head.starts_with('/') {
+            return Ok((pos, Vec::new()));
+        }
+        let start = head.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let current = &head[start..];
+        let words: Vec<&str> = head[..start].split_whitespace().collect();
+        let Some(cmd) = words.first().copied() else {
+            let names = SLASH_COMMANDS.iter().map(|(name, _)| name.to_string());
+            return Ok((start, matching(names, current)));
+        };
+        let index = words.len();
+
+        if PATH_ARGS.iter().any(|(c, i)| *c == cmd && *i == index) {
+            let indexed = indexed_paths(current);
+            if !indexed.is_empty() {
+                return Ok((start, indexed));
+            }
+            return self.files.complete(line, pos, ctx);
+        }
+        if cmd == "/as" && index == 1 {
+            let names = list_prompt_names().unwrap_or_default();
+            return Ok((start, matching(names.into_iter(), current)));
+        }
+        if index == 1 {
+            let subs = SLASH_COMMANDS
+                .iter()
+                .find(|(name, _)| *name == cmd)
+                .map(|(_, subs)| *subs)
+                .unwrap_or(&[]);
+            return Ok((start, matching(subs.iter().map(|s| s.to_string()), current)));
+        }
+        if index == 2 {
+            let names = match (cmd, words[1]) {
+                ("/session", "use" | "rm") => list_saved_sessions().unwrap_or_default(),
+                ("/prompt", "use") => list_prompt_names().unwrap_or_default(),
+                ("/model", "use") => model_names(),
+                _ => Vec::new(),
+            };
+            return Ok((start, matching(names.into_iter(), current)));
+        }
+        Ok((pos, Vec::new()))
+    }
+}
+
+impl Hinter for ChatHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ChatHelper {}
+
+impl Validator for ChatHelper {
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        if is_input_complete(ctx.input()) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+/// False while a ``` fence is unclosed, or while input whose first line ends
+/// in `\` has not yet been ended by an empty line. Slash commands are single-line.
+fn is_input_complete(input: &str) -> bool {
+    if input.lines().filter(|l| is_fence(l)).count() % 2 == 1 {
+        return false;
+    }
+    if input.starts_with('/') {
+        return true;
+    }
+    match input.split_once('\n') {
+        None => !input.trim_end().ends_with('\\'),
+        Some((first, rest)) => {
+            !first.trim_end().ends_with('\\')
+                || rest
+                    .rsplit('\n')
+                    .next()
+                    .is_some_and(|l| l.trim().is_empty())
+        }
+    }
+}
+
+/// Drops the `\` continuation marker and the terminating empty line.
+fn finish_multiline(input: &str) -> String {
+    if input.starts_with('/') {
+        return input.to_string();
+    }
+    let Some((first, rest)) = input.split_once('\n') else {
+        return input.to_string();
+    };
+    match first.trim_end().strip_suffix('\\') {
+        Some(head) => format!("{}\n{}", head.trim_end(), rest.trim_end()),
+        None => input.to_string(),
+    }
+}
+
+impl Helper for ChatHelper {}
+
+fn matching(candidates: impl Iterator<Item = String>, prefix: &str) -> Vec<Pair> {
+    candidates
+        .filter(|c| c.starts_with(prefix))
+        .map(|c| Pair {
+            display: c.clone(),
+            replacement: c,
+        })
+        .collect()
+}
+
+/// Workspace paths completing `current` from the cached file list, one path
+/// segment at a time. Empty for paths outside the workspace or not in the
+/// list (e.g. ignored files), which go to the filename completer.
+fn indexed_paths(current: &str) -> Vec<Pair> {
+    if current.starts_with(['/', '~']) || current.contains("..") {
+        return Vec::new();
+    }
+    let (dir, name) = match current.rfind('/') {
+        Some(i) => current.split_at(i + 1),
+        None => ("", current),
+    };
+    let lookup = if dir.is_empty() { "." } else { dir };
+    let Some((dirs, files)) = indexed_dir_entries(Path::new(lookup)) else {
+        return Vec::new();
+    };
+    let dirs = dirs.into_iter().map(|d| format!("{dir}{d}/"));
+    let files = files.into_iter().map(|f| format!("{dir}{f}"));
+    let mut out = matching(dirs.chain(files), &format!("{dir}{name}"));
+    out.sort_by(|a, b| a.replacement.cmp(&b.replacement));
+    out
+}
+
+fn model_names() -> Vec<String> {
+    let Ok(mut cfg) = load_config_or_default() else {
+        return Vec::new();
+    };
+    ensure_model_catalog(&mut cfg);
+    cfg.model_catalog
+}
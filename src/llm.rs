@@ -1,17 +1,44 @@
-use anyhow::{Context, Result, bail};
-use reqwest::Client;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
 use std::io::{self, Write};
-use std::time::Duration;
-
-use crate::config::{Config, resolve_api_key};
-use crate::util::WorkingStatus;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::{Config, ModelProfile, config_for_model, model_fallbacks, resolve_api_key};
+use crate::i18n::t;
+use crate::logging::{is_quiet, log_debug, log_request_body, log_verbose};
+use crate::tls::client_builder;
+use crate::transcript::{record, record_request, record_response};
+use crate::usage::{TokenUsage, check_budget, record_usage};
+use crate::util::{
+    MarkdownStreamRenderer, WorkingStatus, color_dim, color_yellow, markdown_enabled,
+    render_markdown_terminal,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
+    /// Attached images as `data:` URLs, sent as multi-part content for vision models.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+}
+
+impl ChatMessage {
+    /// A message without images.
+    pub fn text(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -28,22 +55,124 @@ pub struct NativeLlmResponse {
     pub assistant_message: Value,
 }
 
+/// Non-success HTTP status from the provider.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API error {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Process-wide request gate: caps in-flight requests and, when configured,
+/// requests per rolling minute. Sized from the first config that uses it.
+struct RequestLimiter {
+    semaphore: Arc<Semaphore>,
+    rpm: usize,
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+static REQUEST_LIMITER: OnceLock<RequestLimiter> = OnceLock::new();
+
+/// Held for the lifetime of one provider request.
+pub struct RequestPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl RequestLimiter {
+    fn new(cfg: &Config) -> Self {
+        let permits = if cfg.max_concurrent_requests == 0 {
+            Semaphore::MAX_PERMITS
+        } else {
+            cfg.max_concurrent_requests
+        };
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            rpm: cfg.rate_limit_rpm as usize,
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    async fn wait_for_rate_slot(&self) {
+        if self.rpm == 0 {
+            return;
+        }
+        loop {
+            let wait = {
+                let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                while let Some(front) = recent.front() {
+                    if now.duration_since(*front) >= RATE_WINDOW {
+                        recent.pop_front();
+                    } else {
+                        break;
+                    }
+                }
+                match recent.front() {
+                    Some(oldest) if recent.len() >= self.rpm => {
+                        RATE_WINDOW.saturating_sub(now.duration_since(*oldest))
+                    }
+                    _ => {
+                        recent.push_back(now);
+                        return;
+                    }
+                }
+            };
+            log_verbose(
+                "llm",
+                &format!(
+                    "rate limit {} rpm reached, waiting {:.1}s",
+                    self.rpm,
+                    wait.as_secs_f32()
+                ),
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Waits for a free concurrency slot and rate budget before a provider request.
+pub async fn acquire_request_slot(cfg: &Config) -> Result<RequestPermit> {
+    let limiter = REQUEST_LIMITER.get_or_init(|| RequestLimiter::new(cfg));
+    let permit = limiter
+        .semaphore
+        .clone()
+        .acquire_owned()
+        .await
+        .context("request limiter closed")?;
+    limiter.wait_for_rate_slot().await;
+    Ok(RequestPermit { _permit: permit })
+}
+
 pub fn build_openai_messages(system_prompt: &str, history: &[ChatMessage]) -> Vec<Value> {
     let mut messages = vec![json!({"role":"system","content":system_prompt})];
     for m in history {
-        if m.content.trim().is_empty() {
+        if m.content.trim().is_empty() && m.images.is_empty() {
+            continue;
+        }
+        if m.images.is_empty() {
+            messages.push(json!({"role": m.role, "content": m.content}));
             continue;
         }
-        messages.push(json!({"role": m.role, "content": m.content}));
+        let mut parts = vec![json!({"type": "text", "text": m.content})];
+        for url in &m.images {
+            parts.push(json!({"type": "image_url", "image_url": {"url": url}}));
+        }
+        messages.push(json!({"role": m.role, "content": parts}));
     }
     messages
 }
 
 pub async fn call_llm(cfg: &Config, system_prompt: &str, user_prompt: &str) -> Result<String> {
-    let messages = vec![ChatMessage {
-        role: "user".to_string(),
-        content: user_prompt.to_string(),
-    }];
+    let messages = vec![ChatMessage::text("user", user_prompt)];
     call_llm_with_history(cfg, system_prompt, &messages).await
 }
 
@@ -52,15 +181,31 @@ pub async fn call_llm_with_history(
     system_prompt: &str,
     history: &[ChatMessage],
 ) -> Result<String> {
-    call_llm_with_history_impl(cfg, system_prompt, history, false, None).await
+    call_llm_with_history_impl(cfg, system_prompt, history, false, None, false).await
 }
 
+static SEED_OVERRIDE: OnceLock<u64> = OnceLock::new();
+
+/// Applies `--seed`, which wins over the `seed` config key for this run.
+pub fn init_seed(seed: Option<u64>) {
+    if let Some(seed) = seed {
+        let _ = SEED_OVERRIDE.set(seed);
+    }
+}
+
+/// Seed sent with every completion request, if any. Providers without seed
+/// support ignore it.
+pub fn effective_seed(cfg: &Config) -> Option<u64> {
+    SEED_OVERRIDE.get().copied().or(cfg.seed)
+}
+
+/// Streams the answer to stdout as it arrives and returns the full text.
 pub async fn call_llm_with_history_stream(
     cfg: &Config,
     system_prompt: &str,
     history: &[ChatMessage],
 ) -> Result<String> {
-    call_llm_with_history_impl(cfg, system_prompt, history, true, None).await
+    call_llm_with_history_impl(cfg, system_prompt, history, true, None, true).await
 }
 
 pub async fn call_llm_with_history_stream_tools(
@@ -71,18 +216,84 @@ pub async fn call_llm_with_history_stream_tools(
 ) -> Result<String> {
     // 如果配置了 executor_model，用它来处理工具调用
     if let Some(executor_model) = &cfg.executor_model {
-        let mut executor_cfg = cfg.clone();
-        executor_cfg.model = executor_model.clone();
         // 从 model_profiles 中获取对应配置
-        if let Some(profile) = cfg.model_profiles.get(executor_model) {
-            executor_cfg.base_url = profile.base_url.clone();
-            executor_cfg.api_key_env = profile.api_key_env.clone();
-            executor_cfg.api_key = profile.api_key.clone();
-        }
-        call_llm_with_history_impl(&executor_cfg, system_prompt, history, true, Some(tools)).await
+        let executor_cfg = config_for_model(cfg, executor_model);
+        call_llm_with_history_impl(
+            &executor_cfg,
+            system_prompt,
+            history,
+            true,
+            Some(tools),
+            false,
+        )
+        .await
     } else {
-        call_llm_with_history_impl(cfg, system_prompt, history, true, Some(tools)).await
+        call_llm_with_history_impl(cfg, system_prompt, history, true, Some(tools), false).await
+    }
+}
+
+/// Runs `request` against the active model, then each of its profile
+/// `fallbacks` in order while the failure is a timeout, 429 or 5xx.
+async fn with_model_fallback<T, F, Fut>(cfg: &Config, mut request: F) -> Result<T>
+where
+    F: FnMut(Config) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut fallbacks = model_fallbacks(cfg).into_iter();
+    let mut current = cfg.clone();
+    loop {
+        let err = match request(current.clone()).await {
+            Ok(v) => return Ok(v),
+            Err(err) => err,
+        };
+        if !is_fallback_error(&err) {
+            return Err(err);
+        }
+        let Some(next) = fallbacks.next() else {
+            return Err(err);
+        };
+        if !is_quiet() {
+            println!(
+                "{}",
+                color_yellow(&format!(
+                    "model {} failed ({}); falling back to {}",
+                    current.model,
+                    fallback_reason(&err),
+                    next
+                ))
+            );
+        }
+        record(
+            "fallback",
+            json!({ "from": current.model, "to": next, "error": format!("{:#}", err) }),
+        );
+        current = config_for_model(cfg, &next);
+    }
+}
+
+fn is_fallback_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        if let Some(api) = e.downcast_ref::<ApiError>() {
+            return api.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || api.status.is_server_error();
+        }
+        e.downcast_ref::<reqwest::Error>()
+            .is_some_and(|r| r.is_timeout() || r.is_connect())
+    })
+}
+
+fn fallback_reason(err: &anyhow::Error) -> String {
+    for e in err.chain() {
+        if let Some(api) = e.downcast_ref::<ApiError>() {
+            return api.status.to_string();
+        }
+        if e.downcast_ref::<reqwest::Error>()
+            .is_some_and(|r| r.is_timeout())
+        {
+            return "timeout".to_string();
+        }
     }
+    "connection error".to_string()
 }
 
 async fn call_llm_with_history_impl(
@@ -91,11 +302,52 @@ async fn call_llm_with_history_impl(
     history: &[ChatMessage],
     stream_output: bool,
     tools: Option<&[Value]>,
+    print_live: bool,
 ) -> Result<String> {
+    with_model_fallback(cfg, |cfg| async move {
+        request_completion(
+            &cfg,
+            system_prompt,
+            history,
+            stream_output,
+            tools,
+            print_live,
+        )
+        .await
+        .map(|(out, _)| out)
+    })
+    .await
+}
+
+/// Sends one prompt to exactly `cfg.model` without printing or falling back,
+/// returning the answer and its token usage.
+pub async fn call_llm_with_usage(
+    cfg: &Config,
+    system_prompt: &str,
+    user_prompt: &str,
+) -> Result<(String, TokenUsage)> {
+    let history = [ChatMessage::text("user", user_prompt)];
+    request_completion(cfg, system_prompt, &history, true, None, false).await
+}
+
+#[tracing::instrument(
+    name = "llm.request",
+    skip_all,
+    fields(model = %cfg.model, stream = stream_output, tools = tools.is_some())
+)]
+async fn request_completion(
+    cfg: &Config,
+    system_prompt: &str,
+    history: &[ChatMessage],
+    stream_output: bool,
+    tools: Option<&[Value]>,
+    print_live: bool,
+) -> Result<(String, TokenUsage)> {
+    check_budget(cfg)?;
     let working = if stream_output {
         None
     } else {
-        Some(WorkingStatus::start("waiting response"))
+        Some(WorkingStatus::start(t("status.waiting")))
     };
     let api_key = resolve_api_key(cfg)?;
     let messages = build_openai_messages(system_prompt, history);
@@ -106,17 +358,39 @@ async fn call_llm_with_history_impl(
         "stream": stream_output
     });
 
+    if stream_output {
+        // Without this OpenAI-compatible servers leave usage out of the stream.
+        body["stream_options"] = json!({ "include_usage": true });
+    }
     if let Some(tools) = tools {
         body["tools"] = json!(tools);
         body["tool_choice"] = json!("auto");
     }
+    apply_prompt_cache_hints(cfg, &mut body);
+    apply_reasoning_params(cfg, &mut body);
+    if let Some(seed) = effective_seed(cfg) {
+        body["seed"] = json!(seed);
+    }
 
     let timeout_secs = if stream_output { 900 } else { 120 };
-    let client = Client::builder()
+    let client = client_builder()?
         .timeout(Duration::from_secs(timeout_secs))
         .build()
         .context("failed to build HTTP client")?;
 
+    log_verbose(
+        "llm",
+        &format!(
+            "POST {} model={} stream={} tools={}",
+            cfg.base_url,
+            cfg.model,
+            stream_output,
+            tools.map(|t| t.len()).unwrap_or(0)
+        ),
+    );
+    log_request_body(&cfg.base_url, &body, &api_key);
+    record_request(&cfg.base_url, &body);
+    let _permit = acquire_request_slot(cfg).await?;
     let resp = client
         .post(&cfg.base_url)
         .bearer_auth(api_key)
@@ -126,9 +400,10 @@ async fn call_llm_with_history_impl(
         .with_context(|| format!("Request failed: {}", cfg.base_url))?;
 
     let status = resp.status();
+    log_verbose("llm", &format!("response status {}", status));
     if !status.is_success() {
-        let text = resp.text().await.context("Failed to read response body")?;
-        bail!("API error {}: {}", status, text);
+        let body = resp.text().await.context("Failed to read response body")?;
+        return Err(ApiError { status, body }.into());
     }
 
     let content_type = resp
@@ -138,20 +413,34 @@ async fn call_llm_with_history_impl(
         .unwrap_or_default()
         .to_string();
 
-    let out = if stream_output && content_type.contains("text/event-stream") {
-        // Keep stream transport but avoid raw token-by-token stdout output;
+    let (out, usage) = if stream_output && content_type.contains("text/event-stream") {
+        // Chat keeps stream transport but avoids raw token-by-token stdout output;
         // terminal markdown rendering is handled by chat layer after full response.
-        parse_sse_response(resp, false).await?
+        // One-shot callers opt into live printing instead.
+        parse_sse_response(resp, print_live, shows_reasoning(cfg)).await?
     } else {
         let text = resp.text().await.context("Failed to read response body")?;
+        log_debug("response", &text);
         let val: Value = serde_json::from_str(&text).context("Invalid JSON response")?;
-        extract_content(&val).context("Cannot parse response content")?
+        let usage = log_usage(&val);
+        if let Some(message) = val.get("choices").and_then(|c| c.get(0)?.get("message")) {
+            print_reasoning(cfg, message);
+        }
+        let content = extract_content(&val).context("Cannot parse response content")?;
+        if print_live {
+            print!("{}", render_markdown_terminal(&content, markdown_enabled()));
+            let _ = io::stdout().flush();
+        }
+        (content, usage)
     };
 
     if let Some(working) = working {
         working.finish();
     }
-    Ok(out.trim().to_string())
+    record_response(&cfg.model, &out);
+    let usage = usage.unwrap_or_else(|| TokenUsage::estimate(&body["messages"].to_string(), &out));
+    record_usage(cfg, &cfg.model, &usage);
+    Ok((out.trim().to_string(), usage))
 }
 
 pub async fn call_llm_with_messages_native_tools(
@@ -160,21 +449,29 @@ pub async fn call_llm_with_messages_native_tools(
     tools: &[Value],
 ) -> Result<NativeLlmResponse> {
     // 如果配置了 executor_model，用它来处理工具调用
-    let executor_cfg = if let Some(executor_model) = &cfg.executor_model {
-        let mut exec_cfg = cfg.clone();
-        exec_cfg.model = executor_model.clone();
-        if let Some(profile) = cfg.model_profiles.get(executor_model) {
-            exec_cfg.base_url = profile.base_url.clone();
-            exec_cfg.api_key_env = profile.api_key_env.clone();
-            exec_cfg.api_key = profile.api_key.clone();
-        }
-        exec_cfg
-    } else {
-        cfg.clone()
+    let executor_cfg = match &cfg.executor_model {
+        Some(executor_model) => config_for_model(cfg, executor_model),
+        None => cfg.clone(),
     };
+    with_model_fallback(&executor_cfg, |cfg| async move {
+        request_native_tools(&cfg, messages, tools).await
+    })
+    .await
+}
 
-    let api_key = resolve_api_key(&executor_cfg)?;
-    let body = json!({
+#[tracing::instrument(
+    name = "llm.native_tools",
+    skip_all,
+    fields(model = %executor_cfg.model, tools = tools.len())
+)]
+async fn request_native_tools(
+    executor_cfg: &Config,
+    messages: &[Value],
+    tools: &[Value],
+) -> Result<NativeLlmResponse> {
+    check_budget(executor_cfg)?;
+    let api_key = resolve_api_key(executor_cfg)?;
+    let mut body = json!({
         "model": executor_cfg.model,
         "messages": messages,
         "tools": tools,
@@ -182,12 +479,29 @@ pub async fn call_llm_with_messages_native_tools(
         "temperature": 0.2,
         "stream": false
     });
+    apply_prompt_cache_hints(executor_cfg, &mut body);
+    apply_reasoning_params(executor_cfg, &mut body);
+    if let Some(seed) = effective_seed(executor_cfg) {
+        body["seed"] = json!(seed);
+    }
 
-    let client = Client::builder()
+    let client = client_builder()?
         .timeout(Duration::from_secs(900))
         .build()
         .context("failed to build HTTP client")?;
 
+    log_verbose(
+        "llm",
+        &format!(
+            "POST {} model={} native_tools={}",
+            executor_cfg.base_url,
+            executor_cfg.model,
+            tools.len()
+        ),
+    );
+    log_request_body(&executor_cfg.base_url, &body, &api_key);
+    record_request(&executor_cfg.base_url, &body);
+    let _permit = acquire_request_slot(executor_cfg).await?;
     let resp = client
         .post(&executor_cfg.base_url)
         .bearer_auth(api_key)
@@ -197,20 +511,37 @@ pub async fn call_llm_with_messages_native_tools(
         .with_context(|| format!("Request failed: {}", executor_cfg.base_url))?;
 
     let status = resp.status();
+    log_verbose("llm", &format!("response status {}", status));
     if !status.is_success() {
-        let text = resp.text().await.context("Failed to read response body")?;
-        bail!("API error {}: {}", status, text);
+        let body = resp.text().await.context("Failed to read response body")?;
+        return Err(ApiError { status, body }.into());
     }
 
     let text = resp.text().await.context("Failed to read response body")?;
+    log_debug("response", &text);
     let val: Value = serde_json::from_str(&text).context("Invalid JSON response")?;
-    let assistant_message = val
+    let usage = log_usage(&val);
+    let mut assistant_message = val
         .get("choices")
         .and_then(|c| c.get(0))
         .and_then(|c| c.get("message"))
         .cloned()
         .context("Cannot parse response message")?;
+    print_reasoning(executor_cfg, &assistant_message);
+    // Reasoning models reject their own reasoning when it is sent back as history.
+    if let Some(obj) = assistant_message.as_object_mut() {
+        obj.remove("reasoning_content");
+        obj.remove("reasoning");
+    }
 
+    record_response(&executor_cfg.model, &assistant_message.to_string());
+    let usage = usage.unwrap_or_else(|| {
+        TokenUsage::estimate(
+            &body["messages"].to_string(),
+            &assistant_message.to_string(),
+        )
+    });
+    record_usage(executor_cfg, &executor_cfg.model, &usage);
     Ok(NativeLlmResponse {
         content: extract_content_from_message(&assistant_message).unwrap_or_default(),
         tool_calls: extract_native_tool_calls(&assistant_message),
@@ -218,9 +549,165 @@ pub async fn call_llm_with_messages_native_tools(
     })
 }
 
-async fn parse_sse_response(mut resp: reqwest::Response, print_live: bool) -> Result<String> {
+fn model_profile(cfg: &Config) -> Option<&ModelProfile> {
+    cfg.model_profiles.get(&cfg.model)
+}
+
+/// Reasoning models reject `temperature` and take `max_completion_tokens`
+/// and an optional `reasoning_effort` instead.
+pub fn apply_reasoning_params(cfg: &Config, body: &mut Value) {
+    let Some(profile) = model_profile(cfg).filter(|p| p.reasoning) else {
+        return;
+    };
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+    obj.remove("temperature");
+    if let Some(max) = obj.remove("max_tokens") {
+        obj.insert("max_completion_tokens".to_string(), max);
+    }
+    if let Some(effort) = profile.reasoning_effort.as_deref() {
+        obj.insert("reasoning_effort".to_string(), json!(effort));
+    }
+}
+
+fn shows_reasoning(cfg: &Config) -> bool {
+    !is_quiet() && model_profile(cfg).is_some_and(|p| p.show_reasoning)
+}
+
+/// Prints the reasoning of a non-streamed reply dimmed, when enabled.
+fn print_reasoning(cfg: &Config, message: &Value) {
+    if !shows_reasoning(cfg) {
+        return;
+    }
+    if let Some(text) = reasoning_text(message).filter(|t| !t.trim().is_empty()) {
+        println!("{}", color_dim(text.trim()));
+    }
+}
+
+/// `reasoning_content` (DeepSeek, most OpenAI-compatible gateways) or
+/// `reasoning` (OpenRouter) of a message or stream delta.
+fn reasoning_text(message: &Value) -> Option<&str> {
+    message
+        .get("reasoning_content")
+        .or_else(|| message.get("reasoning"))
+        .and_then(|v| v.as_str())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptCacheStyle {
+    /// Explicit `cache_control` breakpoints (Anthropic models, directly or via OpenRouter).
+    Anthropic,
+    /// Automatic prefix caching; a stable `prompt_cache_key` improves hit rate.
+    OpenAi,
+    None,
+}
+
+fn prompt_cache_style(cfg: &Config) -> PromptCacheStyle {
+    if !cfg.prompt_cache {
+        return PromptCacheStyle::None;
+    }
+    let model = cfg.model.to_ascii_lowercase();
+    let url = cfg.base_url.to_ascii_lowercase();
+    let claude = model.contains("claude") || model.contains("anthropic");
+    // Only endpoints known to accept `cache_control`; other relays serving
+    // Claude models may reject the extra field.
+    if url.contains("anthropic.com") || (claude && url.contains("openrouter.ai")) {
+        PromptCacheStyle::Anthropic
+    } else if url.contains("api.openai.com") {
+        PromptCacheStyle::OpenAi
+    } else {
+        PromptCacheStyle::None
+    }
+}
+
+/// Adds provider cache hints to a chat completion body. The system prompt and
+/// the conversation prefix before the newest message are the stable parts, so
+/// those are what get marked.
+fn apply_prompt_cache_hints(cfg: &Config, body: &mut Value) {
+    let style = prompt_cache_style(cfg);
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return;
+    };
+    match style {
+        PromptCacheStyle::None => {}
+        PromptCacheStyle::OpenAi => {
+            let system = messages
+                .first()
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_str())
+                .unwrap_or_default();
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            system.hash(&mut hasher);
+            body["prompt_cache_key"] = json!(format!("dongshan-{:x}", hasher.finish()));
+        }
+        PromptCacheStyle::Anthropic => {
+            if let Some(first) = messages.first_mut()
+                && first.get("role").and_then(|r| r.as_str()) == Some("system")
+            {
+                mark_cache_breakpoint(first);
+            }
+            let prefix_end = messages.len().saturating_sub(1);
+            if let Some(msg) = messages[1.min(prefix_end)..prefix_end]
+                .iter_mut()
+                .rev()
+                .find(|m| is_cacheable_message(m))
+            {
+                mark_cache_breakpoint(msg);
+            }
+        }
+    }
+}
+
+/// Logs token usage, including cache hits reported as OpenAI
+/// `prompt_tokens_details.cached_tokens` or Anthropic `cache_read_input_tokens`.
+fn log_usage(val: &Value) -> Option<TokenUsage> {
+    let usage = TokenUsage::from_response(val)?;
+    log_verbose(
+        "usage",
+        &format!(
+            "prompt={} completion={} cached={}",
+            usage.prompt_tokens, usage.completion_tokens, usage.cached_tokens
+        ),
+    );
+    Some(usage)
+}
+
+fn is_cacheable_message(msg: &Value) -> bool {
+    let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or_default();
+    matches!(role, "user" | "assistant")
+        && msg.get("tool_calls").is_none()
+        && msg.get("content").is_some_and(|c| c.is_string())
+}
+
+fn mark_cache_breakpoint(msg: &mut Value) {
+    let Some(text) = msg.get("content").and_then(|c| c.as_str()) else {
+        return;
+    };
+    if text.is_empty() {
+        return;
+    }
+    msg["content"] = json!([{
+        "type": "text",
+        "text": text,
+        "cache_control": {"type": "ephemeral"}
+    }]);
+}
+
+async fn parse_sse_response(
+    mut resp: reqwest::Response,
+    print_live: bool,
+    show_reasoning: bool,
+) -> Result<(String, Option<TokenUsage>)> {
     let mut full = String::new();
+    let mut in_reasoning = false;
+    let mut usage = None;
     let mut buffer = String::new();
+    let mut renderer = if print_live && markdown_enabled() {
+        Some(MarkdownStreamRenderer::new())
+    } else {
+        None
+    };
 
     while let Some(chunk) = resp.chunk().await.context("Failed to read stream chunk")? {
         buffer.push_str(&String::from_utf8_lossy(&chunk));
@@ -238,26 +725,57 @@ async fn parse_sse_response(mut resp: reqwest::Response, print_live: bool) -> Re
             if data.is_empty() {
                 continue;
             }
+            log_debug("sse", data);
             if data == "[DONE]" {
-                return Ok(full);
+                finish_live_render(renderer.as_mut());
+                return Ok((full, usage));
             }
 
             let Ok(val) = serde_json::from_str::<Value>(data) else {
                 continue;
             };
+            if let Some(u) = log_usage(&val) {
+                usage = Some(u);
+            }
+            if show_reasoning
+                && let Some(text) = val
+                    .get("choices")
+                    .and_then(|c| c.get(0)?.get("delta"))
+                    .and_then(reasoning_text)
+                    .filter(|t| !t.is_empty())
+            {
+                print!("{}", color_dim(text));
+                let _ = io::stdout().flush();
+                in_reasoning = true;
+            }
             let delta = extract_delta_content(&val).unwrap_or_default();
             if delta.is_empty() {
                 continue;
             }
+            if in_reasoning {
+                println!();
+                in_reasoning = false;
+            }
             if print_live {
-                print!("{}", delta);
+                match renderer.as_mut() {
+                    Some(r) => print!("{}", r.push(&delta)),
+                    None => print!("{}", delta),
+                }
                 let _ = io::stdout().flush();
             }
             full.push_str(&delta);
         }
     }
 
-    Ok(full)
+    finish_live_render(renderer.as_mut());
+    Ok((full, usage))
+}
+
+fn finish_live_render(renderer: Option<&mut MarkdownStreamRenderer>) {
+    if let Some(r) = renderer {
+        print!("{}", r.finish());
+        let _ = io::stdout().flush();
+    }
 }
 
 fn extract_delta_content(value: &Value) -> Option<String> {
@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI8, Ordering};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+use tracing::level_filters::LevelFilter;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::Layer;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::config::config_dir;
+use crate::diagnostics::now_unix_ts;
+use crate::util::truncate_with_suffix;
+
+/// -1 = quiet, 0 = normal, 1 = `-v`, 2+ = `-vv` (request/SSE/tool-parse dumps).
+static VERBOSITY: AtomicI8 = AtomicI8::new(0);
+/// Same scale for the log file; -1 when no log file is written.
+static FILE_VERBOSITY: AtomicI8 = AtomicI8::new(-1);
+
+/// Rotated log files kept next to the active one.
+const MAX_LOG_FILES: usize = 7;
+/// Recent log lines kept in memory for crash reports, whatever the verbosity.
+const RECENT_LINES: usize = 50;
+const RECENT_LINE_CHARS: usize = 400;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+
+    fn verbosity(self) -> i8 {
+        match self {
+            LogLevel::Error | LogLevel::Warn => 0,
+            LogLevel::Info => 1,
+            LogLevel::Debug | LogLevel::Trace => 2,
+        }
+    }
+}
+
+const REDACTED: &str = "***redacted***";
+const SECRET_KEYS: &[&str] = &[
+    "api_key",
+    "apikey",
+    "authorization",
+    "access_token",
+    "token",
+    "secret",
+    "password",
+];
+
+/// Default log file, rotated daily: `~/.dongshan/logs/dongshan.log`.
+pub fn default_log_file() -> Result<PathBuf> {
+    Ok(config_dir()?.join("logs").join("dongshan.log"))
+}
+
+/// Installs the tracing subscriber. `-v`/`-vv` print to stderr; with a log
+/// file (or `--log-level`), events and span timings go to the file as JSON
+/// lines instead, rotated daily with the last few days kept.
+pub fn init_logging(
+    verbose: u8,
+    quiet: bool,
+    log_level: Option<LogLevel>,
+    log_file: Option<&Path>,
+) -> Result<()> {
+    let level = if quiet { -1 } else { verbose.min(2) as i8 };
+    VERBOSITY.store(level, Ordering::Relaxed);
+
+    let file_path = match (log_file, log_level) {
+        (Some(path), _) => Some(path.to_path_buf()),
+        (None, Some(_)) => Some(default_log_file()?),
+        (None, None) => None,
+    };
+    let file_layer = match &file_path {
+        Some(path) => {
+            let file_level = log_level.unwrap_or(match level {
+                2.. => LogLevel::Debug,
+                _ => LogLevel::Info,
+            });
+            FILE_VERBOSITY.store(file_level.verbosity(), Ordering::Relaxed);
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_writer(rolling_appender(path)?)
+                    .with_filter(file_level.filter()),
+            )
+        }
+        None => None,
+    };
+    let stderr_filter = match level {
+        ..=0 => LevelFilter::OFF,
+        1 => LevelFilter::INFO,
+        _ => LevelFilter::DEBUG,
+    };
+    let stderr_layer = file_path.is_none().then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(io::stderr)
+            .with_ansi(false)
+            .with_target(false)
+            .with_filter(stderr_filter)
+    });
+    let _ = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(stderr_layer)
+        .try_init();
+    Ok(())
+}
+
+fn rolling_appender(path: &Path) -> Result<RollingFileAppender> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "dongshan.log".to_string());
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(name)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir)
+        .with_context(|| format!("Failed to open log file {}", path.display()))
+}
+
+pub fn is_quiet() -> bool {
+    VERBOSITY.load(Ordering::Relaxed) < 0
+}
+
+/// Highest detail any log sink records; gates expensive dumps.
+pub fn verbosity() -> i8 {
+    VERBOSITY
+        .load(Ordering::Relaxed)
+        .max(FILE_VERBOSITY.load(Ordering::Relaxed))
+}
+
+/// Emits a tracing event (`info` for level 1, `debug` for 2+) tagged with
+/// `category` when some sink records that level.
+pub fn log_at(level: i8, category: &str, message: &str) {
+    remember_line(category, message);
+    if verbosity() < level {
+        return;
+    }
+    if level >= 2 {
+        tracing::debug!(category, "{}", message);
+    } else {
+        tracing::info!(category, "{}", message);
+    }
+}
+
+fn remember_line(category: &str, message: &str) {
+    let Ok(mut recent) = RECENT.lock() else {
+        return;
+    };
+    if recent.len() >= RECENT_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(format!(
+        "[{}][{}] {}",
+        now_unix_ts(),
+        category,
+        truncate_with_suffix(message, RECENT_LINE_CHARS, " ...")
+    ));
+}
+
+/// The last log lines emitted in this process, oldest first.
+pub fn recent_log_lines() -> Vec<String> {
+    RECENT
+        .lock()
+        .map(|r| r.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub fn log_verbose(category: &str, message: &str) {
+    log_at(1, category, message);
+}
+
+pub fn log_debug(category: &str, message: &str) {
+    log_at(2, category, message);
+}
+
+/// Dumps an outgoing request body at `-vv`, with secret-looking fields and the
+/// resolved API key masked.
+pub fn log_request_body(url: &str, body: &Value, api_key: &str) {
+    if verbosity() < 2 {
+        return;
+    }
+    let mut redacted = body.clone();
+    redact_value(&mut redacted);
+    let text = serde_json::to_string_pretty(&redacted).unwrap_or_default();
+    log_debug(
+        "request",
+        &format!("POST {}\n{}", url, redact_text(&text, api_key)),
+    );
+}
+
+pub fn redact_text(text: &str, secret: &str) -> String {
+    if secret.trim().len() < 4 {
+        return text.to_string();
+    }
+    text.replace(secret, REDACTED)
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                let key = k.to_ascii_lowercase();
+                if SECRET_KEYS.iter().any(|s| key == *s) && v.is_string() {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(v);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for v in items {
+                redact_value(v);
+            }
+        }
+        _ => {}
+    }
+}
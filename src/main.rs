@@ -1,63 +1,437 @@
+mod approval;
+mod atomic_file;
+mod auto_commit;
+mod backups;
+mod bundle;
+mod change_diff;
 mod chat;
 mod chat_context;
+mod checkpoint;
+mod ci;
 mod cli;
 mod commands;
 mod config;
+mod config_lint;
+mod crash;
 mod diagnostics;
+mod exec_rules;
+mod file_chunks;
+mod file_index;
 mod fs_tools;
+mod fuzzy_patch;
+mod highlight;
+mod i18n;
+mod ignore_rules;
+mod injection;
+mod jobs;
+mod line_editor;
 mod llm;
+mod logging;
+mod memory;
+mod notebook;
+mod notify;
+mod packages;
+mod prompt_fetch;
 mod prompt_store;
+mod python_tool;
+mod redact;
+mod repo_map;
+mod run_report;
+mod session_crypt;
+mod session_meta;
+mod shell;
+mod sqlite_store;
+mod storage;
+mod task_branch;
+mod test_failures;
+mod tls;
+mod tool_calls;
+mod tool_output;
+mod transcript;
+mod trust;
 mod updater;
+mod usage;
 mod util;
+mod web_fetch;
 mod webui;
+mod workspace_index;
+mod worktree;
+mod write_jail;
 
-use anyhow::Result;
+use std::path::Path;
+
+use anyhow::{Result, anyhow, bail};
 use clap::Parser;
 
-use crate::chat::{run_agent_task, run_chat};
-use crate::cli::{Cli, Commands};
+use crate::approval::init_approval;
+use crate::chat::{last_verification_failed, resolve_session_name, run_agent_task, run_chat};
+use crate::ci::{EXIT_INTERRUPTED, EXIT_VERIFICATION_FAILED, init_ci};
+use crate::cli::{Cli, Commands, PrCommand};
 use crate::commands::{
-    handle_config, handle_fs, handle_models, handle_prompt, run_doctor, run_edit, run_onboard,
-    run_review,
+    EditVerify, MigrateOptions, PrOptions, handle_backups, handle_config, handle_fs, handle_git,
+    handle_history, handle_index, handle_jobs, handle_logs, handle_memory, handle_models,
+    handle_pipeline, handle_prompt, handle_storage, handle_trust, run_arch, run_ask, run_blame,
+    run_commit, run_compare, run_doc, run_doctor, run_edit, run_exec, run_fix, run_migrate,
+    run_migrate_rollback, run_onboard, run_pr_create, run_refactor, run_refactor_rollback,
+    run_restore, run_review, run_shell_hook, run_stats, run_update,
 };
 use crate::config::load_config_or_default;
+use crate::crash::install_crash_handler;
+use crate::highlight::set_color_theme;
+use crate::i18n::init_locale;
+use crate::injection::init_injection_guard;
+use crate::jobs::{BackgroundAgent, finish_job, start_background_job};
+use crate::llm::init_seed;
+use crate::logging::{init_logging, is_quiet};
+use crate::redact::init_redaction;
+use crate::run_report::init_run_report;
+use crate::session_crypt::init_session_encryption;
+use crate::session_meta::record_session_branch;
+use crate::shell::init_shell;
+use crate::storage::init_storage;
+use crate::task_branch::TaskBranch;
+use crate::tls::init_tls;
+use crate::transcript::init_transcript;
+use crate::trust::ensure_workspace_trust;
 use crate::updater::maybe_check_update;
+use crate::usage::{apply_budget_override, init_usage};
+use crate::util::set_plain_output;
 use crate::webui::run_web;
+use crate::worktree::IsolatedWorktree;
+use crate::write_jail::{expand_home, init_write_jail};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    let startup_cfg = load_config_or_default()?;
-    let _ = maybe_check_update(&startup_cfg).await;
+    let mut startup_cfg = load_config_or_default()?;
+    let log_file = cli.log_file.clone().or_else(|| {
+        startup_cfg
+            .log_file
+            .as_deref()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(expand_home)
+    });
+    init_logging(cli.verbose, cli.quiet, cli.log_level, log_file.as_deref())?;
+    set_plain_output(cli.plain);
+    set_color_theme(&startup_cfg.color_theme);
+    init_locale(&startup_cfg.locale);
+    // Resolved before an isolated run moves into its worktree.
+    if let Commands::Agent {
+        report: Some(path), ..
+    } = &cli.command
+    {
+        init_run_report(std::path::absolute(path)?);
+    }
+    let worktree = match &cli.command {
+        Commands::Agent {
+            isolated: true,
+            background: false,
+            branch,
+            ..
+        } => Some(IsolatedWorktree::enter(&startup_cfg, branch.as_deref())?),
+        _ => None,
+    };
+    if let Commands::Agent { cwd: Some(dir), .. } = &cli.command {
+        if !dir.is_dir() {
+            bail!("--cwd {} is not a directory", dir.display());
+        }
+        startup_cfg.exec_cwd = Some(dir.to_string_lossy().to_string());
+    }
+    if let (Some(wt), Some(dir)) = (&worktree, &startup_cfg.exec_cwd) {
+        startup_cfg.exec_cwd = Some(wt.remap(Path::new(dir)).to_string_lossy().to_string());
+    }
+    if let Some(level) = cli.approval {
+        startup_cfg.approval = level;
+    }
+    let ci = matches!(cli.command, Commands::Agent { ci: true, .. });
+    init_ci(ci);
+    init_approval(&startup_cfg);
+    init_shell(&startup_cfg);
+    init_storage(&startup_cfg);
+    init_session_encryption(&startup_cfg);
+    init_tls(&startup_cfg);
+    init_seed(cli.seed);
+    init_redaction(&startup_cfg);
+    init_injection_guard(&startup_cfg);
+    install_crash_handler(cli.command.name());
+    init_write_jail(&startup_cfg);
+    init_usage(cli.command.name());
+    if !matches!(
+        cli.command,
+        Commands::Logs { .. } | Commands::History { .. }
+    ) {
+        init_transcript(&startup_cfg, cli.command.name())?;
+    }
+    // Shell-hook output is eval'd by the shell, so it must stay free of notices.
+    let detached = matches!(
+        cli.command,
+        Commands::Agent {
+            job_id: Some(_),
+            ..
+        }
+    );
+    if !is_quiet() && !detached && !ci && !matches!(cli.command, Commands::ShellHook { .. }) {
+        let _ = maybe_check_update(&startup_cfg).await;
+    }
 
     match cli.command {
         Commands::Onboard => run_onboard().await?,
-        Commands::Agent { task, session } => {
+        Commands::Agent {
+            task,
+            session,
+            budget,
+            cwd,
+            background: true,
+            isolated,
+            branch,
+            report,
+            ..
+        } => {
+            // The detached process has no terminal to ask on, so trust is
+            // settled (and recorded) here.
+            if !ensure_workspace_trust(&startup_cfg)? {
+                bail!("Workspace is not trusted; the background job was not started");
+            }
+            let cwd = cwd.map(|dir| dir.to_string_lossy().to_string());
+            let report = report.map(std::path::absolute).transpose()?;
+            let job = start_background_job(&BackgroundAgent {
+                task: &task,
+                session: &session,
+                budget,
+                cwd: cwd.as_deref(),
+                seed: cli.seed,
+                isolated,
+                branch: branch.as_deref(),
+                report: report.as_deref(),
+                approval: cli.approval,
+            })?;
+            println!("Started job {} (pid {})", job.id, job.pid);
+            println!("Follow it with: dongshan jobs attach {}", job.id);
+        }
+        Commands::Agent {
+            task,
+            session,
+            budget,
+            job_id,
+            branch,
+            ..
+        } => {
+            let mut cfg = load_config_or_default()?;
+            apply_budget_override(&mut cfg, budget)?;
+            let task_branch = match &branch {
+                Some(name) if worktree.is_none() => Some(TaskBranch::checkout(name)?),
+                _ => None,
+            };
+            if let Some(name) = &branch {
+                record_session_branch(&resolve_session_name(&session)?, name)?;
+            }
+            let verify_cfg = cfg.clone();
+            // Ctrl-C ends an isolated run here so the worktree is cleaned up
+            // instead of left behind by a killed process. The task runs on its
+            // own worker so the signal is seen even while it blocks on a prompt.
+            let (mut result, interrupted) = match &worktree {
+                Some(_) => {
+                    let (session, task) = (session.clone(), task.clone());
+                    let mut handle =
+                        tokio::spawn(async move { run_agent_task(cfg, &session, &task).await });
+                    tokio::select! {
+                        joined = &mut handle => match joined {
+                            Ok(result) => (result, false),
+                            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+                            Err(err) => (Err(anyhow!("Agent task failed: {err}")), false),
+                        },
+                        _ = tokio::signal::ctrl_c() => {
+                            handle.abort();
+                            (Err(anyhow!("Interrupted")), true)
+                        }
+                    }
+                }
+                None => (run_agent_task(cfg, &session, &task).await, false),
+            };
+            match worktree {
+                Some(wt) if !interrupted => {
+                    let finished = wt.finish(&verify_cfg, &task);
+                    result = result.and(finished);
+                }
+                // Dropping the guard keeps the changes on the branch and
+                // removes the worktree.
+                other => drop(other),
+            }
+            if let Some(tb) = task_branch {
+                result = result.and(tb.print_diff_stat());
+            }
+            if let Some(id) = job_id {
+                finish_job(&id, result.as_ref().err().map(|e| format!("{e:#}")))?;
+            }
+            if interrupted {
+                // The aborted task may still be blocked reading a prompt answer,
+                // which would keep the runtime from shutting down.
+                eprintln!("Error: Interrupted");
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+            result?;
+            if ci && last_verification_failed() {
+                std::process::exit(EXIT_VERIFICATION_FAILED);
+            }
+        }
+        Commands::Ask {
+            question,
+            files,
+            images,
+            no_context,
+        } => {
             let cfg = load_config_or_default()?;
-            run_agent_task(cfg, &session, &task).await?;
+            run_ask(&cfg, &question, &files, &images, no_context).await?;
         }
-        Commands::Chat { session } => {
+        Commands::Compare { models, prompt } => {
+            let cfg = load_config_or_default()?;
+            run_compare(&cfg, &models, &prompt).await?;
+        }
+        Commands::Exec { request, yes } => {
+            let cfg = load_config_or_default()?;
+            run_exec(&cfg, &request, yes).await?;
+        }
+        Commands::Commit {
+            conventional,
+            yes,
+            message_only,
+        } => {
             let cfg = load_config_or_default()?;
+            run_commit(&cfg, conventional, yes, message_only).await?;
+        }
+        Commands::Chat { session, budget } => {
+            let mut cfg = load_config_or_default()?;
+            apply_budget_override(&mut cfg, budget)?;
             run_chat(cfg, &session).await?;
         }
         Commands::Web { port } => run_web(port).await?,
-        Commands::Config { command } => handle_config(command)?,
+        Commands::Config { command } => handle_config(*command)?,
         Commands::Prompt { command } => handle_prompt(command)?,
         Commands::Models { command } => handle_models(command)?,
+        Commands::Pipeline { command } => handle_pipeline(command).await?,
         Commands::Doctor => run_doctor().await?,
+        Commands::Update { channel, check } => run_update(channel, check).await?,
+        Commands::Index { command } => {
+            let cfg = load_config_or_default()?;
+            handle_index(&cfg, command).await?;
+        }
+        Commands::Logs { command } => handle_logs(command)?,
+        Commands::History {
+            command,
+            limit,
+            session,
+        } => handle_history(command, limit, session.as_deref())?,
+        Commands::Stats { since, json } => run_stats(&since, json)?,
         Commands::Fs { command } => handle_fs(command)?,
-        Commands::Review { file, prompt } => {
+        Commands::Review {
+            files,
+            prompt,
+            images,
+            max_tokens,
+        } => {
             let cfg = load_config_or_default()?;
-            run_review(&cfg, &file, prompt).await?;
+            run_review(&cfg, &files, prompt, &images, max_tokens).await?;
         }
         Commands::Edit {
             file,
             instruction,
             apply,
+            verify,
+            verify_cmd,
+            verify_retries,
+        } => {
+            let cfg = load_config_or_default()?;
+            let verify = (verify || verify_cmd.is_some()).then_some(EditVerify {
+                command: verify_cmd,
+                retries: verify_retries,
+            });
+            run_edit(&cfg, &file, &instruction, apply, verify).await?;
+        }
+        Commands::Arch {
+            output,
+            diagram_only,
+        } => {
+            let cfg = load_config_or_default()?;
+            run_arch(&cfg, &output, diagram_only).await?;
+        }
+        Commands::Doc { target, yes } => {
+            let cfg = load_config_or_default()?;
+            run_doc(&cfg, &target, yes).await?;
+        }
+        Commands::Refactor {
+            scope,
+            instruction,
+            yes,
+            rollback,
+        } => match (scope, instruction) {
+            (Some(scope), Some(instruction)) if !rollback => {
+                let cfg = load_config_or_default()?;
+                run_refactor(&cfg, &scope, &instruction, yes).await?;
+            }
+            _ => run_refactor_rollback()?,
+        },
+        Commands::Migrate {
+            pattern,
+            instruction,
+            check,
+            retries,
+            yes,
+            rollback,
+        } => match (pattern, instruction) {
+            (Some(pattern), Some(instruction)) if !rollback => {
+                let cfg = load_config_or_default()?;
+                let opts = MigrateOptions {
+                    check,
+                    retries,
+                    yes,
+                };
+                run_migrate(&cfg, &pattern, &instruction, &opts).await?;
+            }
+            _ => run_migrate_rollback()?,
+        },
+        Commands::Fix {
+            cmd,
+            max_iterations,
+            yes,
+        } => {
+            let cfg = load_config_or_default()?;
+            run_fix(&cfg, cmd.as_deref(), max_iterations, yes).await?;
+        }
+        Commands::Blame { target } => {
+            let cfg = load_config_or_default()?;
+            run_blame(&cfg, &target).await?;
+        }
+        Commands::ShellHook { shell } => run_shell_hook(shell),
+        Commands::Git { command } => handle_git(command)?,
+        Commands::Restore { file, version } => run_restore(&file, version)?,
+        Commands::Backups { command } => handle_backups(command)?,
+        Commands::Trust { command } => handle_trust(command)?,
+        Commands::Memory { command } => handle_memory(command)?,
+        Commands::Jobs { command } => handle_jobs(command).await?,
+        Commands::Pr {
+            command:
+                PrCommand::Create {
+                    session,
+                    base,
+                    remote,
+                    title,
+                    draft,
+                    yes,
+                },
         } => {
             let cfg = load_config_or_default()?;
-            run_edit(&cfg, &file, &instruction, apply).await?;
+            let opts = PrOptions {
+                session,
+                base,
+                remote,
+                title,
+                draft,
+                yes,
+            };
+            run_pr_create(&cfg, &opts).await?;
         }
+        Commands::Storage { command } => handle_storage(command)?,
     }
 
     Ok(())
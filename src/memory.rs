@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::atomic_file::{with_file_lock, write_atomic};
+use crate::config::config_dir;
+use crate::diagnostics::now_unix_ts;
+use crate::util::truncate_with_suffix;
+
+/// Cap on the memory block added to the system prompt.
+const MEMORY_PROMPT_MAX_CHARS: usize = 6_000;
+
+/// One fact the user asked dongshan to remember.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: u64,
+    pub text: String,
+    pub ts: u64,
+}
+
+/// Global facts apply everywhere; workspace facts only under their directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MemoryStore {
+    #[serde(default)]
+    next_id: u64,
+    #[serde(default)]
+    global: Vec<MemoryEntry>,
+    #[serde(default)]
+    workspaces: BTreeMap<String, Vec<MemoryEntry>>,
+}
+
+fn memory_file() -> Result<PathBuf> {
+    Ok(config_dir()?.join("memory.json"))
+}
+
+fn load_store() -> Result<MemoryStore> {
+    let path = memory_file()?;
+    if !path.exists() {
+        return Ok(MemoryStore::default());
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Invalid memory file: {}", path.display()))
+}
+
+fn save_store(store: &MemoryStore) -> Result<()> {
+    let path = memory_file()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let text = serde_json::to_string_pretty(store)?;
+    write_atomic(&path, text)
+}
+
+/// Loads, changes and saves the store under its file lock, so a `/remember`
+/// in one session does not drop one made concurrently in another.
+fn update_store<T>(
+    change: impl FnOnce(&mut MemoryStore) -> Result<Option<T>>,
+) -> Result<Option<T>> {
+    with_file_lock(&memory_file()?, || {
+        let mut store = load_store()?;
+        let result = change(&mut store)?;
+        if result.is_some() {
+            save_store(&store)?;
+        }
+        Ok(result)
+    })
+}
+
+fn workspace_key(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+fn current_workspace_key() -> Result<String> {
+    let cwd = std::env::current_dir().context("Failed to resolve current directory")?;
+    Ok(workspace_key(&cwd))
+}
+
+/// Stores `text` globally or for the current directory and returns its id.
+pub fn remember(text: &str, global: bool) -> Result<u64> {
+    let id = update_store(|store| {
+        store.next_id += 1;
+        let entry = MemoryEntry {
+            id: store.next_id,
+            text: text.trim().to_string(),
+            ts: now_unix_ts(),
+        };
+        if global {
+            store.global.push(entry);
+        } else {
+            store
+                .workspaces
+                .entry(current_workspace_key()?)
+                .or_default()
+                .push(entry);
+        }
+        Ok(Some(store.next_id))
+    })?;
+    Ok(id.unwrap_or_default())
+}
+
+/// Removes the fact with `id` from any scope; returns whether it existed.
+pub fn forget(id: u64) -> Result<bool> {
+    let removed = update_store(|store| {
+        let before = store.global.len() + store.workspaces.values().map(Vec::len).sum::<usize>();
+        store.global.retain(|e| e.id != id);
+        for entries in store.workspaces.values_mut() {
+            entries.retain(|e| e.id != id);
+        }
+        store.workspaces.retain(|_, entries| !entries.is_empty());
+        let after = store.global.len() + store.workspaces.values().map(Vec::len).sum::<usize>();
+        Ok((after != before).then_some(()))
+    })?;
+    Ok(removed.is_some())
+}
+
+/// Global facts and the facts recorded for the current directory or any of
+/// its ancestors.
+pub fn active_memories() -> Result<(Vec<MemoryEntry>, Vec<MemoryEntry>)> {
+    let store = load_store()?;
+    let key = PathBuf::from(current_workspace_key()?);
+    let mut workspace = key
+        .ancestors()
+        .filter_map(|dir| store.workspaces.get(dir.to_string_lossy().as_ref()))
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    workspace.sort_by_key(|e| e.id);
+    Ok((store.global, workspace))
+}
+
+/// Remembered facts formatted for the system prompt, if there are any.
+pub fn memory_prompt_block() -> Option<String> {
+    let (global, workspace) = active_memories().ok()?;
+    if global.is_empty() && workspace.is_empty() {
+        return None;
+    }
+    let text = global
+        .iter()
+        .chain(workspace.iter())
+        .map(|e| format!("- {}", e.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(truncate_with_suffix(
+        &text,
+        MEMORY_PROMPT_MAX_CHARS,
+        "\n...[memory truncated]",
+    ))
+}
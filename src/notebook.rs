@@ -0,0 +1,247 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::diagnostics::now_unix_ts;
+
+/// Cell header in the text form shown to the model, e.g. `# %% [3] markdown`.
+const CELL_MARKER: &str = "# %%";
+/// Prefix of cell output lines; only shown when reading, never written back.
+const OUTPUT_PREFIX: &str = "#> ";
+const MAX_OUTPUT_LINES: usize = 20;
+
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Renders a notebook as percent-format cells (`# %% [n] type` + source),
+/// optionally followed by each code cell's text outputs.
+pub fn render_notebook(raw: &str, with_outputs: bool) -> Result<String> {
+    let nb: Value = serde_json::from_str(raw).context("Invalid notebook JSON")?;
+    let cells = notebook_cells(&nb)?;
+    let mut out = String::new();
+    for (i, cell) in cells.iter().enumerate() {
+        let cell_type = cell
+            .get("cell_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("code");
+        out.push_str(&format!("{} [{}] {}\n", CELL_MARKER, i + 1, cell_type));
+        let source = source_text(cell.get("source"));
+        out.push_str(&source);
+        if !source.is_empty() && !source.ends_with('\n') {
+            out.push('\n');
+        }
+        if with_outputs && cell_type == "code" {
+            for line in output_lines(cell) {
+                out.push_str(OUTPUT_PREFIX);
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Applies an edited percent-format text back onto the notebook JSON: cells
+/// keep their metadata and outputs unless their source changed, `# %% code`
+/// headers without an index create new cells, and omitted cells are removed.
+pub fn merge_notebook_edit(raw: &str, edited: &str) -> Result<String> {
+    let mut nb: Value = serde_json::from_str(raw).context("Invalid notebook JSON")?;
+    let old_cells = notebook_cells(&nb)?.clone();
+    let with_ids = old_cells.iter().any(|c| c.get("id").is_some())
+        || nb
+            .get("nbformat_minor")
+            .and_then(|v| v.as_u64())
+            .is_some_and(|m| m >= 5);
+    let parsed = parse_cells(edited);
+    if parsed.is_empty() && !old_cells.is_empty() {
+        bail!("Edited notebook has no `{}` cells", CELL_MARKER);
+    }
+
+    let mut used = vec![false; old_cells.len()];
+    let mut cells = Vec::with_capacity(parsed.len());
+    for (n, cell) in parsed.into_iter().enumerate() {
+        let existing = cell
+            .index
+            .and_then(|i| i.checked_sub(1))
+            .filter(|&i| i < old_cells.len() && !used[i]);
+        let merged = match existing {
+            Some(i)
+                if old_cells[i].get("cell_type").and_then(|v| v.as_str())
+                    == Some(cell.cell_type.as_str()) =>
+            {
+                used[i] = true;
+                let mut old = old_cells[i].clone();
+                if source_text(old.get("source")).trim_end() != cell.source {
+                    old["source"] = source_json(&cell.source);
+                    if cell.cell_type == "code" {
+                        old["outputs"] = json!([]);
+                        old["execution_count"] = Value::Null;
+                    }
+                }
+                old
+            }
+            _ => new_cell(&cell, with_ids.then(|| new_cell_id(n))),
+        };
+        cells.push(merged);
+    }
+    nb["cells"] = Value::Array(cells);
+    to_notebook_json(&nb)
+}
+
+struct ParsedCell {
+    index: Option<usize>,
+    cell_type: String,
+    source: String,
+}
+
+fn parse_cells(text: &str) -> Vec<ParsedCell> {
+    let mut cells: Vec<ParsedCell> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    let mut current: Option<(Option<usize>, String)> = None;
+    for line in text.lines() {
+        if let Some(header) = line.strip_prefix(CELL_MARKER) {
+            if let Some((index, cell_type)) = current.take() {
+                cells.push(finish_cell(index, cell_type, &lines));
+            } else if lines.iter().any(|l| !l.trim().is_empty()) {
+                cells.push(finish_cell(None, "code".to_string(), &lines));
+            }
+            lines.clear();
+            current = Some(parse_header(header));
+            continue;
+        }
+        if line.starts_with(OUTPUT_PREFIX) {
+            continue;
+        }
+        lines.push(line);
+    }
+    if let Some((index, cell_type)) = current {
+        cells.push(finish_cell(index, cell_type, &lines));
+    } else if lines.iter().any(|l| !l.trim().is_empty()) {
+        cells.push(finish_cell(None, "code".to_string(), &lines));
+    }
+    cells
+}
+
+/// Parses `[3] markdown`, `markdown` or `[3]` after the cell marker.
+fn parse_header(header: &str) -> (Option<usize>, String) {
+    let mut rest = header.trim();
+    let mut index = None;
+    if let Some(inner) = rest.strip_prefix('[')
+        && let Some(end) = inner.find(']')
+    {
+        index = inner[..end].trim().parse::<usize>().ok();
+        rest = inner[end + 1..].trim();
+    }
+    let cell_type = match rest.split_whitespace().next() {
+        Some(t) if t.eq_ignore_ascii_case("markdown") || t.eq_ignore_ascii_case("md") => "markdown",
+        Some(t) if t.eq_ignore_ascii_case("raw") => "raw",
+        _ => "code",
+    };
+    (index, cell_type.to_string())
+}
+
+fn finish_cell(index: Option<usize>, cell_type: String, lines: &[&str]) -> ParsedCell {
+    ParsedCell {
+        index,
+        cell_type,
+        source: lines.join("\n").trim_end().to_string(),
+    }
+}
+
+fn notebook_cells(nb: &Value) -> Result<&Vec<Value>> {
+    nb.get("cells")
+        .and_then(|c| c.as_array())
+        .context("Notebook has no `cells` array")
+}
+
+fn source_text(source: Option<&Value>) -> String {
+    match source {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts.iter().filter_map(|p| p.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+/// nbformat stores sources as a list of lines, each keeping its `\n`.
+fn source_json(source: &str) -> Value {
+    json!(source.split_inclusive('\n').collect::<Vec<_>>())
+}
+
+fn output_lines(cell: &Value) -> Vec<String> {
+    let mut lines = Vec::new();
+    for output in cell
+        .get("outputs")
+        .and_then(|o| o.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let text = match output.get("output_type").and_then(|t| t.as_str()) {
+            Some("stream") => source_text(output.get("text")),
+            Some("error") => format!(
+                "{}: {}",
+                output
+                    .get("ename")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Error"),
+                output.get("evalue").and_then(|v| v.as_str()).unwrap_or("")
+            ),
+            _ => {
+                let data = output.get("data");
+                match data.and_then(|d| d.get("text/plain")) {
+                    Some(plain) => source_text(Some(plain)),
+                    None if data.is_some_and(|d| d.get("image/png").is_some()) => {
+                        "[image output]".to_string()
+                    }
+                    None => continue,
+                }
+            }
+        };
+        lines.extend(text.lines().map(str::to_string));
+    }
+    if lines.len() > MAX_OUTPUT_LINES {
+        let omitted = lines.len() - MAX_OUTPUT_LINES;
+        lines.truncate(MAX_OUTPUT_LINES);
+        lines.push(format!("... {} more output lines", omitted));
+    }
+    lines
+}
+
+fn new_cell(cell: &ParsedCell, id: Option<String>) -> Value {
+    let mut obj = Map::new();
+    obj.insert("cell_type".to_string(), json!(cell.cell_type));
+    if let Some(id) = id {
+        obj.insert("id".to_string(), json!(id));
+    }
+    obj.insert("metadata".to_string(), json!({}));
+    obj.insert("source".to_string(), source_json(&cell.source));
+    if cell.cell_type == "code" {
+        obj.insert("execution_count".to_string(), Value::Null);
+        obj.insert("outputs".to_string(), json!([]));
+    }
+    Value::Object(obj)
+}
+
+fn new_cell_id(n: usize) -> String {
+    format!(
+        "{:08x}",
+        (now_unix_ts() as usize).wrapping_mul(31).wrapping_add(n) as u32
+    )
+}
+
+/// Serializes like Jupyter does: one-space indent and a trailing newline.
+fn to_notebook_json(nb: &Value) -> Result<String> {
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    nb.serialize(&mut ser)
+        .context("Failed to serialize notebook")?;
+    let mut out = String::from_utf8(buf).context("Notebook JSON is not UTF-8")?;
+    out.push('\n');
+    Ok(out)
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::logging::log_verbose;
+use crate::tls::client_builder;
+use crate::util::{color_dim, truncate_with_suffix};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a turn waits for the desktop notification to be handed off.
+const DESKTOP_NOTIFY_WAIT: Duration = Duration::from_secs(2);
+
+/// Outcome of one `dongshan agent` run, posted to `notify_webhook_url`.
+pub struct AgentRunSummary<'a> {
+    pub task: &'a str,
+    pub session: &'a str,
+    pub duration: Duration,
+    pub changed_files: &'a [String],
+    pub verification: Option<String>,
+    pub error: Option<String>,
+}
+
+impl AgentRunSummary<'_> {
+    /// JSON body; `text` and `content` carry a one-line message so Slack and
+    /// Discord incoming webhooks can display it as is.
+    fn payload(&self) -> Value {
+        let status = if self.error.is_some() { "failed" } else { "ok" };
+        let mut line = format!(
+            "dongshan agent {} in {}s: {}",
+            status,
+            self.duration.as_secs(),
+            self.task.lines().next().unwrap_or_default()
+        );
+        if !self.changed_files.is_empty() {
+            line.push_str(&format!(" ({} files changed)", self.changed_files.len()));
+        }
+        if let Some(err) = &self.error {
+            line.push_str(&format!(" - {}", err));
+        }
+        json!({
+            "event": "agent.finished",
+            "status": status,
+            "task": self.task,
+            "session": self.session,
+            "duration_secs": self.duration.as_secs_f64(),
+            "changed_files": self.changed_files,
+            "verification": self.verification,
+            "error": self.error,
+            "text": line,
+            "content": line,
+        })
+    }
+}
+
+/// Posts the run summary when a webhook is configured and the run took at
+/// least `notify_min_secs`. Failures are reported but never fail the run.
+pub async fn notify_agent_finished(cfg: &Config, summary: &AgentRunSummary<'_>) {
+    let Some(url) = cfg
+        .notify_webhook_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+    else {
+        return;
+    };
+    if summary.duration.as_secs() < cfg.notify_min_secs {
+        return;
+    }
+    if let Err(err) = post_json(url, &summary.payload()).await {
+        log_verbose("notify", &format!("{err:#}"));
+        println!("{}", color_dim(&format!("notify> webhook failed: {}", err)));
+    }
+}
+
+/// Shows a desktop notification when `notifications` is on and the turn took
+/// at least `notify_after_secs`, so a user who switched away sees it is done.
+/// Runs on its own thread because some platforms block until it is dismissed;
+/// waits up to `DESKTOP_NOTIFY_WAIT` so it is not lost when the process exits
+/// right after. Without the `desktop-notify` feature this only logs.
+pub fn notify_desktop_if_slow(cfg: &Config, elapsed: Duration, title: &str, body: &str) {
+    if !cfg.notifications || elapsed.as_secs() < cfg.notify_after_secs {
+        return;
+    }
+    let title = title.to_string();
+    let body = format!(
+        "{} ({}s)",
+        truncate_with_suffix(body.lines().next().unwrap_or_default(), 120, "..."),
+        elapsed.as_secs()
+    );
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        if let Err(err) = show_desktop_notification(&title, &body) {
+            log_verbose("notify", &format!("desktop notification failed: {err}"));
+        }
+        let _ = done_tx.send(());
+    });
+    let _ = done_rx.recv_timeout(DESKTOP_NOTIFY_WAIT);
+}
+
+#[cfg(feature = "desktop-notify")]
+fn show_desktop_notification(title: &str, body: &str) -> Result<()> {
+    notify_rust::Notification::new()
+        .appname("dongshan")
+        .summary(title)
+        .body(body)
+        .show()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "desktop-notify"))]
+fn show_desktop_notification(_title: &str, _body: &str) -> Result<()> {
+    bail!("built without the desktop-notify feature")
+}
+
+async fn post_json(url: &str, body: &Value) -> Result<()> {
+    let client = client_builder()?
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .context("failed to build HTTP client")?;
+    let resp = client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .context("webhook request failed")?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("webhook returned HTTP {}", status);
+    }
+    Ok(())
+}
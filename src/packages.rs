@@ -0,0 +1,712 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value as JsonValue;
+use toml::Value as TomlValue;
+
+use crate::ignore_rules::is_dongshan_ignored;
+use crate::util::truncate_with_suffix;
+
+/// Packages listed in the snapshot; the rest are counted.
+const MAX_LISTED_PACKAGES: usize = 60;
+const MAX_DESCRIPTION_CHARS: usize = 80;
+/// How deep a repo without a workspace manifest is scanned for packages.
+const MULTI_PACKAGE_SCAN_DEPTH: usize = 2;
+const SKIPPED_DIRS: &[&str] = &[
+    ".git",
+    ".dongshan",
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "vendor",
+    ".venv",
+    "venv",
+];
+
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    /// Relative to the workspace root with `/` separators; `.` for the root.
+    pub path: String,
+    /// `cargo`, `npm`, `python` or `go`.
+    pub ecosystem: &'static str,
+    pub role: String,
+}
+
+#[derive(Debug)]
+pub struct Workspace {
+    /// e.g. `Cargo workspace`, `pnpm workspace` or `multi-package repo`.
+    pub kind: String,
+    pub packages: Vec<Package>,
+}
+
+/// Detects a monorepo at `root`: Cargo, pnpm and npm/yarn workspaces from
+/// their manifests, otherwise several package manifests below the root.
+/// A single-package repo returns `None`.
+pub fn detect_workspace(root: &Path) -> Option<Workspace> {
+    let mut sources = vec![
+        ("Cargo workspace", cargo_workspace(root)),
+        ("pnpm workspace", pnpm_workspace(root)),
+        ("npm workspaces", npm_workspaces(root)),
+    ];
+    sources.retain(|(_, found)| !found.is_empty());
+    if sources.is_empty() {
+        let found = scan_packages(root);
+        if found.len() < 2 {
+            return None;
+        }
+        sources.push(("multi-package repo", found));
+    }
+    let kinds: Vec<&str> = sources.iter().map(|(kind, _)| *kind).collect();
+    let mut packages: Vec<Package> = Vec::new();
+    for pkg in sources.iter().flat_map(|(_, found)| found) {
+        if !packages
+            .iter()
+            .any(|p| p.path == pkg.path && p.ecosystem == pkg.ecosystem)
+        {
+            packages.push(pkg.clone());
+        }
+    }
+    packages.sort_by(|a, b| a.path.cmp(&b.path).then(a.ecosystem.cmp(b.ecosystem)));
+    Some(Workspace {
+        kind: kinds.join(" + "),
+        packages,
+    })
+}
+
+/// The per-package lines of the project snapshot.
+pub fn render_workspace(ws: &Workspace) -> String {
+    let mut lines = vec![format!(
+        "Workspace: {} ({} packages). Run package-specific commands in the package directory \
+         or select the package (e.g. `cargo -p`, `pnpm --filter`).",
+        ws.kind,
+        ws.packages.len()
+    )];
+    for pkg in ws.packages.iter().take(MAX_LISTED_PACKAGES) {
+        lines.push(format!(
+            "- {} ({}, {}): {}",
+            pkg.name, pkg.path, pkg.ecosystem, pkg.role
+        ));
+    }
+    if ws.packages.len() > MAX_LISTED_PACKAGES {
+        lines.push(format!(
+            "- ... ({} more)",
+            ws.packages.len() - MAX_LISTED_PACKAGES
+        ));
+    }
+    lines.join("\n")
+}
+
+fn read_toml(path: &Path) -> Option<TomlValue> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn read_json(path: &Path) -> Option<JsonValue> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn toml_strings(value: Option<&TomlValue>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn json_strings(value: Option<&JsonValue>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn with_description(role: &str, description: Option<&str>) -> String {
+    match description.map(str::trim).filter(|d| !d.is_empty()) {
+        Some(d) => format!(
+            "{} - {}",
+            role,
+            truncate_with_suffix(d, MAX_DESCRIPTION_CHARS, "...")
+        ),
+        None => role.to_string(),
+    }
+}
+
+fn rel_display(root: &Path, dir: &Path) -> String {
+    let rel = dir.strip_prefix(root).unwrap_or(dir);
+    let s = rel.to_string_lossy().replace('\\', "/");
+    if s.is_empty() { ".".to_string() } else { s }
+}
+
+/// `*` matches any run of characters within one path segment.
+fn segment_matches(pattern: &str, name: &str) -> bool {
+    let Some((head, rest)) = pattern.split_once('*') else {
+        return pattern == name;
+    };
+    let Some(tail) = name.strip_prefix(head) else {
+        return false;
+    };
+    (0..=tail.len())
+        .filter(|&i| tail.is_char_boundary(i))
+        .any(|i| segment_matches(rest, &tail[i..]))
+}
+
+/// Directories matching a workspace member pattern such as `crates/*` or
+/// `packages/**`. `**` is treated as a single segment, which covers the
+/// usual `packages/**` layouts.
+fn expand_members(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let pattern = pattern
+        .trim()
+        .trim_start_matches("./")
+        .trim_end_matches('/');
+    let mut dirs = vec![root.to_path_buf()];
+    for segment in pattern.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        let mut next = Vec::new();
+        for dir in &dirs {
+            if !segment.contains('*') {
+                let candidate = dir.join(segment);
+                if candidate.is_dir() {
+                    next.push(candidate);
+                }
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().to_string();
+                if path.is_dir()
+                    && !SKIPPED_DIRS.contains(&name.as_str())
+                    && segment_matches(&segment.replace("**", "*"), &name)
+                {
+                    next.push(path);
+                }
+            }
+        }
+        dirs = next;
+    }
+    dirs.sort();
+    dirs
+}
+
+/// Expands include patterns and drops anything matched by an exclude
+/// pattern (`!pattern` entries or Cargo's `exclude`).
+fn member_dirs(root: &Path, include: &[String], exclude: &[String]) -> Vec<PathBuf> {
+    let excluded: Vec<PathBuf> = exclude
+        .iter()
+        .flat_map(|p| expand_members(root, p))
+        .collect();
+    let mut out: Vec<PathBuf> = Vec::new();
+    for pattern in include {
+        for dir in expand_members(root, pattern) {
+            if !excluded.contains(&dir) && !out.contains(&dir) {
+                out.push(dir);
+            }
+        }
+    }
+    out
+}
+
+fn cargo_package(root: &Path, dir: &Path) -> Option<Package> {
+    let manifest = read_toml(&dir.join("Cargo.toml"))?;
+    let package = manifest.get("package")?;
+    let name = package.get("name")?.as_str()?.to_string();
+    let has_lib = dir.join("src/lib.rs").is_file() || manifest.get("lib").is_some();
+    let has_bin = dir.join("src/main.rs").is_file()
+        || dir.join("src/bin").is_dir()
+        || manifest.get("bin").is_some();
+    let role = match (has_lib, has_bin) {
+        (true, true) => "library + binary",
+        (false, true) => "binary",
+        (true, false) => "library",
+        (false, false) => "crate",
+    };
+    Some(Package {
+        name,
+        path: rel_display(root, dir),
+        ecosystem: "cargo",
+        role: with_description(role, package.get("description").and_then(|d| d.as_str())),
+    })
+}
+
+fn npm_package(root: &Path, dir: &Path) -> Option<Package> {
+    let manifest = read_json(&dir.join("package.json"))?;
+    let name = manifest
+        .get("name")
+        .and_then(|n| n.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| rel_display(root, dir));
+    let scripts = manifest.get("scripts");
+    let has_script = |s: &str| scripts.and_then(|m| m.get(s)).is_some();
+    let role = if manifest.get("bin").is_some() {
+        "cli"
+    } else if has_script("dev") || has_script("start") {
+        "app"
+    } else {
+        "library"
+    };
+    Some(Package {
+        name,
+        path: rel_display(root, dir),
+        ecosystem: "npm",
+        role: with_description(role, manifest.get("description").and_then(|d| d.as_str())),
+    })
+}
+
+fn python_package(root: &Path, dir: &Path) -> Option<Package> {
+    let manifest = read_toml(&dir.join("pyproject.toml"))?;
+    let project = manifest
+        .get("project")
+        .or_else(|| manifest.get("tool").and_then(|t| t.get("poetry")));
+    let name = project
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| rel_display(root, dir));
+    let description = project
+        .and_then(|p| p.get("description"))
+        .and_then(|d| d.as_str());
+    Some(Package {
+        name,
+        path: rel_display(root, dir),
+        ecosystem: "python",
+        role: with_description("package", description),
+    })
+}
+
+fn go_package(root: &Path, dir: &Path) -> Option<Package> {
+    let text = fs::read_to_string(dir.join("go.mod")).ok()?;
+    let name = text
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("module "))
+        .map(|m| m.trim().to_string())?;
+    let role = if dir.join("main.go").is_file() || dir.join("cmd").is_dir() {
+        "module with commands"
+    } else {
+        "module"
+    };
+    Some(Package {
+        name,
+        path: rel_display(root, dir),
+        ecosystem: "go",
+        role: role.to_string(),
+    })
+}
+
+fn cargo_workspace(root: &Path) -> Vec<Package> {
+    let Some(manifest) = read_toml(&root.join("Cargo.toml")) else {
+        return Vec::new();
+    };
+    let Some(workspace) = manifest.get("workspace") else {
+        return Vec::new();
+    };
+    let members = toml_strings(workspace.get("members"));
+    let exclude = toml_strings(workspace.get("exclude"));
+    let mut out: Vec<Package> = member_dirs(root, &members, &exclude)
+        .iter()
+        .filter_map(|dir| cargo_package(root, dir))
+        .collect();
+    if let Some(pkg) = cargo_package(root, root)
+        && !out.iter().any(|p| p.path == pkg.path)
+    {
+        out.push(pkg);
+    }
+    out
+}
+
+/// `packages:` entries of pnpm-workspace.yaml, read line by line so no YAML
+/// parser is needed for the usual flat list.
+fn pnpm_workspace(root: &Path) -> Vec<Package> {
+    let Ok(text) = fs::read_to_string(root.join("pnpm-workspace.yaml")) else {
+        return Vec::new();
+    };
+    let (mut include, mut exclude) = (Vec::new(), Vec::new());
+    let mut in_packages = false;
+    for line in text.lines() {
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = line.trim_end().trim_end_matches(':') == "packages";
+            continue;
+        }
+        let Some(item) = line.trim().strip_prefix('-').filter(|_| in_packages) else {
+            continue;
+        };
+        let item = item.trim().trim_matches(['\'', '"']);
+        match item.strip_prefix('!') {
+            Some(negated) => exclude.push(negated.to_string()),
+            None if !item.is_empty() => include.push(item.to_string()),
+            None => {}
+        }
+    }
+    member_dirs(root, &include, &exclude)
+        .iter()
+        .filter_map(|dir| npm_package(root, dir))
+        .collect()
+}
+
+fn npm_workspaces(root: &Path) -> Vec<Package> {
+    let Some(manifest) = read_json(&root.join("package.json")) else {
+        return Vec::new();
+    };
+    let workspaces = manifest.get("workspaces");
+    let patterns = match workspaces {
+        Some(JsonValue::Array(_)) => json_strings(workspaces),
+        Some(JsonValue::Object(map)) => json_strings(map.get("packages")),
+        _ => return Vec::new(),
+    };
+    let (exclude, include): (Vec<String>, Vec<String>) =
+        patterns.into_iter().partition(|p| p.starts_with('!'));
+    let exclude: Vec<String> = exclude
+        .iter()
+        .map(|p| p.trim_start_matches('!').to_string())
+        .collect();
+    member_dirs(root, &include, &exclude)
+        .iter()
+        .filter_map(|dir| npm_package(root, dir))
+        .collect()
+}
+
+/// Package manifests in the root and up to `MULTI_PACKAGE_SCAN_DEPTH` levels
+/// below it, for repos that hold several projects without a workspace file.
+fn scan_packages(root: &Path) -> Vec<Package> {
+    let mut out = Vec::new();
+    let mut stack = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = stack.pop() {
+        out.extend(cargo_package(root, &dir));
+        out.extend(npm_package(root, &dir));
+        out.extend(python_package(root, &dir));
+        out.extend(go_package(root, &dir));
+        if depth >= MULTI_PACKAGE_SCAN_DEPTH {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir()
+                && !name.starts_with('.')
+                && !SKIPPED_DIRS.contains(&name.as_str())
+                && !is_dongshan_ignored(&path)
+            {
+                stack.push((path, depth + 1));
+            }
+        }
+    }
+    out
+}
+
+/// Entries listed per manifest section; the rest are counted.
+const MAX_SECTION_ITEMS: usize = 40;
+const MAX_SCRIPT_CHARS: usize = 60;
+
+/// Compact summaries of the manifests in `dir` (Cargo.toml, package.json,
+/// pyproject.toml, go.mod): dependencies, scripts, bin targets and features
+/// instead of the raw files. Empty when there is none.
+pub fn summarize_manifests(dir: &Path) -> String {
+    [
+        summarize_cargo(dir),
+        summarize_npm(dir),
+        summarize_pyproject(dir),
+        summarize_go_mod(dir),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn push_section(lines: &mut Vec<String>, label: &str, items: Vec<String>) {
+    if items.is_empty() {
+        return;
+    }
+    let mut shown = items
+        .iter()
+        .take(MAX_SECTION_ITEMS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if items.len() > MAX_SECTION_ITEMS {
+        shown.push_str(&format!(" (+{} more)", items.len() - MAX_SECTION_ITEMS));
+    }
+    lines.push(format!("  {}: {}", label, shown));
+}
+
+fn header(file: &str, name: Option<&str>, version: Option<&str>) -> String {
+    match (name, version) {
+        (Some(n), Some(v)) => format!("{}: {} {}", file, n, v),
+        (Some(n), None) => format!("{}: {}", file, n),
+        _ => format!("{}:", file),
+    }
+}
+
+/// `name version`, plus features, source and optionality when set.
+fn cargo_dependency(name: &str, spec: &TomlValue) -> String {
+    let Some(table) = spec.as_table() else {
+        return format!("{} {}", name, spec.as_str().unwrap_or("*"));
+    };
+    let mut out = name.to_string();
+    if let Some(v) = table.get("version").and_then(|v| v.as_str()) {
+        out.push_str(&format!(" {}", v));
+    } else if table.contains_key("path") {
+        out.push_str(" (path)");
+    } else if table.contains_key("git") {
+        out.push_str(" (git)");
+    } else if table.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+        out.push_str(" (workspace)");
+    }
+    let features = toml_strings(table.get("features"));
+    if !features.is_empty() {
+        out.push_str(&format!(" +{}", features.join(",")));
+    }
+    if table.get("optional").and_then(|o| o.as_bool()) == Some(true) {
+        out.push_str(" (optional)");
+    }
+    out
+}
+
+fn cargo_dependencies(table: Option<&TomlValue>) -> Vec<String> {
+    table
+        .and_then(|t| t.as_table())
+        .map(|t| t.iter().map(|(k, v)| cargo_dependency(k, v)).collect())
+        .unwrap_or_default()
+}
+
+fn summarize_cargo(dir: &Path) -> Option<String> {
+    let manifest = read_toml(&dir.join("Cargo.toml"))?;
+    let package = manifest.get("package");
+    let name = package.and_then(|p| p.get("name")).and_then(|n| n.as_str());
+    let version = package
+        .and_then(|p| p.get("version"))
+        .and_then(|v| v.as_str());
+    let mut lines = vec![header("Cargo.toml", name, version)];
+    if let Some(edition) = package
+        .and_then(|p| p.get("edition"))
+        .and_then(|e| e.as_str())
+    {
+        lines.push(format!("  edition: {}", edition));
+    }
+    if let Some(workspace) = manifest.get("workspace") {
+        push_section(
+            &mut lines,
+            "workspace members",
+            toml_strings(workspace.get("members")),
+        );
+        push_section(
+            &mut lines,
+            "workspace dependencies",
+            cargo_dependencies(workspace.get("dependencies")),
+        );
+    }
+    for (key, label) in [
+        ("dependencies", "dependencies"),
+        ("dev-dependencies", "dev-dependencies"),
+        ("build-dependencies", "build-dependencies"),
+    ] {
+        push_section(&mut lines, label, cargo_dependencies(manifest.get(key)));
+    }
+
+    let mut bins: Vec<String> = manifest
+        .get("bin")
+        .and_then(|b| b.as_array())
+        .map(|bins| {
+            bins.iter()
+                .filter_map(|b| b.get("name").and_then(|n| n.as_str()))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    if bins.is_empty()
+        && let Some(name) = name
+        && dir.join("src/main.rs").is_file()
+    {
+        bins.push(format!("{} (src/main.rs)", name));
+    }
+    if let Ok(entries) = fs::read_dir(dir.join("src/bin")) {
+        let mut extra: Vec<String> = entries
+            .flatten()
+            .filter_map(|e| {
+                let path = e.path();
+                if path.is_dir() {
+                    return path.file_name().map(|n| n.to_string_lossy().to_string());
+                }
+                (path.extension().and_then(|x| x.to_str()) == Some("rs"))
+                    .then(|| path.file_stem())
+                    .flatten()
+                    .map(|s| s.to_string_lossy().to_string())
+            })
+            .filter(|b| !bins.contains(b))
+            .collect();
+        extra.sort();
+        bins.extend(extra);
+    }
+    push_section(&mut lines, "bins", bins);
+    if manifest.get("lib").is_some() || dir.join("src/lib.rs").is_file() {
+        lines.push("  lib: yes".to_string());
+    }
+    let features: Vec<String> = manifest
+        .get("features")
+        .and_then(|f| f.as_table())
+        .map(|t| {
+            t.iter()
+                .map(|(name, enables)| {
+                    let enables = toml_strings(Some(enables));
+                    if enables.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{} = [{}]", name, enables.join(", "))
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    push_section(&mut lines, "features", features);
+    Some(lines.join("\n"))
+}
+
+fn json_map_entries(value: Option<&JsonValue>, script: bool) -> Vec<String> {
+    let Some(map) = value.and_then(|v| v.as_object()) else {
+        return Vec::new();
+    };
+    map.iter()
+        .map(|(k, v)| {
+            let v = v.as_str().unwrap_or_default();
+            if script {
+                format!(
+                    "{} = `{}`",
+                    k,
+                    truncate_with_suffix(v, MAX_SCRIPT_CHARS, "...")
+                )
+            } else {
+                format!("{} {}", k, v)
+            }
+        })
+        .collect()
+}
+
+fn summarize_npm(dir: &Path) -> Option<String> {
+    let manifest = read_json(&dir.join("package.json"))?;
+    let str_field = |key: &str| manifest.get(key).and_then(|v| v.as_str());
+    let mut lines = vec![header(
+        "package.json",
+        str_field("name"),
+        str_field("version"),
+    )];
+    if let Some(kind) = str_field("type") {
+        lines.push(format!("  type: {}", kind));
+    }
+    if let Some(manager) = str_field("packageManager") {
+        lines.push(format!("  package manager: {}", manager));
+    }
+    let workspaces = manifest.get("workspaces");
+    let workspaces = match workspaces {
+        Some(JsonValue::Object(map)) => json_strings(map.get("packages")),
+        _ => json_strings(workspaces),
+    };
+    push_section(&mut lines, "workspaces", workspaces);
+    push_section(
+        &mut lines,
+        "scripts",
+        json_map_entries(manifest.get("scripts"), true),
+    );
+    let bins = match manifest.get("bin") {
+        Some(JsonValue::String(path)) => {
+            vec![format!("{} ({})", str_field("name").unwrap_or("bin"), path)]
+        }
+        Some(JsonValue::Object(map)) => map.keys().cloned().collect(),
+        _ => Vec::new(),
+    };
+    push_section(&mut lines, "bin", bins);
+    for key in ["dependencies", "devDependencies", "peerDependencies"] {
+        push_section(&mut lines, key, json_map_entries(manifest.get(key), false));
+    }
+    Some(lines.join("\n"))
+}
+
+fn summarize_pyproject(dir: &Path) -> Option<String> {
+    let manifest = read_toml(&dir.join("pyproject.toml"))?;
+    let poetry = manifest.get("tool").and_then(|t| t.get("poetry"));
+    let project = manifest.get("project").or(poetry);
+    let field = |key: &str| project.and_then(|p| p.get(key)).and_then(|v| v.as_str());
+    let mut lines = vec![header("pyproject.toml", field("name"), field("version"))];
+    if let Some(python) = field("requires-python") {
+        lines.push(format!("  requires-python: {}", python));
+    }
+    if let Some(backend) = manifest
+        .get("build-system")
+        .and_then(|b| b.get("build-backend"))
+        .and_then(|b| b.as_str())
+    {
+        lines.push(format!("  build backend: {}", backend));
+    }
+    let dependencies = match project.and_then(|p| p.get("dependencies")) {
+        Some(TomlValue::Table(table)) => table
+            .iter()
+            .filter(|(name, _)| name.as_str() != "python")
+            .map(|(name, spec)| cargo_dependency(name, spec))
+            .collect(),
+        other => toml_strings(other),
+    };
+    push_section(&mut lines, "dependencies", dependencies);
+    let extras: Vec<String> = project
+        .and_then(|p| p.get("optional-dependencies").or_else(|| p.get("extras")))
+        .and_then(|e| e.as_table())
+        .map(|t| {
+            t.iter()
+                .map(|(name, deps)| format!("{} = [{}]", name, toml_strings(Some(deps)).join(", ")))
+                .collect()
+        })
+        .unwrap_or_default();
+    push_section(&mut lines, "extras", extras);
+    let scripts: Vec<String> = project
+        .and_then(|p| p.get("scripts"))
+        .and_then(|s| s.as_table())
+        .map(|t| {
+            t.iter()
+                .map(|(name, target)| format!("{} = {}", name, target.as_str().unwrap_or("?")))
+                .collect()
+        })
+        .unwrap_or_default();
+    push_section(&mut lines, "scripts", scripts);
+    Some(lines.join("\n"))
+}
+
+fn summarize_go_mod(dir: &Path) -> Option<String> {
+    let text = fs::read_to_string(dir.join("go.mod")).ok()?;
+    let mut module = None;
+    let mut go_version = None;
+    let mut requires = Vec::new();
+    let mut in_require = false;
+    for line in text.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if in_require {
+            if line == ")" {
+                in_require = false;
+            } else if !line.is_empty() {
+                requires.push(line.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+        } else if let Some(rest) = line.strip_prefix("module ") {
+            module = Some(rest.trim().to_string());
+        } else if let Some(rest) = line.strip_prefix("go ") {
+            go_version = Some(rest.trim().to_string());
+        } else if line == "require (" {
+            in_require = true;
+        } else if let Some(rest) = line.strip_prefix("require ") {
+            requires.push(rest.trim().to_string());
+        }
+    }
+    let mut lines = vec![header("go.mod", module.as_deref(), None)];
+    if let Some(version) = go_version {
+        lines.push(format!("  go: {}", version));
+    }
+    push_section(&mut lines, "require", requires);
+    Some(lines.join("\n"))
+}
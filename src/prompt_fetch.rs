@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::logging::log_verbose;
+use crate::tls::client_builder;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+/// Prompts are text; anything larger is almost certainly the wrong URL.
+const MAX_PROMPT_BYTES: usize = 256 * 1024;
+
+pub struct FetchedPrompt {
+    pub name: String,
+    pub content: String,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct GistFile {
+    filename: String,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    truncated: bool,
+    raw_url: String,
+}
+
+#[derive(Deserialize)]
+struct Gist {
+    html_url: String,
+    files: BTreeMap<String, GistFile>,
+}
+
+fn client() -> Result<Client> {
+    client_builder()?
+        .timeout(FETCH_TIMEOUT)
+        .user_agent(concat!("dongshan/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("failed to build HTTP client")
+}
+
+/// Gist id from `gh:user/<id>`, `gist:<id>` or a gist.github.com URL.
+fn gist_id(source: &str) -> Option<String> {
+    let rest = source
+        .strip_prefix("gh:")
+        .or_else(|| source.strip_prefix("gist:"))
+        .or_else(|| {
+            source
+                .strip_prefix("https://gist.github.com/")
+                .or_else(|| source.strip_prefix("http://gist.github.com/"))
+        })?;
+    let id = rest.trim_end_matches('/').rsplit('/').next()?;
+    let id = id.split(['#', '?']).next().unwrap_or(id);
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric())).then(|| id.to_string())
+}
+
+/// GitHub file pages are HTML; the raw file is what the user means.
+fn raw_github_url(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("https://github.com/")
+        && let Some((repo, path)) = rest.split_once("/blob/")
+    {
+        return format!("https://raw.githubusercontent.com/{}/{}", repo, path);
+    }
+    url.to_string()
+}
+
+/// Prompt name from the last path segment, without extension.
+fn name_from_path(path: &str) -> String {
+    let file = path
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(path)
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let stem = file.split_once('.').map(|(s, _)| s).unwrap_or(file);
+    if stem.is_empty() {
+        "fetched".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+async fn get_text(client: &Client, url: &str) -> Result<String> {
+    log_verbose("prompt", &format!("GET {}", url));
+    let resp = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Request failed: {}", url))?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("Fetch failed with HTTP {}: {}", status, url);
+    }
+    let bytes = resp
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to read response from {}", url))?;
+    if bytes.len() > MAX_PROMPT_BYTES {
+        bail!(
+            "{} is {} bytes; prompts larger than {} bytes are not accepted",
+            url,
+            bytes.len(),
+            MAX_PROMPT_BYTES
+        );
+    }
+    String::from_utf8(bytes.to_vec()).with_context(|| format!("{} is not UTF-8 text", url))
+}
+
+/// A JSON body may hold a set: `[{"name", "content"}, ...]`, `{"name": "text"}`
+/// or a single `{"name", "content"}` doc. `None` when it is plain text.
+fn parse_prompt_set(text: &str) -> Option<Vec<(String, String)>> {
+    let value: Value = serde_json::from_str(text.trim()).ok()?;
+    let doc = |v: &Value| -> Option<(String, String)> {
+        Some((
+            v.get("name")?.as_str()?.to_string(),
+            v.get("content")?.as_str()?.to_string(),
+        ))
+    };
+    match &value {
+        Value::Array(items) => items.iter().map(doc).collect(),
+        Value::Object(map) => doc(&value).map(|d| vec![d]).or_else(|| {
+            map.iter()
+                .map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect()
+        }),
+        _ => None,
+    }
+}
+
+async fn fetch_gist(client: &Client, id: &str) -> Result<Vec<FetchedPrompt>> {
+    let api = format!("https://api.github.com/gists/{}", id);
+    let gist: Gist = serde_json::from_str(&get_text(client, &api).await?)
+        .with_context(|| format!("Unexpected response from {}", api))?;
+    let mut out = Vec::new();
+    for file in gist.files.into_values() {
+        let content = match file.content {
+            Some(c) if !file.truncated => c,
+            _ => get_text(client, &file.raw_url).await?,
+        };
+        match parse_prompt_set(&content) {
+            Some(set) => out.extend(set.into_iter().map(|(name, content)| FetchedPrompt {
+                name,
+                content,
+                url: gist.html_url.clone(),
+            })),
+            None => out.push(FetchedPrompt {
+                name: name_from_path(&file.filename),
+                content,
+                url: gist.html_url.clone(),
+            }),
+        }
+    }
+    Ok(out)
+}
+
+/// Downloads one prompt or a set from an http(s) URL or a GitHub gist. With
+/// `name`, a single plain-text prompt is saved under that name.
+pub async fn fetch_prompts(source: &str, name: Option<&str>) -> Result<Vec<FetchedPrompt>> {
+    let source = source.trim();
+    let client = client()?;
+    let mut prompts = if let Some(id) = gist_id(source) {
+        fetch_gist(&client, &id).await?
+    } else if source.starts_with("https://") || source.starts_with("http://") {
+        let url = raw_github_url(source);
+        let text = get_text(&client, &url).await?;
+        match parse_prompt_set(&text) {
+            Some(set) => set
+                .into_iter()
+                .map(|(name, content)| FetchedPrompt {
+                    name,
+                    content,
+                    url: source.to_string(),
+                })
+                .collect(),
+            None => vec![FetchedPrompt {
+                name: name_from_path(&url),
+                content: text,
+                url: source.to_string(),
+            }],
+        }
+    } else {
+        bail!(
+            "Expected an http(s) URL or gh:<user>/<gist id>, got `{}`",
+            source
+        );
+    };
+    prompts.retain(|p| !p.name.trim().is_empty() && !p.content.trim().is_empty());
+    if prompts.is_empty() {
+        bail!("No prompts found at {}", source);
+    }
+    if let Some(name) = name.map(str::trim).filter(|n| !n.is_empty()) {
+        if prompts.len() > 1 {
+            bail!(
+                "{} contains {} prompts; --name only applies to a single prompt",
+                source,
+                prompts.len()
+            );
+        }
+        prompts[0].name = name.to_string();
+    }
+    Ok(prompts)
+}
@@ -8,6 +8,15 @@ use serde::{Deserialize, Serialize};
 pub struct PromptDoc {
     name: String,
     content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<PromptSource>,
+}
+
+/// Where a prompt was downloaded from (`dongshan prompt fetch`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptSource {
+    pub url: String,
+    pub fetched_unix: u64,
 }
 
 const DEFAULT_PROMPT_NAME: &str = "default";
@@ -120,6 +129,14 @@ pub fn get_prompt(name: &str) -> Result<Option<String>> {
 }
 
 pub fn save_prompt(name: &str, content: &str) -> Result<()> {
+    save_prompt_with_source(name, content, None)
+}
+
+pub fn save_prompt_with_source(
+    name: &str,
+    content: &str,
+    source: Option<PromptSource>,
+) -> Result<()> {
     let n = name.trim();
     if n.is_empty() {
         bail!("Prompt name cannot be empty");
@@ -132,6 +149,7 @@ pub fn save_prompt(name: &str, content: &str) -> Result<()> {
     let doc = PromptDoc {
         name: n.to_string(),
         content: content.to_string(),
+        source,
     };
     let text = serde_json::to_string_pretty(&doc)?;
     fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
@@ -179,4 +197,7 @@ impl PromptDoc {
     pub fn content(&self) -> &str {
         &self.content
     }
+    pub fn source(&self) -> Option<&PromptSource> {
+        self.source.as_ref()
+    }
 }
@@ -0,0 +1,255 @@
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::Config;
+use crate::run_report::note_command;
+use crate::transcript::record_command;
+use crate::util::WorkingStatus;
+use crate::write_jail::write_roots;
+
+/// Environment variables passed through to the interpreter; everything else
+/// (API keys, proxies, PYTHON* overrides) is dropped.
+const KEPT_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USERPROFILE",
+    "SYSTEMROOT",
+    "TEMP",
+    "TMP",
+    "TMPDIR",
+    "LANG",
+    "LC_ALL",
+    "VIRTUAL_ENV",
+];
+
+/// Passed with `-c`; runs the snippet file given as the first argument after
+/// blocking sockets (unless network is allowed) and refusing file writes
+/// outside `DONGSHAN_WRITE_ROOTS` when the write jail is on. These are
+/// monkeypatches the snippet can undo, not a sandbox; the command policy in
+/// `execute_python` is what gates a snippet.
+const PRELUDE: &str = r#"import builtins as _b, os as _os, runpy as _runpy, sys as _sys
+if _os.environ.get("DONGSHAN_NO_NETWORK") == "1":
+    import socket as _socket
+    def _no_net(*_a, **_k):
+        raise PermissionError("network access is disabled for the python tool")
+    _socket.socket = _no_net
+    _socket.create_connection = _no_net
+    _socket.getaddrinfo = _no_net
+_roots = [r for r in _os.environ.get("DONGSHAN_WRITE_ROOTS", "").split(_os.pathsep) if r]
+if _roots:
+    def _allowed(p):
+        p = _os.path.realpath(p)
+        return any(p == r or p.startswith(r.rstrip(_os.sep) + _os.sep) for r in _roots)
+    _open = _b.open
+    def _jailed_open(file, mode="r", *a, **k):
+        if isinstance(file, (str, bytes, _os.PathLike)) and any(c in mode for c in "wax+"):
+            if not _allowed(_os.fsdecode(file)):
+                raise PermissionError("write outside workspace is not allowed: %s" % _os.fsdecode(file))
+        return _open(file, mode, *a, **k)
+    _b.open = _jailed_open
+    for _name in ("remove", "unlink", "rmdir", "mkdir", "makedirs", "rename", "replace"):
+        _fn = getattr(_os, _name)
+        def _guard(_fn=_fn):
+            def inner(*a, **k):
+                for p in a:
+                    if isinstance(p, (str, bytes, _os.PathLike)) and not _allowed(_os.fsdecode(p)):
+                        raise PermissionError("write outside workspace is not allowed: %s" % _os.fsdecode(p))
+                return _fn(*a, **k)
+            return inner
+        setattr(_os, _name, _guard())
+_path = _sys.argv[1]
+_sys.argv = _sys.argv[1:]
+_runpy.run_path(_path, run_name="__main__")
+"#;
+
+pub struct PythonRun {
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    pub timeout: Duration,
+}
+
+impl PythonRun {
+    pub fn to_context(&self) -> String {
+        let status = if self.timed_out {
+            format!("killed after {}s timeout", self.timeout.as_secs())
+        } else {
+            match self.exit_code {
+                Some(code) => format!("exit {}", code),
+                None => "terminated by signal".to_string(),
+            }
+        };
+        let mut out = format!("python: {}", status);
+        if !self.stdout.trim().is_empty() {
+            out.push_str("\nstdout:\n");
+            out.push_str(self.stdout.trim_end());
+        }
+        if !self.stderr.trim().is_empty() {
+            out.push_str("\nstderr:\n");
+            out.push_str(self.stderr.trim_end());
+        }
+        if self.stdout.trim().is_empty() && self.stderr.trim().is_empty() {
+            out.push_str("\n(no output)");
+        }
+        out
+    }
+}
+
+/// First of `python_bin`, `python3`, `python` that starts.
+fn interpreter(cfg: &Config) -> Result<String> {
+    let candidates: Vec<String> = match cfg.python_bin.as_deref().map(str::trim) {
+        Some(bin) if !bin.is_empty() => vec![bin.to_string()],
+        _ => vec!["python3".to_string(), "python".to_string()],
+    };
+    for bin in &candidates {
+        let ok = Command::new(bin)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if ok {
+            return Ok(bin.clone());
+        }
+    }
+    bail!(
+        "no Python interpreter found (tried {}); set one with `dongshan config set --python-bin`",
+        candidates.join(", ")
+    )
+}
+
+fn snippet_path() -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    std::env::temp_dir().join(format!("dongshan-py-{}-{}.py", std::process::id(), nanos))
+}
+
+/// Writes `code` to a temp file and runs it in isolated mode (`-I`) from the
+/// workspace, with a scrubbed environment and a hard timeout. The timeout the
+/// model asks for is capped at `python_timeout_secs`.
+pub fn run_python(cfg: &Config, code: &str, timeout_secs: Option<u64>) -> Result<PythonRun> {
+    let bin = interpreter(cfg)?;
+    let timeout = Duration::from_secs(
+        timeout_secs
+            .unwrap_or(cfg.python_timeout_secs)
+            .clamp(1, cfg.python_timeout_secs.max(1)),
+    );
+    let cwd = std::env::current_dir().context("Failed to resolve current directory")?;
+    let script = snippet_path();
+    fs::write(&script, code).with_context(|| format!("Failed to write {}", script.display()))?;
+
+    let mut cmd = Command::new(&bin);
+    own_process_group(&mut cmd);
+    cmd.args(["-I", "-c", PRELUDE])
+        .arg(&script)
+        .current_dir(&cwd)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for key in KEPT_ENV {
+        if let Some(v) = std::env::var_os(key) {
+            cmd.env(key, v);
+        }
+    }
+    cmd.env("PYTHONIOENCODING", "utf-8")
+        .env("PYTHONDONTWRITEBYTECODE", "1");
+    if !cfg.python_allow_network {
+        cmd.env("DONGSHAN_NO_NETWORK", "1");
+    }
+    if let Some(roots) = write_roots() {
+        let joined = std::env::join_paths(roots).unwrap_or_default();
+        cmd.env("DONGSHAN_WRITE_ROOTS", joined);
+    }
+
+    let working = WorkingStatus::start(format!("python ({} lines)", code.lines().count()));
+    let result = wait_with_timeout(&mut cmd, timeout);
+    working.finish();
+    let _ = fs::remove_file(&script);
+    let run = result.with_context(|| format!("Failed to run {}", bin))?;
+    record_command("python <snippet>", run.exit_code, &run.to_context());
+    note_command("python <snippet>", run.exit_code);
+    Ok(run)
+}
+
+fn wait_with_timeout(cmd: &mut Command, timeout: Duration) -> Result<PythonRun> {
+    let mut child = cmd.spawn()?;
+    let mut stdout = child.stdout.take().context("stdout not captured")?;
+    let mut stderr = child.stderr.take().context("stderr not captured")?;
+    let out_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        buf
+    });
+    let err_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        buf
+    });
+
+    let started = Instant::now();
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            timed_out = true;
+            // The child is not reaped yet, so its group id cannot have been reused.
+            kill_group(child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(25));
+    };
+    let stdout = out_reader.join().unwrap_or_default();
+    let stderr = err_reader.join().unwrap_or_default();
+    Ok(PythonRun {
+        exit_code: status.and_then(|s| s.code()),
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        timed_out,
+        timeout,
+    })
+}
+
+/// Starts the interpreter in its own process group so a timeout also stops
+/// the processes the snippet spawned.
+#[cfg(unix)]
+fn own_process_group(cmd: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    cmd.process_group(0);
+}
+
+#[cfg(windows)]
+fn own_process_group(cmd: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(not(any(unix, windows)))]
+fn own_process_group(_cmd: &mut Command) {}
+
+fn kill_group(pid: u32) {
+    let mut kill = if cfg!(target_os = "windows") {
+        let mut c = Command::new("taskkill");
+        c.args(["/PID", &pid.to_string(), "/T", "/F"]);
+        c
+    } else {
+        let mut c = Command::new("kill");
+        c.args(["-KILL", "--", &format!("-{pid}")]);
+        c
+    };
+    let _ = kill.stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
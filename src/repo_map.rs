@@ -0,0 +1,421 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use regex::Regex;
+use tree_sitter::{Language, Node, Parser};
+
+use crate::file_index::indexed_files;
+
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+const MAX_SYMBOLS_PER_FILE: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lang {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+}
+
+impl Lang {
+    fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rs" => Some(Lang::Rust),
+            "py" => Some(Lang::Python),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Lang::JavaScript),
+            "ts" | "mts" | "cts" => Some(Lang::TypeScript),
+            "tsx" => Some(Lang::Tsx),
+            "go" => Some(Lang::Go),
+            _ => None,
+        }
+    }
+
+    fn language(self) -> Language {
+        match self {
+            Lang::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Lang::Python => tree_sitter_python::LANGUAGE.into(),
+            Lang::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Lang::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Lang::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Lang::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// Maps a node kind to a short label, or `None` for nodes that are not symbols.
+    fn symbol_label(self, kind: &str) -> Option<&'static str> {
+        match (self, kind) {
+            (Lang::Rust, "function_item" | "function_signature_item") => Some("fn"),
+            (Lang::Rust, "struct_item") => Some("struct"),
+            (Lang::Rust, "enum_item") => Some("enum"),
+            (Lang::Rust, "trait_item") => Some("trait"),
+            (Lang::Rust, "impl_item") => Some("impl"),
+            (Lang::Rust, "mod_item") => Some("mod"),
+            (Lang::Rust, "type_item") => Some("type"),
+            (Lang::Rust, "macro_definition") => Some("macro"),
+            (Lang::Python, "function_definition") => Some("def"),
+            (Lang::Python, "class_definition") => Some("class"),
+            (
+                Lang::JavaScript | Lang::TypeScript | Lang::Tsx,
+                "function_declaration" | "generator_function_declaration",
+            ) => Some("function"),
+            (Lang::JavaScript | Lang::TypeScript | Lang::Tsx, "class_declaration") => Some("class"),
+            (Lang::JavaScript | Lang::TypeScript | Lang::Tsx, "method_definition") => {
+                Some("method")
+            }
+            (Lang::TypeScript | Lang::Tsx, "interface_declaration") => Some("interface"),
+            (Lang::TypeScript | Lang::Tsx, "type_alias_declaration") => Some("type"),
+            (Lang::TypeScript | Lang::Tsx, "enum_declaration") => Some("enum"),
+            (Lang::Go, "function_declaration") => Some("func"),
+            (Lang::Go, "method_declaration") => Some("method"),
+            (Lang::Go, "type_spec") => Some("type"),
+            _ => None,
+        }
+    }
+
+    /// Containers whose bodies hold member symbols worth listing.
+    fn is_container(self, label: &str) -> bool {
+        matches!(
+            (self, label),
+            (Lang::Rust, "impl" | "trait" | "mod") | (_, "class")
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Symbol {
+    label: &'static str,
+    name: String,
+    members: Vec<String>,
+}
+
+type CacheEntry = (u64, u64, Vec<Symbol>);
+
+static SYMBOL_CACHE: OnceLock<Mutex<HashMap<PathBuf, CacheEntry>>> = OnceLock::new();
+
+/// Builds a compressed one-line-per-file outline of the symbols in `root`,
+/// truncated to `max_chars`. Parsed files are cached by size and mtime so
+/// repeated agent turns only re-parse what changed.
+pub fn build_repo_map(root: &Path, max_chars: usize) -> Result<String> {
+    let mut out = String::new();
+    let files: Vec<PathBuf> = indexed_files(root)?
+        .into_iter()
+        .filter(|p| Lang::from_path(p).is_some())
+        .collect();
+    let mut listed = 0usize;
+    for path in &files {
+        let symbols = file_symbols(path);
+        if symbols.is_empty() {
+            continue;
+        }
+        let rel = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let line = format!("{}: {}\n", rel, format_symbols(&symbols));
+        if out.len() + line.len() > max_chars {
+            let remaining = files.len().saturating_sub(listed);
+            out.push_str(&format!("... ({} more files not shown)\n", remaining));
+            break;
+        }
+        out.push_str(&line);
+        listed += 1;
+    }
+    Ok(out.trim_end().to_string())
+}
+
+fn format_symbols(symbols: &[Symbol]) -> String {
+    symbols
+        .iter()
+        .map(|s| {
+            if s.members.is_empty() {
+                format!("{} {}", s.label, s.name)
+            } else {
+                format!("{} {} {{{}}}", s.label, s.name, s.members.join(", "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn file_symbols(path: &Path) -> Vec<Symbol> {
+    let Ok(meta) = fs::metadata(path) else {
+        return Vec::new();
+    };
+    if meta.len() > MAX_FILE_BYTES {
+        return Vec::new();
+    }
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let cache = SYMBOL_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Ok(guard) = cache.lock()
+        && let Some((size, time, symbols)) = guard.get(path)
+        && *size == meta.len()
+        && *time == mtime
+    {
+        return symbols.clone();
+    }
+    let symbols = parse_symbols(path).unwrap_or_default();
+    if let Ok(mut guard) = cache.lock() {
+        guard.insert(path.to_path_buf(), (meta.len(), mtime, symbols.clone()));
+    }
+    symbols
+}
+
+fn parse_symbols(path: &Path) -> Option<Vec<Symbol>> {
+    let lang = Lang::from_path(path)?;
+    let source = fs::read_to_string(path).ok()?;
+    let mut parser = Parser::new();
+    parser.set_language(&lang.language()).ok()?;
+    let tree = parser.parse(&source, None)?;
+    let mut symbols = Vec::new();
+    collect_symbols(lang, tree.root_node(), source.as_bytes(), &mut symbols);
+    symbols.truncate(MAX_SYMBOLS_PER_FILE);
+    Some(symbols)
+}
+
+fn collect_symbols(lang: Lang, node: Node, src: &[u8], out: &mut Vec<Symbol>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let Some(label) = lang.symbol_label(child.kind()) else {
+            // Descend through wrappers such as `export_statement`, `decorated_definition`
+            // and Go `type_declaration`, but never into function bodies.
+            if !child.kind().contains("function") && !child.kind().contains("method") {
+                collect_symbols(lang, child, src, out);
+            }
+            continue;
+        };
+        let Some(name) = symbol_name(lang, child, src) else {
+            continue;
+        };
+        let mut members = Vec::new();
+        if lang.is_container(label) {
+            let mut nested = Vec::new();
+            if let Some(body) = child.child_by_field_name("body") {
+                collect_symbols(lang, body, src, &mut nested);
+            }
+            members = nested.into_iter().map(|s| s.name).collect();
+        }
+        out.push(Symbol {
+            label,
+            name,
+            members,
+        });
+    }
+}
+
+fn symbol_name(lang: Lang, node: Node, src: &[u8]) -> Option<String> {
+    let field = if lang == Lang::Rust && node.kind() == "impl_item" {
+        "type"
+    } else {
+        "name"
+    };
+    let name = node.child_by_field_name(field)?.utf8_text(src).ok()?;
+    let mut name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if lang == Lang::Rust
+        && node.kind() == "impl_item"
+        && let Some(t) = node.child_by_field_name("trait")
+        && let Ok(t) = t.utf8_text(src)
+    {
+        name = format!("{} for {}", t, name);
+    }
+    Some(name)
+}
+
+/// Source files grouped into top-level modules, with the imports between them.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    /// Module name -> number of source files in it.
+    pub modules: BTreeMap<String, usize>,
+    /// `(from, to)` pairs of modules where a file in `from` imports `to`.
+    pub edges: BTreeSet<(String, String)>,
+}
+
+/// Leading directories that hold the code rather than name a module.
+const SOURCE_DIRS: &[&str] = &["src", "lib", "app", "source"];
+
+/// Module key of a root-relative path: the first `depth` components after a
+/// source directory, with the extension dropped from a file name.
+fn module_of(rel: &Path, depth: usize) -> Option<String> {
+    let mut parts: Vec<String> = rel
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(p) => Some(p.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    if parts.len() > 1 && SOURCE_DIRS.contains(&parts[0].as_str()) {
+        parts.remove(0);
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    let take = depth.min(parts.len());
+    let mut key: Vec<String> = parts[..take].to_vec();
+    if take == parts.len() {
+        let last = key.pop()?;
+        let stem = last.split('.').next().unwrap_or(&last).to_string();
+        // `mod.rs`, `index.ts` and `__init__.py` stand for their directory.
+        if !matches!(stem.as_str(), "mod" | "index" | "__init__") || key.is_empty() {
+            key.push(stem);
+        }
+    }
+    Some(key.join("/"))
+}
+
+/// Normalizes `a/b/../c` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Root-relative paths a file imports from the same project.
+fn import_targets(lang: Lang, rel: &Path, source: &str, go_module: Option<&str>) -> Vec<PathBuf> {
+    static RUST: OnceLock<Regex> = OnceLock::new();
+    static JS: OnceLock<Regex> = OnceLock::new();
+    static PY: OnceLock<Regex> = OnceLock::new();
+    static GO: OnceLock<Regex> = OnceLock::new();
+    let dir = rel.parent().unwrap_or(Path::new(""));
+    let mut out = Vec::new();
+    match lang {
+        Lang::Rust => {
+            let re = RUST.get_or_init(|| Regex::new(r"\bcrate::(\w+)").expect("valid regex"));
+            // `crate::` is relative to the `src` directory this file lives in.
+            let crate_root = rel
+                .ancestors()
+                .find(|a| a.file_name().is_some_and(|n| n == "src"))
+                .unwrap_or(Path::new("src"));
+            for c in re.captures_iter(source) {
+                out.push(crate_root.join(&c[1]));
+            }
+        }
+        Lang::JavaScript | Lang::TypeScript | Lang::Tsx => {
+            let re = JS.get_or_init(|| {
+                Regex::new(r#"(?:from\s+|import\s+|require\(\s*)['"](\.{1,2}/[^'"]+)['"]"#)
+                    .expect("valid regex")
+            });
+            for c in re.captures_iter(source) {
+                out.push(normalize(&dir.join(&c[1])));
+            }
+        }
+        Lang::Python => {
+            let re = PY.get_or_init(|| {
+                Regex::new(r"(?m)^\s*(?:from\s+(\.*)([\w.]*)\s+import|import\s+([\w.]+))")
+                    .expect("valid regex")
+            });
+            for c in re.captures_iter(source) {
+                let dots = c.get(1).map_or(0, |m| m.as_str().len());
+                let name = c.get(2).or(c.get(3)).map_or("", |m| m.as_str());
+                let mut base = if dots > 0 {
+                    let mut base = dir.to_path_buf();
+                    for _ in 1..dots {
+                        base.pop();
+                    }
+                    base
+                } else {
+                    PathBuf::new()
+                };
+                for part in name.split('.').filter(|p| !p.is_empty()) {
+                    base.push(part);
+                }
+                if !base.as_os_str().is_empty() {
+                    out.push(base);
+                }
+            }
+        }
+        Lang::Go => {
+            let Some(module) = go_module else {
+                return out;
+            };
+            let re = GO.get_or_init(|| Regex::new(r#""([^"\s]+)""#).expect("valid regex"));
+            for c in re.captures_iter(source) {
+                if let Some(path) = c[1].strip_prefix(module) {
+                    out.push(PathBuf::from(path.trim_start_matches('/')));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Groups the project's source files into modules and links a module to every
+/// module its files import. When all files fall into one module (a single
+/// Python package, say), the grouping goes one directory deeper.
+pub fn build_module_graph(root: &Path) -> Result<ModuleGraph> {
+    let files: Vec<(PathBuf, Lang)> = indexed_files(root)?
+        .into_iter()
+        .filter_map(|p| {
+            let lang = Lang::from_path(&p)?;
+            let rel = p.strip_prefix(root).unwrap_or(&p).to_path_buf();
+            Some((rel, lang))
+        })
+        .collect();
+    let go_module = fs::read_to_string(root.join("go.mod"))
+        .ok()
+        .and_then(|text| {
+            text.lines()
+                .find_map(|l| l.trim().strip_prefix("module "))
+                .map(|m| m.trim().to_string())
+        });
+
+    let group = |depth: usize| -> BTreeMap<String, usize> {
+        let mut modules = BTreeMap::new();
+        for (rel, _) in &files {
+            if let Some(key) = module_of(rel, depth) {
+                *modules.entry(key).or_insert(0) += 1;
+            }
+        }
+        modules
+    };
+    let mut depth = 1;
+    let mut modules = group(depth);
+    if modules.len() == 1 && files.len() > 1 {
+        depth = 2;
+        modules = group(depth);
+    }
+
+    let mut edges = BTreeSet::new();
+    for (rel, lang) in &files {
+        let Some(from) = module_of(rel, depth) else {
+            continue;
+        };
+        let Ok(meta) = fs::metadata(root.join(rel)) else {
+            continue;
+        };
+        if meta.len() > MAX_FILE_BYTES {
+            continue;
+        }
+        let Ok(source) = fs::read_to_string(root.join(rel)) else {
+            continue;
+        };
+        for target in import_targets(*lang, rel, &source, go_module.as_deref()) {
+            if let Some(to) = module_of(&target, depth)
+                && to != from
+                && modules.contains_key(&to)
+            {
+                edges.insert((from.clone(), to));
+            }
+        }
+    }
+    Ok(ModuleGraph { modules, edges })
+}
@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::atomic_file::write_atomic;
+use crate::usage::tracked_turns;
+use crate::worktree::git;
+
+static REPORT_PATH: OnceLock<PathBuf> = OnceLock::new();
+static COLLECTED: Mutex<Collected> = Mutex::new(Collected {
+    replies: Vec::new(),
+    commands: Vec::new(),
+    verification: None,
+});
+
+/// What the run produced along the way, gathered only when a report was asked for.
+struct Collected {
+    replies: Vec<String>,
+    commands: Vec<ReportCommand>,
+    verification: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ReportCommand {
+    pub command: String,
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Default, Serialize)]
+pub struct ReportUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    pub cost_usd: f64,
+    /// True when some request had no provider usage and tokens were estimated.
+    pub estimated: bool,
+}
+
+/// The `agent --report` artifact.
+#[derive(Serialize)]
+pub struct RunReport {
+    pub task: String,
+    pub session: String,
+    pub model: String,
+    /// `ok`, `failed` (the run errored) or `verification_failed`.
+    pub status: &'static str,
+    pub error: Option<String>,
+    pub duration_secs: u64,
+    /// The model's first reply, which normally lays out its approach.
+    pub plan: Option<String>,
+    /// The model's last reply, when it differs from the plan.
+    pub summary: Option<String>,
+    pub commands: Vec<ReportCommand>,
+    pub changed_files: Vec<String>,
+    pub diff_stat: Option<String>,
+    pub verification: Option<String>,
+    pub usage: ReportUsage,
+}
+
+/// Sets where `run_agent_task` writes its report (`--report`). Collection
+/// only happens once this is set.
+pub fn init_run_report(path: PathBuf) {
+    let _ = REPORT_PATH.set(path);
+}
+
+fn collecting() -> bool {
+    REPORT_PATH.get().is_some()
+}
+
+fn with_collected(f: impl FnOnce(&mut Collected)) {
+    if collecting()
+        && let Ok(mut collected) = COLLECTED.lock()
+    {
+        f(&mut collected);
+    }
+}
+
+pub fn note_reply(text: &str) {
+    let text = text.trim();
+    if !text.is_empty() {
+        with_collected(|c| c.replies.push(text.to_string()));
+    }
+}
+
+pub fn note_command(command: &str, exit_code: Option<i32>) {
+    with_collected(|c| {
+        c.commands.push(ReportCommand {
+            command: command.to_string(),
+            exit_code,
+        })
+    });
+}
+
+/// Keeps the latest verification report; earlier ones are superseded.
+pub fn note_verification(report: &str) {
+    with_collected(|c| c.verification = Some(report.trim().to_string()));
+}
+
+/// Details of a finished run that `run_agent_task` knows directly.
+pub struct RunOutcome<'a> {
+    pub task: &'a str,
+    pub session: &'a str,
+    pub model: &'a str,
+    pub duration: Duration,
+    pub changed_files: &'a [String],
+    pub error: Option<&'a str>,
+    /// The latest verification of the run failed.
+    pub verification_failed: bool,
+}
+
+/// Writes the report when `--report` was given: JSON for a `.json` path,
+/// markdown otherwise.
+pub fn write_run_report(outcome: &RunOutcome) -> Result<Option<PathBuf>> {
+    let Some(path) = REPORT_PATH.get() else {
+        return Ok(None);
+    };
+    let report = build_report(outcome);
+    let is_json = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let text = if is_json {
+        serde_json::to_string_pretty(&report)?
+    } else {
+        render_markdown(&report)
+    };
+    write_atomic(path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(Some(path.clone()))
+}
+
+fn build_report(outcome: &RunOutcome) -> RunReport {
+    let (replies, commands, verification) = COLLECTED
+        .lock()
+        .map(|c| {
+            (
+                c.replies.clone(),
+                c.commands.clone(),
+                c.verification.clone(),
+            )
+        })
+        .unwrap_or_default();
+    let plan = replies.first().cloned();
+    let summary = replies.last().filter(|_| replies.len() > 1).cloned();
+    let mut usage = ReportUsage::default();
+    for turn in tracked_turns() {
+        usage.requests += turn.requests;
+        usage.prompt_tokens += turn.prompt_tokens;
+        usage.completion_tokens += turn.completion_tokens;
+        usage.cached_tokens += turn.cached_tokens;
+        usage.cost_usd += turn.cost_usd;
+        usage.estimated |= turn.estimated;
+    }
+    RunReport {
+        task: outcome.task.to_string(),
+        session: outcome.session.to_string(),
+        model: outcome.model.to_string(),
+        status: if outcome.error.is_some() {
+            "failed"
+        } else if outcome.verification_failed {
+            "verification_failed"
+        } else {
+            "ok"
+        },
+        error: outcome.error.map(str::to_string),
+        duration_secs: outcome.duration.as_secs(),
+        plan,
+        summary,
+        commands,
+        changed_files: outcome.changed_files.to_vec(),
+        diff_stat: diff_stat(),
+        verification,
+        usage,
+    }
+}
+
+/// `git diff --stat HEAD` with its alignment intact; `None` outside a git repo
+/// or without changes.
+fn diff_stat() -> Option<String> {
+    let out = git(Path::new("."), &["diff", "--stat", "HEAD"]).ok()?;
+    let stat = String::from_utf8_lossy(&out.stdout).trim_end().to_string();
+    (out.status.success() && !stat.is_empty()).then_some(stat)
+}
+
+fn render_markdown(report: &RunReport) -> String {
+    let mut out = String::from("# dongshan agent report\n\n");
+    out.push_str(&format!("- Task: {}\n", report.task));
+    out.push_str(&format!("- Session: `{}`\n", report.session));
+    out.push_str(&format!("- Model: `{}`\n", report.model));
+    match &report.error {
+        Some(err) => out.push_str(&format!("- Status: failed ({})\n", err)),
+        None if report.status == "verification_failed" => {
+            out.push_str("- Status: verification failed\n")
+        }
+        None => out.push_str("- Status: ok\n"),
+    }
+    out.push_str(&format!("- Duration: {}s\n", report.duration_secs));
+    if let Some(plan) = &report.plan {
+        out.push_str(&format!("\n## Plan\n\n{}\n", plan));
+    }
+    if let Some(summary) = &report.summary {
+        out.push_str(&format!("\n## Summary\n\n{}\n", summary));
+    }
+    out.push_str("\n## Commands\n\n");
+    if report.commands.is_empty() {
+        out.push_str("None.\n");
+    }
+    for cmd in &report.commands {
+        let exit = cmd
+            .exit_code
+            .map(|c| format!("exit {c}"))
+            .unwrap_or_else(|| "no exit code".to_string());
+        out.push_str(&format!("- `{}` ({})\n", cmd.command, exit));
+    }
+    out.push_str("\n## Changes\n\n");
+    if report.changed_files.is_empty() {
+        out.push_str("No files changed.\n");
+    }
+    for file in &report.changed_files {
+        out.push_str(&format!("- {}\n", file));
+    }
+    if let Some(stat) = &report.diff_stat {
+        out.push_str(&format!("\n```text\n{}\n```\n", stat));
+    }
+    out.push_str("\n## Verification\n\n");
+    match &report.verification {
+        Some(text) => out.push_str(&format!("```text\n{}\n```\n", text)),
+        None => out.push_str("Not run.\n"),
+    }
+    let u = &report.usage;
+    out.push_str(&format!(
+        "\n## Usage\n\n{} request(s), {} prompt / {} completion tokens ({} cached), {}${:.4}\n",
+        u.requests,
+        u.prompt_tokens,
+        u.completion_tokens,
+        u.cached_tokens,
+        if u.estimated { "~" } else { "" },
+        u.cost_usd
+    ));
+    out
+}
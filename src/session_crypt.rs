@@ -0,0 +1,212 @@
+use std::fs;
+use std::io::IsTerminal;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::atomic_file::{with_file_lock, write_atomic};
+use crate::config::{Config, SessionEncryption, config_dir};
+
+/// First line of an encrypted session file, followed by the key source.
+const MAGIC: &str = "dongshan-encrypted-v1";
+const PASSPHRASE_ENV: &str = "DONGSHAN_SESSION_PASSPHRASE";
+const KEYRING_SERVICE: &str = "dongshan";
+const KEYRING_USER: &str = "session-key";
+/// Sealed into `session_key.json` to tell a wrong passphrase from a corrupt file.
+const KEY_CHECK: &[u8] = b"dongshan session key";
+const NONCE_LEN: usize = 24;
+
+type Key = [u8; 32];
+type KeySlot = Mutex<Option<Key>>;
+
+static MODE: OnceLock<SessionEncryption> = OnceLock::new();
+static PASSPHRASE_KEY: KeySlot = Mutex::new(None);
+static KEYRING_KEY: KeySlot = Mutex::new(None);
+
+/// Salt of the passphrase key and a value sealed with it, in
+/// ~/.dongshan/session_key.json. Holds nothing secret.
+#[derive(Serialize, Deserialize)]
+struct PassphraseKeyFile {
+    salt: String,
+    check: String,
+}
+
+pub fn init_session_encryption(cfg: &Config) {
+    let _ = MODE.set(cfg.session_encryption);
+}
+
+pub fn session_encryption() -> SessionEncryption {
+    MODE.get().copied().unwrap_or_default()
+}
+
+/// Encrypts a session file's contents with the configured key, or returns
+/// them unchanged when encryption is off.
+pub fn seal(plain: &[u8]) -> Result<Vec<u8>> {
+    let mode = session_encryption();
+    let (label, key) = match mode {
+        SessionEncryption::Off => return Ok(plain.to_vec()),
+        SessionEncryption::Passphrase => ("passphrase", key_for(mode)?),
+        SessionEncryption::Keyring => ("keyring", key_for(mode)?),
+    };
+    let mut out = format!("{MAGIC} {label}\n").into_bytes();
+    out.extend_from_slice(STANDARD.encode(encrypt(&key, plain)?).as_bytes());
+    out.push(b'\n');
+    Ok(out)
+}
+
+/// Decrypts what [`seal`] wrote, using the key named in the header. Plain
+/// files pass through, so sessions saved before encryption was turned on
+/// keep loading.
+pub fn unseal(data: &[u8]) -> Result<Vec<u8>> {
+    let Some(rest) = data.strip_prefix(MAGIC.as_bytes()) else {
+        return Ok(data.to_vec());
+    };
+    let text = std::str::from_utf8(rest).context("Encrypted session is not valid text")?;
+    let (label, body) = text
+        .trim_start_matches(' ')
+        .split_once('\n')
+        .ok_or_else(|| anyhow!("Encrypted session has no body"))?;
+    let mode = match label.trim() {
+        "passphrase" => SessionEncryption::Passphrase,
+        "keyring" => SessionEncryption::Keyring,
+        other => bail!("Unknown session key source `{}`", other),
+    };
+    let sealed = STANDARD
+        .decode(body.trim())
+        .context("Encrypted session body is not base64")?;
+    decrypt(&key_for(mode)?, &sealed)
+        .with_context(|| format!("Failed to decrypt session with the {} key", label.trim()))
+}
+
+fn encrypt(key: &Key, plain: &[u8]) -> Result<Vec<u8>> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain)
+        .map_err(|_| anyhow!("encryption failed"))?;
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt(key: &Key, sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        bail!("ciphertext is truncated");
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("wrong key or corrupted file"))
+}
+
+/// The key for `mode`, loaded once per process.
+fn key_for(mode: SessionEncryption) -> Result<Key> {
+    let (slot, load): (&KeySlot, fn() -> Result<Key>) = match mode {
+        SessionEncryption::Off => bail!("session encryption is off"),
+        SessionEncryption::Passphrase => (&PASSPHRASE_KEY, passphrase_key),
+        SessionEncryption::Keyring => (&KEYRING_KEY, keyring_key),
+    };
+    let mut guard = slot
+        .lock()
+        .map_err(|_| anyhow!("failed to lock the session key"))?;
+    if let Some(key) = *guard {
+        return Ok(key);
+    }
+    let key = load()?;
+    *guard = Some(key);
+    Ok(key)
+}
+
+/// Derives the key from the passphrase with Argon2id. The first use picks
+/// the salt and asks for the passphrase twice.
+fn passphrase_key() -> Result<Key> {
+    let path = config_dir()?.join("session_key.json");
+    with_file_lock(&path, || {
+        if path.exists() {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let file: PassphraseKeyFile = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid {}", path.display()))?;
+            let salt = STANDARD.decode(&file.salt)?;
+            let check = STANDARD.decode(&file.check)?;
+            let key = derive_key(&read_passphrase("Session passphrase: ")?, &salt)?;
+            if decrypt(&key, &check).ok().as_deref() != Some(KEY_CHECK) {
+                bail!("Wrong session passphrase");
+            }
+            return Ok(key);
+        }
+        let passphrase = read_passphrase("New session passphrase: ")?;
+        if std::env::var(PASSPHRASE_ENV).is_err()
+            && read_passphrase("Repeat passphrase: ")? != passphrase
+        {
+            bail!("Passphrases do not match");
+        }
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let key = derive_key(&passphrase, &salt)?;
+        let file = PassphraseKeyFile {
+            salt: STANDARD.encode(salt),
+            check: STANDARD.encode(encrypt(&key, KEY_CHECK)?),
+        };
+        write_atomic(&path, serde_json::to_string_pretty(&file)?)?;
+        Ok(key)
+    })
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive the session key: {}", e))?;
+    Ok(key)
+}
+
+fn read_passphrase(label: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "Sessions are encrypted with a passphrase; set {} when running without a terminal",
+            PASSPHRASE_ENV
+        );
+    }
+    let passphrase = rpassword::prompt_password(label).context("Failed to read passphrase")?;
+    if passphrase.is_empty() {
+        bail!("Empty session passphrase");
+    }
+    Ok(passphrase)
+}
+
+/// Random key stored in the OS credential store, created on first use.
+fn keyring_key() -> Result<Key> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .context("OS keyring is unavailable; use `--session-encryption passphrase` instead")?;
+    let encoded = match entry.get_password() {
+        Ok(encoded) => encoded,
+        Err(keyring::Error::NoEntry) => {
+            let encoded = STANDARD.encode(XChaCha20Poly1305::generate_key(&mut OsRng));
+            entry.set_password(&encoded).context(
+                "Failed to store the session key in the OS keyring; use `--session-encryption passphrase` instead",
+            )?;
+            encoded
+        }
+        Err(err) => {
+            return Err(err).context(
+                "Failed to read the session key from the OS keyring; use `--session-encryption passphrase` instead",
+            );
+        }
+    };
+    STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| Key::try_from(bytes.as_slice()).ok())
+        .ok_or_else(|| anyhow!("The session key in the OS keyring is malformed"))
+}
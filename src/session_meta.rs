@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::atomic_file::{with_file_lock, write_atomic};
+use crate::config::{Config, config_dir, set_active_model};
+use crate::prompt_store::list_prompt_names;
+use crate::session_crypt::{seal, unseal};
+
+/// Per-session settings, stored next to the history as
+/// `~/.dongshan/sessions/<name>.meta.json` and encrypted like it when
+/// `session_encryption` is on. Session names never contain dots, so these
+/// files cannot be mistaken for sessions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionMeta {
+    /// Replaces the active prompt's text for this session (`/system set`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_override: Option<String>,
+    /// Appended to the system prompt for this session (`/system append`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_append: Option<String>,
+    /// Model picked with `/model use` in this session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Prompt picked with `/prompt use` in this session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Sampling seed the session last ran with (`seed` config or `--seed`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Branch the session's agent runs work on (`agent --branch`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Task of the session's latest `dongshan agent` run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    /// Verification result of that run, e.g. "cargo: ok".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<String>,
+}
+
+impl SessionMeta {
+    /// Applies the session's settings on top of the global config, undoing
+    /// whatever a previously applied session had overridden. A model or prompt
+    /// that no longer exists falls back to the global default.
+    pub fn apply(&self, cfg: &mut Config) {
+        if let Some(model) = cfg.global_model.take() {
+            set_active_model(cfg, &model);
+        }
+        if let Some(prompt) = cfg.global_prompt.take() {
+            cfg.active_prompt = prompt;
+        }
+        cfg.system_override = self.system_override.clone();
+        cfg.system_append = self.system_append.clone();
+        if let Some(model) = &self.model
+            && cfg.model_profiles.contains_key(model)
+        {
+            use_session_model(cfg, model);
+        }
+        if let Some(prompt) = &self.prompt
+            && list_prompt_names().unwrap_or_default().contains(prompt)
+        {
+            use_session_prompt(cfg, prompt);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.system_override.is_none()
+            && self.system_append.is_none()
+            && self.model.is_none()
+            && self.prompt.is_none()
+            && self.seed.is_none()
+            && self.branch.is_none()
+            && self.task.is_none()
+            && self.verification.is_none()
+    }
+}
+
+/// Switches the active model for the current session only; `save_config`
+/// keeps writing the global default while `global_model` is set.
+pub fn use_session_model(cfg: &mut Config, model: &str) {
+    let global = cfg.global_model.take().unwrap_or_else(|| cfg.model.clone());
+    if global != model {
+        cfg.global_model = Some(global);
+    }
+    set_active_model(cfg, model);
+}
+
+/// Switches the active prompt for the current session only.
+pub fn use_session_prompt(cfg: &mut Config, prompt: &str) {
+    let global = cfg
+        .global_prompt
+        .take()
+        .unwrap_or_else(|| cfg.active_prompt.clone());
+    if global != prompt {
+        cfg.global_prompt = Some(global);
+    }
+    cfg.active_prompt = prompt.to_string();
+}
+
+/// Names of the sessions that have a metadata file.
+pub fn sessions_with_meta() -> Result<Vec<String>> {
+    let dir = config_dir()?.join("sessions");
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| {
+            e.file_name()
+                .to_str()?
+                .strip_suffix(".meta.json")
+                .map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn meta_path(session: &str) -> Result<PathBuf> {
+    Ok(config_dir()?
+        .join("sessions")
+        .join(format!("{session}.meta.json")))
+}
+
+/// Loads, changes and saves the metadata of `session`, holding the file lock
+/// throughout so concurrent updates from other processes are not lost.
+pub fn update_session_meta(session: &str, change: impl FnOnce(&mut SessionMeta)) -> Result<()> {
+    with_file_lock(&meta_path(session)?, || {
+        let mut meta = load_session_meta(session)?;
+        change(&mut meta);
+        save_session_meta(session, &meta)
+    })
+}
+
+/// Records the seed a session runs with, so the run can be reproduced.
+pub fn record_session_seed(session: &str, seed: Option<u64>) -> Result<()> {
+    if load_session_meta(session)?.seed == seed {
+        return Ok(());
+    }
+    update_session_meta(session, |meta| meta.seed = seed)
+}
+
+pub fn record_session_branch(session: &str, branch: &str) -> Result<()> {
+    update_session_meta(session, |meta| meta.branch = Some(branch.to_string()))
+}
+
+pub fn load_session_meta(session: &str) -> Result<SessionMeta> {
+    let path = meta_path(session)?;
+    if !path.exists() {
+        return Ok(SessionMeta::default());
+    }
+    let data = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let text = unseal(&data).with_context(|| format!("Failed to open {}", path.display()))?;
+    serde_json::from_slice(&text)
+        .with_context(|| format!("Invalid session metadata: {}", path.display()))
+}
+
+/// Writes the metadata, or removes the file when nothing is overridden.
+fn save_session_meta(session: &str, meta: &SessionMeta) -> Result<()> {
+    if meta.is_empty() {
+        return remove_session_meta(session);
+    }
+    let text = serde_json::to_string_pretty(meta)?;
+    write_atomic(&meta_path(session)?, seal(text.as_bytes())?)
+}
+
+pub fn remove_session_meta(session: &str) -> Result<()> {
+    let path = meta_path(session)?;
+    if path.exists() {
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use anyhow::{Result, bail};
+
+use crate::config::Config;
+use crate::write_jail::expand_home;
+
+/// Accepted values for the `shell` config key on this platform.
+pub const SHELL_SETTINGS: &[&str] = if cfg!(target_os = "windows") {
+    &["auto", "pwsh", "powershell", "cmd"]
+} else {
+    &["auto", "bash", "zsh", "sh"]
+};
+
+/// Shell that runs agent and `exec` commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// PowerShell 7+
+    Pwsh,
+    /// Windows PowerShell 5.1
+    Powershell,
+    Cmd,
+    Bash,
+    Zsh,
+    Sh,
+}
+
+/// Extra environment and working directory applied to every executed command.
+#[derive(Debug, Default)]
+struct ExecContext {
+    env: BTreeMap<String, String>,
+    cwd: Option<PathBuf>,
+}
+
+static SHELL: OnceLock<Shell> = OnceLock::new();
+static EXEC: OnceLock<ExecContext> = OnceLock::new();
+
+const PS_UTF8_PRELUDE: &str =
+    "$OutputEncoding = [Console]::OutputEncoding = [System.Text.UTF8Encoding]::new($false); ";
+
+impl Shell {
+    pub fn name(self) -> &'static str {
+        match self {
+            Shell::Pwsh => "pwsh",
+            Shell::Powershell => "powershell",
+            Shell::Cmd => "cmd",
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Sh => "sh",
+        }
+    }
+
+    /// A command that runs `script` in this shell.
+    pub fn command(self, script: &str) -> Command {
+        let mut cmd = Command::new(self.name());
+        match self {
+            Shell::Pwsh => {
+                cmd.args([
+                    "-NoProfile",
+                    "-Command",
+                    &format!("{PS_UTF8_PRELUDE}{script}"),
+                ]);
+            }
+            Shell::Powershell => {
+                let script = normalize_windows_shell_command(script);
+                cmd.args([
+                    "-NoProfile",
+                    "-Command",
+                    &format!("{PS_UTF8_PRELUDE}{script}"),
+                ]);
+            }
+            Shell::Cmd => {
+                cmd.args(["/C", script]);
+            }
+            Shell::Bash | Shell::Zsh | Shell::Sh => {
+                cmd.args(["-lc", script]);
+            }
+        }
+        cmd
+    }
+
+    /// `arg` quoted so this shell passes it through as one literal word.
+    pub fn quote(self, arg: &str) -> String {
+        let windows = matches!(self, Shell::Pwsh | Shell::Powershell | Shell::Cmd);
+        let plain = !arg.is_empty()
+            && arg.chars().all(|c| {
+                c.is_ascii_alphanumeric() || "_-./:@+=,".contains(c) || (windows && c == '\\')
+            });
+        if plain {
+            return arg.to_string();
+        }
+        match self {
+            Shell::Pwsh | Shell::Powershell => format!("'{}'", arg.replace('\'', "''")),
+            Shell::Cmd => format!("\"{}\"", arg),
+            Shell::Bash | Shell::Zsh | Shell::Sh => format!("'{}'", arg.replace('\'', "'\\''")),
+        }
+    }
+}
+
+/// Applies the `shell`, `exec_env` and `exec_cwd` config values; `auto` is
+/// resolved on first use. A relative `exec_cwd` is taken from the current
+/// directory.
+pub fn init_shell(cfg: &Config) {
+    if let Some(shell) = parse_shell(&cfg.shell) {
+        let _ = SHELL.set(shell);
+    }
+    let cwd = cfg
+        .exec_cwd
+        .as_deref()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(expand_home)
+        .map(|p| match std::env::current_dir() {
+            Ok(base) if p.is_relative() => base.join(p),
+            _ => p,
+        });
+    let _ = EXEC.set(ExecContext {
+        env: cfg.exec_env.clone(),
+        cwd,
+    });
+}
+
+pub fn active_shell() -> Shell {
+    *SHELL.get_or_init(detect_shell)
+}
+
+/// Directory executed commands run in when it is not the current one.
+pub fn exec_cwd() -> Option<&'static Path> {
+    EXEC.get()?.cwd.as_deref()
+}
+
+/// The active shell running `script` with the configured env and working directory.
+pub fn shell_command(script: &str) -> Command {
+    let mut cmd = active_shell().command(script);
+    if let Some(exec) = EXEC.get() {
+        cmd.envs(&exec.env);
+        if let Some(dir) = &exec.cwd {
+            cmd.current_dir(dir);
+        }
+    }
+    cmd
+}
+
+/// Validates a `shell` config value for this platform.
+pub fn validate_shell_setting(setting: &str) -> Result<()> {
+    let s = setting.trim();
+    if s.eq_ignore_ascii_case("auto") || parse_shell(s).is_some() {
+        return Ok(());
+    }
+    bail!(
+        "Unknown shell: {}. Available: {}",
+        setting,
+        SHELL_SETTINGS.join(", ")
+    )
+}
+
+fn parse_shell(setting: &str) -> Option<Shell> {
+    let shell = match setting.trim().to_ascii_lowercase().as_str() {
+        "pwsh" => Shell::Pwsh,
+        "powershell" => Shell::Powershell,
+        "cmd" => Shell::Cmd,
+        "bash" => Shell::Bash,
+        "zsh" => Shell::Zsh,
+        "sh" => Shell::Sh,
+        _ => return None,
+    };
+    SHELL_SETTINGS.contains(&shell.name()).then_some(shell)
+}
+
+/// PowerShell 7 when it starts, else Windows PowerShell; `sh` on Unix.
+fn detect_shell() -> Shell {
+    if !cfg!(target_os = "windows") {
+        return Shell::Sh;
+    }
+    let pwsh = Command::new("pwsh")
+        .args(["-NoProfile", "-Command", "exit 0"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if pwsh { Shell::Pwsh } else { Shell::Powershell }
+}
+
+fn normalize_windows_shell_command(cmd: &str) -> String {
+    // Windows PowerShell 5.1 does not support "&&"; convert to sequential separator.
+    // This keeps common model-generated commands like `cd path && ls -la` runnable.
+    let mut out = String::with_capacity(cmd.len());
+    let mut chars = cmd.chars().peekable();
+    let mut in_single = false;
+    let mut in_double = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' if !in_double => {
+                in_single = !in_single;
+                out.push(ch);
+            }
+            '"' if !in_single => {
+                in_double = !in_double;
+                out.push(ch);
+            }
+            '&' if !in_single && !in_double && chars.peek() == Some(&'&') => {
+                let _ = chars.next();
+                out.push_str("; ");
+            }
+            _ => out.push(ch),
+        }
+    }
+    out
+}
@@ -0,0 +1,299 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::Value;
+
+use crate::config::config_dir;
+use crate::diagnostics::now_unix_ts;
+use crate::llm::ChatMessage;
+use crate::storage::{MessageHit, PruneReport, Storage, snippet_around};
+use crate::usage::UsageRecord;
+
+/// How long a write waits for another process holding the database lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    name TEXT PRIMARY KEY,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS messages (
+    session TEXT NOT NULL,
+    idx INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    images TEXT NOT NULL DEFAULT '[]',
+    PRIMARY KEY (session, idx)
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+    content, session UNINDEXED, idx UNINDEXED, role UNINDEXED
+);
+CREATE TABLE IF NOT EXISTS usage (
+    ts INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    cached_tokens INTEGER NOT NULL,
+    estimated INTEGER NOT NULL,
+    cost_usd REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS usage_ts ON usage (ts);
+CREATE TABLE IF NOT EXISTS events (
+    ts INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    session TEXT,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_ts ON events (ts);
+";
+
+/// Connection opened on first use and shared by the whole process.
+static CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+
+pub fn database_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("dongshan.db"))
+}
+
+fn with_connection<T>(f: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+    let mut guard = CONNECTION
+        .lock()
+        .map_err(|_| anyhow!("failed to lock the storage database"))?;
+    if guard.is_none() {
+        let path = database_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let conn = Connection::open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to prepare {}", path.display()))?;
+        *guard = Some(conn);
+    }
+    match guard.as_mut() {
+        Some(conn) => f(conn),
+        None => unreachable!("connection opened above"),
+    }
+}
+
+/// FTS5 query matching every word of `query` literally.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Everything in ~/.dongshan/dongshan.db.
+pub struct SqliteStorage;
+
+impl SqliteStorage {
+    /// Replaces a session's messages, keeping `updated_at` (used by migration).
+    pub fn import_session(
+        &self,
+        name: &str,
+        messages: &[ChatMessage],
+        updated_at: u64,
+    ) -> Result<()> {
+        with_connection(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM messages WHERE session = ?1", [name])?;
+            tx.execute("DELETE FROM messages_fts WHERE session = ?1", [name])?;
+            for (idx, msg) in messages.iter().enumerate() {
+                let images = serde_json::to_string(&msg.images)?;
+                tx.execute(
+                    "INSERT INTO messages (session, idx, role, content, images) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![name, idx as i64, msg.role, msg.content, images],
+                )?;
+                tx.execute(
+                    "INSERT INTO messages_fts (content, session, idx, role) VALUES (?1, ?2, ?3, ?4)",
+                    params![msg.content, name, idx as i64, msg.role],
+                )?;
+            }
+            tx.execute(
+                "INSERT INTO sessions (name, updated_at) VALUES (?1, ?2)
+                 ON CONFLICT (name) DO UPDATE SET updated_at = excluded.updated_at",
+                params![name, updated_at as i64],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// Row count of `table` (`usage` or `events`).
+    pub fn count(&self, table: &str) -> Result<u64> {
+        with_connection(|conn| {
+            let n: i64 =
+                conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |r| r.get(0))?;
+            Ok(n as u64)
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_session(&self, name: &str) -> Result<Option<Vec<ChatMessage>>> {
+        with_connection(|conn| {
+            let exists = conn
+                .query_row("SELECT 1 FROM sessions WHERE name = ?1", [name], |_| Ok(()))
+                .optional()?;
+            if exists.is_none() {
+                return Ok(None);
+            }
+            let mut stmt = conn.prepare(
+                "SELECT role, content, images FROM messages WHERE session = ?1 ORDER BY idx",
+            )?;
+            let rows = stmt.query_map([name], |row| {
+                let images: String = row.get(2)?;
+                Ok(ChatMessage {
+                    role: row.get(0)?,
+                    content: row.get(1)?,
+                    images: serde_json::from_str(&images).unwrap_or_default(),
+                })
+            })?;
+            Ok(Some(rows.collect::<rusqlite::Result<Vec<_>>>()?))
+        })
+    }
+
+    fn save_session(&self, name: &str, messages: &[ChatMessage]) -> Result<()> {
+        self.import_session(name, messages, now_unix_ts())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        with_connection(|conn| {
+            let mut stmt = conn.prepare("SELECT name FROM sessions ORDER BY name")?;
+            let names = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(names)
+        })
+    }
+
+    fn remove_session(&self, name: &str) -> Result<bool> {
+        with_connection(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM messages WHERE session = ?1", [name])?;
+            tx.execute("DELETE FROM messages_fts WHERE session = ?1", [name])?;
+            let removed = tx.execute("DELETE FROM sessions WHERE name = ?1", [name])?;
+            tx.commit()?;
+            Ok(removed > 0)
+        })
+    }
+
+    fn append_usage(&self, record: &UsageRecord) -> Result<()> {
+        with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO usage (ts, command, model, prompt, prompt_tokens, completion_tokens, cached_tokens, estimated, cost_usd)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    record.ts as i64,
+                    record.command,
+                    record.model,
+                    record.prompt,
+                    record.prompt_tokens as i64,
+                    record.completion_tokens as i64,
+                    record.cached_tokens as i64,
+                    record.estimated,
+                    record.cost_usd
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn load_usage(&self, since_ts: Option<u64>) -> Result<Vec<UsageRecord>> {
+        with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT ts, command, model, prompt, prompt_tokens, completion_tokens, cached_tokens, estimated, cost_usd
+                 FROM usage WHERE ts >= ?1 ORDER BY ts",
+            )?;
+            let rows = stmt.query_map([since_ts.unwrap_or(0) as i64], |row| {
+                Ok(UsageRecord {
+                    ts: row.get::<_, i64>(0)? as u64,
+                    command: row.get(1)?,
+                    model: row.get(2)?,
+                    prompt: row.get(3)?,
+                    prompt_tokens: row.get::<_, i64>(4)? as u64,
+                    completion_tokens: row.get::<_, i64>(5)? as u64,
+                    cached_tokens: row.get::<_, i64>(6)? as u64,
+                    estimated: row.get(7)?,
+                    cost_usd: row.get(8)?,
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+    }
+
+    fn record_event(&self, entry: &Value) -> Result<()> {
+        let field = |key: &str| entry.get(key).and_then(Value::as_str);
+        let ts = entry
+            .get("ts")
+            .and_then(Value::as_u64)
+            .unwrap_or_else(now_unix_ts);
+        with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO events (ts, kind, session, data) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    ts as i64,
+                    field("kind").unwrap_or_default(),
+                    field("session"),
+                    entry.to_string()
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<MessageHit>> {
+        let fts = fts_query(query);
+        if fts.is_empty() {
+            return Ok(Vec::new());
+        }
+        with_connection(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT session, idx, role, content FROM messages_fts
+                 WHERE messages_fts MATCH ?1 ORDER BY rank LIMIT ?2",
+            )?;
+            let rows = stmt.query_map(params![fts, limit as i64], |row| {
+                let content: String = row.get(3)?;
+                Ok(MessageHit {
+                    session: row.get(0)?,
+                    index: row.get::<_, i64>(1)? as usize,
+                    role: row.get(2)?,
+                    snippet: snippet_around(&content, query),
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+    }
+
+    fn prune(&self, cutoff: u64) -> Result<PruneReport> {
+        let cutoff = i64::try_from(cutoff).unwrap_or(i64::MAX);
+        let sessions = with_connection(|conn| {
+            let mut stmt = conn.prepare("SELECT name FROM sessions WHERE updated_at < ?1")?;
+            let names = stmt
+                .query_map([cutoff], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(names)
+        })?;
+        for name in &sessions {
+            self.remove_session(name)?;
+        }
+        with_connection(|conn| {
+            let usage = conn.execute("DELETE FROM usage WHERE ts < ?1", [cutoff])?;
+            let events = conn.execute("DELETE FROM events WHERE ts < ?1", [cutoff])?;
+            Ok(PruneReport {
+                sessions,
+                usage,
+                events,
+            })
+        })
+    }
+}
@@ -0,0 +1,341 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::atomic_file::write_locked;
+use crate::config::{Config, StorageBackend, config_dir};
+use crate::diagnostics::now_unix_ts;
+use crate::llm::ChatMessage;
+use crate::logging::log_verbose;
+use crate::session_crypt::{seal, unseal};
+use crate::session_meta::remove_session_meta;
+use crate::sqlite_store::SqliteStorage;
+use crate::transcript::{list_transcripts, utc_date};
+use crate::usage::UsageRecord;
+
+static BACKEND: OnceLock<StorageBackend> = OnceLock::new();
+static JSON: JsonStorage = JsonStorage;
+static SQLITE: SqliteStorage = SqliteStorage;
+
+/// Characters of message text shown around a search hit.
+const SNIPPET_CHARS: usize = 160;
+
+/// One message matching `dongshan storage search`.
+pub struct MessageHit {
+    pub session: String,
+    /// Position of the message in the session.
+    pub index: usize,
+    pub role: String,
+    pub snippet: String,
+}
+
+/// What a retention run deleted.
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub sessions: Vec<String>,
+    pub usage: usize,
+    pub events: usize,
+}
+
+/// Persistence of sessions, usage records and audit events. Session metadata,
+/// backups and jobs stay in their own files with either backend.
+pub trait Storage: Sync {
+    fn load_session(&self, name: &str) -> Result<Option<Vec<ChatMessage>>>;
+    fn save_session(&self, name: &str, messages: &[ChatMessage]) -> Result<()>;
+    fn list_sessions(&self) -> Result<Vec<String>>;
+    /// Returns false when the session did not exist.
+    fn remove_session(&self, name: &str) -> Result<bool>;
+    fn append_usage(&self, record: &UsageRecord) -> Result<()>;
+    fn load_usage(&self, since_ts: Option<u64>) -> Result<Vec<UsageRecord>>;
+    /// Stores one transcript entry. The JSON backend's transcript file is the
+    /// record, so it does nothing here.
+    fn record_event(&self, entry: &Value) -> Result<()>;
+    fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<MessageHit>>;
+    /// Deletes sessions, usage records and events last touched before `cutoff`.
+    fn prune(&self, cutoff: u64) -> Result<PruneReport>;
+}
+
+/// Selects the backend from the `storage` config key and applies the
+/// retention policy at most once a day.
+pub fn init_storage(cfg: &Config) {
+    let _ = BACKEND.set(cfg.storage);
+    if cfg.storage_retention_days > 0
+        && let Err(err) = prune_if_due(cfg.storage_retention_days)
+    {
+        log_verbose("storage", &format!("retention failed: {err:#}"));
+    }
+}
+
+pub fn storage_backend() -> StorageBackend {
+    BACKEND.get().copied().unwrap_or_default()
+}
+
+pub fn storage() -> &'static dyn Storage {
+    backend_storage(storage_backend())
+}
+
+pub fn backend_storage(backend: StorageBackend) -> &'static dyn Storage {
+    match backend {
+        StorageBackend::Json => &JSON,
+        StorageBackend::Sqlite => &SQLITE,
+    }
+}
+
+fn prune_if_due(days: u64) -> Result<()> {
+    let marker = config_dir()?.join("last_prune");
+    let now = now_unix_ts();
+    let last = fs::read_to_string(&marker)
+        .ok()
+        .and_then(|t| t.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    if now.saturating_sub(last) < 86_400 {
+        return Ok(());
+    }
+    fs::write(&marker, now.to_string())
+        .with_context(|| format!("Failed to write {}", marker.display()))?;
+    let report = prune_storage(now.saturating_sub(days * 86_400))?;
+    log_verbose(
+        "storage",
+        &format!(
+            "retention removed {} session(s), {} usage record(s), {} event(s)",
+            report.sessions.len(),
+            report.usage,
+            report.events
+        ),
+    );
+    Ok(())
+}
+
+/// Prunes the active backend and drops the metadata of removed sessions.
+pub fn prune_storage(cutoff: u64) -> Result<PruneReport> {
+    let mut report = storage().prune(cutoff)?;
+    for session in &report.sessions {
+        remove_session_meta(session)?;
+    }
+    // Transcript files are written with either backend; under SQLite their
+    // entries are copies of the rows already counted.
+    let lines = prune_transcripts(cutoff)?;
+    if storage_backend() == StorageBackend::Json {
+        report.events += lines;
+    }
+    Ok(report)
+}
+
+/// Removes transcript files (one UTC day each) for days that ended before
+/// `cutoff`, returning how many entries they held.
+fn prune_transcripts(cutoff: u64) -> Result<usize> {
+    let cutoff_day = utc_date(cutoff);
+    let mut removed = 0;
+    for path in list_transcripts()? {
+        let day = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("transcript-"))
+            .and_then(|n| n.strip_suffix(".jsonl"))
+            .unwrap_or_default()
+            .to_string();
+        if !day.is_empty() && day < cutoff_day {
+            removed += fs::read_to_string(&path)
+                .map(|t| t.lines().count())
+                .unwrap_or(0);
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+    Ok(removed)
+}
+
+/// Up to `SNIPPET_CHARS` of `text` around the first case-insensitive match of `query`.
+pub fn snippet_around(text: &str, query: &str) -> String {
+    let flat = text.replace('\n', " ");
+    let lower = flat.to_lowercase();
+    // Lowercasing can change byte lengths; offsets only carry over when it did not.
+    let start = lower
+        .find(&query.to_lowercase())
+        .filter(|_| lower.len() == flat.len())
+        .unwrap_or(0);
+    let mut begin = start.saturating_sub(SNIPPET_CHARS / 3);
+    while !flat.is_char_boundary(begin) {
+        begin -= 1;
+    }
+    let body: String = flat[begin..].chars().take(SNIPPET_CHARS).collect();
+    let prefix = if begin > 0 { "..." } else { "" };
+    let suffix = if flat.len() - begin > body.len() {
+        "..."
+    } else {
+        ""
+    };
+    format!("{prefix}{body}{suffix}")
+}
+
+/// Files under ~/.dongshan: `sessions/<name>.json`, `usage.jsonl` and the
+/// transcript logs.
+pub struct JsonStorage;
+
+impl JsonStorage {
+    fn sessions_dir() -> Result<PathBuf> {
+        Ok(config_dir()?.join("sessions"))
+    }
+
+    fn session_path(name: &str) -> Result<PathBuf> {
+        Ok(Self::sessions_dir()?.join(format!("{name}.json")))
+    }
+
+    fn usage_file() -> Result<PathBuf> {
+        Ok(config_dir()?.join("usage.jsonl"))
+    }
+
+    /// Unix mtime of a session file, used as its last-update time.
+    pub fn session_mtime(name: &str) -> Option<u64> {
+        let modified = fs::metadata(Self::session_path(name).ok()?)
+            .ok()?
+            .modified()
+            .ok()?;
+        Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+    }
+}
+
+impl Storage for JsonStorage {
+    fn load_session(&self, name: &str) -> Result<Option<Vec<ChatMessage>>> {
+        let path = Self::session_path(name)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let text = unseal(&data).with_context(|| format!("Failed to open {}", path.display()))?;
+        let messages = serde_json::from_slice(&text)
+            .with_context(|| format!("Invalid session JSON: {}", path.display()))?;
+        Ok(Some(messages))
+    }
+
+    fn save_session(&self, name: &str, messages: &[ChatMessage]) -> Result<()> {
+        let text = serde_json::to_string_pretty(messages)?;
+        write_locked(&Self::session_path(name)?, seal(text.as_bytes())?)
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        let dir = Self::sessions_dir()?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read session dir {}", dir.display()))?
+        {
+            let entry =
+                entry.with_context(|| format!("Failed to read entry in {}", dir.display()))?;
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            // `<name>.meta.json` holds session metadata, not a session.
+            if stem.ends_with(".meta") {
+                continue;
+            }
+            names.push(stem.to_string());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn remove_session(&self, name: &str) -> Result<bool> {
+        let path = Self::session_path(name)?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        Ok(true)
+    }
+
+    fn append_usage(&self, record: &UsageRecord) -> Result<()> {
+        let path = Self::usage_file()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    fn load_usage(&self, since_ts: Option<u64>) -> Result<Vec<UsageRecord>> {
+        let path = Self::usage_file()?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(text
+            .lines()
+            .filter_map(|l| serde_json::from_str::<UsageRecord>(l).ok())
+            .filter(|r| since_ts.is_none_or(|since| r.ts >= since))
+            .collect())
+    }
+
+    fn record_event(&self, _entry: &Value) -> Result<()> {
+        Ok(())
+    }
+
+    fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<MessageHit>> {
+        let needle = query.to_lowercase();
+        let mut hits = Vec::new();
+        for name in self.list_sessions()? {
+            // A session that fails to load (e.g. a wrong passphrase) is an
+            // error, not a miss.
+            let Some(messages) = self.load_session(&name)? else {
+                continue;
+            };
+            for (index, msg) in messages.iter().enumerate() {
+                if hits.len() >= limit {
+                    return Ok(hits);
+                }
+                if msg.content.to_lowercase().contains(&needle) {
+                    hits.push(MessageHit {
+                        session: name.clone(),
+                        index,
+                        role: msg.role.clone(),
+                        snippet: snippet_around(&msg.content, query),
+                    });
+                }
+            }
+        }
+        Ok(hits)
+    }
+
+    fn prune(&self, cutoff: u64) -> Result<PruneReport> {
+        let mut report = PruneReport::default();
+        for name in self.list_sessions()? {
+            if Self::session_mtime(&name).is_some_and(|ts| ts < cutoff)
+                && self.remove_session(&name)?
+            {
+                report.sessions.push(name);
+            }
+        }
+        let all = self.load_usage(None)?;
+        let kept: Vec<&UsageRecord> = all.iter().filter(|r| r.ts >= cutoff).collect();
+        report.usage = all.len() - kept.len();
+        if report.usage > 0 {
+            let mut text = String::new();
+            for record in kept {
+                text.push_str(&serde_json::to_string(record)?);
+                text.push('\n');
+            }
+            write_locked(&Self::usage_file()?, text)?;
+        }
+        // The transcript files are this backend's events; `prune_storage`
+        // removes them for both backends.
+        Ok(report)
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::util::color_dim;
+use crate::worktree::{git, git_ok};
+
+/// The branch `agent --branch` works on and the branch it started from.
+pub struct TaskBranch {
+    pub name: String,
+    base: String,
+}
+
+/// Fails unless `name` is a valid branch name.
+pub fn validate_branch_name(name: &str) -> Result<()> {
+    let out = git(Path::new("."), &["check-ref-format", "--branch", name])?;
+    if !out.status.success() {
+        bail!("Invalid branch name: {}", name);
+    }
+    Ok(())
+}
+
+impl TaskBranch {
+    /// Switches to `name`, creating it from the current HEAD when it does not
+    /// exist yet. Uncommitted changes move along with the switch.
+    pub fn checkout(name: &str) -> Result<Self> {
+        let here = Path::new(".");
+        validate_branch_name(name)?;
+        let current = match git_ok(here, &["symbolic-ref", "--quiet", "--short", "HEAD"]) {
+            Ok(branch) => branch,
+            Err(_) => git_ok(here, &["rev-parse", "HEAD"])
+                .context("--branch needs a git repository with at least one commit")?,
+        };
+        if current == name {
+            // Already on the task branch: compare against the default branch.
+            let base = default_branch(name).unwrap_or(current);
+            println!(
+                "{}",
+                color_dim(&format!("branch> on {} (base {})", name, base))
+            );
+            return Ok(TaskBranch {
+                name: name.to_string(),
+                base,
+            });
+        }
+        let exists = branch_exists(name)?;
+        let args: &[&str] = if exists {
+            &["switch", name]
+        } else {
+            &["switch", "-c", name]
+        };
+        git_ok(here, args)?;
+        let verb = if exists { "switched to" } else { "created" };
+        println!(
+            "{}",
+            color_dim(&format!("branch> {} {} (base {})", verb, name, current))
+        );
+        Ok(TaskBranch {
+            name: name.to_string(),
+            base: current,
+        })
+    }
+
+    /// Diff stat of the branch, including uncommitted changes, against the
+    /// point where it left the base branch.
+    pub fn print_diff_stat(&self) -> Result<()> {
+        let here = Path::new(".");
+        let fork = git_ok(here, &["merge-base", &self.base, "HEAD"])?;
+        let stat = git_ok(here, &["diff", "--stat", &fork])?;
+        println!("== {} vs {} ==", self.name, self.base);
+        if stat.is_empty() {
+            println!("no changes");
+        } else {
+            println!("{}", stat);
+        }
+        Ok(())
+    }
+}
+
+fn branch_exists(name: &str) -> Result<bool> {
+    let out = git(
+        Path::new("."),
+        &[
+            "show-ref",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{name}"),
+        ],
+    )?;
+    Ok(out.status.success())
+}
+
+/// `origin/HEAD`'s branch, else `main` or `master`, skipping `except`.
+pub(crate) fn default_branch(except: &str) -> Option<String> {
+    let remote = git_ok(
+        Path::new("."),
+        &["rev-parse", "--abbrev-ref", "origin/HEAD"],
+    )
+    .ok();
+    remote
+        .into_iter()
+        .chain(["main".to_string(), "master".to_string()])
+        .filter(|b| b != except)
+        .find(|b| b.starts_with("origin/") || branch_exists(b).unwrap_or(false))
+}
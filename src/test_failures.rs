@@ -0,0 +1,239 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::util::truncate_with_suffix;
+
+/// Failures listed in the summary; the rest are only counted.
+const MAX_FAILURES: usize = 20;
+const MAX_MESSAGE_CHARS: usize = 600;
+
+/// One failing test or compiler error pulled out of a build/test log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFailure {
+    pub name: String,
+    /// `file:line[:col]` when the log points at one.
+    pub location: Option<String>,
+    pub message: String,
+}
+
+fn re(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+/// Parses cargo (build and test), pytest and jest output. Empty when the log
+/// has none of their failure markers.
+pub fn parse_test_failures(output: &str) -> Vec<TestFailure> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut out = Vec::new();
+    parse_cargo_tests(&lines, &mut out);
+    parse_rustc_errors(&lines, &mut out);
+    parse_pytest(&lines, &mut out);
+    parse_jest(&lines, &mut out);
+    let mut seen = std::collections::HashSet::new();
+    out.retain(|f: &TestFailure| seen.insert((f.name.clone(), f.location.clone())));
+    out
+}
+
+/// Failure list for the model, or `None` when nothing could be parsed and the
+/// raw log should be sent instead.
+pub fn summarize_test_failures(output: &str) -> Option<String> {
+    let failures = parse_test_failures(output);
+    if failures.is_empty() {
+        return None;
+    }
+    let mut text = format!("{} failure(s):", failures.len());
+    for (i, f) in failures.iter().take(MAX_FAILURES).enumerate() {
+        text.push_str(&format!("\n{}. {}", i + 1, f.name));
+        if let Some(loc) = &f.location {
+            text.push_str(&format!(" at {}", loc));
+        }
+        for line in f.message.lines() {
+            text.push_str(&format!("\n   {}", line));
+        }
+    }
+    if failures.len() > MAX_FAILURES {
+        text.push_str(&format!("\n... {} more", failures.len() - MAX_FAILURES));
+    }
+    Some(text)
+}
+
+fn clip(message: &str) -> String {
+    truncate_with_suffix(message.trim(), MAX_MESSAGE_CHARS, " ...")
+}
+
+/// `---- name stdout ----` blocks with the panic location and message, plus
+/// `test name ... FAILED` lines for tests without captured output.
+fn parse_cargo_tests(lines: &[&str], out: &mut Vec<TestFailure>) {
+    static HEADER: OnceLock<Regex> = OnceLock::new();
+    static PANIC_NEW: OnceLock<Regex> = OnceLock::new();
+    static PANIC_OLD: OnceLock<Regex> = OnceLock::new();
+    static FAILED_LINE: OnceLock<Regex> = OnceLock::new();
+    let header = re(&HEADER, r"^---- (\S+) stdout ----$");
+    // Rust 1.73+: `thread 'x' panicked at src/lib.rs:3:5:` then the message.
+    let panic_new = re(&PANIC_NEW, r"panicked at ([^\s']+:\d+:\d+):$");
+    // Older: `thread 'x' panicked at 'message', src/lib.rs:3:5`.
+    let panic_old = re(&PANIC_OLD, r"panicked at '(.*)', ([^\s]+:\d+:\d+)");
+    let failed_line = re(&FAILED_LINE, r"^test (\S+) \.\.\. FAILED$");
+
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(caps) = header.captures(lines[i].trim_end()) else {
+            i += 1;
+            continue;
+        };
+        let name = caps[1].to_string();
+        let mut location = None;
+        let mut message = Vec::new();
+        i += 1;
+        while i < lines.len() {
+            let line = lines[i].trim_end();
+            if header.is_match(line) || line == "failures:" {
+                break;
+            }
+            if let Some(c) = panic_new.captures(line) {
+                location = Some(c[1].to_string());
+                i += 1;
+                while i < lines.len() {
+                    let l = lines[i].trim_end();
+                    if l.is_empty()
+                        || l.starts_with("note:")
+                        || l.starts_with("stack backtrace:")
+                        || header.is_match(l)
+                    {
+                        break;
+                    }
+                    message.push(l);
+                    i += 1;
+                }
+                continue;
+            }
+            if let Some(c) = panic_old.captures(line) {
+                location = Some(c[2].to_string());
+                message.push(c.get(1).map(|m| m.as_str()).unwrap_or_default());
+            }
+            i += 1;
+        }
+        out.push(TestFailure {
+            name,
+            location,
+            message: clip(&message.join("\n")),
+        });
+    }
+
+    for line in lines {
+        if let Some(c) = failed_line.captures(line.trim_end())
+            && !out.iter().any(|f| f.name == c[1])
+        {
+            out.push(TestFailure {
+                name: c[1].to_string(),
+                location: None,
+                message: String::new(),
+            });
+        }
+    }
+}
+
+/// `error[E0308]: mismatched types` followed by ` --> src/main.rs:4:9`.
+fn parse_rustc_errors(lines: &[&str], out: &mut Vec<TestFailure>) {
+    static ERROR: OnceLock<Regex> = OnceLock::new();
+    static ARROW: OnceLock<Regex> = OnceLock::new();
+    let error = re(&ERROR, r"^error(\[E\d+\])?: (.+)$");
+    let arrow = re(&ARROW, r"^\s*--> (\S+:\d+:\d+)");
+    for (i, line) in lines.iter().enumerate() {
+        let Some(c) = error.captures(line.trim_end()) else {
+            continue;
+        };
+        let message = c[2].to_string();
+        // Summary lines that repeat what the individual errors already say.
+        if message.starts_with("could not compile")
+            || message.starts_with("aborting due to")
+            || message.starts_with("test failed")
+            || message.starts_with("build failed")
+        {
+            continue;
+        }
+        let location = lines[i + 1..]
+            .iter()
+            .take(4)
+            .find_map(|l| arrow.captures(l).map(|c| c[1].to_string()));
+        out.push(TestFailure {
+            name: format!("error{}", c.get(1).map(|m| m.as_str()).unwrap_or_default()),
+            location,
+            message: clip(&message),
+        });
+    }
+}
+
+/// `FAILED tests/test_x.py::test_y - AssertionError: ...` summary lines; the
+/// line number comes from the `tests/test_x.py:12: AssertionError` trace line.
+fn parse_pytest(lines: &[&str], out: &mut Vec<TestFailure>) {
+    static FAILED: OnceLock<Regex> = OnceLock::new();
+    let failed = re(&FAILED, r"^(FAILED|ERROR) (\S+?)(?:::(\S+))?(?: - (.*))?$");
+    for line in lines {
+        let Some(c) = failed.captures(line.trim_end()) else {
+            continue;
+        };
+        let file = &c[2];
+        let name = match c.get(3) {
+            Some(test) => format!("{}::{}", file, test.as_str()),
+            None => file.to_string(),
+        };
+        let prefix = format!("{}:", file);
+        let location = lines.iter().find_map(|l| {
+            let rest = l.strip_prefix(&prefix)?;
+            let (num, _) = rest.split_once(':')?;
+            num.parse::<usize>().ok().map(|n| format!("{}:{}", file, n))
+        });
+        out.push(TestFailure {
+            name,
+            location,
+            message: clip(c.get(4).map(|m| m.as_str()).unwrap_or_default()),
+        });
+    }
+}
+
+/// `● Suite › test name` blocks; the location is the first stack frame
+/// outside node_modules.
+fn parse_jest(lines: &[&str], out: &mut Vec<TestFailure>) {
+    static FRAME: OnceLock<Regex> = OnceLock::new();
+    let frame = re(&FRAME, r"\(?([^\s()]+:\d+:\d+)\)?$");
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(name) = lines[i].trim().strip_prefix("● ") else {
+            i += 1;
+            continue;
+        };
+        let name = name.trim().to_string();
+        let mut location = None;
+        let mut message = Vec::new();
+        i += 1;
+        while i < lines.len() {
+            let line = lines[i].trim();
+            if line.starts_with("● ") || line.starts_with("Test Suites:") {
+                break;
+            }
+            if let Some(rest) = line.strip_prefix("at ") {
+                if location.is_none()
+                    && !rest.contains("node_modules")
+                    && let Some(c) = frame.captures(rest)
+                {
+                    location = Some(c[1].to_string());
+                }
+            } else if !line.is_empty()
+                && message.len() < 4
+                && !line.starts_with('>')
+                && !line.starts_with('|')
+                && !line.starts_with(|c: char| c.is_ascii_digit())
+            {
+                message.push(line);
+            }
+            i += 1;
+        }
+        out.push(TestFailure {
+            name,
+            location,
+            message: clip(&message.join("\n")),
+        });
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{Certificate, ClientBuilder, Identity};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::logging::log_verbose;
+use crate::write_jail::expand_home;
+
+/// `[tls]` section: extra trust roots for TLS-intercepting proxies and a client
+/// certificate for mTLS gateways. Applied to every HTTP client dongshan builds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file with one or more CA certificates trusted in addition to the built-in roots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert_path: Option<String>,
+    /// PEM client certificate (chain); may also hold the private key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    /// PEM private key for `client_cert` when it is kept in a separate file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    /// Skip certificate verification entirely. Only for debugging.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+}
+
+struct TlsSettings {
+    roots: Vec<Certificate>,
+    identity: Option<Identity>,
+    accept_invalid_certs: bool,
+}
+
+/// Loaded once at startup; a broken setting is kept as an error so commands
+/// that need no network (such as `config set` to fix it) still run.
+static TLS: OnceLock<std::result::Result<TlsSettings, String>> = OnceLock::new();
+
+pub fn init_tls(cfg: &Config) {
+    let settings = load_settings(&cfg.tls).map_err(|err| format!("{:#}", err));
+    if let Err(err) = &settings {
+        log_verbose("tls", err);
+    }
+    let _ = TLS.set(settings);
+}
+
+/// Reads and parses the configured certificate files.
+pub fn load_tls(tls: &TlsConfig) -> Result<()> {
+    load_settings(tls).map(|_| ())
+}
+
+fn load_settings(tls: &TlsConfig) -> Result<TlsSettings> {
+    let roots = match configured(&tls.ca_cert_path) {
+        Some(path) => {
+            let pem = read_pem(path, "tls.ca_cert_path")?;
+            let certs = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("tls.ca_cert_path {} is not a PEM bundle", path))?;
+            if certs.is_empty() {
+                return Err(anyhow!("tls.ca_cert_path {} holds no certificates", path));
+            }
+            certs
+        }
+        None => Vec::new(),
+    };
+    let identity = match (configured(&tls.client_cert), configured(&tls.client_key)) {
+        (None, None) => None,
+        (None, Some(_)) => return Err(anyhow!("tls.client_key is set without tls.client_cert")),
+        (Some(cert), key) => {
+            let mut pem = read_pem(cert, "tls.client_cert")?;
+            if let Some(key) = key {
+                pem.push(b'\n');
+                pem.extend(read_pem(key, "tls.client_key")?);
+            }
+            Some(Identity::from_pem(&pem).with_context(|| {
+                format!("tls.client_cert {} has no usable certificate and key", cert)
+            })?)
+        }
+    };
+    Ok(TlsSettings {
+        roots,
+        identity,
+        accept_invalid_certs: tls.danger_accept_invalid_certs,
+    })
+}
+
+fn configured(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn read_pem(path: &str, field: &str) -> Result<Vec<u8>> {
+    let full = expand_home(path);
+    fs::read(&full).with_context(|| format!("Failed to read {} {}", field, full.display()))
+}
+
+/// `reqwest::Client::builder()` with the `[tls]` options applied. Fails when
+/// the configured certificates could not be loaded.
+pub fn client_builder() -> Result<ClientBuilder> {
+    let mut builder = reqwest::Client::builder();
+    let Some(settings) = TLS.get() else {
+        return Ok(builder);
+    };
+    let settings = settings
+        .as_ref()
+        .map_err(|err| anyhow!("Invalid [tls] config: {}", err))?;
+    for cert in &settings.roots {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    if let Some(identity) = &settings.identity {
+        builder = builder.identity(identity.clone());
+    }
+    if settings.accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder)
+}
@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::tool_output::canonical_tool_name;
+
+/// Argument shapes shown to the model when a call does not validate.
+const TOOL_USAGE: &[(&str, &str)] = &[
+    ("shell", "{command}"),
+    ("fs.read_file", "{path, offset?, limit?}"),
+    ("fs.create_file", "{path, content, overwrite?}"),
+    ("fs.edit_file", "{path, old_str?, new_str, replace_all?}"),
+    (
+        "fs.apply_patch",
+        "{path, edits:[{old|old_str, new|new_str, replace_all?}...], strict?}",
+    ),
+    ("fs.list_files", "{path?, glob?, max_results?}"),
+    (
+        "fs.grep",
+        "{pattern, path?, glob?, ignore_case?, literal?, max_results?}",
+    ),
+    ("fs.move", "{from, to}"),
+    ("fs.delete", "{path, recursive?}"),
+    ("web.fetch", "{url}"),
+    ("http.get", "{url, headers?}"),
+    ("python", "{code, timeout_secs?}"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShellArgs {
+    #[serde(alias = "cmd")]
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReadFileArgs {
+    #[serde(alias = "file")]
+    pub path: String,
+    /// First line to return, 1-based.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    /// Number of lines to return.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateFileArgs {
+    #[serde(alias = "file")]
+    pub path: String,
+    pub content: String,
+    #[serde(default = "default_true")]
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EditFileArgs {
+    #[serde(alias = "file")]
+    pub path: String,
+    /// Empty replaces the whole file.
+    #[serde(default, alias = "old")]
+    pub old_str: String,
+    #[serde(alias = "new")]
+    pub new_str: String,
+    #[serde(default)]
+    pub replace_all: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatchEdit {
+    #[serde(alias = "old_str")]
+    pub old: String,
+    #[serde(default, alias = "new_str")]
+    pub new: String,
+    #[serde(default)]
+    pub replace_all: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApplyPatchArgs {
+    #[serde(alias = "file")]
+    pub path: String,
+    #[serde(alias = "patches")]
+    pub edits: Vec<PatchEdit>,
+    /// Abort without writing when any edit misses.
+    #[serde(default = "default_true")]
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListFilesArgs {
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrepArgs {
+    /// Regex, or plain text with `literal`.
+    #[serde(alias = "query")]
+    pub pattern: String,
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+    #[serde(default)]
+    pub ignore_case: bool,
+    #[serde(default)]
+    pub literal: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MoveArgs {
+    #[serde(alias = "src", alias = "source")]
+    pub from: String,
+    #[serde(alias = "dst", alias = "target")]
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeleteArgs {
+    #[serde(alias = "file", alias = "target")]
+    pub path: String,
+    #[serde(default, alias = "r")]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebFetchArgs {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpGetArgs {
+    pub url: String,
+    /// Extra request headers such as `Accept`; credentials are rejected.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PythonArgs {
+    #[serde(alias = "script", alias = "source")]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_path() -> String {
+    ".".to_string()
+}
+
+/// A tool call whose arguments passed validation.
+#[derive(Debug, Clone)]
+pub enum ToolCall {
+    Shell(ShellArgs),
+    ReadFile(ReadFileArgs),
+    CreateFile(CreateFileArgs),
+    EditFile(EditFileArgs),
+    ApplyPatch(ApplyPatchArgs),
+    ListFiles(ListFilesArgs),
+    Grep(GrepArgs),
+    Move(MoveArgs),
+    Delete(DeleteArgs),
+    WebFetch(WebFetchArgs),
+    HttpGet(HttpGetArgs),
+    Python(PythonArgs),
+}
+
+#[derive(Debug, Clone)]
+pub enum ToolCallError {
+    /// The model named a tool dongshan does not have; such calls are skipped.
+    Unsupported(String),
+    /// A known tool with arguments that do not match its schema.
+    Invalid { tool: String, message: String },
+}
+
+impl fmt::Display for ToolCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolCallError::Unsupported(tool) => {
+                let names: Vec<&str> = TOOL_USAGE.iter().map(|(name, _)| *name).collect();
+                write!(
+                    f,
+                    "unsupported tool: {} (available: {})",
+                    tool,
+                    names.join(", ")
+                )
+            }
+            ToolCallError::Invalid { tool, message } => {
+                write!(f, "invalid arguments for {}: {}", tool, message)?;
+                if let Some((_, usage)) = TOOL_USAGE.iter().find(|(name, _)| name == tool) {
+                    write!(f, "; expected args {}", usage)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ToolCall {
+    /// Validates `args` against the schema of `tool` (any accepted alias).
+    pub fn parse(tool: &str, args: Value) -> Result<Self, ToolCallError> {
+        let args = match args {
+            Value::Null => Value::Object(Map::new()),
+            other => other,
+        };
+        let name = canonical_tool_name(tool);
+        match name.as_str() {
+            "shell" => typed(&name, args).map(ToolCall::Shell),
+            "fs.read_file" => typed(&name, args).map(ToolCall::ReadFile),
+            "fs.create_file" => typed(&name, args).map(ToolCall::CreateFile),
+            "fs.edit_file" => typed(&name, args).map(ToolCall::EditFile),
+            "fs.apply_patch" => typed(&name, args).map(ToolCall::ApplyPatch),
+            "fs.list_files" => typed(&name, args).map(ToolCall::ListFiles),
+            "fs.grep" => typed(&name, args).map(ToolCall::Grep),
+            "fs.move" => typed(&name, args).map(ToolCall::Move),
+            "fs.delete" => typed(&name, args).map(ToolCall::Delete),
+            "web.fetch" => typed(&name, args).map(ToolCall::WebFetch),
+            "http.get" => typed(&name, args).map(ToolCall::HttpGet),
+            "python" => typed(&name, args).map(ToolCall::Python),
+            _ => Err(ToolCallError::Unsupported(tool.trim().to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToolCall::Shell(_) => "shell",
+            ToolCall::ReadFile(_) => "fs.read_file",
+            ToolCall::CreateFile(_) => "fs.create_file",
+            ToolCall::EditFile(_) => "fs.edit_file",
+            ToolCall::ApplyPatch(_) => "fs.apply_patch",
+            ToolCall::ListFiles(_) => "fs.list_files",
+            ToolCall::Grep(_) => "fs.grep",
+            ToolCall::Move(_) => "fs.move",
+            ToolCall::Delete(_) => "fs.delete",
+            ToolCall::WebFetch(_) => "web.fetch",
+            ToolCall::HttpGet(_) => "http.get",
+            ToolCall::Python(_) => "python",
+        }
+    }
+
+    /// Validated arguments, with aliases resolved to their canonical keys.
+    pub fn args_json(&self) -> Value {
+        let value = match self {
+            ToolCall::Shell(a) => serde_json::to_value(a),
+            ToolCall::ReadFile(a) => serde_json::to_value(a),
+            ToolCall::CreateFile(a) => serde_json::to_value(a),
+            ToolCall::EditFile(a) => serde_json::to_value(a),
+            ToolCall::ApplyPatch(a) => serde_json::to_value(a),
+            ToolCall::ListFiles(a) => serde_json::to_value(a),
+            ToolCall::Grep(a) => serde_json::to_value(a),
+            ToolCall::Move(a) => serde_json::to_value(a),
+            ToolCall::Delete(a) => serde_json::to_value(a),
+            ToolCall::WebFetch(a) => serde_json::to_value(a),
+            ToolCall::HttpGet(a) => serde_json::to_value(a),
+            ToolCall::Python(a) => serde_json::to_value(a),
+        };
+        value.unwrap_or(Value::Null)
+    }
+
+    /// Paths a file-writing call changes, as the model wrote them.
+    pub fn written_paths(&self) -> Vec<String> {
+        match self {
+            ToolCall::CreateFile(a) => vec![a.path.clone()],
+            ToolCall::EditFile(a) => vec![a.path.clone()],
+            ToolCall::ApplyPatch(a) => vec![a.path.clone()],
+            ToolCall::Delete(a) => vec![a.path.clone()],
+            ToolCall::Move(a) => vec![a.from.clone(), a.to.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Short progress line for read-only calls that may take a moment.
+    pub fn progress_label(&self) -> Option<String> {
+        match self {
+            ToolCall::ReadFile(a) => Some(format!("reading {}", a.path)),
+            ToolCall::ListFiles(a) => Some(format!("listing {}", a.path)),
+            ToolCall::Grep(a) => Some(format!("searching '{}' in {}", a.pattern, a.path)),
+            ToolCall::WebFetch(a) => Some(format!("fetching {}", a.url)),
+            ToolCall::HttpGet(a) => Some(format!("GET {}", a.url)),
+            _ => None,
+        }
+    }
+}
+
+fn typed<T: DeserializeOwned>(tool: &str, args: Value) -> Result<T, ToolCallError> {
+    serde_json::from_value(args).map_err(|err| ToolCallError::Invalid {
+        tool: tool.to_string(),
+        message: err.to_string(),
+    })
+}
+
+/// A call as the model named it, with its validation result.
+#[derive(Debug, Clone)]
+pub struct ParsedToolCall {
+    pub tool: String,
+    pub call: Result<ToolCall, ToolCallError>,
+}
+
+impl ParsedToolCall {
+    pub fn valid(tool: &str, call: ToolCall) -> Self {
+        Self {
+            tool: tool.to_string(),
+            call: Ok(call),
+        }
+    }
+
+    /// A native function call; `arguments` is the raw JSON string from the API.
+    pub fn from_native(name: &str, arguments: &str) -> Self {
+        let call = if arguments.trim().is_empty() {
+            ToolCall::parse(name, Value::Null)
+        } else {
+            match serde_json::from_str::<Value>(arguments) {
+                Ok(args) => ToolCall::parse(name, args),
+                Err(err) => Err(ToolCallError::Invalid {
+                    tool: canonical_tool_name(name),
+                    message: format!("arguments are not valid JSON ({err})"),
+                }),
+            }
+        };
+        Self {
+            tool: name.to_string(),
+            call,
+        }
+    }
+
+    /// One entry of a JSON `tool_calls` array: the name under `tool` (or
+    /// `type`), arguments under `args` or inline, and a shell command
+    /// optionally at the top level. `None` when the object names no tool.
+    pub fn from_json(map: &Map<String, Value>) -> Option<Self> {
+        let tool = map
+            .get("tool")
+            .or_else(|| map.get("type"))
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|t| !t.is_empty())?
+            .to_string();
+        let mut args = match map.get("args") {
+            Some(Value::Object(args)) => args.clone(),
+            Some(Value::Null) | None => map
+                .iter()
+                .filter(|(k, _)| !matches!(k.as_str(), "tool" | "type" | "command" | "cmd"))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            Some(_) => {
+                return Some(Self {
+                    call: Err(ToolCallError::Invalid {
+                        tool: canonical_tool_name(&tool),
+                        message: "args must be a JSON object".to_string(),
+                    }),
+                    tool,
+                });
+            }
+        };
+        if let Some(command) = map.get("command").or_else(|| map.get("cmd"))
+            && !args.contains_key("command")
+            && !args.contains_key("cmd")
+        {
+            args.insert("command".to_string(), command.clone());
+        }
+        Some(Self {
+            call: ToolCall::parse(&tool, Value::Object(args)),
+            tool,
+        })
+    }
+
+    /// The call as logged in session records: validated args, or the error.
+    pub fn to_json(&self) -> Value {
+        match &self.call {
+            Ok(call) => serde_json::json!({ "tool": self.tool, "args": call.args_json() }),
+            Err(err) => serde_json::json!({ "tool": self.tool, "error": err.to_string() }),
+        }
+    }
+}
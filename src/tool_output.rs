@@ -0,0 +1,143 @@
+use crate::config::{Config, config_for_model, utility_model};
+use crate::i18n::t;
+use crate::llm::call_llm;
+use crate::logging::log_verbose;
+use crate::util::{WorkingStatus, prefix_chars, truncate_with_suffix};
+
+/// Omitted output sent to the summary model is clipped to this many chars.
+const MAX_SUMMARY_INPUT_CHARS: usize = 40_000;
+
+/// Budget for `tool`: its entry in `tool_output_limits`, else
+/// `tool_output_max_chars`. 0 means unlimited.
+pub fn tool_output_budget(cfg: &Config, tool: &str) -> usize {
+    let name = canonical_tool_name(tool);
+    cfg.tool_output_limits
+        .get(&name)
+        .copied()
+        .unwrap_or(cfg.tool_output_max_chars)
+}
+
+/// Maps the aliases the dispatcher accepts (`fs_read_file`, `run_command`,
+/// `fetch`) to the dotted names used as config keys.
+pub fn canonical_tool_name(tool: &str) -> String {
+    let tool = tool.trim().to_ascii_lowercase();
+    match tool.as_str() {
+        "run_command" => "shell".to_string(),
+        "fetch" | "web_fetch" => "web.fetch".to_string(),
+        "http_get" => "http.get".to_string(),
+        "py" | "python3" | "run_python" => "python".to_string(),
+        "fs.read" | "fs_read" => "fs.read_file".to_string(),
+        "fs.list" | "fs_list" => "fs.list_files".to_string(),
+        _ => match tool.strip_prefix("fs_") {
+            Some(rest) => format!("fs.{rest}"),
+            None => tool,
+        },
+    }
+}
+
+/// Fits `text` into the tool's budget by keeping the first and last lines and
+/// replacing the middle with "[N lines omitted: ...]". The note summarizes the
+/// omitted part when `summary_model` or `utility_model` is configured.
+pub fn fit_tool_output(cfg: &Config, tool: &str, text: &str) -> String {
+    let budget = tool_output_budget(cfg, tool);
+    if budget == 0 || text.chars().count() <= budget {
+        return text.to_string();
+    }
+    let head_budget = budget * 6 / 10;
+    let tail_budget = budget * 3 / 10;
+    let note_budget = (budget - head_budget - tail_budget).max(200);
+
+    let lines: Vec<&str> = text.lines().collect();
+    let head_count = lines_within(lines.iter().copied(), head_budget);
+    let tail_count =
+        lines_within(lines.iter().rev().copied(), tail_budget).min(lines.len() - head_count);
+    let omitted = lines.len() - head_count - tail_count;
+    if head_count == 0 || omitted == 0 {
+        // A few very long lines: elide by characters instead.
+        let total = text.chars().count();
+        let head = prefix_chars(text, head_budget);
+        let tail: String = text.chars().skip(total - tail_budget).collect();
+        let middle: String = text
+            .chars()
+            .skip(head_budget)
+            .take(total - head_budget - tail_budget)
+            .collect();
+        let note = omission_note(
+            cfg,
+            tool,
+            &format!("{} chars", total - head_budget - tail_budget),
+            &middle,
+            note_budget,
+        );
+        return format!("{head}\n{note}\n{tail}");
+    }
+
+    let head = lines[..head_count].join("\n");
+    let tail = lines[lines.len() - tail_count..].join("\n");
+    let middle = lines[head_count..lines.len() - tail_count].join("\n");
+    let note = omission_note(cfg, tool, &format!("{omitted} lines"), &middle, note_budget);
+    if tail.is_empty() {
+        format!("{head}\n{note}")
+    } else {
+        format!("{head}\n{note}\n{tail}")
+    }
+}
+
+/// Number of leading items whose lengths (plus newlines) fit in `budget` chars.
+fn lines_within<'a>(lines: impl Iterator<Item = &'a str>, budget: usize) -> usize {
+    let mut used = 0usize;
+    let mut count = 0usize;
+    for line in lines {
+        used += line.chars().count() + 1;
+        if used > budget {
+            break;
+        }
+        count += 1;
+    }
+    count
+}
+
+fn omission_note(
+    cfg: &Config,
+    tool: &str,
+    amount: &str,
+    omitted: &str,
+    max_chars: usize,
+) -> String {
+    match summarize_omitted(cfg, tool, omitted) {
+        Some(summary) => format!(
+            "[{} omitted: {}]",
+            amount,
+            truncate_with_suffix(summary.trim(), max_chars, " ...")
+        ),
+        None => format!("[{amount} omitted]"),
+    }
+}
+
+/// Summary of the omitted output from `summary_model` (or `utility_model`);
+/// `None` when neither is configured or the call fails.
+fn summarize_omitted(cfg: &Config, tool: &str, omitted: &str) -> Option<String> {
+    let model = utility_model(cfg, cfg.summary_model.as_deref())?;
+    let summary_cfg = config_for_model(cfg, model);
+    let system = "You summarize the middle part of a tool output that was cut to fit a coding agent's context.\n\
+        In at most 5 short lines, report errors, warnings, failing tests, file paths and counts it contains. \
+        Reply \"routine output\" if nothing stands out. No preamble.";
+    let user = format!(
+        "Tool: {}\nOmitted output:\n{}",
+        tool,
+        truncate_with_suffix(omitted, MAX_SUMMARY_INPUT_CHARS, "\n...[clipped]")
+    );
+    let working = WorkingStatus::start(t("status.summarizing_output"));
+    let res = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(call_llm(&summary_cfg, system, &user))
+    });
+    working.finish();
+    match res {
+        Ok(summary) if !summary.trim().is_empty() => Some(summary.trim().to_string()),
+        Ok(_) => None,
+        Err(err) => {
+            log_verbose("tool-output", &format!("summary failed: {err}"));
+            None
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+
+use crate::change_diff::render_change_diff;
+use crate::config::{Config, config_dir};
+use crate::diagnostics::now_unix_ts;
+use crate::storage::storage;
+use crate::util::truncate_with_suffix;
+
+/// Large payloads (file contents, command output) are clipped to keep logs usable.
+const MAX_FIELD_CHARS: usize = 20_000;
+
+static TRANSCRIPT: OnceLock<Mutex<File>> = OnceLock::new();
+static COMMAND: OnceLock<String> = OnceLock::new();
+/// Chat or agent session that later entries belong to.
+static SESSION: Mutex<String> = Mutex::new(String::new());
+
+pub fn logs_dir() -> Result<PathBuf> {
+    Ok(config_dir()?.join("logs"))
+}
+
+/// Opens today's transcript file when `transcript_log` is enabled. Every later
+/// `record_*` call is a no-op otherwise.
+pub fn init_transcript(cfg: &Config, command: &str) -> Result<()> {
+    if !cfg.transcript_log {
+        return Ok(());
+    }
+    let dir = logs_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("transcript-{}.jsonl", utc_date(now_unix_ts())));
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open transcript {}", path.display()))?;
+    let _ = TRANSCRIPT.set(Mutex::new(file));
+    let _ = COMMAND.set(command.to_string());
+    record("start", json!({ "cwd": std::env::current_dir().ok() }));
+    Ok(())
+}
+
+pub fn transcript_enabled() -> bool {
+    TRANSCRIPT.get().is_some()
+}
+
+/// Tags later entries with `session` (called when a session starts or is switched).
+pub fn set_transcript_session(session: &str) {
+    if let Ok(mut current) = SESSION.lock() {
+        *current = session.to_string();
+    }
+}
+
+/// Appends one JSON line `{ts, pid, command, session?, kind, ...data}`.
+pub fn record(kind: &str, data: Value) {
+    let Some(file) = TRANSCRIPT.get() else {
+        return;
+    };
+    let mut entry = json!({
+        "ts": now_unix_ts(),
+        "pid": std::process::id(),
+        "command": COMMAND.get().map(String::as_str).unwrap_or(""),
+        "kind": kind,
+    });
+    if let Some(obj) = entry.as_object_mut() {
+        if let Ok(session) = SESSION.lock()
+            && !session.is_empty()
+        {
+            obj.insert("session".to_string(), json!(*session));
+        }
+        if let Value::Object(extra) = data {
+            obj.extend(extra);
+        }
+    }
+    if let Ok(mut f) = file.lock() {
+        let _ = writeln!(f, "{}", entry);
+    }
+    let _ = storage().record_event(&entry);
+}
+
+pub fn record_request(url: &str, body: &Value) {
+    if transcript_enabled() {
+        record("request", json!({ "url": url, "body": body }));
+    }
+}
+
+pub fn record_response(model: &str, content: &str) {
+    if transcript_enabled() {
+        record(
+            "response",
+            json!({ "model": model, "content": clip(content) }),
+        );
+    }
+}
+
+pub fn record_tool_call(tool: &str, args: &Value, result: &Result<String>) {
+    if !transcript_enabled() {
+        return;
+    }
+    let (ok, output) = match result {
+        Ok(v) => (true, clip(v)),
+        Err(err) => (false, clip(&err.to_string())),
+    };
+    record(
+        "tool_call",
+        json!({ "tool": tool, "args": args, "ok": ok, "output": output }),
+    );
+}
+
+pub fn record_command(command: &str, exit_code: Option<i32>, output: &str) {
+    if transcript_enabled() {
+        record(
+            "command",
+            json!({ "cmd": command, "exit_code": exit_code, "output": clip(output) }),
+        );
+    }
+}
+
+pub fn record_file_write(op: &str, path: &Path, bytes: Option<usize>) {
+    if transcript_enabled() {
+        record(
+            "file_write",
+            json!({ "op": op, "path": path.display().to_string(), "bytes": bytes }),
+        );
+    }
+}
+
+/// Like [`record_file_write`], with the diff from `before` to `after` so
+/// `dongshan history show` can print the exact change.
+pub fn record_file_change(op: &str, path: &Path, before: &str, after: &str) {
+    if transcript_enabled() {
+        record(
+            "file_write",
+            json!({
+                "op": op,
+                "path": path.display().to_string(),
+                "bytes": after.len(),
+                "diff": clip(&render_change_diff(before, after)),
+            }),
+        );
+    }
+}
+
+/// Summary of a finished `dongshan agent` run.
+pub fn record_agent_run(
+    task: &str,
+    files: &[String],
+    verification: Option<&str>,
+    error: Option<&str>,
+) {
+    if transcript_enabled() {
+        record(
+            "agent_run",
+            json!({
+                "task": clip(task),
+                "files": files,
+                "verification": verification,
+                "error": error,
+            }),
+        );
+    }
+}
+
+fn clip(text: &str) -> String {
+    truncate_with_suffix(text, MAX_FIELD_CHARS, "...[clipped]")
+}
+
+/// Transcript files sorted oldest first.
+pub fn list_transcripts() -> Result<Vec<PathBuf>> {
+    let dir = logs_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let is_transcript = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("transcript-") && n.ends_with(".jsonl"));
+        if is_transcript {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `YYYY-MM-DD` in UTC for a unix timestamp.
+pub fn utc_date(ts: u64) -> String {
+    let (y, m, d) = civil_from_days((ts / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// `HH:MM:SS` in UTC for a unix timestamp.
+pub fn utc_time(ts: u64) -> String {
+    let secs = ts % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+// Howard Hinnant's days-to-civil conversion.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::approval::{NetworkAccess, approval_level, approval_policy};
+use crate::ci::is_ci;
+use crate::config::{ApprovalLevel, AutoExecMode, Config, config_dir};
+use crate::diagnostics::now_unix_ts;
+use crate::i18n::{t, tf};
+use crate::util::{ask, color_dim, color_yellow, tagged_prompt};
+
+/// One recorded decision; a directory inherits the decision of its nearest
+/// recorded ancestor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustEntry {
+    pub trusted: bool,
+    pub ts: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TrustStore {
+    #[serde(default)]
+    workspaces: BTreeMap<String, TrustEntry>,
+}
+
+fn trust_file() -> Result<PathBuf> {
+    Ok(config_dir()?.join("trust.json"))
+}
+
+fn load_store() -> Result<TrustStore> {
+    let path = trust_file()?;
+    if !path.exists() {
+        return Ok(TrustStore::default());
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Invalid trust file: {}", path.display()))
+}
+
+fn save_store(store: &TrustStore) -> Result<()> {
+    let path = trust_file()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let text = serde_json::to_string_pretty(store)?;
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn workspace_key(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Recorded decision for `path` or its nearest recorded ancestor.
+pub fn workspace_trust(path: &Path) -> Option<bool> {
+    let store = load_store().ok()?;
+    let key = PathBuf::from(workspace_key(path));
+    key.ancestors()
+        .find_map(|dir| store.workspaces.get(dir.to_string_lossy().as_ref()))
+        .map(|e| e.trusted)
+}
+
+pub fn set_workspace_trust(path: &Path, trusted: bool) -> Result<()> {
+    let mut store = load_store()?;
+    store.workspaces.insert(
+        workspace_key(path),
+        TrustEntry {
+            trusted,
+            ts: now_unix_ts(),
+        },
+    );
+    save_store(&store)
+}
+
+/// Forgets the decision recorded for exactly `path`; returns whether one existed.
+pub fn remove_workspace_trust(path: &Path) -> Result<bool> {
+    let mut store = load_store()?;
+    let removed = store.workspaces.remove(&workspace_key(path)).is_some();
+    if removed {
+        save_store(&store)?;
+    }
+    Ok(removed)
+}
+
+pub fn list_workspace_trust() -> Result<Vec<(String, TrustEntry)>> {
+    Ok(load_store()?.workspaces.into_iter().collect())
+}
+
+pub fn is_workspace_trusted() -> bool {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| workspace_trust(&cwd))
+        .unwrap_or(false)
+}
+
+/// Whether the agent may run in the current directory. Asks once per new
+/// workspace; without a terminal nothing is recorded and the answer is no.
+pub fn ensure_workspace_trust(cfg: &Config) -> Result<bool> {
+    let cwd = std::env::current_dir().context("Failed to resolve current directory")?;
+    if let Some(trusted) = workspace_trust(&cwd) {
+        return Ok(trusted);
+    }
+    // `agent --ci` is an explicit request to work here; it trusts the
+    // workspace for this run without recording a decision.
+    if is_ci() {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        println!(
+            "{}",
+            color_yellow(&tf("trust.noninteractive", &[&cwd.display()]))
+        );
+        return Ok(false);
+    }
+    prompt_workspace_trust(cfg)
+}
+
+/// Shows the auto-exec policy that applies here, asks, and records the answer.
+pub fn prompt_workspace_trust(cfg: &Config) -> Result<bool> {
+    let cwd = std::env::current_dir().context("Failed to resolve current directory")?;
+    println!("{}", tf("trust.header", &[&cwd.display()]));
+    for line in policy_summary(cfg) {
+        println!("  - {}", line);
+    }
+    let input = ask(&tagged_prompt("trust", t("trust.confirm")))?;
+    let trusted = input.trim().eq_ignore_ascii_case("y");
+    set_workspace_trust(&cwd, trusted)?;
+    if !trusted {
+        println!("{}", color_dim(t("trust.denied")));
+    }
+    Ok(trusted)
+}
+
+fn policy_summary(cfg: &Config) -> Vec<String> {
+    let policy = approval_policy(cfg);
+    let mut lines = Vec::new();
+    if approval_level() != ApprovalLevel::Custom {
+        lines.push(format!("approval preset: {}", approval_level().as_str()));
+    }
+    lines.push(match policy.exec_mode {
+        AutoExecMode::Safe if !policy.file_writes => {
+            "shell: read-only commands (ls, cat, rg, git status/diff/log, ...) run, anything else asks"
+                .to_string()
+        }
+        AutoExecMode::Safe => {
+            "shell: only read-only commands (ls, cat, rg, grep, git status/diff/log, ...)"
+                .to_string()
+        }
+        AutoExecMode::All => "shell: any command (auto_exec_mode = all)".to_string(),
+        AutoExecMode::Custom => format!(
+            "shell: commands starting with {}",
+            join_or_none(&cfg.auto_exec_allow)
+        ),
+    });
+    if !cfg.auto_exec_deny.is_empty() {
+        lines.push(format!("never: {}", join_or_none(&cfg.auto_exec_deny)));
+    }
+    lines.push(if policy.confirm_exec {
+        format!(
+            "each command asks for confirmation, except {}",
+            join_or_none(&cfg.auto_exec_trusted)
+        )
+    } else {
+        "commands run without confirmation (auto_confirm_exec = false)".to_string()
+    });
+    lines.push(if !policy.file_writes {
+        "file tools: read only, no writes or python (approval = read-only)".to_string()
+    } else if cfg.write_jail {
+        "file tools: create, edit, move and delete files inside this directory".to_string()
+    } else {
+        "file tools: create, edit, move and delete files anywhere (write_jail = false)".to_string()
+    });
+    let network = match policy.network {
+        NetworkAccess::Allow => None,
+        NetworkAccess::Ask => Some("always ask"),
+        NetworkAccess::Block => Some("and the web.fetch/http.get tools are blocked"),
+    };
+    if let Some(rule) = network {
+        lines.push(format!(
+            "network commands (curl, git push, npm install, ...) {rule}"
+        ));
+    }
+    lines
+}
+
+fn join_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        "(none)".to_string()
+    } else {
+        items.join(", ")
+    }
+}
@@ -1,21 +1,36 @@
+use std::cmp::Ordering;
 use std::fs;
+use std::process::Command;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::config::{Config, config_dir};
+use crate::config::{Config, UpdateChannel, config_dir};
+use crate::tls::client_builder;
+use crate::util::truncate_with_suffix;
 
 const REPO_OWNER: &str = "KonshinHaoshin";
 const REPO_NAME: &str = "dongshan-cli";
 const CHECK_INTERVAL_SECS: u64 = 60 * 60 * 24;
+const MAX_RELEASE_NOTE_LINES: usize = 12;
+const MAX_RELEASE_NOTE_LINE_CHARS: usize = 120;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 struct UpdateState {
     last_check_unix: u64,
     last_seen_remote: Option<String>,
+    #[serde(default)]
+    last_release_notes: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteRelease {
+    pub version: String,
+    pub tag: String,
+    pub notes: Option<String>,
 }
 
 pub async fn maybe_check_update(cfg: &Config) -> Result<()> {
@@ -29,8 +44,8 @@ pub async fn maybe_check_update(cfg: &Config) -> Result<()> {
         return Ok(());
     }
 
-    let current = env!("CARGO_PKG_VERSION");
-    let latest = match fetch_latest_version().await {
+    let current = current_version();
+    let latest = match fetch_latest_release(cfg.update_channel).await {
         Ok(v) => v,
         Err(_) => {
             state.last_check_unix = now;
@@ -40,31 +55,58 @@ pub async fn maybe_check_update(cfg: &Config) -> Result<()> {
     };
 
     state.last_check_unix = now;
-    state.last_seen_remote = Some(latest.clone());
+    state.last_seen_remote = Some(latest.version.clone());
+    state.last_release_notes = latest.notes.clone();
     save_state(&state)?;
 
-    if is_remote_newer(current, &latest) {
-        println!("Update available: {} -> {}", current, latest);
-        println!(
-            "Release page: https://github.com/{}/{}/releases/latest",
-            REPO_OWNER, REPO_NAME
-        );
-        if cfg!(target_os = "windows") {
-            println!("Windows installer asset: dongshan-setup-windows-x86_64.exe");
-            println!("Portable zip asset: dongshan-windows-x86_64.zip");
-        } else {
-            println!(
-                "Or run: cargo install --git https://github.com/{}/{} --force",
-                REPO_OWNER, REPO_NAME
-            );
-        }
+    if is_remote_newer(current, &latest.version) {
+        print_update_available(current, &latest, cfg.update_channel);
     }
 
     Ok(())
 }
 
-async fn fetch_latest_version() -> Result<String> {
-    let client = Client::new();
+pub fn current_version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+pub fn print_update_available(current: &str, latest: &RemoteRelease, channel: UpdateChannel) {
+    println!(
+        "Update available ({}): {} -> {}",
+        channel.as_str(),
+        current,
+        latest.version
+    );
+    if let Some(notes) = &latest.notes {
+        println!("What's new:");
+        println!("{}", notes);
+    }
+    println!("Release page: {}", release_page_url(latest));
+    if cfg!(target_os = "windows") {
+        println!("Windows installer asset: dongshan-setup-windows-x86_64.exe");
+        println!("Portable zip asset: dongshan-windows-x86_64.zip");
+    } else {
+        println!("Or run: dongshan update");
+    }
+}
+
+pub fn release_page_url(release: &RemoteRelease) -> String {
+    format!(
+        "https://github.com/{}/{}/releases/tag/{}",
+        REPO_OWNER, REPO_NAME, release.tag
+    )
+}
+
+pub async fn fetch_latest_release(channel: UpdateChannel) -> Result<RemoteRelease> {
+    let client = client_builder()?.build()?;
+    if channel != UpdateChannel::Stable {
+        let releases_url = format!(
+            "https://api.github.com/repos/{}/{}/releases?per_page=30",
+            REPO_OWNER, REPO_NAME
+        );
+        return fetch_release_for_channel(&client, &releases_url, channel).await;
+    }
+
     let latest_release_url = format!(
         "https://api.github.com/repos/{}/{}/releases/latest",
         REPO_OWNER, REPO_NAME
@@ -77,10 +119,118 @@ async fn fetch_latest_version() -> Result<String> {
     if let Ok(v) = fetch_release_latest(&client, &latest_release_url).await {
         return Ok(v);
     }
-    fetch_tag_latest(&client, &latest_tag_url).await
+    let tag = fetch_tag_latest(&client, &latest_tag_url).await?;
+    Ok(RemoteRelease {
+        version: normalize_version(&tag),
+        tag,
+        notes: None,
+    })
 }
 
-async fn fetch_release_latest(client: &Client, url: &str) -> Result<String> {
+/// Install the given release in place. Non-Windows builds go through
+/// `cargo install`; Windows users are pointed at the installer asset.
+pub fn install_release(release: &RemoteRelease) -> Result<()> {
+    if cfg!(target_os = "windows") {
+        println!("Download the installer from: {}", release_page_url(release));
+        println!("Asset: dongshan-setup-windows-x86_64.exe");
+        return Ok(());
+    }
+    let repo_url = format!("https://github.com/{}/{}", REPO_OWNER, REPO_NAME);
+    println!(
+        "Running: cargo install --git {} --tag {} --force",
+        repo_url, release.tag
+    );
+    let status = Command::new("cargo")
+        .args([
+            "install",
+            "--git",
+            &repo_url,
+            "--tag",
+            &release.tag,
+            "--force",
+        ])
+        .status()
+        .context("Failed to run cargo install (is cargo on PATH?)")?;
+    if !status.success() {
+        bail!("cargo install exited with {}", status);
+    }
+    println!("Installed dongshan {}", release.version);
+    Ok(())
+}
+
+async fn fetch_release_for_channel(
+    client: &Client,
+    url: &str,
+    channel: UpdateChannel,
+) -> Result<RemoteRelease> {
+    let resp = client
+        .get(url)
+        .header("User-Agent", "dongshan-cli-update-checker")
+        .send()
+        .await
+        .context("request releases failed")?;
+    if !resp.status().is_success() {
+        bail!("releases status {}", resp.status());
+    }
+    let v: Value = resp.json().await.context("invalid releases json")?;
+    let mut best: Option<RemoteRelease> = None;
+    for item in v.as_array().map(|a| a.as_slice()).unwrap_or_default() {
+        if item.get("draft").and_then(|x| x.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        let tag = item
+            .get("tag_name")
+            .and_then(|x| x.as_str())
+            .unwrap_or_default()
+            .trim();
+        if tag.is_empty() {
+            continue;
+        }
+        let prerelease = item
+            .get("prerelease")
+            .and_then(|x| x.as_bool())
+            .unwrap_or(false);
+        let version = normalize_version(tag);
+        if !channel_accepts(channel, &version, prerelease) {
+            continue;
+        }
+        if best
+            .as_ref()
+            .is_some_and(|b| !is_remote_newer(&b.version, &version))
+        {
+            continue;
+        }
+        best = Some(RemoteRelease {
+            version,
+            tag: tag.to_string(),
+            notes: item
+                .get("body")
+                .and_then(|x| x.as_str())
+                .and_then(condense_release_notes),
+        });
+    }
+    best.with_context(|| format!("no release found for channel {}", channel.as_str()))
+}
+
+fn channel_accepts(channel: UpdateChannel, version: &str, prerelease: bool) -> bool {
+    let suffix = version
+        .split_once('-')
+        .map(|(_, s)| s.to_ascii_lowercase())
+        .unwrap_or_default();
+    let is_stable = !prerelease && suffix.is_empty();
+    match channel {
+        UpdateChannel::Stable => is_stable,
+        UpdateChannel::Beta => {
+            is_stable
+                || suffix.starts_with("beta")
+                || suffix.starts_with("rc")
+                || (prerelease && suffix.is_empty())
+        }
+        UpdateChannel::Nightly => true,
+    }
+}
+
+async fn fetch_release_latest(client: &Client, url: &str) -> Result<RemoteRelease> {
     let resp = client
         .get(url)
         .header("User-Agent", "dongshan-cli-update-checker")
@@ -99,7 +249,15 @@ async fn fetch_release_latest(client: &Client, url: &str) -> Result<String> {
     if tag.is_empty() {
         anyhow::bail!("missing tag_name");
     }
-    Ok(normalize_version(tag))
+    let notes = v
+        .get("body")
+        .and_then(|x| x.as_str())
+        .and_then(condense_release_notes);
+    Ok(RemoteRelease {
+        version: normalize_version(tag),
+        tag: tag.to_string(),
+        notes,
+    })
 }
 
 async fn fetch_tag_latest(client: &Client, url: &str) -> Result<String> {
@@ -123,7 +281,40 @@ async fn fetch_tag_latest(client: &Client, url: &str) -> Result<String> {
     if tag.is_empty() {
         anyhow::bail!("missing tag name");
     }
-    Ok(normalize_version(tag))
+    Ok(tag.to_string())
+}
+
+fn condense_release_notes(body: &str) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut total = 0usize;
+    for raw in body.lines() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with("<!--") {
+            continue;
+        }
+        total += 1;
+        if lines.len() >= MAX_RELEASE_NOTE_LINES {
+            continue;
+        }
+        let text = line.trim_start_matches('#').trim();
+        let text = text
+            .strip_prefix("- ")
+            .or_else(|| text.strip_prefix("* "))
+            .map(|rest| format!("  - {}", rest.trim()))
+            .unwrap_or_else(|| text.to_string());
+        lines.push(truncate_with_suffix(
+            &text,
+            MAX_RELEASE_NOTE_LINE_CHARS,
+            "...",
+        ));
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    if total > lines.len() {
+        lines.push(format!("  ... ({} more lines)", total - lines.len()));
+    }
+    Some(lines.join("\n"))
 }
 
 fn normalize_version(v: &str) -> String {
@@ -139,8 +330,47 @@ fn parse_version(v: &str) -> (u64, u64, u64) {
     (major, minor, patch)
 }
 
-fn is_remote_newer(current: &str, remote: &str) -> bool {
-    parse_version(remote) > parse_version(current)
+pub fn is_remote_newer(current: &str, remote: &str) -> bool {
+    let (remote_core, current_core) = (parse_version(remote), parse_version(current));
+    if remote_core != current_core {
+        return remote_core > current_core;
+    }
+    // Same core version: a final release outranks its pre-releases.
+    match (pre_release_suffix(current), pre_release_suffix(remote)) {
+        (Some(_), None) => true,
+        (Some(c), Some(r)) => compare_pre_release(r, c) == Ordering::Greater,
+        _ => false,
+    }
+}
+
+/// Orders pre-release suffixes like semver: dot-separated identifiers are
+/// compared in turn, numeric ones as numbers and below alphanumeric ones, and
+/// a shorter list ranks lower when it is a prefix of the other
+/// (`beta.9` < `beta.10` < `beta.10.1`).
+fn compare_pre_release(a: &str, b: &str) -> Ordering {
+    let mut a_ids = a.split('.');
+    let mut b_ids = b.split('.');
+    loop {
+        let (a_id, b_id) = match (a_ids.next(), b_ids.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_id), Some(b_id)) => (a_id, b_id),
+        };
+        let order = match (a_id.parse::<u64>(), b_id.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a_id.cmp(b_id),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+fn pre_release_suffix(v: &str) -> Option<&str> {
+    v.split_once('-').map(|(_, s)| s)
 }
 
 fn now_unix() -> u64 {
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{Config, prompt_name};
+use crate::diagnostics::now_unix_ts;
+use crate::i18n::tf;
+use crate::storage::storage;
+use crate::util::{ask, tagged_prompt};
+
+/// Approximate list prices in USD per 1M tokens (input, output), matched by
+/// substring of the model name; longer keys win. `model_prices` in config
+/// overrides these.
+const BUILTIN_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("o3-mini", 1.10, 4.40),
+    ("o4-mini", 1.10, 4.40),
+    ("deepseek-chat", 0.27, 1.10),
+    ("deepseek-reasoner", 0.55, 2.19),
+    ("grok-code-fast", 0.20, 1.50),
+    ("grok-4-fast", 0.20, 0.50),
+    ("grok-4.1-fast", 0.20, 0.50),
+    ("grok-4", 3.00, 15.00),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-haiku", 1.00, 5.00),
+    ("claude-sonnet", 3.00, 15.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-opus", 15.00, 75.00),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    /// True when the provider reported no usage and counts were estimated from text length.
+    pub estimated: bool,
+}
+
+/// Rough token count of ~4 bytes per token, used wherever exact counts are unknown.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.len() as u64).div_ceil(4)
+}
+
+impl TokenUsage {
+    /// Reads an OpenAI-style `usage` object from a response or stream chunk.
+    pub fn from_response(val: &Value) -> Option<Self> {
+        let usage = val.get("usage").filter(|u| u.is_object())?;
+        let num = |v: Option<&Value>| v.and_then(|x| x.as_u64()).unwrap_or(0);
+        let cached = num(usage
+            .get("prompt_tokens_details")
+            .and_then(|d| d.get("cached_tokens")))
+        .max(num(usage.get("cache_read_input_tokens")));
+        Some(Self {
+            prompt_tokens: num(usage.get("prompt_tokens")),
+            completion_tokens: num(usage.get("completion_tokens")),
+            cached_tokens: cached,
+            estimated: false,
+        })
+    }
+
+    /// Rough fallback when the provider reports no usage.
+    pub fn estimate(prompt: &str, completion: &str) -> Self {
+        Self {
+            prompt_tokens: estimate_tokens(prompt),
+            completion_tokens: estimate_tokens(completion),
+            cached_tokens: 0,
+            estimated: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// USD per 1M input tokens
+    pub input: f64,
+    /// USD per 1M output tokens
+    pub output: f64,
+    /// USD per 1M cached input tokens (defaults to `input`)
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub ts: u64,
+    pub command: String,
+    pub model: String,
+    pub prompt: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub cached_tokens: u64,
+    #[serde(default)]
+    pub estimated: bool,
+    pub cost_usd: f64,
+}
+
+static COMMAND: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug, Default)]
+struct Spend {
+    /// Estimated USD spent since the current chat session or agent run started.
+    spent: f64,
+    /// Spend level the user already agreed to go past; 0 until the first confirmation.
+    approved: f64,
+    /// Per-turn breakdown of `spent`, oldest first.
+    turns: Vec<TurnSpend>,
+}
+
+static SPEND: Mutex<Spend> = Mutex::new(Spend {
+    spent: 0.0,
+    approved: 0.0,
+    turns: Vec::new(),
+});
+
+/// Usage of one chat turn (one line of user input), summed over its requests.
+#[derive(Debug, Default, Clone)]
+pub struct TurnSpend {
+    pub label: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    /// True when any request of the turn had estimated token counts.
+    pub estimated: bool,
+    pub cost_usd: f64,
+}
+
+pub fn init_usage(command: &str) {
+    let _ = COMMAND.set(command.to_string());
+}
+
+pub fn model_price(cfg: &Config, model: &str) -> Option<ModelPrice> {
+    if let Some(p) = cfg.model_prices.get(model) {
+        return Some(p.clone());
+    }
+    let lower = model.to_ascii_lowercase();
+    BUILTIN_PRICES
+        .iter()
+        .filter(|(key, _, _)| lower.contains(key))
+        .max_by_key(|(key, _, _)| key.len())
+        .map(|(_, input, output)| ModelPrice {
+            input: *input,
+            output: *output,
+            cached_input: None,
+        })
+}
+
+/// Estimated USD cost of one request; 0 when the model has no known price.
+pub fn estimate_cost(cfg: &Config, model: &str, usage: &TokenUsage) -> f64 {
+    let Some(price) = model_price(cfg, model) else {
+        return 0.0;
+    };
+    let cached = usage.cached_tokens.min(usage.prompt_tokens);
+    let uncached = usage.prompt_tokens - cached;
+    (uncached as f64 * price.input
+        + cached as f64 * price.cached_input.unwrap_or(price.input)
+        + usage.completion_tokens as f64 * price.output)
+        / 1_000_000.0
+}
+
+/// Stores one usage record (`usage.jsonl` or the SQLite `usage` table) and
+/// returns its estimated cost.
+pub fn record_usage(cfg: &Config, model: &str, usage: &TokenUsage) -> f64 {
+    let cost = estimate_cost(cfg, model, usage);
+    if let Ok(mut spend) = SPEND.lock() {
+        spend.spent += cost;
+        if spend.turns.is_empty() {
+            spend.turns.push(TurnSpend::default());
+        }
+        if let Some(turn) = spend.turns.last_mut() {
+            turn.requests += 1;
+            turn.prompt_tokens += usage.prompt_tokens;
+            turn.completion_tokens += usage.completion_tokens;
+            turn.cached_tokens += usage.cached_tokens;
+            turn.estimated |= usage.estimated;
+            turn.cost_usd += cost;
+        }
+    }
+    let record = UsageRecord {
+        ts: now_unix_ts(),
+        command: COMMAND.get().cloned().unwrap_or_default(),
+        model: model.to_string(),
+        prompt: prompt_name(cfg).to_string(),
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        cached_tokens: usage.cached_tokens,
+        estimated: usage.estimated,
+        cost_usd: cost,
+    };
+    let _ = storage().append_usage(&record);
+    cost
+}
+
+/// Starts a new turn in the per-turn breakdown; a previous turn that made no
+/// requests (e.g. a local slash command) is replaced.
+pub fn begin_usage_turn(label: &str) {
+    if let Ok(mut spend) = SPEND.lock() {
+        if spend.turns.last().is_some_and(|t| t.requests == 0) {
+            spend.turns.pop();
+        }
+        spend.turns.push(TurnSpend {
+            label: label.to_string(),
+            ..TurnSpend::default()
+        });
+    }
+}
+
+/// Turns recorded since tracking last started, oldest first.
+pub fn tracked_turns() -> Vec<TurnSpend> {
+    SPEND
+        .lock()
+        .map(|s| s.turns.iter().filter(|t| t.requests > 0).cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Starts budget tracking over, e.g. when chat switches to another session.
+pub fn reset_tracked_spend() {
+    if let Ok(mut spend) = SPEND.lock() {
+        *spend = Spend::default();
+    }
+}
+
+pub fn apply_budget_override(cfg: &mut Config, budget: Option<f64>) -> Result<()> {
+    if let Some(v) = budget {
+        if v < 0.0 {
+            bail!("--budget must be >= 0");
+        }
+        cfg.cost_budget_usd = v;
+    }
+    Ok(())
+}
+
+/// Called before each provider request. Once tracked spend reaches
+/// `cost_budget_usd`, asks whether to continue for another budget's worth;
+/// declining (or running without a terminal) stops with an error.
+/// The user declined to raise the cost budget. Chat skips the turn on it;
+/// everywhere else it ends the run like any other error.
+#[derive(Debug)]
+pub struct BudgetDeclined {
+    budget: f64,
+}
+
+impl fmt::Display for BudgetDeclined {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            tf("budget.stopped", &[&format!("{:.2}", self.budget)])
+        )
+    }
+}
+
+impl std::error::Error for BudgetDeclined {}
+
+pub fn is_budget_declined(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<BudgetDeclined>())
+}
+
+pub fn check_budget(cfg: &Config) -> Result<()> {
+    let budget = cfg.cost_budget_usd;
+    if budget <= 0.0 {
+        return Ok(());
+    }
+    let (spent, approved) = SPEND
+        .lock()
+        .map(|s| (s.spent, s.approved))
+        .unwrap_or((0.0, 0.0));
+    let limit = budget.max(approved);
+    if spent < limit {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        bail!(
+            "Cost budget exceeded: spent ~${:.4} of ${:.2}. Raise it with --budget or `dongshan config set --cost-budget-usd`.",
+            spent,
+            budget
+        );
+    }
+    println!();
+    let input = ask(&tagged_prompt(
+        "budget",
+        &tf(
+            "budget.confirm",
+            &[
+                &format!("{:.4}", spent),
+                &format!("{:.2}", budget),
+                &format!("{:.2}", budget),
+            ],
+        ),
+    ))?;
+    if !input.trim().eq_ignore_ascii_case("y") {
+        return Err(BudgetDeclined { budget }.into());
+    }
+    if let Ok(mut spend) = SPEND.lock() {
+        spend.approved = spent + budget;
+    }
+    Ok(())
+}
+
+pub fn load_usage_records(since_ts: Option<u64>) -> Result<Vec<UsageRecord>> {
+    storage().load_usage(since_ts)
+}
+
+/// Parses durations like `30m`, `24h`, `7d`, `2w`; `all` means no limit.
+pub fn parse_since(raw: &str) -> Result<Option<u64>> {
+    let raw = raw.trim().to_ascii_lowercase();
+    if raw.is_empty() || raw == "all" {
+        return Ok(None);
+    }
+    let unit = raw.chars().last().unwrap_or_default();
+    let Ok(n) = raw[..raw.len() - unit.len_utf8()].parse::<u64>() else {
+        bail!(
+            "Invalid duration '{}': use e.g. 30m, 24h, 7d, 2w or all",
+            raw
+        );
+    };
+    let unit_secs: u64 = match unit {
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 7 * 86_400,
+        _ => bail!(
+            "Invalid duration '{}': use e.g. 30m, 24h, 7d, 2w or all",
+            raw
+        ),
+    };
+    let Some(secs) = n.checked_mul(unit_secs) else {
+        bail!("Invalid duration '{}': too large", raw);
+    };
+    Ok(Some(now_unix_ts().saturating_sub(secs)))
+}
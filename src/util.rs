@@ -1,5 +1,4 @@
-use std::io::{self, Write};
-use std::path::{Path, PathBuf};
+use std::io::{self, IsTerminal, Write};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
@@ -7,8 +6,15 @@ use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 
+use crate::ci::{decline_prompt, is_ci};
+use crate::highlight::CodeHighlighter;
+use crate::i18n::t;
+use crate::logging::is_quiet;
+
 // ── color helpers ────────────────────────────────────────────────────────────
 
+static PLAIN_OUTPUT: AtomicBool = AtomicBool::new(false);
+
 pub fn colors_enabled() -> bool {
     std::env::var_os("NO_COLOR").is_none()
 }
@@ -54,6 +60,9 @@ pub fn blue_label(text: &str) -> String {
 // ── prompts / input ──────────────────────────────────────────────────────────
 
 pub fn ask(label: &str) -> Result<String> {
+    if is_ci() {
+        return Ok(decline_prompt(label));
+    }
     print!("{label}");
     io::stdout().flush().context("Failed to flush stdout")?;
     let mut input = String::new();
@@ -64,6 +73,9 @@ pub fn ask(label: &str) -> Result<String> {
 }
 
 pub fn ask_or_eof(label: &str) -> Result<Option<String>> {
+    if is_ci() {
+        return Ok(Some(decline_prompt(label)));
+    }
     print!("{label}");
     io::stdout().flush().context("Failed to flush stdout")?;
     let mut input = String::new();
@@ -76,6 +88,41 @@ pub fn ask_or_eof(label: &str) -> Result<Option<String>> {
     Ok(Some(input.trim_end_matches(['\n', '\r']).to_string()))
 }
 
+/// Prints `lines` one screen at a time when stdout is a terminal (`LINES`
+/// rows, default 40); `q` stops early.
+pub fn print_paged(lines: &[String]) -> Result<()> {
+    let page = std::env::var("LINES")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(40)
+        .saturating_sub(2)
+        .max(5);
+    if !io::stdout().is_terminal() || !io::stdin().is_terminal() || lines.len() <= page {
+        for line in lines {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+    for (i, chunk) in lines.chunks(page).enumerate() {
+        for line in chunk {
+            println!("{}", line);
+        }
+        let shown = (i * page + chunk.len()).min(lines.len());
+        if shown == lines.len() {
+            break;
+        }
+        let reply = ask(&color_dim(&format!(
+            "-- {}/{} lines, Enter for more, q to stop --",
+            shown,
+            lines.len()
+        )))?;
+        if reply.trim().eq_ignore_ascii_case("q") {
+            break;
+        }
+    }
+    Ok(())
+}
+
 pub fn tagged_prompt(tag: &str, label: &str) -> String {
     format!("{} {}", blue_label(&format!("[{}]", tag)), label)
 }
@@ -100,12 +147,24 @@ pub fn print_startup_banner(session: &str, model: &str, exec_mode: &str) {
         println!(
             "  {}  {}",
             color_bold(&color_rust("dongshan")),
-            color_dim("v0.2.0  ·  AI Coding Assistant")
+            color_dim(&format!("v0.2.0  ·  {}", t("banner.tagline")))
         );
         println!("  {}", color_rust(&sep));
-        println!("  {}  {}", color_dim("session :"), color_cyan(session));
-        println!("  {}  {}", color_dim("model   :"), color_blue(model));
-        println!("  {}  {}", color_dim("mode    :"), color_yellow(exec_mode));
+        println!(
+            "  {}  {}",
+            color_dim(&format!("{:<7} :", t("banner.session"))),
+            color_cyan(session)
+        );
+        println!(
+            "  {}  {}",
+            color_dim(&format!("{:<7} :", t("banner.model"))),
+            color_blue(model)
+        );
+        println!(
+            "  {}  {}",
+            color_dim(&format!("{:<7} :", t("banner.mode"))),
+            color_yellow(exec_mode)
+        );
         println!("  {}", color_rust(&sep));
         println!(
             "  {}",
@@ -115,11 +174,16 @@ pub fn print_startup_banner(session: &str, model: &str, exec_mode: &str) {
         for line in FERRIS.trim_matches('\n').lines() {
             println!("{}", line);
         }
-        println!("  dongshan v0.2.0  ·  AI Coding Assistant");
+        println!("  dongshan v0.2.0  ·  {}", t("banner.tagline"));
         println!("  {}", sep);
         println!(
-            "  session : {}  model : {}  mode : {}",
-            session, model, exec_mode
+            "  {} : {}  {} : {}  {} : {}",
+            t("banner.session"),
+            session,
+            t("banner.model"),
+            model,
+            t("banner.mode"),
+            exec_mode
         );
         println!("  {}", sep);
         println!("  /help · /exit · /mode · /session · /model");
@@ -133,21 +197,6 @@ pub fn truncate_preview(text: &str, max_len: usize) -> String {
     truncate_with_suffix(text, max_len, "...")
 }
 
-pub fn backup_path(file: &Path) -> PathBuf {
-    let stem = file
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("backup")
-        .to_string();
-    let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
-    let file_name = if ext.is_empty() {
-        format!("{stem}.bak")
-    } else {
-        format!("{stem}.bak.{ext}")
-    };
-    file.with_file_name(file_name)
-}
-
 pub fn truncate_with_suffix(text: &str, max_chars: usize, suffix: &str) -> String {
     let mut iter = text.char_indices();
     let mut count = 0usize;
@@ -180,21 +229,81 @@ pub fn prefix_chars(text: &str, max_chars: usize) -> String {
     text[..cut_at].to_string()
 }
 
+pub fn set_plain_output(plain: bool) {
+    PLAIN_OUTPUT.store(plain, Ordering::Relaxed);
+}
+
+/// Markdown rendering is on unless `--plain` was given or stdout is piped.
+pub fn markdown_enabled() -> bool {
+    !PLAIN_OUTPUT.load(Ordering::Relaxed) && io::stdout().is_terminal()
+}
+
 pub fn render_markdown_terminal(text: &str, enabled: bool) -> String {
     if !enabled {
         return text.to_string();
     }
+    let mut renderer = MarkdownStreamRenderer::new();
+    let mut out = renderer.push(text);
+    out.push_str(&renderer.finish());
+    out.trim_end_matches('\n').to_string()
+}
 
-    let mut out = String::new();
-    let mut in_code_block = false;
-    for raw_line in text.lines() {
+/// Incremental markdown renderer: feed streamed deltas with `push`, which
+/// returns rendered output for every completed line. Table rows are held
+/// back until the table ends so columns can be aligned.
+#[derive(Default)]
+pub struct MarkdownStreamRenderer {
+    pending: String,
+    in_code_block: bool,
+    highlighter: Option<CodeHighlighter>,
+    table_rows: Vec<String>,
+}
+
+impl MarkdownStreamRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, delta: &str) -> String {
+        self.pending.push_str(delta);
+        let mut out = String::new();
+        while let Some(idx) = self.pending.find('\n') {
+            let line = self.pending[..idx].to_string();
+            self.pending.drain(..=idx);
+            out.push_str(&self.render_line(&line));
+        }
+        out
+    }
+
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            out.push_str(&self.render_line(&line));
+        }
+        out.push_str(&self.flush_table());
+        out
+    }
+
+    fn render_line(&mut self, raw_line: &str) -> String {
         let line = raw_line.trim_end_matches('\r');
         let trimmed = line.trim_start();
 
+        if !self.in_code_block && is_table_row(trimmed) {
+            self.table_rows.push(trimmed.to_string());
+            return String::new();
+        }
+        let mut out = self.flush_table();
+
         if trimmed.starts_with("```") {
-            in_code_block = !in_code_block;
+            self.in_code_block = !self.in_code_block;
             let lang = trimmed.trim_start_matches("```").trim();
-            if in_code_block {
+            self.highlighter = if self.in_code_block {
+                CodeHighlighter::for_lang(lang)
+            } else {
+                None
+            };
+            if self.in_code_block {
                 if lang.is_empty() {
                     out.push_str(&color_dim("┌ code"));
                 } else {
@@ -203,43 +312,105 @@ pub fn render_markdown_terminal(text: &str, enabled: bool) -> String {
             } else {
                 out.push_str(&color_dim("└ end"));
             }
-            out.push('\n');
-            continue;
-        }
-
-        if in_code_block {
+        } else if self.in_code_block {
             out.push_str(&color_dim("│ "));
-            out.push_str(line);
-            out.push('\n');
-            continue;
-        }
-
-        if let Some(h) = render_heading(trimmed) {
+            match self.highlighter.as_mut() {
+                Some(h) => out.push_str(&h.highlight_line(line)),
+                None => out.push_str(line),
+            }
+        } else if let Some(h) = render_heading(trimmed) {
             out.push_str(&h);
-            out.push('\n');
-            continue;
-        }
-        if let Some(q) = trimmed.strip_prefix(">") {
+        } else if let Some(q) = trimmed.strip_prefix(">") {
             out.push_str(&color_dim("▏ "));
             out.push_str(&style_inline_md(q.trim_start()));
-            out.push('\n');
-            continue;
-        }
-        if is_hr(trimmed) {
+        } else if is_hr(trimmed) {
             out.push_str(&color_dim("────────────────────────"));
-            out.push('\n');
-            continue;
-        }
-        if let Some(li) = render_list_item(trimmed) {
+        } else if let Some(li) = render_list_item(trimmed) {
+            out.push_str(&line[..line.len() - trimmed.len()]);
             out.push_str(&li);
-            out.push('\n');
-            continue;
+        } else {
+            out.push_str(&style_inline_md(line));
+        }
+        out.push('\n');
+        out
+    }
+
+    fn flush_table(&mut self) -> String {
+        if self.table_rows.is_empty() {
+            return String::new();
+        }
+        let rows = std::mem::take(&mut self.table_rows);
+        render_table(&rows)
+    }
+}
+
+fn is_table_row(line: &str) -> bool {
+    line.len() > 1 && line.starts_with('|') && line.ends_with('|')
+}
+
+fn is_table_separator(cells: &[String]) -> bool {
+    !cells.is_empty()
+        && cells.iter().all(|c| {
+            let t = c.trim();
+            !t.is_empty() && t.chars().all(|ch| matches!(ch, '-' | ':'))
+        })
+}
+
+fn split_table_cells(row: &str) -> Vec<String> {
+    row.trim()
+        .trim_start_matches('|')
+        .trim_end_matches('|')
+        .split('|')
+        .map(|c| c.trim().to_string())
+        .collect()
+}
+
+fn render_table(rows: &[String]) -> String {
+    let parsed: Vec<Vec<String>> = rows.iter().map(|r| split_table_cells(r)).collect();
+    let has_header = parsed.len() > 1 && is_table_separator(&parsed[1]);
+    let body: Vec<&Vec<String>> = parsed
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !(has_header && *i == 1))
+        .map(|(_, r)| r)
+        .collect();
+    let cols = body.iter().map(|r| r.len()).max().unwrap_or(0);
+    let mut widths = vec![0usize; cols];
+    for row in &body {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
         }
+    }
 
-        out.push_str(&style_inline_md(line));
+    let mut out = String::new();
+    for (idx, row) in body.iter().enumerate() {
+        let is_header = has_header && idx == 0;
+        let mut line = String::new();
+        for (i, width) in widths.iter().enumerate() {
+            let cell = row.get(i).map(String::as_str).unwrap_or("");
+            if i > 0 {
+                line.push_str(&color_dim(" │ "));
+            }
+            if is_header {
+                line.push_str(&color_bold(cell));
+            } else {
+                line.push_str(&style_inline_md(cell));
+            }
+            line.push_str(&" ".repeat(width.saturating_sub(cell.chars().count())));
+        }
+        out.push_str(line.trim_end());
         out.push('\n');
+        if is_header {
+            let sep = widths
+                .iter()
+                .map(|w| "─".repeat(*w))
+                .collect::<Vec<_>>()
+                .join("─┼─");
+            out.push_str(&color_dim(&sep));
+            out.push('\n');
+        }
     }
-    out.trim_end_matches('\n').to_string()
+    out
 }
 
 fn render_heading(line: &str) -> Option<String> {
@@ -299,29 +470,49 @@ fn is_hr(line: &str) -> bool {
 fn style_inline_md(text: &str) -> String {
     let mut out = String::new();
     let mut in_code = false;
+    let mut in_bold = false;
     let mut buf = String::new();
-    for ch in text.chars() {
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
         if ch == '`' {
             if in_code {
                 out.push_str(&color_cyan(&buf));
-                buf.clear();
             } else {
-                out.push_str(&buf);
-                buf.clear();
+                push_styled(&mut out, &buf, in_bold);
             }
+            buf.clear();
             in_code = !in_code;
             continue;
         }
+        if !in_code && ch == '*' && chars.peek() == Some(&'*') {
+            chars.next();
+            push_styled(&mut out, &buf, in_bold);
+            buf.clear();
+            in_bold = !in_bold;
+            continue;
+        }
         buf.push(ch);
     }
     if in_code {
         out.push_str(&color_cyan(&buf));
+    } else if in_bold {
+        // Unterminated bold: keep the marker so nothing is silently dropped.
+        out.push_str("**");
+        out.push_str(&buf);
     } else {
         out.push_str(&buf);
     }
     out
 }
 
+fn push_styled(out: &mut String, text: &str, bold: bool) {
+    if bold {
+        out.push_str(&color_bold(text));
+    } else {
+        out.push_str(text);
+    }
+}
+
 // ── working-status spinner ───────────────────────────────────────────────────
 
 const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
@@ -340,6 +531,15 @@ impl WorkingStatus {
         let label = label.into();
         let start = Instant::now();
         let done = Arc::new(AtomicBool::new(false));
+        if is_quiet() || is_ci() {
+            return Self {
+                label,
+                start,
+                done,
+                handle: None,
+                finished: true,
+            };
+        }
         let done_flag = Arc::clone(&done);
         let label_clone = label.clone();
         let use_color = colors_enabled();
@@ -374,6 +574,9 @@ impl WorkingStatus {
     }
 
     pub fn finish(mut self) {
+        if self.handle.is_none() {
+            return;
+        }
         self.stop_thread();
         let secs = self.start.elapsed().as_secs();
         if colors_enabled() {
@@ -406,3 +609,17 @@ impl Drop for WorkingStatus {
         }
     }
 }
+
+// ── blocking work ────────────────────────────────────────────────────────────
+
+/// Runs blocking work (directory walks, file scans, subprocesses) on tokio's
+/// blocking pool, so streaming and timers keep running on the async threads.
+pub async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .context("Blocking task panicked")?
+}
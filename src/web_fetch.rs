@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use reqwest::Url;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use reqwest::redirect::Policy;
+
+use crate::config::Config;
+use crate::logging::log_verbose;
+use crate::tls::client_builder;
+use crate::util::truncate_with_suffix;
+
+/// Bytes downloaded at most; larger pages are cut before conversion.
+const MAX_DOWNLOAD_BYTES: usize = 2 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct FetchedPage {
+    pub url: String,
+    pub title: Option<String>,
+    pub text: String,
+}
+
+impl FetchedPage {
+    /// Text added to model context, clipped to `fetch_max_chars`.
+    pub fn to_context(&self, max_chars: usize) -> String {
+        let mut out = format!("URL: {}\n", self.url);
+        if let Some(title) = &self.title {
+            out.push_str(&format!("Title: {}\n", title));
+        }
+        out.push('\n');
+        out.push_str(&truncate_with_suffix(
+            &self.text,
+            max_chars.max(500),
+            "...\n[truncated]",
+        ));
+        out
+    }
+}
+
+/// True when the agent may fetch `url`: its host equals or is a subdomain of
+/// an entry in `fetch_allow_domains` (`*` allows any host).
+pub fn is_fetch_allowed(cfg: &Config, url: &str) -> bool {
+    host_allowed(&cfg.fetch_allow_domains, url)
+}
+
+/// Same rule as [`is_fetch_allowed`] for the `http.get` tool, which has its
+/// own list: `http_allow_domains`.
+pub fn is_http_allowed(cfg: &Config, url: &str) -> bool {
+    host_allowed(&cfg.http_allow_domains, url)
+}
+
+fn host_allowed(domains: &[String], url: &str) -> bool {
+    let Some(host) = url_host(url) else {
+        return false;
+    };
+    domains.iter().any(|d| {
+        let d = d.trim().trim_start_matches("*.").to_ascii_lowercase();
+        d == "*" || host == d || host.ends_with(&format!(".{d}"))
+    })
+}
+
+/// Host of an http(s) URL as the HTTP client will resolve it, so tricks like
+/// `https://evil.com\@docs.rs/` are judged by the host actually contacted.
+fn url_host(url: &str) -> Option<String> {
+    let parsed = Url::parse(url.trim()).ok()?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return None;
+    }
+    let host = parsed.host_str()?;
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+/// Follows at most 5 redirects, each only to a host in `domains`; `setting`
+/// names the config key in the error.
+fn allowlist_redirect(domains: Vec<String>, setting: &'static str) -> Policy {
+    Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 5 {
+            attempt.error("too many redirects")
+        } else if host_allowed(&domains, attempt.url().as_str()) {
+            attempt.follow()
+        } else {
+            let msg = format!("redirect to {} is outside {}", attempt.url(), setting);
+            attempt.error(msg)
+        }
+    })
+}
+
+/// Downloads a page and converts HTML to readable text. With `allowed` (the
+/// agent's `fetch_allow_domains`), redirects are followed only to those hosts.
+pub async fn fetch_url(url: &str, allowed: Option<&[String]>) -> Result<FetchedPage> {
+    let url = url.trim();
+    if url_host(url).is_none() {
+        bail!("Only http(s) URLs can be fetched: {}", url);
+    }
+    let mut builder = client_builder()?;
+    if let Some(domains) = allowed {
+        builder = builder.redirect(allowlist_redirect(domains.to_vec(), "fetch_allow_domains"));
+    }
+    let client = builder
+        .timeout(FETCH_TIMEOUT)
+        .user_agent(concat!("dongshan/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("failed to build HTTP client")?;
+    log_verbose("fetch", &format!("GET {}", url));
+    let mut resp = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Request failed: {}", url))?;
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("Fetch failed with HTTP {}: {}", status, url);
+    }
+    let final_url = resp.url().to_string();
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let is_html = content_type.contains("html") || content_type.is_empty();
+    let is_text = content_type.starts_with("text/")
+        || content_type.contains("json")
+        || content_type.contains("xml")
+        || content_type.contains("javascript");
+    if !is_html && !is_text {
+        bail!("Unsupported content type `{}`: {}", content_type, url);
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await.context("Failed to read response body")? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_DOWNLOAD_BYTES {
+            body.truncate(MAX_DOWNLOAD_BYTES);
+            break;
+        }
+    }
+    let raw = String::from_utf8_lossy(&body);
+    let looks_like_html = is_html && raw.trim_start().starts_with('<');
+    let (title, text) = if looks_like_html {
+        (html_title(&raw), html_to_text(&raw))
+    } else {
+        (None, raw.into_owned())
+    };
+    Ok(FetchedPage {
+        url: final_url,
+        title,
+        text,
+    })
+}
+
+/// Request headers `http.get` refuses to send, so the model cannot pass
+/// credentials (or have dongshan's own keys echoed) to a remote API.
+const BLOCKED_HTTP_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+    "api-key",
+    "x-auth-token",
+    "host",
+];
+
+pub struct HttpResponse {
+    pub url: String,
+    pub status: u16,
+    pub content_type: String,
+    pub body: String,
+    /// Bytes read before the body hit `http_max_bytes`.
+    pub truncated: bool,
+}
+
+impl HttpResponse {
+    pub fn to_context(&self) -> String {
+        let mut out = format!(
+            "HTTP {} {}\nContent-Type: {}\n\n{}",
+            self.status,
+            self.url,
+            if self.content_type.is_empty() {
+                "(none)"
+            } else {
+                &self.content_type
+            },
+            self.body
+        );
+        if self.truncated {
+            out.push_str("\n[truncated: response exceeded http_max_bytes]");
+        }
+        out
+    }
+}
+
+/// Plain GET for the agent: no cookies or credentials, redirects only to
+/// allowlisted hosts, body cut at `http_max_bytes`. Non-2xx statuses are
+/// returned rather than treated as errors so the model sees the API's reply.
+pub async fn http_get(
+    cfg: &Config,
+    url: &str,
+    headers: &BTreeMap<String, String>,
+) -> Result<HttpResponse> {
+    let url = url.trim();
+    if !host_allowed(&cfg.http_allow_domains, url) {
+        bail!("{} is not an http(s) URL in http_allow_domains", url);
+    }
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let lower = name.trim().to_ascii_lowercase();
+        if BLOCKED_HTTP_HEADERS.contains(&lower.as_str()) {
+            bail!("Header `{}` is not allowed in http.get", name.trim());
+        }
+        let name = HeaderName::from_bytes(lower.as_bytes())
+            .with_context(|| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for header {}", name))?;
+        header_map.insert(name, value);
+    }
+    let client = client_builder()?
+        .timeout(Duration::from_secs(cfg.http_timeout_secs.max(1)))
+        .redirect(allowlist_redirect(
+            cfg.http_allow_domains.clone(),
+            "http_allow_domains",
+        ))
+        .user_agent(concat!("dongshan/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("failed to build HTTP client")?;
+    log_verbose("http", &format!("GET {}", url));
+    let mut resp = client
+        .get(url)
+        .headers(header_map)
+        .send()
+        .await
+        .with_context(|| format!("Request failed: {}", url))?;
+    let status = resp.status().as_u16();
+    let final_url = resp.url().to_string();
+    let content_type = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let max_bytes = cfg.http_max_bytes.max(1);
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = resp.chunk().await.context("Failed to read response body")? {
+        body.extend_from_slice(&chunk);
+        if body.len() > max_bytes {
+            body.truncate(max_bytes);
+            truncated = true;
+            break;
+        }
+    }
+    if body.contains(&0) {
+        bail!(
+            "Binary response ({}) from {}; http.get only returns text",
+            content_type,
+            final_url
+        );
+    }
+    Ok(HttpResponse {
+        url: final_url,
+        status,
+        content_type,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        truncated,
+    })
+}
+
+struct HtmlPatterns {
+    dropped: Vec<Regex>,
+    title: Regex,
+    block: Regex,
+    tag: Regex,
+    blank_lines: Regex,
+}
+
+fn html_patterns() -> &'static HtmlPatterns {
+    static PATTERNS: OnceLock<HtmlPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| HtmlPatterns {
+        dropped: ["script", "style", "noscript", "svg", "template", "head"]
+            .iter()
+            .filter_map(|t| Regex::new(&format!(r"(?is)<{t}\b.*?</{t}\s*>")).ok())
+            .chain(Regex::new(r"(?s)<!--.*?-->").ok())
+            .collect(),
+        title: Regex::new(r"(?is)<title[^>]*>(.*?)</title\s*>").expect("valid regex"),
+        block: Regex::new(
+            r"(?i)<\s*(/?(p|div|section|article|header|footer|main|nav|aside|ul|ol|table|tr|pre|blockquote|h[1-6])|br|li|hr)\b[^>]*>",
+        )
+        .expect("valid regex"),
+        tag: Regex::new(r"(?s)<[^>]*>").expect("valid regex"),
+        blank_lines: Regex::new(r"\n{3,}").expect("valid regex"),
+    })
+}
+
+fn html_title(html: &str) -> Option<String> {
+    let caps = html_patterns().title.captures(html)?;
+    let title = decode_entities(caps[1].trim());
+    (!title.is_empty()).then_some(title)
+}
+
+/// Drops scripts/styles/markup and keeps one line per block element.
+fn html_to_text(html: &str) -> String {
+    let p = html_patterns();
+    let mut text = html.to_string();
+    for re in &p.dropped {
+        text = re.replace_all(&text, "").into_owned();
+    }
+    text = p
+        .block
+        .replace_all(&text, |caps: &regex::Captures| {
+            if caps[0].to_ascii_lowercase().starts_with("<li") {
+                "\n- ".to_string()
+            } else {
+                "\n".to_string()
+            }
+        })
+        .into_owned();
+    text = p.tag.replace_all(&text, "").into_owned();
+    let text = decode_entities(&text);
+    let lines: Vec<String> = text
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    p.blank_lines
+        .replace_all(lines.join("\n").trim(), "\n\n")
+        .into_owned()
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let Some(end) = rest.find(';').filter(|&e| e <= 12) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" | "#39" => Some('\''),
+            "nbsp" => Some(' '),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            "lsquo" => Some('‘'),
+            "rsquo" => Some('’'),
+            "ldquo" => Some('“'),
+            "rdquo" => Some('”'),
+            "middot" => Some('·'),
+            "copy" => Some('©'),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
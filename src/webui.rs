@@ -10,8 +10,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::config::{
     AutoExecMode, ModelApiProvider, add_model_with_active_profile, ensure_model_catalog,
-    load_config_or_default, remove_model, save_config, set_active_model,
-    update_active_model_profile, upsert_model_profile,
+    load_config_or_default, remove_model, set_active_model, update_active_model_profile,
+    update_config, upsert_model_profile,
 };
 use crate::diagnostics::{LastDiagnostic, read_last_diagnostic};
 use crate::prompt_store::{list_prompts, remove_prompt, save_prompt};
@@ -106,29 +106,30 @@ async fn api_state() -> ApiResult<Json<StateResponse>> {
 }
 
 async fn api_set_config(Json(req): Json<ConfigUpdateRequest>) -> ApiResult<Json<SimpleOk>> {
-    let mut cfg = load_config_or_default().map_err(api_err)?;
-    if let Some(v) = req.model {
-        set_active_model(&mut cfg, &v);
-    }
-    if let Some(v) = req.base_url {
-        cfg.base_url = v;
-    }
-    if let Some(v) = req.api_key_env {
-        cfg.api_key_env = v;
-    }
-    if let Some(v) = req.api_key {
-        cfg.api_key = if v.trim().is_empty() { None } else { Some(v) };
-    }
-    if let Some(v) = req.allow_nsfw {
-        cfg.allow_nsfw = v;
-    }
-    update_active_model_profile(&mut cfg);
-    if let Some(provider) = req.provider {
-        let active_model = cfg.model.clone();
-        upsert_model_profile(&mut cfg, &active_model, None, None, None, Some(provider));
-    }
-    ensure_model_catalog(&mut cfg);
-    save_config(&cfg).map_err(api_err)?;
+    update_config(|cfg| {
+        if let Some(v) = req.model {
+            set_active_model(cfg, &v);
+        }
+        if let Some(v) = req.base_url {
+            cfg.base_url = v;
+        }
+        if let Some(v) = req.api_key_env {
+            cfg.api_key_env = v;
+        }
+        if let Some(v) = req.api_key {
+            cfg.api_key = if v.trim().is_empty() { None } else { Some(v) };
+        }
+        if let Some(v) = req.allow_nsfw {
+            cfg.allow_nsfw = v;
+        }
+        update_active_model_profile(cfg);
+        if let Some(provider) = req.provider {
+            let active_model = cfg.model.clone();
+            upsert_model_profile(cfg, &active_model, None, None, None, Some(provider));
+        }
+        ensure_model_catalog(cfg);
+    })
+    .map_err(api_err)?;
     Ok(Json(SimpleOk { ok: true }))
 }
 
@@ -138,81 +139,87 @@ async fn api_prompt_save(Json(req): Json<PromptSaveRequest>) -> ApiResult<Json<S
 }
 
 async fn api_prompt_use(Json(req): Json<PromptUseRequest>) -> ApiResult<Json<SimpleOk>> {
-    let mut cfg = load_config_or_default().map_err(api_err)?;
-    cfg.active_prompt = req.name;
-    save_config(&cfg).map_err(api_err)?;
+    update_config(|cfg| {
+        cfg.active_prompt = req.name;
+    })
+    .map_err(api_err)?;
     Ok(Json(SimpleOk { ok: true }))
 }
 
 async fn api_prompt_delete(Json(req): Json<PromptDeleteRequest>) -> ApiResult<Json<SimpleOk>> {
-    let mut cfg = load_config_or_default().map_err(api_err)?;
     remove_prompt(&req.name).map_err(api_err)?;
-    if cfg.active_prompt == req.name {
-        cfg.active_prompt = "default".to_string();
-        save_config(&cfg).map_err(api_err)?;
-    }
+    update_config(|cfg| {
+        if cfg.active_prompt == req.name {
+            cfg.active_prompt = "default".to_string();
+        }
+    })
+    .map_err(api_err)?;
     Ok(Json(SimpleOk { ok: true }))
 }
 
 async fn api_model_add(Json(req): Json<ModelAddRequest>) -> ApiResult<Json<SimpleOk>> {
-    let mut cfg = load_config_or_default().map_err(api_err)?;
-    add_model_with_active_profile(&mut cfg, &req.name);
-    if req.provider.is_some()
-        || req.base_url.is_some()
-        || req.api_key_env.is_some()
-        || req.api_key.is_some()
-    {
-        upsert_model_profile(
-            &mut cfg,
-            &req.name,
-            req.base_url,
-            req.api_key_env,
-            req.api_key,
-            req.provider,
-        );
-    }
-    save_config(&cfg).map_err(api_err)?;
+    update_config(|cfg| {
+        add_model_with_active_profile(cfg, &req.name);
+        if req.provider.is_some()
+            || req.base_url.is_some()
+            || req.api_key_env.is_some()
+            || req.api_key.is_some()
+        {
+            upsert_model_profile(
+                cfg,
+                &req.name,
+                req.base_url,
+                req.api_key_env,
+                req.api_key,
+                req.provider,
+            );
+        }
+    })
+    .map_err(api_err)?;
     Ok(Json(SimpleOk { ok: true }))
 }
 
 async fn api_model_use(Json(req): Json<ModelUseRequest>) -> ApiResult<Json<SimpleOk>> {
-    let mut cfg = load_config_or_default().map_err(api_err)?;
-    set_active_model(&mut cfg, &req.name);
-    save_config(&cfg).map_err(api_err)?;
+    update_config(|cfg| {
+        set_active_model(cfg, &req.name);
+    })
+    .map_err(api_err)?;
     Ok(Json(SimpleOk { ok: true }))
 }
 
 async fn api_model_remove(Json(req): Json<ModelRemoveRequest>) -> ApiResult<Json<SimpleOk>> {
-    let mut cfg = load_config_or_default().map_err(api_err)?;
-    if cfg.model == req.name {
+    if load_config_or_default().map_err(api_err)?.model == req.name {
         return Err((
             StatusCode::BAD_REQUEST,
             "Cannot remove active model".to_string(),
         ));
     }
-    remove_model(&mut cfg, &req.name);
-    save_config(&cfg).map_err(api_err)?;
+    update_config(|cfg| {
+        remove_model(cfg, &req.name);
+    })
+    .map_err(api_err)?;
     Ok(Json(SimpleOk { ok: true }))
 }
 
 async fn api_policy_update(Json(req): Json<PolicyUpdateRequest>) -> ApiResult<Json<SimpleOk>> {
-    let mut cfg = load_config_or_default().map_err(api_err)?;
-    if let Some(v) = req.auto_exec_mode {
-        cfg.auto_exec_mode = v;
-    }
-    if let Some(v) = req.auto_exec_allow {
-        cfg.auto_exec_allow = v;
-    }
-    if let Some(v) = req.auto_exec_deny {
-        cfg.auto_exec_deny = v;
-    }
-    if let Some(v) = req.auto_confirm_exec {
-        cfg.auto_confirm_exec = v;
-    }
-    if let Some(v) = req.auto_exec_trusted {
-        cfg.auto_exec_trusted = v;
-    }
-    save_config(&cfg).map_err(api_err)?;
+    update_config(|cfg| {
+        if let Some(v) = req.auto_exec_mode {
+            cfg.auto_exec_mode = v;
+        }
+        if let Some(v) = req.auto_exec_allow {
+            cfg.auto_exec_allow = v;
+        }
+        if let Some(v) = req.auto_exec_deny {
+            cfg.auto_exec_deny = v;
+        }
+        if let Some(v) = req.auto_confirm_exec {
+            cfg.auto_confirm_exec = v;
+        }
+        if let Some(v) = req.auto_exec_trusted {
+            cfg.auto_exec_trusted = v;
+        }
+    })
+    .map_err(api_err)?;
     Ok(Json(SimpleOk { ok: true }))
 }
 
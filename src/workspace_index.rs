@@ -0,0 +1,468 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::config::{Config, resolve_api_key};
+use crate::diagnostics::now_unix_ts;
+use crate::file_index::indexed_files;
+use crate::fs_tools::relative_path;
+use crate::llm::acquire_request_slot;
+use crate::logging::{log_request_body, log_verbose};
+use crate::tls::client_builder;
+use crate::usage::{TokenUsage, record_usage};
+use crate::util::run_blocking;
+
+const INDEX_VERSION: u32 = 1;
+/// Model name used for the built-in hashed bag-of-words embedder.
+pub const LOCAL_EMBEDDING_MODEL: &str = "local";
+const LOCAL_DIMS: usize = 512;
+const CHUNK_LINES: usize = 60;
+const CHUNK_OVERLAP: usize = 10;
+const CHUNK_MAX_CHARS: usize = 3000;
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+const EMBED_BATCH: usize = 64;
+
+/// Size, mtime and parsed contents of an `index.json`.
+type CachedIndex = (u64, Option<SystemTime>, Arc<WorkspaceIndex>);
+
+static INDEX_CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedIndex>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceIndex {
+    pub version: u32,
+    pub model: String,
+    pub built_at: u64,
+    #[serde(default)]
+    pub files: BTreeMap<String, IndexedFile>,
+    #[serde(default)]
+    pub chunks: Vec<IndexChunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IndexedFile {
+    pub size: u64,
+    pub mtime: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexChunk {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScoredChunk {
+    pub score: f32,
+    pub chunk: IndexChunk,
+}
+
+#[derive(Debug, Default)]
+pub struct BuildStats {
+    pub files: usize,
+    pub reused_files: usize,
+    pub embedded_chunks: usize,
+    pub total_chunks: usize,
+}
+
+pub fn index_dir(root: &Path) -> PathBuf {
+    root.join(".dongshan").join("index")
+}
+
+fn index_file(root: &Path) -> PathBuf {
+    index_dir(root).join("index.json")
+}
+
+pub fn embedding_model(cfg: &Config) -> String {
+    cfg.embedding_model
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(LOCAL_EMBEDDING_MODEL)
+        .to_string()
+}
+
+pub fn load_index(root: &Path) -> Result<Option<WorkspaceIndex>> {
+    let path = index_file(root);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let index: WorkspaceIndex = serde_json::from_str(&text)
+        .with_context(|| format!("Invalid index JSON: {}", path.display()))?;
+    if index.version != INDEX_VERSION {
+        return Ok(None);
+    }
+    Ok(Some(index))
+}
+
+/// [`load_index`], parsed again only when `index.json` changed size or mtime,
+/// so retrieval does not re-read a large index on every turn.
+fn cached_index(root: &Path) -> Result<Option<Arc<WorkspaceIndex>>> {
+    let path = index_file(root);
+    let Ok(meta) = fs::metadata(&path) else {
+        return Ok(None);
+    };
+    let mtime = meta.modified().ok();
+    let cache = INDEX_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Ok(guard) = cache.lock()
+        && let Some((size, time, index)) = guard.get(&path)
+        && *size == meta.len()
+        && *time == mtime
+    {
+        return Ok(Some(index.clone()));
+    }
+    let Some(index) = load_index(root)? else {
+        return Ok(None);
+    };
+    let index = Arc::new(index);
+    if let Ok(mut guard) = cache.lock() {
+        guard.insert(path, (meta.len(), mtime, index.clone()));
+    }
+    Ok(Some(index))
+}
+
+fn save_index(root: &Path, index: &WorkspaceIndex) -> Result<()> {
+    let dir = index_dir(root);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = index_file(root);
+    let text = serde_json::to_string(index)?;
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+pub fn clear_index(root: &Path) -> Result<bool> {
+    let dir = index_dir(root);
+    if !dir.exists() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))?;
+    Ok(true)
+}
+
+/// Builds or refreshes the index. Unchanged files keep their existing vectors
+/// unless `rebuild` is set or the embedding model changed.
+pub async fn build_index(cfg: &Config, root: &Path, rebuild: bool) -> Result<BuildStats> {
+    let model = embedding_model(cfg);
+    let previous = if rebuild {
+        None
+    } else {
+        load_index(root)?.filter(|idx| idx.model == model)
+    };
+
+    let mut stats = BuildStats::default();
+    let mut files = BTreeMap::new();
+    let mut chunks = Vec::new();
+    let mut pending: Vec<IndexChunk> = Vec::new();
+
+    for path in indexed_files(root)? {
+        let Some(rel) = relative_path(root, &path) else {
+            continue;
+        };
+        if rel.starts_with(".dongshan/") {
+            continue;
+        }
+        let Ok(meta) = fs::metadata(&path) else {
+            continue;
+        };
+        if !meta.is_file() || meta.len() == 0 || meta.len() > MAX_FILE_BYTES {
+            continue;
+        }
+        let entry = IndexedFile {
+            size: meta.len(),
+            mtime: meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+
+        if let Some(prev) = &previous
+            && prev.files.get(&rel) == Some(&entry)
+        {
+            chunks.extend(prev.chunks.iter().filter(|c| c.path == rel).cloned());
+            files.insert(rel, entry);
+            stats.files += 1;
+            stats.reused_files += 1;
+            continue;
+        }
+
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if text.contains('\0') {
+            continue;
+        }
+        pending.extend(chunk_text(&rel, &text));
+        files.insert(rel, entry);
+        stats.files += 1;
+    }
+
+    let total = pending.len();
+    for batch in pending.chunks_mut(EMBED_BATCH) {
+        let inputs: Vec<String> = batch.iter().map(embedding_input).collect();
+        let vectors = embed_texts(cfg, &model, &inputs).await?;
+        for (chunk, vector) in batch.iter_mut().zip(vectors) {
+            chunk.vector = vector;
+        }
+        stats.embedded_chunks += batch.len();
+        log_verbose(
+            "index",
+            &format!("embedded {}/{} chunks", stats.embedded_chunks, total),
+        );
+    }
+    chunks.extend(pending);
+    chunks.sort_by(|a, b| a.path.cmp(&b.path).then(a.start_line.cmp(&b.start_line)));
+    stats.total_chunks = chunks.len();
+
+    save_index(
+        root,
+        &WorkspaceIndex {
+            version: INDEX_VERSION,
+            model,
+            built_at: now_unix_ts(),
+            files,
+            chunks,
+        },
+    )?;
+    Ok(stats)
+}
+
+/// Returns the `top_k` chunks most similar to `query`, or an empty list when the
+/// workspace has no index.
+pub async fn search_index(
+    cfg: &Config,
+    root: &Path,
+    query: &str,
+    top_k: usize,
+) -> Result<Vec<ScoredChunk>> {
+    let index_root = root.to_path_buf();
+    let Some(index) = run_blocking(move || cached_index(&index_root)).await? else {
+        return Ok(Vec::new());
+    };
+    if index.chunks.is_empty() || query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let query_vec = embed_texts(cfg, &index.model, &[query.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+    let mut scored: Vec<(f32, &IndexChunk)> = index
+        .chunks
+        .iter()
+        .map(|chunk| (cosine(&query_vec, &chunk.vector), chunk))
+        .filter(|(score, _)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.truncate(top_k);
+    Ok(scored
+        .into_iter()
+        .map(|(score, chunk)| ScoredChunk {
+            score,
+            chunk: chunk.clone(),
+        })
+        .collect())
+}
+
+fn chunk_text(path: &str, text: &str) -> Vec<IndexChunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = Vec::new();
+    let mut start = 0usize;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let mut body = lines[start..end].join("\n");
+        if body.len() > CHUNK_MAX_CHARS {
+            let mut cut = CHUNK_MAX_CHARS;
+            while !body.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            body.truncate(cut);
+        }
+        if !body.trim().is_empty() {
+            out.push(IndexChunk {
+                path: path.to_string(),
+                start_line: start + 1,
+                end_line: end,
+                text: body,
+                vector: Vec::new(),
+            });
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    out
+}
+
+fn embedding_input(chunk: &IndexChunk) -> String {
+    format!("{}\n{}", chunk.path, chunk.text)
+}
+
+async fn embed_texts(cfg: &Config, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    if model == LOCAL_EMBEDDING_MODEL {
+        return Ok(inputs.iter().map(|t| local_embedding(t)).collect());
+    }
+    embed_texts_remote(cfg, model, inputs).await
+}
+
+async fn embed_texts_remote(cfg: &Config, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+    let api_key = resolve_api_key(cfg)?;
+    let url = derive_embeddings_url(&cfg.base_url);
+    let body = json!({ "model": model, "input": inputs });
+    let client = client_builder()?
+        .timeout(Duration::from_secs(120))
+        .build()
+        .context("failed to build HTTP client")?;
+    log_verbose(
+        "index",
+        &format!("POST {} model={} inputs={}", url, model, inputs.len()),
+    );
+    log_request_body(&url, &body, &api_key);
+    let _permit = acquire_request_slot(cfg).await?;
+    let resp = client
+        .post(&url)
+        .bearer_auth(api_key)
+        .json(&body)
+        .send()
+        .await
+        .with_context(|| format!("Request failed: {}", url))?;
+    let status = resp.status();
+    let text = resp.text().await.context("Failed to read response body")?;
+    if !status.is_success() {
+        bail!("Embeddings API error {}: {}", status, text);
+    }
+    let val: Value = serde_json::from_str(&text).context("Invalid embeddings JSON response")?;
+    if let Some(usage) = TokenUsage::from_response(&val) {
+        record_usage(cfg, model, &usage);
+    }
+    let Some(data) = val.get("data").and_then(|d| d.as_array()) else {
+        bail!("Embeddings response has no data array");
+    };
+    let mut out = vec![Vec::new(); inputs.len()];
+    for (pos, item) in data.iter().enumerate() {
+        let idx = item
+            .get("index")
+            .and_then(|i| i.as_u64())
+            .map(|i| i as usize)
+            .unwrap_or(pos);
+        let vector: Vec<f32> = item
+            .get("embedding")
+            .and_then(|e| e.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|x| x.as_f64())
+                    .map(|x| x as f32)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(slot) = out.get_mut(idx) {
+            *slot = normalize(vector);
+        }
+    }
+    if out.iter().any(|v| v.is_empty()) {
+        bail!("Embeddings response is missing vectors");
+    }
+    Ok(out)
+}
+
+fn derive_embeddings_url(base_url: &str) -> String {
+    if base_url.contains("/chat/completions") {
+        return base_url.replace("/chat/completions", "/embeddings");
+    }
+    format!("{}/embeddings", base_url.trim_end_matches('/'))
+}
+
+/// Feature-hashed bag of identifier tokens. Good enough for keyword-ish
+/// retrieval without any network access.
+fn local_embedding(text: &str) -> Vec<f32> {
+    let mut vector = vec![0f32; LOCAL_DIMS];
+    for token in tokenize(text) {
+        let h = fnv1a(token.as_bytes());
+        let slot = (h % LOCAL_DIMS as u64) as usize;
+        let sign = if (h >> 32) & 1 == 0 { 1.0 } else { -1.0 };
+        vector[slot] += sign;
+    }
+    for v in vector.iter_mut() {
+        *v = v.signum() * (1.0 + v.abs()).ln();
+    }
+    normalize(vector)
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if word.is_empty() {
+            continue;
+        }
+        let lower = word.to_lowercase();
+        let parts = split_identifier(word);
+        if parts.len() > 1 {
+            out.extend(parts.into_iter().filter(|p| p.chars().count() > 1));
+        }
+        if lower.chars().count() > 1 {
+            out.push(lower);
+        }
+    }
+    out
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in word.chars() {
+        if c == '_' {
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in vector.iter_mut() {
+            *x /= norm;
+        }
+    }
+    vector
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
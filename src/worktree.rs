@@ -0,0 +1,240 @@
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use anyhow::{Context, Result, bail};
+
+use crate::chat::run_auto_verification;
+use crate::config::Config;
+use crate::diagnostics::now_unix_ts;
+use crate::task_branch::validate_branch_name;
+use crate::trust::{remove_workspace_trust, set_workspace_trust, workspace_trust};
+use crate::util::{ask_or_eof, color_cyan, color_dim, color_yellow, prefix_chars};
+
+/// A temporary worktree on its own branch that `agent --isolated` runs in.
+/// The process works inside it until `finish` returns to the original tree;
+/// dropped without `finish` (Ctrl-C, an error or a panic) it still cleans up.
+pub struct IsolatedWorktree {
+    path: PathBuf,
+    branch: String,
+    repo_root: PathBuf,
+    original_dir: PathBuf,
+    base: String,
+    /// Skip git hooks on the branch commit (`auto_commit_no_verify`).
+    no_verify: bool,
+    /// Set once the worktree directory is gone.
+    removed: Cell<bool>,
+}
+
+pub(crate) fn git(dir: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run git {}", args.join(" ")))
+}
+
+pub(crate) fn git_ok(dir: &Path, args: &[&str]) -> Result<String> {
+    let out = git(dir, args)?;
+    if !out.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+impl IsolatedWorktree {
+    /// Creates the worktree from HEAD on `branch` (default
+    /// `dongshan/isolated-<ts>`) and moves the process into the matching
+    /// subdirectory. Uncommitted changes in the current tree are not copied.
+    pub fn enter(cfg: &Config, branch: Option<&str>) -> Result<Self> {
+        let original_dir =
+            std::env::current_dir().context("Failed to resolve current directory")?;
+        let repo_root = PathBuf::from(
+            git_ok(&original_dir, &["rev-parse", "--show-toplevel"])
+                .context("--isolated needs a git repository")?,
+        );
+        let base = git_ok(&original_dir, &["rev-parse", "HEAD"])
+            .context("--isolated needs at least one commit")?;
+        if !git_ok(&original_dir, &["status", "--porcelain"])?.is_empty() {
+            println!(
+                "{}",
+                color_yellow(
+                    "isolated> uncommitted changes stay in your tree; the agent starts from HEAD"
+                )
+            );
+        }
+        let repo_name = repo_root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "repo".to_string());
+        let stamp = now_unix_ts();
+        let branch = match branch {
+            Some(name) => {
+                validate_branch_name(name)?;
+                name.to_string()
+            }
+            None => format!("dongshan/isolated-{stamp}"),
+        };
+        let path = std::env::temp_dir()
+            .join("dongshan-worktrees")
+            .join(format!("{repo_name}-{stamp}"));
+        let path_arg = path.to_string_lossy().to_string();
+        git_ok(
+            &repo_root,
+            &["worktree", "add", "-b", &branch, &path_arg, &base],
+        )?;
+        let worktree = IsolatedWorktree {
+            path,
+            branch,
+            repo_root,
+            original_dir,
+            base,
+            no_verify: cfg.auto_commit_no_verify,
+            removed: Cell::new(false),
+        };
+        let start = worktree.remap(&worktree.original_dir);
+        // The worktree is the same project: carry over the trust decision.
+        if let Some(trusted) = workspace_trust(&worktree.original_dir) {
+            set_workspace_trust(&worktree.path, trusted)?;
+        }
+        std::env::set_current_dir(&start)
+            .with_context(|| format!("Failed to enter {}", start.display()))?;
+        println!(
+            "{}",
+            color_dim(&format!(
+                "isolated> working in {} on branch {}",
+                worktree.path.display(),
+                worktree.branch
+            ))
+        );
+        Ok(worktree)
+    }
+
+    /// Maps a path inside the original repository to the same place in the worktree.
+    pub fn remap(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.repo_root) {
+            Ok(rel) => self.path.join(rel),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Verifies and commits the agent's changes on the branch, returns to the
+    /// original tree and asks whether to merge, keep or discard the branch.
+    pub fn finish(self, cfg: &Config, task: &str) -> Result<()> {
+        match run_auto_verification(cfg) {
+            Ok(report) => println!("{}", report),
+            Err(err) => println!("verification: error ({err:#})"),
+        }
+        git_ok(&self.path, &["add", "-A"])?;
+        let staged = git(&self.path, &["diff", "--cached", "--quiet"])?;
+        std::env::set_current_dir(&self.original_dir)
+            .with_context(|| format!("Failed to return to {}", self.original_dir.display()))?;
+        if staged.status.success() {
+            self.remove(true)?;
+            println!("isolated> no changes; worktree removed");
+            return Ok(());
+        }
+        self.commit(task)?;
+        let range = format!("{}..{}", self.base, self.branch);
+        println!("{}", git_ok(&self.repo_root, &["diff", "--stat", &range])?);
+        let prompt = format!(
+            "{} [m]erge into the current branch / [k]eep branch / [d]iscard (default keep): ",
+            color_cyan("isolated>")
+        );
+        let choice = ask_or_eof(&prompt)?.unwrap_or_default();
+        match choice.trim().to_ascii_lowercase().as_str() {
+            "m" | "merge" => {
+                let out = git(&self.original_dir, &["merge", "--no-edit", &self.branch])?;
+                if !out.status.success() {
+                    println!("{}", String::from_utf8_lossy(&out.stdout).trim());
+                    self.remove(false)?;
+                    bail!(
+                        "Merge of {} failed; resolve it or run `git merge --abort`. The branch is kept.",
+                        self.branch
+                    );
+                }
+                self.remove(true)?;
+                println!("isolated> merged {}", self.branch);
+            }
+            "d" | "discard" => {
+                self.remove(true)?;
+                println!("isolated> discarded {}", self.branch);
+            }
+            _ => {
+                self.remove(false)?;
+                println!(
+                    "isolated> kept branch {} (merge later with `git merge {}`)",
+                    self.branch, self.branch
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn commit(&self, task: &str) -> Result<()> {
+        let message = format!("dongshan: {}", prefix_chars(task.trim(), 72));
+        let mut args = Vec::new();
+        if git_ok(&self.path, &["config", "user.email"]).is_err() {
+            args.extend([
+                "-c",
+                "user.name=dongshan",
+                "-c",
+                "user.email=dongshan@localhost",
+            ]);
+        }
+        args.extend(["commit", "-q"]);
+        if self.no_verify {
+            args.push("--no-verify");
+        }
+        args.extend(["-m", &message]);
+        git_ok(&self.path, &args)?;
+        Ok(())
+    }
+
+    /// Removes the worktree directory and, with `delete_branch`, the branch.
+    fn remove(&self, delete_branch: bool) -> Result<()> {
+        let _ = remove_workspace_trust(&self.path);
+        let path_arg = self.path.to_string_lossy().to_string();
+        git_ok(
+            &self.repo_root,
+            &["worktree", "remove", "--force", &path_arg],
+        )?;
+        self.removed.set(true);
+        if delete_branch {
+            git_ok(&self.repo_root, &["branch", "-D", &self.branch])?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for IsolatedWorktree {
+    /// Returns to the original tree, commits whatever the agent changed on the
+    /// branch so nothing is lost, and removes the worktree directory.
+    fn drop(&mut self) {
+        if self.removed.get() {
+            return;
+        }
+        let _ = std::env::set_current_dir(&self.original_dir);
+        let changed = git_ok(&self.path, &["status", "--porcelain"]).is_ok_and(|s| !s.is_empty());
+        let kept = changed
+            && git_ok(&self.path, &["add", "-A"]).is_ok()
+            && self.commit("interrupted isolated run").is_ok();
+        let note = match self.remove(!kept) {
+            Ok(()) if kept => format!(
+                "isolated> run did not finish; worktree removed, changes kept on branch {}",
+                self.branch
+            ),
+            Ok(()) => "isolated> run did not finish; worktree removed".to_string(),
+            Err(err) => format!(
+                "isolated> run did not finish; could not remove {}: {:#}",
+                self.path.display(),
+                err
+            ),
+        };
+        println!("{}", color_yellow(&note));
+    }
+}
@@ -0,0 +1,247 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde_json::json;
+
+use crate::config::Config;
+use crate::transcript::record;
+use crate::util::color_red;
+
+/// Commands (first word of a pipeline segment) that create, modify or remove files.
+const WRITE_COMMANDS: &[&str] = &[
+    "rm",
+    "rmdir",
+    "mv",
+    "cp",
+    "touch",
+    "mkdir",
+    "ln",
+    "chmod",
+    "chown",
+    "tee",
+    "truncate",
+    "dd",
+    "install",
+    "rsync",
+    "del",
+    "erase",
+    "rd",
+    "move",
+    "copy",
+    "ren",
+    "remove-item",
+    "move-item",
+    "copy-item",
+    "new-item",
+    "set-content",
+    "add-content",
+    "out-file",
+    "rename-item",
+];
+
+/// Device files that redirects commonly target.
+const ALWAYS_ALLOWED: &[&str] = &["/dev/null", "/dev/stdout", "/dev/stderr", "nul", "$null"];
+
+struct WriteJail {
+    enabled: bool,
+    /// `write_jail_roots` from config.
+    extra_roots: Vec<PathBuf>,
+    /// Allowed for shell commands, not for the native file tools.
+    temp_root: PathBuf,
+}
+
+static JAIL: OnceLock<WriteJail> = OnceLock::new();
+
+/// Loads the jail policy from config; the workspace root is always the current directory.
+pub fn init_write_jail(cfg: &Config) {
+    let extra_roots = cfg
+        .write_jail_roots
+        .iter()
+        .map(|r| r.trim())
+        .filter(|r| !r.is_empty())
+        .map(|r| normalize_path(&expand_home(r)))
+        .collect();
+    let _ = JAIL.set(WriteJail {
+        enabled: cfg.write_jail,
+        extra_roots,
+        temp_root: normalize_path(&env::temp_dir()),
+    });
+}
+
+fn jail() -> Option<&'static WriteJail> {
+    JAIL.get().filter(|j| j.enabled)
+}
+
+/// Directories writes are confined to (workspace first); `None` when the jail is off.
+pub fn write_roots() -> Option<Vec<PathBuf>> {
+    let jail = jail()?;
+    let cwd = normalize_path(&env::current_dir().ok()?);
+    Some(
+        std::iter::once(cwd)
+            .chain(jail.extra_roots.iter().cloned())
+            .chain(std::iter::once(jail.temp_root.clone()))
+            .collect(),
+    )
+}
+
+/// True when `path` lies inside the workspace or an allowlisted root, or the jail is off.
+pub fn is_path_allowed(path: &Path) -> bool {
+    let Some(jail) = jail() else {
+        return true;
+    };
+    let target = normalize_path(path);
+    let Ok(cwd) = env::current_dir() else {
+        return false;
+    };
+    let cwd = normalize_path(&cwd);
+    target.starts_with(&cwd)
+        || target.starts_with(&jail.temp_root)
+        || jail.extra_roots.iter().any(|r| target.starts_with(r))
+}
+
+/// True when `path` lies inside one of the `write_jail_roots`. The native file
+/// tools accept these on top of the workspace; with the jail off there are none.
+pub fn in_extra_root(path: &Path) -> bool {
+    let Some(jail) = jail() else {
+        return false;
+    };
+    let target = normalize_path(path);
+    jail.extra_roots.iter().any(|r| target.starts_with(r))
+}
+
+/// Returns the first path a shell command would write outside the jail: any
+/// path argument of a write command (`rm`, `cp`, `Set-Content`, ...) or a
+/// redirect target.
+pub fn find_write_outside_jail(cmd: &str) -> Option<String> {
+    jail()?;
+    let cwd = env::current_dir().ok()?;
+    for seg in cmd.split(['&', '|', ';', '\n']) {
+        let words: Vec<&str> = seg.split_whitespace().collect();
+        let targets = if is_write_segment(&words) {
+            words.iter().skip(1).map(|w| clean_token(w)).collect()
+        } else {
+            redirect_targets(seg)
+        };
+        for raw in targets {
+            if !looks_like_path(&raw) || ALWAYS_ALLOWED.iter().any(|a| raw.eq_ignore_ascii_case(a))
+            {
+                continue;
+            }
+            let expanded = expand_home(&raw);
+            let path = if expanded.is_absolute() {
+                expanded
+            } else {
+                cwd.join(expanded)
+            };
+            if !is_path_allowed(&path) {
+                return Some(raw);
+            }
+        }
+    }
+    None
+}
+
+/// Prints and records a blocked write so it is visible in the terminal and transcript.
+pub fn report_blocked(action: &str, target: &str) {
+    println!(
+        "{}",
+        color_red(&format!(
+            "blocked: {} outside workspace: {} (see `write_jail_roots`)",
+            action, target
+        ))
+    );
+    record("blocked", json!({ "action": action, "target": target }));
+}
+
+fn is_write_segment(words: &[&str]) -> bool {
+    let Some(first) = words.first() else {
+        return false;
+    };
+    let first = first.to_ascii_lowercase();
+    let name = first.rsplit(['/', '\\']).next().unwrap_or(&first);
+    WRITE_COMMANDS.contains(&name) || (name == "sed" && words.iter().any(|a| a.starts_with("-i")))
+}
+
+/// Targets of `>`, `>>` and `2>` redirects within one pipeline segment.
+fn redirect_targets(seg: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut rest = seg;
+    while let Some(idx) = rest.find('>') {
+        rest = rest[idx..].trim_start_matches('>');
+        if let Some(target) = rest.split_whitespace().next() {
+            out.push(clean_token(target));
+        }
+    }
+    out
+}
+
+fn clean_token(token: &str) -> String {
+    token
+        .trim_start_matches(['>', '<'])
+        .trim_matches(['"', '\'', '`', ',', '(', ')'])
+        .to_string()
+}
+
+/// Absolute, home-relative, or climbing out of the workspace with `..`.
+fn looks_like_path(t: &str) -> bool {
+    let b = t.as_bytes();
+    t.starts_with('/')
+        || t.starts_with('\\')
+        || t.starts_with('~')
+        || t.starts_with("$HOME")
+        || t.starts_with("${HOME}")
+        || t.starts_with("$env:USERPROFILE")
+        || t.starts_with("%USERPROFILE%")
+        || t == ".."
+        || t.starts_with("../")
+        || t.starts_with("..\\")
+        || t.contains("/../")
+        || t.contains("\\..\\")
+        || (b.len() >= 3 && b[1] == b':' && matches!(b[2], b'\\' | b'/'))
+}
+
+pub(crate) fn expand_home(raw: &str) -> PathBuf {
+    let home = dirs::home_dir().unwrap_or_default();
+    for prefix in ["~", "$HOME", "${HOME}", "$env:USERPROFILE", "%USERPROFILE%"] {
+        if let Some(rest) = raw.strip_prefix(prefix)
+            && (rest.is_empty() || rest.starts_with(['/', '\\']))
+        {
+            return home.join(rest.trim_start_matches(['/', '\\']));
+        }
+    }
+    PathBuf::from(raw)
+}
+
+/// Resolves `..` and symlinks of the longest existing ancestor, so paths that
+/// do not exist yet still compare correctly against the jail roots.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut lexical = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            Component::CurDir => {}
+            other => lexical.push(other.as_os_str()),
+        }
+    }
+    let mut existing = lexical.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        let Some(parent) = existing.parent() else {
+            return lexical;
+        };
+        if let Some(name) = existing.file_name() {
+            rest.push(name.to_os_string());
+        }
+        existing = parent;
+    }
+    let mut out = existing
+        .canonicalize()
+        .unwrap_or_else(|_| existing.to_path_buf());
+    for name in rest.into_iter().rev() {
+        out.push(name);
+    }
+    out
+}